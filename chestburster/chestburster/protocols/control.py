@@ -1,18 +1,29 @@
 """ This is the protocol used to communicated over the control channel with libsysforward.
 """
+import hashlib
+import hmac
+import json
 import logging
+import os
+import queue
 import socket
 import subprocess
 import time
 
 from enum import Enum
-from threading import Thread
+from threading import Lock, Thread
+
+from avatar2.message import BreakpointHitMessage, UpdateStateMessage
+from avatar2.targets import TargetStates
 
 from .ssh_mixin import SshMixin
 
 
 log = logging.getLogger(__name__)
 
+# The largest block read_mem returns, larger reads are split
+MEMORY_CHUNK_SIZE = 4096
+
 
 
 # Maybe not useful...
@@ -96,6 +107,16 @@ log = logging.getLogger(__name__)
 #        self._closed.set()
 
 
+class ControlError(Exception):
+    """ Error reply of the debugger, with the JSON-RPC error code (None for local errors).
+    """
+
+    def __init__(self, code, message):
+        super().__init__(f"{message} ({code})" if code is not None else message)
+        self.code = code
+        self.message = message
+
+
 class Configuration(Enum):
     Tracer = 0
     Executor = 1
@@ -108,7 +129,7 @@ class ControlChannel(Thread):
 
     def __init__(self, avatar, server_address, server_port, configuration=Configuration.Tracer,
                  dbg_executable='ptracer', additional_args=[],
-                 origin=None, output_dir='/tmp/cb'):
+                 origin=None, output_dir='/tmp/cb', key=None):
 
         super().__init__()
 
@@ -126,6 +147,20 @@ class ControlChannel(Thread):
         self._conn = None
         self._client_address = None
 
+        # Pre-shared key authenticating the commands, None when the debugger does not require it
+        self._key = key
+        self._session_key = None
+        self._nonce = 0
+        self._recv_nonce = 0
+
+        # The background thread splits what the debugger sends into replies and asynchronous events
+        self._replies = queue.Queue()
+        self._request_id = 0
+        self._lock = Lock()
+        self.breakpoints = {}
+        self.last_stop_registers = {}
+        self._event_callbacks = {}
+
         self._cmdline = [dbg_executable]
         self._cmdline += additional_args
         
@@ -182,52 +217,192 @@ class ControlChannel(Thread):
         try:
             self.socket.connect((self.server_address, self.server_port))
             log.debug(f'Connected with {self.server_address}')
+            reader = self.socket.makefile('r', encoding='utf-8')
+            if self._key is not None:
+                self._authenticate(reader)
             self._conn = self.socket
 
-            while True:
-                # In the future, if needed
-                time.sleep(10)
+            for line in reader:
+                if self._session_key is not None:
+                    line = self._open_line(line)
+                    if line is None:
+                        continue
+                self._dispatch_received(line)
 
         except:
             log.exception(f"Control Channel on port {self.server_port} background thread died")
+        self._replies.put(None)
         log.debug(f"Control Channelon on port {self.server_port} background thread exiting")
 
 
+    def _dispatch_received(self, line):
+        """ Queue the responses and handle the notifications, one JSON-RPC message per line.
+        """
+        try:
+            message = json.loads(line)
+        except json.JSONDecodeError:
+            log.error(f"Invalid message from the debugger: {line!r}")
+            return
+
+        if 'id' in message:
+            self._replies.put(message)
+        elif 'method' in message:
+            self._handle_event(message['method'], message.get('params'))
+        else:
+            log.warning(f"Unknown message: {message}")
+
+
+    def _handle_event(self, method, params):
+        log.debug(f"Event received: {method} {params}")
+
+        if method == 'breakpoint':
+            pid, address = params['pid'], params['address']
+            self.last_stop_registers[pid] = params['registers']
+            if self.queue is not None:
+                number = self.breakpoints.get((pid, address))
+                self.queue.put(BreakpointHitMessage(self._origin, number, address))
+        elif method == 'process_exited':
+            if self.queue is not None:
+                self.queue.put(UpdateStateMessage(self._origin, TargetStates.EXITED))
+        elif method not in self._event_callbacks:
+            log.warning(f"Unknown event: {method}")
+
+        for callback in self._event_callbacks.get(method, []):
+            try:
+                callback(params)
+            except Exception:
+                log.exception(f"Callback of event {method} failed")
+
+
+    def add_event_callback(self, event, callback):
+        """ Call callback(params) from the background thread on each event of this name.
+            The debugger only sends the events subscribed to, see subscribe().
+        """
+        self._event_callbacks.setdefault(event, []).append(callback)
+
+    def remove_event_callback(self, event, callback):
+        self._event_callbacks.get(event, []).remove(callback)
+
+
+    def _authenticate(self, reader):
+        """ Answer the challenge of the debugger with the pre-shared key.
+        """
+        words = reader.readline().split()
+        if len(words) != 2 or words[0] != 'CHALLENGE':
+            raise RuntimeError(f"Expected a challenge, received {words}")
+
+        challenge = bytes.fromhex(words[1])
+        random = os.urandom(16)
+        tag = hmac.new(self._key, b'sysfwd-control' + challenge + random, hashlib.sha256).hexdigest()
+        self.socket.sendall(f"AUTH {random.hex()} {tag}\n".encode('utf-8'))
+
+        # The reply is only signed on success
+        self._session_key = hmac.new(self._key, b'sysfwd-session' + challenge + random, hashlib.sha256).digest()
+        self._nonce = 0
+        self._recv_nonce = 0
+        line = reader.readline()
+        reply = json.loads(self._open_line(line) or line)
+        if 'error' in reply:
+            self._session_key = None
+            raise ControlError(reply['error']['code'], reply['error']['message'])
+        log.debug("Authenticated with the Syscall Debugger")
+
+
+    def _open_line(self, line):
+        """ Check the nonce and the MAC prefixing a line of the debugger, None if the line must be dropped.
+        """
+        parts = line.rstrip('\n').split(' ', 2)
+        if len(parts) != 3 or not parts[0].isdigit():
+            log.error(f"Drop an unsigned line from the debugger: {line!r}")
+            return None
+        nonce, tag, message = int(parts[0]), parts[1], parts[2]
+
+        signed = f"{nonce} {message}".encode('utf-8')
+        expected = hmac.new(self._session_key, b'debugger' + signed, hashlib.sha256).hexdigest()
+        if not hmac.compare_digest(expected, tag):
+            log.error(f"Drop a line with an invalid MAC from the debugger: {line!r}")
+            return None
+        if nonce <= self._recv_nonce:
+            log.error(f"Drop a replayed line with nonce {nonce} from the debugger")
+            return None
+        self._recv_nonce = nonce
+        return message
+
+
     def _send_message(self, message):
+        if self._session_key is not None:
+            # Prefix each command with an increasing nonce and the MAC of both
+            self._nonce += 1
+            signed = f"{self._nonce} {message}"
+            tag = hmac.new(self._session_key, signed.encode('utf-8'), hashlib.sha256).hexdigest()
+            message = f"{self._nonce} {tag} {message}"
         message += '\n'
         message = message.encode('utf-8')
         log.debug(f"Send message: {message}")
         if self._conn:
             self._conn.sendall(message)
         else:
-            log.error("No target connected")
-
-    def _receive_message(self):
-
-        if self._conn:
-            buffer = self._conn.recv(1024)
-            log.debug(f"Message receive: {buffer}")
-
-            if len(buffer) == 0: 
-                log.error("File socket is closed")
-                return None
-
-            return buffer
-            
-        else:
-            log.error("No target connected")
-            return None
-            
+            raise ControlError(None, "No target connected")
 
+    def _receive_message(self, request_id, timeout=10):
+        """ Return the response to the request `request_id`, responses to older requests are dropped.
+        """
+        while True:
+            try:
+                message = self._replies.get(timeout=timeout)
+            except queue.Empty:
+                raise ControlError(None, "No reply from the debugger")
+            log.debug(f"Message receive: {message}")
+
+            if message is None:
+                # Let the other callers know as well
+                self._replies.put(None)
+                raise ControlError(None, "File socket is closed")
+
+            if message.get('id') == request_id or message.get('id') is None:
+                return message
+            log.warning(f"Drop response to request {message.get('id')}")
+
+
+    def _call(self, method, **params):
+        """ Send a JSON-RPC request and return its result, raise a ControlError with the error of the
+            debugger otherwise.
+        """
+        with self._lock:
+            self._request_id += 1
+            request_id = self._request_id
+            request = {'jsonrpc': '2.0', 'id': request_id, 'method': method, 'params': params}
+            self._send_message(json.dumps(request))
+            response = self._receive_message(request_id)
+
+        if 'error' in response:
+            error = response['error']
+            raise ControlError(error.get('code'), error.get('message'))
+        return response.get('result')
+
+
+    def switch_configuration(self, pid=[]):
+        """ Switch the debugger between the tracer and executor roles, taking the processes along.
+            Only supported by a debugger holding both roles. Return the new configuration.
+        """
+        result = self._call('switch', pids=list(pid))
+        self.configuration = Configuration[result['configuration'].capitalize()]
+        return self.configuration
 
-    def switch_configuration(self):
-        # TODO
-        pass
 
+    def set_breakpoint(self, pid, address):
+        """ The hits are reported with a BreakpointHitMessage and the process stays stopped until
+            tracer_cont_tracing.
+        """
+        self._call('set_breakpoint', pid=pid, address=address)
+        number = len(self.breakpoints)
+        self.breakpoints[(pid, address)] = number
+        return number
 
-    def set_breakpoint(self):
-        # TODO
-        pass
+    def remove_breakpoint(self, pid, address):
+        self._call('remove_breakpoint', pid=pid, address=address)
+        self.breakpoints.pop((pid, address), None)
+        return True
 
     def step(self):
         # TODO
@@ -242,47 +417,103 @@ class ControlChannel(Thread):
         pass
 
 
-    def read_registers(self):
-        # TODO
-        pass
+    def read_registers(self, pid):
+        """ Return the registers of the process as a dict name -> value.
+        """
+        return self._call('read_regs', pid=pid)
 
-    def write_registers(self):
-        # TODO
-        pass
+    def write_registers(self, pid, registers):
+        """ Write the given registers, a dict name -> value, the others are left untouched.
+        """
+        self._call('write_regs', pid=pid, registers=registers)
+        return True
 
 
-    def read_memory(self):
-        # TODO
-        pass
+    def read_memory(self, pid, address, size):
+        """ Return the bytes read in the memory of the process.
+        """
+        data = b''
+        while len(data) < size:
+            chunk = min(size - len(data), MEMORY_CHUNK_SIZE)
+            result = self._call('read_mem', pid=pid, address=address + len(data), size=chunk)
+            data += bytes.fromhex(result['data'])
+        return data
+
+    def write_memory(self, pid, address, data):
+        """ Write the bytes in the memory of the process.
+        """
+        self._call('write_mem', pid=pid, address=address, data=bytes(data).hex())
+        return True
 
-    def write_memory(self):
-        # TODO
-        pass
 
+    def subscribe(self, events=['*']):
+        """ Receive the events among breakpoint, process_exited, signal_received, syscall_forwarded,
+            rule_fired, executor_disconnected, syscall_refused and replay_diverged, '*' for all of them.
+            Only the breakpoint hits are sent when connecting. Return the events subscribed to.
+        """
+        return self._call('subscribe', events=list(events))['events']
+
+    def unsubscribe(self, events=['*']):
+        return self._call('unsubscribe', events=list(events))['events']
+
+
+    def get_procmaps(self, pid):
+        """ Return the memory regions of the process as a list of dicts with the keys start, end,
+            permissions (read, write, execute, shared), offset, device, inode and pathname.
+        """
+        return self._call('get_procmaps', pid=pid)['regions']
+
+
+    def list_rules(self, pid):
+        """ Return the filter rules of the traced process by priority, as dicts with the keys index, name
+            and params, and the names of the rules which can be loaded.
+        """
+        result = self._call('list_rules', pid=pid)
+        return result['rules'], result['available']
 
-    def spawn_process(self, program='', args=[]):
+    def load_rule(self, pid, name, params=None, index=None):
+        """ Load the rule `name` of the tracer rule registry, with the lowest priority when index is None.
+            The change is applied once the process is out of its current syscall.
+        """
+        self._call('load_rule', pid=pid, name=name, params=params, index=index)
+        return True
+
+    def unload_rule(self, pid, index):
+        self._call('unload_rule', pid=pid, index=index)
+        return True
+
+    def move_rule(self, pid, from_index, to_index):
+        self._call('move_rule', pid=pid, **{'from': from_index, 'to': to_index})
+        return True
+
+    def gdb_server(self, pid, port=0):
+        """ Start a gdb server for the traced process, any free port when 0, and return its port.
+            gdb connects with "target remote :<port>" while the syscalls keep being forwarded.
+        """
+        return self._call('gdb_server', pid=pid, port=port)['port']
+
+
+    def spawn_process(self, program='', args=[], **config):
         if self.configuration is Configuration.Tracer:
             return self.tracer_spawn_process(program, args)
         else:
-            return self.exec_spawn_process(program, args)
+            return self.exec_spawn_process(program, args, **config)
 
     def tracer_spawn_process(self, program='', args=[]):
-        # spawn_process /path/to/bin args1 args2r
-        cmd = ['spawn_process', program] + args
-        cmd = ' '.join(cmd)
-        self._send_message(cmd)
-        message = self._receive_message()
-        reply = int.from_bytes(message, byteorder='big', signed=True)
-        return reply
-
-    def exec_spawn_process(self, program='', args=[]):
-        # spawn_process /path/to/bin args1 args2r
-        cmd = ['spawn_process', program] + args
-        cmd = ' '.join(cmd)
-        self._send_message(cmd)
-        message = self._receive_message()
-        reply = int.from_bytes(message, byteorder='big', signed=True)
-        return reply
+        return self._call('spawn_process', program=program, args=list(args))['pid']
+
+    def exec_spawn_process(self, program='', args=[], **config):
+        """ Spawn the process executing the syscalls, by default /bin/true without ASLR.
+            The config keys are env, clear_env, cwd, uid, gid, personality, disable_aslr, native, sandbox,
+            record and replay, a native session executes the syscalls in the executor itself.
+            A sandbox, e.g. {'binds': ['/dev/kbuf']}, confines the process to the given paths and devices.
+            record is the file where the executed syscalls are written, replay answers them from such a file
+            instead, e.g. {'trace': 'session.jsonl', 'divergence': 'Resync', 'lookahead': 8}.
+        """
+        params = {key: value for key, value in config.items() if value is not None}
+        if program:
+            params.update(program=program, args=list(args))
+        return self._call('spawn_process', **params)['pid']
 
 
     def kill_process(self, pid=[]):
@@ -292,50 +523,30 @@ class ControlChannel(Thread):
             return self.exec_kill_process(pid)
 
     def tracer_kill_process(self, pid=[]):
-        # kill_process pid1 pid2 ...
-        cmd = ['kill_process'] + list(map(str, pid))
-        cmd = ' '.join(cmd)
-        self._send_message(cmd)
-        message = self._receive_message()
-        reply = str(message)    # wait for ACK string
-        return reply
+        for p in pid:
+            self._call('kill_process', pid=p)
+        return True
 
     def exec_kill_process(self, pid=[]):
-        # kill_process pid1 pid2 ...
-        cmd = ['kill_process'] + list(map(str, pid))
-        cmd = ' '.join(cmd)
-        self._send_message(cmd)
-        message = self._receive_message()
-        reply = str(message)    # wait for ACK string
-        return reply
+        for p in pid:
+            self._call('kill_process', pid=p)
+        return True
 
 
     def tracer_start_tracing(self, pid=[]):
-        # start_tracing pid1 pid2 ...
-        cmd = ['start_tracing'] + list(map(str, pid))
-        cmd = ' '.join(cmd)
-        self._send_message(cmd)
-        message = self._receive_message()
-        reply = str(message)    # wait for ACK string
-        return reply
+        for p in pid:
+            self._call('start_tracing', pid=p)
+        return True
 
     def tracer_cont_tracing(self, pid=[], signal=0):
-        # stop_tracing pid1 pid2 ...
-        cmd = ['stop_tracing'] + list(map(str, pid)) + [signal]
-        cmd = ' '.join(cmd)
-        self._send_message(cmd)
-        message = self._receive_message()
-        reply = str(message)    # wait for ACK string
-        return reply
+        for p in pid:
+            self._call('cont_tracing', pid=p, signal=signal)
+        return True
 
     def tracer_stop_tracing(self, pid=[]):
-        # stop_tracing pid1 pid2 ...
-        cmd = ['stop_tracing'] + list(map(str, pid))
-        cmd = ' '.join(cmd)
-        self._send_message(cmd)
-        message = self._receive_message()
-        reply = str(message)    # wait for ACK string
-        return reply
+        for p in pid:
+            self._call('stop_tracing', pid=p)
+        return True
 
 
 ''' Not needed yet:
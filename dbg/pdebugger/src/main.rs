@@ -0,0 +1,54 @@
+/*
+ * A debugger holding both the ptrace tracer and executor.
+ * Its role is switched from the control channel and the traced or executing processes are taken along,
+ * e.g., a process traced locally becomes the executor of another host's tracee.
+ */
+use std::{
+    env,
+    net::Ipv4Addr,
+};
+
+use sysfwd::protocol::{
+    auth::read_key_file,
+    control::{ Configuration, ControlChannel },
+};
+use ptracer::TraceDebuggerCallback;
+use pexecutor::ExecDebuggerCallback;
+
+
+/* Static variable to change */
+/* File holding a pre-shared key to authenticate the control channel, unset to disable, as for ptracer and pexecutor */
+static KEY_FILE_VAR: &str = "SYSFWD_KEY_FILE";
+
+
+
+fn main()
+{
+    let configuration = match env::args().nth(1).as_deref() {
+        None | Some("tracer") => Configuration::Tracer,
+        Some("executor") => Configuration::Executor,
+        Some(_) => {
+            println!("Usage: [SYSFWD_KEY_FILE=<file>] ./pdebugger [tracer|executor]");
+            return;
+        },
+    };
+
+    // Listen on the control port of the initial role, as ptracer and pexecutor do
+    let ip = Ipv4Addr::new(127, 0, 0, 1);
+    let port: u16 = match configuration {
+        Configuration::Tracer => 31000,
+        Configuration::Executor => 31001,
+    };
+
+    let mut control_channel = ControlChannel::new(configuration,
+                                                  Some(Box::new(TraceDebuggerCallback::new())),
+                                                  Some(Box::new(ExecDebuggerCallback::new())));
+    if let Some(path) = env::var(KEY_FILE_VAR).ok().filter(|path| !path.is_empty()) {
+        control_channel.require_key(read_key_file(&path).expect("Fail to read the pre-shared key"));
+    }
+
+    println!("[DEBUGGER] Start debugger as {:?}...", configuration);
+    control_channel.listen(ip, port);
+
+    println!("[DEBUGGER] Stop debugger.");
+}
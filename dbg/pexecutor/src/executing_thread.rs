@@ -1,10 +1,10 @@
 /*
  *
  */
-use core::ffi::c_void;
 use std::{
+    cell::Cell,
     os::unix::process::CommandExt,
-    process::{ self, Child, Command, Stdio },
+    process::{ self, Child },
     sync::{ 
         Arc,
         mpsc::{ Sender, Receiver },
@@ -15,23 +15,22 @@ use nix::{
     sys::{
         ptrace,
         wait::{ waitpid, WaitStatus},
-        signal::Signal,
+        signal::{ self, Signal },
     },
-    unistd::Pid,
+    unistd::{ Pid, gettid },
 };
-#[cfg(target_os = "linux")]
-use libc;
 
 use sysfwd::{
     sync::Event,
-    arch::TargetArch,
-    memory::{ read_process_memory_maps, print_memory_regions },
-    executor::{ ExecutorEngine, Invoker },
-    targets::{ self, operation::Operation },
+    arch::{ TargetArch, SYSCALL_GADGET },
+    memory::{ read_process_memory_maps, print_memory_regions, find_code },
+    executor::{ ExecutorEngine, Invoker, ChildConfig, NativeInvoker, Policy, Recorder, Replay },
+    protocol::{ data::Server, auth::read_key_file, control::Notifier },
+    targets::{ self, local::Local, operation::{ Operation, OperationRequest } },
 };
 
 use crate::{
-    IP_ADDRESS, TRACER_PORT, EXECUTOR_PORT,
+    IP_ADDRESS, TRACER_PORT, EXECUTOR_PORT, SHM_SOCKET_VAR, KEY_FILE_VAR, POLICY_FILE_VAR, SESSION_TIMEOUT, setting,
 };
 
 
@@ -41,35 +40,78 @@ use crate::{
  */
 #[derive(Debug)]
 pub struct ExecutingThread {
-    tx: Sender<String>,
-    _rx: Receiver<String>,
+    /* The pid of the child once booted, or why it failed */
+    tx: Sender<Result<i32, io::Error>>,
+    rx: Option<Receiver<OperationRequest>>,
 
+    /* How to spawn the child when none is given */
+    config: ChildConfig,
+    child: Option<Child>,
     child_pid: Option<i32>,
+    notifier: Option<Notifier>,
 
     stop: Arc<Event>,
     stopped: Arc<Event>,
+    /* Give the child back once stopped instead of leaving it */
+    release: Arc<Event>,
 }
 
 impl ExecutingThread {
 
-    pub fn new(tx: Sender<String>, rx: Receiver<String>, stop: Arc<Event>, stopped: Arc<Event>) -> Self 
+    pub fn new(tx: Sender<Result<i32, io::Error>>, rx: Receiver<OperationRequest>, stop: Arc<Event>, stopped: Arc<Event>,
+               release: Arc<Event>, notifier: Option<Notifier>) -> Self 
     {
         Self { 
-            tx: tx,
-            _rx: rx,
+            tx,
+            rx: Some(rx),
+            config: ChildConfig::default(),
+            child: None,
             child_pid: None,
-            stop: stop,
-            stopped: stopped,
+            notifier,
+            stop,
+            stopped,
+            release,
         }
     }
 
-    pub fn start(&mut self)
+    pub fn with_config(mut self, config: ChildConfig) -> Self
+    {
+        self.config = config;
+        self
+    }
+
+    /*
+     * Execute the syscalls in a process given by another debugger thread instead of spawning one,
+     * see ExecutorCallback::adopt_process.
+     */
+    pub fn with_child(mut self, child: Child) -> Self
+    {
+        self.child = Some(child);
+        self
+    }
+
+    /*
+     * Return the child when the thread is stopped to release it.
+     */
+    pub fn start(&mut self) -> Option<Child>
     {
-        let executor = self.boot_thread().expect("Fail to boot executing thread");
+        let executor = match self.boot_thread() {
+            Ok(executor) => executor,
+            Err(err) => {
+                println!("Fail to boot executing thread: {}", err);
+                let _ = self.tx.send(Err(err));
+                return None;
+            },
+        };
 
         let executor = self.run_thread(executor).unwrap();
 
         self.shutdown_thread(executor).unwrap();
+
+        match self.release.is_set() {
+            true => self.release_child().map_err(|err| println!("Fail to release the child: {}", err)).ok(),
+            false => None,
+        }
     }
 
     /*
@@ -79,9 +121,6 @@ impl ExecutingThread {
     {
         println!("Executing thread {} booting...", process::id());
 
-        let mut invoker = ExecInvoker::new();
-        self.spawn_child(&mut invoker)?;
-
         let copy_stop = self.stop.clone();
         let copy_stopped = self.stopped.clone();
         let ptrace_op = targets::ptrace::Ptrace{ };
@@ -89,16 +128,85 @@ impl ExecutingThread {
         let mem_op = Box::new(ptrace_op);
         let operator = Box::new(Operation{ register: regs_op, memory: mem_op});
 
-        let executor = ExecutorEngine::new(TargetArch::X86_64,
-                                                               IP_ADDRESS,
-                                                               EXECUTOR_PORT,
-                                                               TRACER_PORT,
-                                                               copy_stop,
-                                                               copy_stopped,
-                                                               operator,
-                                                               Box::new(invoker),
-                                                               self.child_pid.unwrap(),
-                                                              );
+        let protocol = match setting(SHM_SOCKET_VAR) {
+            Some(path) => Server::new_shared_memory(&path)?,
+            None => Server::new(IP_ADDRESS, EXECUTOR_PORT, TRACER_PORT),
+        };
+        let protocol = match setting(KEY_FILE_VAR) {
+            Some(path) => protocol.with_key(&read_key_file(&path)?),
+            None => protocol,
+        };
+        let policy = setting(POLICY_FILE_VAR);
+
+        let mut invoker = ExecInvoker::new();
+        let mut executor = match self.child.is_some() {
+            false if self.config.native => {
+                if self.config.sandbox.is_some() {
+                    return Err(io::Error::new(io::ErrorKind::Unsupported, "A native session cannot be sandboxed"));
+                }
+                // The session is identified by the thread executing its syscalls
+                let tid = gettid().as_raw();
+                println!("Executing the syscalls of session {} natively", tid);
+                let operator = Box::new(Operation{ register: Box::new(Local{ }), memory: Box::new(Local{ }) });
+                // The paths are only resolved in the executor when the policy checks them
+                let native = match policy {
+                    Some(_) => NativeInvoker::new().with_paths(),
+                    None => NativeInvoker::new(),
+                };
+                self.child_pid = Some(tid);
+                ExecutorEngine::new(TargetArch::X86_64,
+                                    protocol,
+                                    copy_stop,
+                                    copy_stopped,
+                                    operator,
+                                    Box::new(native),
+                                    tid,
+                                   )
+            },
+            true => {
+                self.attach_child(&mut invoker)?;
+                ExecutorEngine::new(TargetArch::X86_64,
+                                    protocol,
+                                    copy_stop,
+                                    copy_stopped,
+                                    operator,
+                                    Box::new(invoker),
+                                    self.child_pid.unwrap(),
+                                   )
+            },
+            false => {
+                println!("Spawning {} {:?}", self.config.program, self.config.args);
+                let (executor, child) = ExecutorEngine::spawn(TargetArch::X86_64,
+                                                              protocol,
+                                                              copy_stop,
+                                                              copy_stopped,
+                                                              operator,
+                                                              Box::new(invoker),
+                                                              &self.config,
+                                                             )?;
+                self.child_pid = Some(child.id() as i32);
+                self.child = Some(child);
+                executor
+            },
+        };
+
+        if let Some(path) = policy {
+            executor.set_policy(Policy::from_file(&path)?);
+        }
+        if let Some(timeout) = SESSION_TIMEOUT {
+            executor.set_session_timeout(timeout);
+        }
+        if let Some(path) = self.config.record.as_ref() {
+            executor.set_recorder(Recorder::create(path)?);
+        }
+        if let Some(replay) = self.config.replay.clone() {
+            println!("Replaying the syscalls recorded in {}", replay.trace);
+            executor.set_replay(Replay::new(replay)?);
+        }
+        executor.set_request_channel(self.rx.take().unwrap());
+        if let Some(notifier) = self.notifier.take() {
+            executor.set_notifier(notifier);
+        }
 
         /* Show initial memory layout */
         let pid = self.child_pid.unwrap() as u32;
@@ -106,22 +214,43 @@ impl ExecutingThread {
         print_memory_regions(&mem);
 
         // Send the PID of the child to the control thread
-        self.tx.send(pid.to_string()).unwrap();
+        self.tx.send(Ok(pid as i32)).unwrap();
 
         Ok(executor)
     }
 
-    fn spawn_child(&mut self, invoker: &mut ExecInvoker) -> Result<(), io::Error>
+    /*
+     * The child is stopped, the pending SIGSTOP of PTRACE_ATTACH is suppressed by the invoker.
+     */
+    fn attach_child(&mut self, invoker: &mut ExecInvoker) -> Result<(), io::Error>
     {
-        let child = invoker.invoke_new_process().expect("Failed to spawn child process");
-        let pid = child.id() as i32;
+        let pid = Pid::from_raw(self.child.as_ref().unwrap().id() as i32);
+        println!("Attaching {}", pid);
 
-        invoker.child = Some(child);
-        self.child_pid = Some(pid);
+        ptrace::attach(pid)?;
+        waitpid(pid, None)?;
 
+        invoker.pid = Some(pid);
+        self.child_pid = Some(pid.as_raw());
         Ok(())
     }
 
+    /*
+     * Detach from the child between two syscalls with a pending SIGSTOP so that it stays stopped.
+     */
+    fn release_child(&mut self) -> Result<Child, io::Error>
+    {
+        if self.child.is_none() {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "A native session has no process to release"));
+        }
+        let pid = Pid::from_raw(self.child_pid.unwrap());
+        println!("Releasing {}", pid);
+
+        signal::kill(pid, Signal::SIGSTOP)?;
+        ptrace::detach(pid, None)?;
+        Ok(self.child.take().unwrap())
+    }
+
     fn run_thread(&self, mut executor: ExecutorEngine) -> Result<ExecutorEngine, io::Error>
     {
         executor.run();
@@ -142,14 +271,31 @@ impl ExecutingThread {
 
 
 struct ExecInvoker {
-    child: Option<Child>,
+    pid: Option<Pid>,
+    /* Where the syscalls are executed in the child, found on the first one */
+    gadget: Cell<Option<u64>>,
  }
 
 impl ExecInvoker {
 
     fn new() -> Self
     {
-        Self { child: None }
+        Self { pid: None, gadget: Cell::new(None) }
+    }
+
+    /*
+     * An existing syscall instruction of the child is used instead of patching its code,
+     * so that nothing is written in its read-only text.
+     */
+    fn gadget(&self, pid: Pid) -> Result<u64, io::Error>
+    {
+        if let Some(address) = self.gadget.get() {
+            return Ok(address);
+        }
+        let address = find_code(pid.as_raw(), SYSCALL_GADGET)?;
+        println!("Syscalls of {} invoked at {:#x}", pid, address);
+        self.gadget.set(Some(address));
+        Ok(address)
     }
 }
 
@@ -161,20 +307,11 @@ impl Invoker for ExecInvoker {
                       -> Result<(usize, usize), io::Error>
     {
         /* Setup the register context */        
-        let pid = self.child.as_ref().unwrap().id() as i32;
-        let pid = Pid::from_raw(pid);
-        let mut regs = ptrace::getregs(pid).unwrap();
-        let saved_regs = regs.clone();
-
-        // setup syscall instruction somewhere
-        let address = 0x555555555000;
-        let addr = address as ptrace::AddressType;
-        let word = 0x9090050f as *mut c_void;
-        let saved_word = ptrace::read(pid, addr).unwrap() as *mut c_void;
-        unsafe {
-            ptrace::write(pid, addr, word).unwrap();
-        }
-        regs.rip = address;
+        let pid = self.pid.unwrap();
+        let mut regs = ptrace::getregs(pid)?;
+        let saved_regs = regs;
+
+        regs.rip = self.gadget(pid)?;
         
         // syscall registers:
         // x86_64 specific
@@ -187,27 +324,10 @@ impl Invoker for ExecInvoker {
         regs.r8  = arg5 as u64;
         regs.r9  = arg6 as u64;
         // nothing on the stack
-        ptrace::setregs(pid, regs).unwrap();
-
-        
-        // Debug:
-        //let regs2 = ptrace::getregs(pid).unwrap();
-        //println!("regs before step: {:?}", regs2);
-        /* small checks on memory
-        let path_addr = arg2 as *mut c_void;
-        let mut pathname: Vec<u8> = Vec::new();
-        let mut count = 12;
-        while count > 0 {
-            let word: u32 = ptrace::read(pid, path_addr).unwrap() as u32;
-            pathname.extend_from_slice(&word.to_le_bytes());
-            count -= 4;
-        }
-        println!("pathname: {:?}", pathname);
-        println!("pathname: {}", std::str::from_utf8(&pathname).unwrap());
-        */
+        ptrace::setregs(pid, regs)?;
 
         /* Invoke the syscall */
-        ptrace::step(pid, None).unwrap();
+        ptrace::step(pid, None)?;
 
         match waitpid(pid, None) {
             Err(err) => panic!("Oops something happens when waiting: {}", err),
@@ -216,8 +336,13 @@ impl Invoker for ExecInvoker {
                     WaitStatus::Stopped(pid, signo) => {
                         match signo {
                             Signal::SIGTRAP => (), // the syscall returned
+                            // The SIGSTOP left by a previous debugger stops the child before the instruction
+                            Signal::SIGSTOP => {
+                                ptrace::setregs(pid, saved_regs)?;
+                                return self.invoke_syscall(scno, arg1, arg2, arg3, arg4, arg5, arg6, _arg7);
+                            },
                             Signal::SIGSEGV => {
-                                let regs = ptrace::getregs(pid).unwrap();
+                                let regs = ptrace::getregs(pid)?;
                                 println!("Tracee {} segfault at {:#x}", pid, regs.rip);
                             },
                             _ => panic!("Tracee {} received signal {} which is not handled", pid, signo),
@@ -232,41 +357,39 @@ impl Invoker for ExecInvoker {
         }
 
         /* Capture what changed */
-        let regs = ptrace::getregs(pid).unwrap();
-        // Debug:
-        //println!("exit regs: {:?}", regs);
+        let regs = ptrace::getregs(pid)?;
         let retval = regs.rax as usize;
         let errno = regs.rdx as usize;
 
         /* Restore the context (optional) */
-        ptrace::setregs(pid, saved_regs).unwrap();
-        unsafe {
-            ptrace::write(pid, addr, saved_word).unwrap();
-        }
+        ptrace::setregs(pid, saved_regs)?;
 
         Ok((retval, errno))
 
     }
 
 
-    fn invoke_new_process(&self) -> Result<Child, io::Error>
+    /*
+     * The child is stopped on its exec, after its personality has been set.
+     */
+    fn invoke_new_process(&mut self, config: &ChildConfig) -> Result<Child, io::Error>
     {
-        let program = "/bin/true";
-        let mut command = Command::new(program);
-        command.stdout(Stdio::inherit());
-        command.stderr(Stdio::inherit());
+        let mut command = config.command()?;
 
         unsafe {
             command.pre_exec(|| {
-                ptrace::traceme().unwrap();
-                // Disable ASLR
-                libc::personality(libc::ADDR_NO_RANDOMIZE.try_into().unwrap());
+                ptrace::traceme()?;
                 Ok(())
             });
         }
 
         let child = command.spawn()?;
 
+        let pid = Pid::from_raw(child.id() as i32);
+        waitpid(pid, None)?;
+        self.pid = Some(pid);
+        self.gadget.set(None);
+
         Ok(child)
     }
 }
\ No newline at end of file
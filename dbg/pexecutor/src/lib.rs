@@ -0,0 +1,299 @@
+/*
+ *
+ */
+mod executing_thread;
+
+
+use std::{
+    collections::HashMap,
+    thread::{ Builder, JoinHandle },
+    process::Child,
+    sync::{ 
+        Arc,
+        mpsc::{ channel, Sender, Receiver },
+    },
+    net::Ipv4Addr,
+    io,
+    env,
+    time::Duration,
+};
+
+use nix::{
+    unistd::Pid,
+    libc::user_regs_struct,
+};
+
+use sysfwd::{
+    sync::Event,
+    protocol::{
+        auth::read_key_file,
+        control::{ Configuration, ControlChannel, Notifier },
+    },
+    executor::{ ExecutorCallback, ChildConfig },
+    targets::operation::OperationRequest,
+};
+
+use crate::executing_thread::ExecutingThread;
+
+
+/* Static variable to change */
+static IP_ADDRESS: &str = "127.0.0.1";
+//static CONTROL_PORT: u16 = 31000;
+static TRACER_PORT: u16 = 32000;
+static EXECUTOR_PORT: u16 = 32001;
+/* Use the shared memory transport through this UNIX socket instead of UDP when both run on the same host */
+static SHM_SOCKET_VAR: &str = "SYSFWD_SHM_SOCKET";
+/* File holding a pre-shared key to authenticate the data and control channels, unset to disable */
+static KEY_FILE_VAR: &str = "SYSFWD_KEY_FILE";
+/* JSON file listing the syscalls, paths and devices the executor accepts, see sysfwd::executor::Policy */
+static POLICY_FILE_VAR: &str = "SYSFWD_POLICY_FILE";
+/* Release what a session created once the tracer is silent for this long, its heartbeat is sent every second */
+static SESSION_TIMEOUT: Option<Duration> = Some(Duration::from_secs(10));
+
+
+
+/*
+ * The value of a setting given in the environment, unset or empty for none.
+ */
+fn setting(name: &str) -> Option<String>
+{
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+
+
+/*
+ * The debugger is the high-level structure which manage the executing threads and the connection with the python commands.
+ */
+pub struct ExecDebugger {
+    control_channel: ControlChannel,
+}
+
+impl ExecDebugger {
+
+    pub fn new() -> Self
+    {
+        let mut control_channel = ControlChannel::new(Configuration::Executor, None, Some(Box::new(ExecDebuggerCallback::new())));
+        if let Some(path) = setting(KEY_FILE_VAR) {
+            control_channel.require_key(read_key_file(&path).expect("Fail to read the pre-shared key"));
+        }
+
+        Self {
+            control_channel,
+        }
+    }
+
+    pub fn run(&mut self)
+    {
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        let port: u16 = 31001;
+
+        self.control_channel.listen(ip, port);
+    }
+}
+
+impl Default for ExecDebugger {
+
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+
+/*
+ * A structure used to control an executing thread.
+ */
+struct ThreadCtrl {
+    handler: JoinHandle<Option<Child>>,
+    tx: Sender<OperationRequest>,
+    rx: Receiver<Result<i32, io::Error>>,
+    stop: Arc<Event>,
+    stopped: Arc<Event>,
+    release: Arc<Event>,
+}
+
+
+pub struct ExecDebuggerCallback {
+    thread_map: HashMap<Pid, ThreadCtrl>,
+    notifier: Option<Notifier>,
+}
+
+impl ExecDebuggerCallback {
+
+    pub fn new() -> Self
+    {
+        Self {
+            thread_map: HashMap::new(),
+            notifier: None,
+        }
+    }
+
+    /*
+     * Send an operation to the thread executing syscalls in `pid` and wait for the result.
+     */
+    fn request<T, F>(&mut self, pid: Pid, build: F) -> Result<T, io::Error>
+        where F: FnOnce(Sender<T>) -> OperationRequest
+    {
+        let thread = match self.thread_map.get(&pid) {
+            Some(thread) => thread,
+            None => return Err(io::Error::other("No such pid")),
+        };
+
+        let (reply_tx, reply_rx) = channel();
+        thread.tx.send(build(reply_tx))
+            .map_err(|_| io::Error::other("Executing thread terminated"))?;
+
+        reply_rx.recv().map_err(|_| io::Error::other("Executing thread terminated"))
+    }
+
+    /*
+     * Run an executing thread, either in a new child or in the given one, and wait for the pid of the child.
+     */
+    fn start_thread(&mut self, config: ChildConfig, child: Option<Child>) -> Result<Pid, io::Error>
+    {
+        let (tx_ctrl, rx_ctrl) = channel();
+        let (tx_thread, rx_thread) = channel();
+        let stop = Arc::new(Event::new());
+        let stop_clone = stop.clone();
+        let stopped = Arc::new(Event::new());
+        let stopped_clone = stopped.clone();
+        let release = Arc::new(Event::new());
+        let release_clone = release.clone();
+
+        let mut executing_thread = ExecutingThread::new(tx_thread, rx_ctrl, stop, stopped, release, self.notifier.clone())
+            .with_config(config);
+        if let Some(child) = child {
+            executing_thread = executing_thread.with_child(child);
+        }
+
+        /* Creat thread and start it */
+        let builder = Builder::new();
+        let handler = builder.spawn(move ||
+            executing_thread.start()
+        ).unwrap();
+
+        let thread_ctrl = ThreadCtrl { 
+            handler,
+            tx: tx_ctrl,
+            rx: rx_thread,
+            stop: stop_clone,
+            stopped: stopped_clone,
+            release: release_clone,
+        };
+
+        let pid = thread_ctrl.rx.recv().map_err(|_| io::Error::other("Executing thread terminated"))??;
+        let pid = Pid::from_raw(pid);
+        self.thread_map.insert(pid, thread_ctrl);
+        
+        Ok(pid)
+    }
+}
+
+impl Default for ExecDebuggerCallback {
+
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+impl ExecutorCallback for ExecDebuggerCallback {
+
+    fn spawn_process(&mut self, config: ChildConfig) -> Result<Pid, io::Error>
+    {
+        println!("Creating new executing thread...");
+        self.start_thread(config, None)
+    }
+
+    fn release_process(&mut self, pid: Pid) -> Result<Child, io::Error>
+    {
+        println!("* Release process {:?} *", pid);
+
+        let thread = self.thread_map.remove(&pid)
+            .ok_or(io::Error::other("No such pid"))?;
+        thread.release.set();
+        if ! thread.stopped.is_set() {
+            thread.stop.set();
+            thread.stopped.wait();
+        }
+
+        match thread.handler.join() {
+            Ok(Some(child)) => Ok(child),
+            Ok(None) => Err(io::Error::other("Fail to release the process")),
+            Err(err) => {
+                println!("Couldn't joind the thread {}: {:?}", pid, err);
+                Err(io::Error::other("Couldn't join the thread"))
+            },
+        }
+    }
+
+    fn adopt_process(&mut self, child: Child) -> Result<Pid, io::Error>
+    {
+        println!("Creating new executing thread for process {}...", child.id());
+        self.start_thread(ChildConfig::default(), Some(child))
+    }
+
+    fn set_notifier(&mut self, notifier: Notifier)
+    {
+        self.notifier = Some(notifier);
+    }
+
+    fn kill_process(&mut self, pid: Pid) -> Result<(), io::Error>
+    {
+        println!("* Kill process {:?} *", pid);
+        
+        // TODO
+        match self.thread_map.remove(&pid) {
+            Some(thread) => {
+
+                println!("stopping..");
+                if ! thread.stopped.is_set() {
+                    thread.stop.set();
+                    thread.stopped.wait();
+                }
+
+                match thread.handler.join() {
+                    Ok(_) => { },
+                    Err(err) => {
+                        println!("Couldn't joind the thread {}: {:?}", pid, err);
+                        return Err(io::Error::other("Couldn't join the thread"))
+                    }
+                }
+            },
+
+            None => {
+                // nix error: ESRC
+                println!("Error: No such process: {}", pid);
+                return Err(io::Error::other("No such pid"))
+            }
+        }
+        Ok(())
+    }
+
+    fn has_process(&self, pid: Pid) -> bool
+    {
+        self.thread_map.contains_key(&pid)
+    }
+
+    fn read_memory(&mut self, pid: Pid, address: usize, size: usize) -> Result<Vec<u8>, io::Error>
+    {
+        self.request(pid, |reply| OperationRequest::ReadMemory { address, size, reply })
+    }
+
+    fn write_memory(&mut self, pid: Pid, address: usize, data: Vec<u8>) -> Result<usize, io::Error>
+    {
+        self.request(pid, |reply| OperationRequest::WriteMemory { address, data, reply })
+    }
+
+    fn read_registers(&mut self, pid: Pid) -> Result<user_regs_struct, io::Error>
+    {
+        self.request(pid, |reply| OperationRequest::ReadRegisters { reply })?
+            .ok_or(io::Error::other("Fail to read registers"))
+    }
+
+    fn write_registers(&mut self, pid: Pid, regs: user_regs_struct) -> Result<(), io::Error>
+    {
+        self.request(pid, |reply| OperationRequest::WriteRegisters { regs, reply })?
+    }
+}
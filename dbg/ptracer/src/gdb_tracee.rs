@@ -0,0 +1,158 @@
+/*
+ * The tracee of a tracing thread exposed to a gdb client.
+ * Like the control thread, the requests go through the tracing thread which keeps forwarding the syscalls.
+ */
+
+use std::{
+    io::{ self, ErrorKind },
+    sync::mpsc::{ channel, Sender, Receiver, RecvTimeoutError },
+    time::Duration,
+};
+
+use nix::{
+    sys::signal::{ self, Signal },
+    unistd::Pid,
+    libc::user_regs_struct,
+};
+
+use sysfwd::{
+    protocol::gdb::{ GdbTarget, StopReason },
+    targets::operation::OperationRequest,
+};
+
+use crate::tracing_thread::{ ThreadRequest, Interrupts };
+
+
+
+pub struct GdbTracee {
+    pid: Pid,
+    tx: Sender<ThreadRequest>,
+    /* The stops reported by the tracing thread */
+    stops: Receiver<StopReason>,
+    interrupts: Interrupts,
+}
+
+impl GdbTracee {
+
+    /*
+     * Ask the tracing thread to report the stops of the tracee, until another client attaches.
+     */
+    pub fn attach(pid: Pid, tx: Sender<ThreadRequest>, interrupts: Interrupts) -> Result<Self, io::Error>
+    {
+        let (stops_tx, stops) = channel();
+        tx.send(ThreadRequest::AttachGdb { stops: stops_tx }).map_err(|_| terminated())?;
+        Ok(Self { pid, tx, stops, interrupts })
+    }
+
+    fn send(&self, request: ThreadRequest) -> Result<(), io::Error>
+    {
+        self.tx.send(request).map_err(|_| terminated())
+    }
+
+    /*
+     * The tracee is stopped, held by the tracing thread which serves the request right away.
+     */
+    fn request<T, F>(&self, build: F) -> Result<T, io::Error>
+        where F: FnOnce(Sender<T>) -> ThreadRequest
+    {
+        let (reply_tx, reply_rx) = channel();
+        self.send(build(reply_tx))?;
+        reply_rx.recv().map_err(|_| terminated())
+    }
+
+    fn operation<T, F>(&self, build: F) -> Result<T, io::Error>
+        where F: FnOnce(Sender<T>) -> OperationRequest
+    {
+        self.request(|reply| ThreadRequest::Operation(build(reply)))
+    }
+}
+
+fn terminated() -> io::Error
+{
+    io::Error::new(ErrorKind::BrokenPipe, "Tracing thread terminated")
+}
+
+impl GdbTarget for GdbTracee {
+
+    fn pid(&self) -> Pid
+    {
+        self.pid
+    }
+
+    /*
+     * Like TraceDebuggerCallback::request_thread, the SIGSTOP makes the tracing thread serve the request
+     * even when the tracee does no syscall.
+     */
+    fn interrupt(&mut self) -> io::Result<()>
+    {
+        self.send(ThreadRequest::Halt)?;
+        self.interrupts.send(self.pid)?;
+        Ok(())
+    }
+
+    fn resume(&mut self, signal: Option<Signal>) -> io::Result<()>
+    {
+        self.send(ThreadRequest::Continue(signal))
+    }
+
+    fn step(&mut self, signal: Option<Signal>) -> io::Result<()>
+    {
+        self.send(ThreadRequest::Step(signal))
+    }
+
+    fn wait_stop(&mut self, timeout: Duration) -> io::Result<Option<StopReason>>
+    {
+        match self.stops.recv_timeout(timeout) {
+            Ok(stop) => Ok(Some(stop)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(terminated()),
+        }
+    }
+
+    fn read_registers(&mut self) -> io::Result<user_regs_struct>
+    {
+        self.operation(|reply| OperationRequest::ReadRegisters { reply })?
+            .ok_or(io::Error::new(ErrorKind::InvalidData, "Fail to read registers"))
+    }
+
+    fn write_registers(&mut self, regs: user_regs_struct) -> io::Result<()>
+    {
+        self.operation(|reply| OperationRequest::WriteRegisters { regs, reply })?
+    }
+
+    fn read_memory(&mut self, address: usize, size: usize) -> io::Result<Vec<u8>>
+    {
+        self.operation(|reply| OperationRequest::ReadMemory { address, size, reply })
+    }
+
+    fn write_memory(&mut self, address: usize, data: Vec<u8>) -> io::Result<usize>
+    {
+        self.operation(|reply| OperationRequest::WriteMemory { address, data, reply })
+    }
+
+    fn set_breakpoint(&mut self, address: usize) -> io::Result<()>
+    {
+        self.request(|reply| ThreadRequest::SetBreakpoint { address, reply })?
+    }
+
+    fn remove_breakpoint(&mut self, address: usize) -> io::Result<()>
+    {
+        self.request(|reply| ThreadRequest::RemoveBreakpoint { address, reply })?
+    }
+
+    fn detach(&mut self) -> io::Result<()>
+    {
+        self.send(ThreadRequest::DetachGdb)?;
+        self.send(ThreadRequest::Continue(None))
+    }
+
+    /*
+     * The tracing thread sees the tracee killed once it resumes it.
+     */
+    fn kill(&mut self) -> io::Result<()>
+    {
+        signal::kill(self.pid, Signal::SIGKILL)?;
+        self.send(ThreadRequest::DetachGdb)?;
+        self.send(ThreadRequest::Continue(None))
+    }
+}
@@ -0,0 +1,456 @@
+/*
+ * Example to use libsysfwd with ptrace.
+ * Works with an executor instance.
+ *
+ * The library part is shared with the debuggers holding both roles.
+ */
+
+mod tracing_thread;
+mod gdb_tracee;
+
+
+use std::{
+    collections::HashMap,
+    thread::{ Builder, JoinHandle },
+    sync::{ 
+        mpsc::{ channel, Sender, Receiver },
+    },
+    process::Child,
+    io::{self, ErrorKind },
+    net::{ Ipv4Addr, TcpListener },
+    env,
+};
+
+use nix::{
+    sys::{
+        ptrace,
+        signal::Signal,
+    },
+    unistd::Pid,
+    libc::user_regs_struct,
+};
+use serde_json::Value;
+
+use sysfwd::{
+    protocol::{
+        auth::read_key_file,
+        control::{ Configuration, ControlChannel, Notifier },
+        gdb::GdbServer,
+    },
+    tracer::{ TracerCallback, filtering::RuleInfo },
+    targets::operation::OperationRequest,
+};
+use sysfwd_filter::RuleRegistry;
+
+use crate::{
+    tracing_thread::{ TracingThread, ThreadRequest, Interrupts },
+    gdb_tracee::GdbTracee,
+};
+
+
+/* Static variable to change */
+static IP_ADDRESS: &str = "127.0.0.1";
+//static CONTROL_PORT: u16 = 31000;
+static TRACER_PORT: u16 = 32000;
+static EXECUTOR_PORT: u16 = 32001;
+/* Use the shared memory transport through this UNIX socket instead of UDP when both run on the same host */
+static SHM_SOCKET_VAR: &str = "SYSFWD_SHM_SOCKET";
+/* File holding a pre-shared key to authenticate the data and control channels, unset to disable */
+static KEY_FILE_VAR: &str = "SYSFWD_KEY_FILE";
+/* Make the executor child adopt the credentials, umask, current directory and limits of the tracee */
+static MIRROR_CONTEXT: bool = false;
+
+
+
+/*
+ * The value of a setting given in the environment, unset or empty for none.
+ */
+fn setting(name: &str) -> Option<String>
+{
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+
+
+/*
+ * The debugger is the high-level structure which manage the tracing threads and connection with the executor.
+ */
+pub struct TraceDebugger {
+    control_channel: ControlChannel,
+}
+
+
+impl TraceDebugger {
+
+    pub fn new() -> Self
+    {
+        // TODO: configure with ptrace?
+
+        /* HERE !!!
+         * The idea is to be able to call from the control channel some functions from the debugger,
+         * such as spawn_process, kill_process, start_tracing, read_mem, write_regs, set_breakpoint, etc.
+         * 
+         * For that, we need a callback mechanisms to "register" or refer to the right function within the 
+         * control_channel object.
+         * 
+         * We could use:
+         *      1. function pointers
+         *      2. callback with closure
+         *      3. Trait
+         *      4. Rc<RefCell<>>
+         * 
+         */
+        let mut control_channel = ControlChannel::new(Configuration::Tracer, Some(Box::new(TraceDebuggerCallback::new())), None);
+        if let Some(path) = setting(KEY_FILE_VAR) {
+            control_channel.require_key(read_key_file(&path).expect("Fail to read the pre-shared key"));
+        }
+
+        Self {
+            control_channel,
+        }
+    }
+
+    pub fn run(&mut self)
+    {
+        let ip = Ipv4Addr::new(127, 0, 0, 1);
+        let port: u16 = 31000;
+
+        //self.control_channel.connect(ip, port).unwrap();
+
+        self.control_channel.listen(ip, port);
+    }
+}
+
+impl Default for TraceDebugger {
+
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+
+/*
+ * A structure used to control tracing thread.
+ */
+struct ThreadCtrl {
+    handler: JoinHandle<()>,
+    tx: Sender<ThreadRequest>,
+    rx: Receiver<String>,
+    started: bool,
+    interrupts: Interrupts,
+    /* The port of the gdb server of the tracee, if started */
+    gdb_port: Option<u16>,
+}
+
+pub struct TraceDebuggerCallback {
+    thread_map: HashMap<Pid, ThreadCtrl>,
+    notifier: Option<Notifier>,
+    /* The rules which can be loaded in the tracing threads */
+    registry: RuleRegistry,
+}
+
+impl TraceDebuggerCallback {
+    pub fn new() -> Self {
+        Self {
+            thread_map: HashMap::new(),
+            notifier: None,
+            registry: RuleRegistry::with_builtin_rules(),
+        }
+    }
+
+    fn send_to_thread(&self, pid: Pid, request: ThreadRequest) -> Result<(), io::Error>
+    {
+        let thread = match self.thread_map.get(&pid) {
+            Some(thread) => thread,
+            None => return Err(io::Error::other("No such pid")),
+        };
+        thread.tx.send(request).map_err(|_| io::Error::other("Tracing thread terminated"))
+    }
+
+    /*
+     * Send a request to the thread tracing `pid` and wait for the result.
+     * Once the tracing has started, the tracee is interrupted with a SIGSTOP in case it does not do any syscall.
+     */
+    fn request_thread<T, F>(&mut self, pid: Pid, build: F) -> Result<T, io::Error>
+        where F: FnOnce(Sender<T>) -> ThreadRequest
+    {
+        let (reply_tx, reply_rx) = channel();
+        self.send_to_thread(pid, build(reply_tx))?;
+
+        if let Some(thread) = self.thread_map.get(&pid).filter(|thread| thread.started) {
+            thread.interrupts.send(pid)?;
+        }
+
+        reply_rx.recv().map_err(|_| io::Error::other("Tracing thread terminated"))
+    }
+
+    /*
+     * Run the tracing thread and wait for the pid of its tracee.
+     */
+    fn start_thread(&mut self, mut tracing_thread: TracingThread, tx_ctrl: Sender<ThreadRequest>, rx_thread: Receiver<String>) -> Result<Pid, io::Error>
+    {
+        /* Create thread and start it */
+        let interrupts = tracing_thread.interrupts();
+        let builder = Builder::new();
+        let handler = builder.spawn(move ||
+            tracing_thread.start()
+        ).unwrap();
+        
+        let thread_ctrl = ThreadCtrl {
+            handler,
+            tx: tx_ctrl,
+            rx: rx_thread,
+            started: false,
+            interrupts,
+            gdb_port: None,
+        };
+
+        let pid = thread_ctrl.rx.recv().map_err(|_| io::Error::other("Tracing thread terminated"))?;
+        let pid = pid.parse().unwrap();
+        let pid = Pid::from_raw(pid);
+        self.thread_map.insert(pid, thread_ctrl);
+
+        Ok(pid)
+    }
+
+    /*
+     * Send an operation on the tracee to the thread tracing `pid`, see request_thread.
+     */
+    fn request<T, F>(&mut self, pid: Pid, build: F) -> Result<T, io::Error>
+        where F: FnOnce(Sender<T>) -> OperationRequest
+    {
+        self.request_thread(pid, |reply| ThreadRequest::Operation(build(reply)))
+    }
+}
+
+impl Default for TraceDebuggerCallback {
+
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+impl TracerCallback for TraceDebuggerCallback {
+
+    fn spawn_process(&mut self, program: String, prog_args: Vec<String>) -> Result<Pid, io::Error>
+    {
+        println!("Creating new tracing thread...");
+
+        let (tx_ctrl, rx_ctrl) = channel();
+        let (tx_thread, rx_thread) = channel();
+        let tracing_thread = TracingThread::new(program, prog_args, tx_thread, rx_ctrl, self.notifier.clone(), self.registry.clone());
+        let pid = self.start_thread(tracing_thread, tx_ctrl, rx_thread)?;
+
+        // Notify the executor
+        //self.notify_new_process();
+
+        Ok(pid)
+    }
+
+    fn kill_process(&mut self, pid: Pid) -> Result<(), io::Error>
+    {
+        println!("* Kill process {:?} *", pid);
+        
+        match self.thread_map.remove(&pid) {
+            Some(thread) => {
+                println!("killing...");
+                // TODO: Instead, ask the tracing thread to kill the process using Child.kill()?
+                // TODO: Check the process still lives... otherwise ESRCH...
+                //ptrace::kill(pid).unwrap(); // panic when ESRCH !
+                match ptrace::kill(pid) {
+                    Ok(()) => { },
+                    Err(err) => {  
+                        // ESRCH ?
+                        println!("Couldn't kill process: {}", err);
+                    },
+                }
+
+                println!("joining...");
+                //thread.handler.join().unwrap();
+                match thread.handler.join() {
+                    Ok(()) => { },
+                    Err(err) => {
+                        println!("Couldn't joind the thread {}: {:?}", pid, err);
+                        return Err(io::Error::other("Couldn't join the thread"))
+                    }
+                }
+                println!("kill command finished");
+            },
+
+            None => {
+                // nix error: ESRC
+                println!("Error: No such process: {}", pid);
+                return Err(io::Error::other("No such pid"))
+            }
+        }
+        Ok(())
+    }
+
+    fn start_tracing(&mut self, pid: Pid) -> Result<(), io::Error>
+    {
+        println!("* Trace process {:?} *", pid);
+
+        match self.thread_map.get_mut(&pid) {
+            Some(thread) => {
+                println!("Starting tracing thread for {}", pid);
+                thread.tx.send(ThreadRequest::Start)
+                    .map_err(|_| io::Error::other("Tracing thread terminated"))?;
+                thread.started = true;
+            },
+
+            None => {
+                // nix error: ESRC
+                println!("Error: No such running process: {}", pid);
+                return Err(io::Error::other("No such running process"))
+            }
+        }
+        Ok(())
+    }
+
+    fn cont_tracing(&mut self, pid: Pid, signal: Option<Signal>) -> Result<(), io::Error>
+    {
+        println!("* Continue process {:?} with {:?} *", pid, signal);
+        // Only the tracing thread can resume the tracee, which is stopped on a breakpoint otherwise it is running
+        self.send_to_thread(pid, ThreadRequest::Continue(signal))
+    }
+
+    fn stop_tracing(&mut self, pid: Pid) -> Result<(), io::Error>
+    {
+        println!("* Stop process {:?} *", pid);
+        //ptrace::
+        // TODO: which signal use GDB ?
+        Ok(())
+    }
+
+    fn has_process(&self, pid: Pid) -> bool
+    {
+        self.thread_map.contains_key(&pid)
+    }
+
+    fn read_memory(&mut self, pid: Pid, address: usize, size: usize) -> Result<Vec<u8>, io::Error>
+    {
+        self.request(pid, |reply| OperationRequest::ReadMemory { address, size, reply })
+    }
+
+    fn write_memory(&mut self, pid: Pid, address: usize, data: Vec<u8>) -> Result<usize, io::Error>
+    {
+        self.request(pid, |reply| OperationRequest::WriteMemory { address, data, reply })
+    }
+
+    fn read_registers(&mut self, pid: Pid) -> Result<user_regs_struct, io::Error>
+    {
+        self.request(pid, |reply| OperationRequest::ReadRegisters { reply })?
+            .ok_or(io::Error::other("Fail to read registers"))
+    }
+
+    fn write_registers(&mut self, pid: Pid, regs: user_regs_struct) -> Result<(), io::Error>
+    {
+        self.request(pid, |reply| OperationRequest::WriteRegisters { regs, reply })?
+    }
+
+    fn set_breakpoint(&mut self, pid: Pid, address: usize) -> Result<(), io::Error>
+    {
+        self.request_thread(pid, |reply| ThreadRequest::SetBreakpoint { address, reply })?
+    }
+
+    fn remove_breakpoint(&mut self, pid: Pid, address: usize) -> Result<(), io::Error>
+    {
+        self.request_thread(pid, |reply| ThreadRequest::RemoveBreakpoint { address, reply })?
+    }
+
+    fn list_rules(&mut self, pid: Pid) -> Result<Vec<RuleInfo>, io::Error>
+    {
+        self.request_thread(pid, |reply| ThreadRequest::ListRules { reply })
+    }
+
+    fn available_rules(&mut self) -> Vec<String>
+    {
+        self.registry.names()
+    }
+
+    fn load_rule(&mut self, pid: Pid, index: Option<usize>, name: String, params: Value) -> Result<(), io::Error>
+    {
+        self.request_thread(pid, |reply| ThreadRequest::LoadRule { index, name, params, reply })?
+    }
+
+    fn unload_rule(&mut self, pid: Pid, index: usize) -> Result<(), io::Error>
+    {
+        self.request_thread(pid, |reply| ThreadRequest::UnloadRule { index, reply })?
+    }
+
+    fn move_rule(&mut self, pid: Pid, from: usize, to: usize) -> Result<(), io::Error>
+    {
+        self.request_thread(pid, |reply| ThreadRequest::MoveRule { from, to, reply })?
+    }
+
+    fn set_notifier(&mut self, notifier: Notifier)
+    {
+        self.notifier = Some(notifier);
+    }
+
+    fn release_process(&mut self, pid: Pid) -> Result<Child, io::Error>
+    {
+        println!("* Release process {:?} *", pid);
+
+        let child = self.request_thread(pid, |reply| ThreadRequest::Release { reply })??;
+        if let Some(thread) = self.thread_map.remove(&pid) {
+            if let Err(err) = thread.handler.join() {
+                println!("Couldn't join the thread {}: {:?}", pid, err);
+            }
+        }
+        Ok(child)
+    }
+
+    fn adopt_process(&mut self, child: Child) -> Result<Pid, io::Error>
+    {
+        println!("Creating new tracing thread for process {}...", child.id());
+
+        let (tx_ctrl, rx_ctrl) = channel();
+        let (tx_thread, rx_thread) = channel();
+        let tracing_thread = TracingThread::new(String::new(), Vec::new(), tx_thread, rx_ctrl, self.notifier.clone(), self.registry.clone())
+            .with_tracee(child);
+        self.start_thread(tracing_thread, tx_ctrl, rx_thread)
+    }
+
+    /*
+     * One client at a time, the server stops with the tracing thread.
+     */
+    fn start_gdb_server(&mut self, pid: Pid, port: u16) -> Result<u16, io::Error>
+    {
+        let thread = self.thread_map.get_mut(&pid).ok_or_else(|| io::Error::new(ErrorKind::NotFound, "No such pid"))?;
+        if let Some(port) = thread.gdb_port {
+            return Ok(port);
+        }
+
+        let listener = TcpListener::bind((IP_ADDRESS, port))?;
+        let port = listener.local_addr()?.port();
+        println!("GDB server of {} listening on port {}", pid, port);
+
+        let tx = thread.tx.clone();
+        let interrupts = thread.interrupts.clone();
+        Builder::new().name(format!("gdb-{}", pid)).spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        println!("GDB server of {} fails to accept a client: {}", pid, err);
+                        continue;
+                    },
+                };
+                let tracee = match GdbTracee::attach(pid, tx.clone(), interrupts.clone()) {
+                    Ok(tracee) => tracee,
+                    Err(_) => break,
+                };
+                if let Err(err) = GdbServer::new(tracee, stream).serve() {
+                    println!("GDB session of {} ends: {}", pid, err);
+                }
+            }
+            println!("GDB server of {} shutdown", pid);
+        })?;
+
+        thread.gdb_port = Some(port);
+        Ok(port)
+    }
+}
@@ -3,10 +3,12 @@
  */
 
 use std::{
+    cell::{ Cell, RefCell },
     os::unix::process::CommandExt,
     process::{ self, Child, Command, Stdio },
     sync::{ 
-        Arc, Barrier,
+        Arc,
+        atomic::{ AtomicUsize, Ordering },
         mpsc::{ Sender, Receiver },
     },
     io,
@@ -16,37 +18,137 @@ use nix::{
     sys::{
         ptrace,
         wait::{ waitpid, WaitStatus},
-        signal::Signal,
+        signal::{ self, Signal },
     },
     unistd::Pid,
 };
 
 #[cfg(target_os = "linux")]
-use libc;
+use serde_json::{ json, Value };
 
 use sysfwd::{
     arch::TargetArch,
-    tracer::TracerEngine,
-    targets::operation::Operation,
+    tracer::{ TracerEngine, filtering::RuleInfo },
+    targets::operation::{ Operation, OperationRequest },
     targets,
+    arch::{ BREAKPOINT_OPCODE, SYSCALL_OPCODE },
+    protocol::{ control::Notifier, rpc::Event, gdb::StopReason },
+    protocol::{ data::Client, auth::read_key_file },
     memory::{ read_process_memory_maps, print_memory_regions },
 };
-use sysfwd_filter::ForwardFileRule;
+use sysfwd_filter::{ ForwardFileRule, RuleRegistry };
 
 use crate::{
-    IP_ADDRESS, TRACER_PORT, EXECUTOR_PORT,
+    IP_ADDRESS, TRACER_PORT, EXECUTOR_PORT, SHM_SOCKET_VAR, KEY_FILE_VAR, MIRROR_CONTEXT, setting,
 };
 
 
 
+/*
+ * The requests sent by the control thread to a tracing thread.
+ */
+pub enum ThreadRequest {
+    /* Start the tracee execution and syscall tracing */
+    Start,
+    /* Resume a tracee stopped on a breakpoint or halted */
+    Continue(Option<Signal>),
+    /* Execute a single instruction of a halted tracee, a syscall is executed until it returns */
+    Step(Option<Signal>),
+    /* Keep the tracee stopped until it is resumed, as on a breakpoint */
+    Halt,
+    /* Report the stops of the tracee to a gdb client, see gdb_tracee */
+    AttachGdb { stops: Sender<StopReason> },
+    DetachGdb,
+    Operation(OperationRequest),
+    SetBreakpoint { address: usize, reply: Sender<Result<(), io::Error>> },
+    RemoveBreakpoint { address: usize, reply: Sender<Result<(), io::Error>> },
+    /* Filter rules management, see TracerCallback */
+    ListRules { reply: Sender<Vec<RuleInfo>> },
+    LoadRule { index: Option<usize>, name: String, params: Value, reply: Sender<Result<(), io::Error>> },
+    UnloadRule { index: usize, reply: Sender<Result<(), io::Error>> },
+    MoveRule { from: usize, to: usize, reply: Sender<Result<(), io::Error>> },
+    /* Stop tracing and give back the tracee, left stopped, see TracerCallback::release_process */
+    Release { reply: Sender<Result<Child, io::Error>> },
+}
+
+impl ThreadRequest {
+
+    /*
+     * The requests which must wait for the current syscall to return.
+     */
+    fn needs_syscall_boundary(&self) -> bool
+    {
+        matches!(self, Self::LoadRule { .. } | Self::UnloadRule { .. } | Self::MoveRule { .. } | Self::Release { .. })
+    }
+}
+
+/*
+ * The SIGSTOPs sent by the control thread to make the tracing thread serve its requests.
+ * Only these stops are suppressed, a SIGSTOP from anyone else is delivered to the tracee.
+ */
+#[derive(Clone, Default)]
+pub struct Interrupts {
+    pending: Arc<AtomicUsize>,
+}
+
+impl Interrupts {
+
+    /*
+     * Stop the tracee, counted before the signal so that the stop always finds it.
+     */
+    pub fn send(&self, pid: Pid) -> Result<(), io::Error>
+    {
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        if let Err(errno) = signal::kill(pid, Signal::SIGSTOP) {
+            self.pending.fetch_sub(1, Ordering::SeqCst);
+            return Err(errno.into());
+        }
+        Ok(())
+    }
+
+    /*
+     * Whether a SIGSTOP stop was requested. A SIGSTOP is not queued, the pending ones make a single stop.
+     */
+    fn take(&self) -> bool
+    {
+        self.pending.swap(0, Ordering::SeqCst) > 0
+    }
+}
+
+/*
+ * Why the tracee stopped.
+ */
+enum TraceeStop {
+    Syscall(Pid),
+    Trap(Pid),
+    /* Stopped by the control thread to serve its requests */
+    Interrupted,
+    /* Any other signal, delivered when the tracee restarts */
+    Signal(Signal),
+}
+
+
+
 /*
  * Represent a thread tracing the execution of a child thread.
  */
 //#[derive(Clone, Debug)]
 pub struct TracingThread {
-    pub boot_barrier: Arc<Barrier>,
     tx: Sender<String>,
-    _rx: Receiver<String>,
+    rx: Receiver<ThreadRequest>,
+    notifier: Option<Notifier>,
+    registry: RuleRegistry,
+    /* Rule changes received inside a syscall, applied once it returns */
+    deferred: RefCell<Vec<ThreadRequest>>,
+    /* Set when the tracee must be released, the tracing then stops */
+    release: RefCell<Option<Sender<Result<Child, io::Error>>>>,
+    /* The gdb client debugging the tracee */
+    gdb: RefCell<Option<Sender<StopReason>>>,
+    /* The signal delivered when the tracee restarts */
+    signal: Cell<Option<Signal>>,
+    /* Single-stepping a syscall, the step completes when it returns */
+    stepping: Cell<bool>,
+    interrupts: Interrupts,
 
     program: String,
     prog_args: Vec<String>,
@@ -58,29 +160,78 @@ pub struct TracingThread {
 
 impl TracingThread {
 
-    pub fn new(program: String, prog_args: Vec<String>, tx: Sender<String>, rx: Receiver<String>, barrier: Arc<Barrier>) -> Self 
+    pub fn new(program: String, prog_args: Vec<String>, tx: Sender<String>, rx: Receiver<ThreadRequest>,
+               notifier: Option<Notifier>, registry: RuleRegistry) -> Self 
     {
         TracingThread { 
-            boot_barrier: barrier,
-            tx: tx,
-            _rx: rx,
-            program: program,
-            prog_args: prog_args,
+            tx,
+            rx,
+            notifier,
+            registry,
+            deferred: RefCell::new(Vec::new()),
+            release: RefCell::new(None),
+            gdb: RefCell::new(None),
+            signal: Cell::new(None),
+            stepping: Cell::new(false),
+            interrupts: Interrupts::default(),
+            program,
+            prog_args,
             tracee: None,
             //use_pkexec: true,
         }
     }
     
+    /*
+     * Trace a process spawned by another debugger thread instead of spawning the program,
+     * see TracerCallback::adopt_process.
+     */
+    pub fn with_tracee(mut self, tracee: Child) -> Self
+    {
+        self.tracee = Some(tracee);
+        self
+    }
+
+    /*
+     * How the control thread interrupts the tracee.
+     */
+    pub fn interrupts(&self) -> Interrupts
+    {
+        self.interrupts.clone()
+    }
+
     pub fn start(&mut self)
     {
-        let tracer = self.boot_thread().expect("Fail to setup tracing thread");
+        let mut tracer = self.boot_thread().expect("Fail to setup tracing thread");
 
         // Wait for the signal to start the tracee execution and syscall tracing from the control thread.
-        self.boot_barrier.wait();
-
-        let tracer = self.run_thread(tracer).unwrap();
+        // Meanwhile the tracee is stopped and its state can be requested.
+        while !self.is_released() {
+            match self.rx.recv() {
+                Ok(ThreadRequest::Start) => break,
+                Ok(request) => self.handle_request(&mut tracer, request),
+                Err(_) => return,
+            }
+        }
 
-        self.shutdown_thread(tracer).expect("Fail to properly clean tracing thread");
+        let result = match self.is_released() {
+            true => Ok(tracer),
+            false => self.run_thread(tracer),
+        };
+        match result {
+            Ok(mut tracer) => {
+                match self.release.take() {
+                    Some(reply) => { let _ = reply.send(self.release_tracee(&mut tracer)); },
+                    // The tracee is gone
+                    None => tracer.end_session(),
+                }
+                self.shutdown_thread(tracer).expect("Fail to properly clean tracing thread")
+            },
+            Err(err) => {
+                // The tracee cannot go on without the executor
+                println!("Tracing of process {} aborted: {}", self.tracee.as_ref().unwrap().id(), err);
+                let _ = self.tracee.as_mut().unwrap().kill();
+            },
+        }
     }
 
     /*
@@ -91,7 +242,10 @@ impl TracingThread {
         println!("Tracing thread {} booting...", process::id());
 
         /* Setup the tracee */
-        self.spawn_tracee(self.program.clone(), self.prog_args.clone())?; 
+        match self.tracee.is_some() {
+            true => self.attach_tracee()?,
+            false => self.spawn_tracee(self.program.clone(), self.prog_args.clone())?,
+        }
         let pid = self.tracee.as_ref().unwrap().id() as i32;
 
         let mem = read_process_memory_maps(pid as u32);
@@ -103,17 +257,28 @@ impl TracingThread {
         let mem_op = Box::new(ptrace_op);
         let operator = Box::new(Operation{ register: regs_op, memory: mem_op });
 
+        let protocol = match setting(SHM_SOCKET_VAR) {
+            Some(path) => Client::new_shared_memory(&path)?,
+            None => Client::new(IP_ADDRESS, TRACER_PORT, EXECUTOR_PORT),
+        };
+        let protocol = match setting(KEY_FILE_VAR) {
+            Some(path) => protocol.with_key(&read_key_file(&path)?)?,
+            None => protocol,
+        };
+
         let mut tracer = TracerEngine::new(pid,
                                                          TargetArch::X86_64,
-                                                         IP_ADDRESS,
-                                                         TRACER_PORT,
-                                                         EXECUTOR_PORT,
+                                                         protocol,
                                                          operator,
                                                         );
+        if let Some(notifier) = self.notifier.as_ref() {
+            tracer.set_notifier(notifier.clone());
+        }
+        tracer.set_mirror_context(MIRROR_CONTEXT);
 
-        /* Load filters */
-        let rule = Box::new(ForwardFileRule::new(String::from("/dev/kbuf")));
-        tracer.load_rule(0, rule);
+        /* Load filters, the control channel can change them afterwards */
+        let rule = self.registry.create(ForwardFileRule::NAME, json!({ "filename": "/dev/kbuf" }))?;
+        tracer.load_rule(0, rule)?;
         
         // Send the PID of the tracee to the control thread
         self.tx.send(pid.to_string()).unwrap();
@@ -150,6 +315,11 @@ impl TracingThread {
         }
         self.tracee = Some(command.spawn().expect("Failed to spawn child process"));
 
+        // Wait for the stop on exec to set the tracing options
+        let pid = Pid::from_raw(self.tracee.as_ref().unwrap().id() as i32);
+        waitpid(pid, None)?;
+        ptrace::setoptions(pid, ptrace::Options::PTRACE_O_TRACESYSGOOD)?;
+
         /* 
         let status: std::process::ExitStatus = self.tracee.wait().unwrap();
         println!("Status is {:?}", status);
@@ -160,15 +330,43 @@ impl TracingThread {
         Ok(())
     }
 
-    fn _attach_tracee(&mut self, _pid: u32) -> Result<(), io::Error>
+    /*
+     * Attach to a stopped tracee, the SIGSTOP of PTRACE_ATTACH is suppressed when restarting it.
+     */
+    fn attach_tracee(&mut self) -> Result<(), io::Error>
     {
-        /* TODO */
-        panic!("Not implemented");
+        let pid = Pid::from_raw(self.tracee.as_ref().unwrap().id() as i32);
+        println!("Attaching {}", pid);
+
+        ptrace::attach(pid)?;
+        waitpid(pid, None)?;
+        ptrace::setoptions(pid, ptrace::Options::PTRACE_O_TRACESYSGOOD)?;
+        Ok(())
+    }
+
+    fn is_released(&self) -> bool
+    {
+        self.release.borrow().is_some()
+    }
+
+    /*
+     * Detach from the tracee between two syscalls, without breakpoints and with a pending SIGSTOP
+     * so that it stays stopped until another thread attaches it.
+     */
+    fn release_tracee(&mut self, tracer: &mut TracerEngine) -> Result<Child, io::Error>
+    {
+        let pid = Pid::from_raw(self.tracee.as_ref().unwrap().id() as i32);
+        println!("Releasing {}", pid);
+
+        tracer.clear_breakpoints()?;
+        signal::kill(pid, Signal::SIGSTOP)?;
+        ptrace::detach(pid, None)?;
+        Ok(self.tracee.take().unwrap())
     }
 
     fn shutdown_thread(&mut self, mut tracer: TracerEngine) -> Result<(), io::Error>
     {
-        println!("Thread tracing process {} shutdown", self.tracee.as_ref().unwrap().id());
+        println!("Thread tracing process {} shutdown", tracer.pid);
         //let status = self.tracee.as_mut().unwrap().wait().expect("Not running");
         //println!("Tracee exits with status {}", status.code().unwrap());
         tracer.shutdown().unwrap();
@@ -182,20 +380,256 @@ impl TracingThread {
          * The main loop
          */
         loop {
-            self.restart_syscall(pid).unwrap();
+            self.restart_syscall(pid, self.signal.take()).unwrap();
 
             match self.wait_for_syscall(pid) {
                 Err(()) => break,
 
-                Ok(pid) => { 
+                // A gdb client decides whether the signal is delivered
+                Ok(TraceeStop::Signal(signo)) => {
+                    let signal = self.stop_for_gdb(&mut tracer, StopReason::Signal(signo), Some(signo));
+                    self.signal.set(signal);
+                },
+
+                Ok(TraceeStop::Interrupted) => self.serve_requests(&mut tracer),
+
+                Ok(TraceeStop::Trap(pid)) => self.handle_breakpoint(pid, &mut tracer)?,
+
+                Ok(TraceeStop::Syscall(pid)) => { 
                     self.sync_registers(pid, &mut tracer)?;
                     tracer.trace()?;
+                    if !tracer.is_in_syscall() {
+                        self.serve_deferred(&mut tracer);
+                        if self.stepping.replace(false) {
+                            let signal = self.stop_for_gdb(&mut tracer, StopReason::Signal(Signal::SIGTRAP), None);
+                            self.signal.set(signal);
+                        }
+                    }
+                    self.serve_requests(&mut tracer);
                 },
             }
+
+            if self.is_released() {
+                break;
+            }
         }
         Ok(tracer)
     }
 
+    fn serve_requests(&self, tracer: &mut TracerEngine)
+    {
+        while let Ok(request) = self.rx.try_recv() {
+            self.handle_request(tracer, request);
+        }
+    }
+
+    fn serve_deferred(&self, tracer: &mut TracerEngine)
+    {
+        let deferred = self.deferred.take();
+        for request in deferred {
+            self.handle_request(tracer, request);
+        }
+    }
+
+    fn handle_request(&self, tracer: &mut TracerEngine, request: ThreadRequest)
+    {
+        if request.needs_syscall_boundary() && tracer.is_in_syscall() {
+            self.deferred.borrow_mut().push(request);
+            return;
+        }
+
+        match request {
+            ThreadRequest::Operation(request) => tracer.serve_request(request),
+            ThreadRequest::SetBreakpoint { address, reply } => {
+                let _ = reply.send(tracer.set_breakpoint(address));
+            },
+            ThreadRequest::RemoveBreakpoint { address, reply } => {
+                let _ = reply.send(tracer.remove_breakpoint(address));
+            },
+            ThreadRequest::ListRules { reply } => {
+                let _ = reply.send(tracer.list_rules());
+            },
+            ThreadRequest::LoadRule { index, name, params, reply } => {
+                let index = index.unwrap_or(tracer.rule_count());
+                let result = self.registry.create(&name, params).and_then(|rule| tracer.load_rule(index, rule));
+                let _ = reply.send(result);
+            },
+            ThreadRequest::UnloadRule { index, reply } => {
+                let _ = reply.send(tracer.unload_rule(index).map(|_| ()));
+            },
+            ThreadRequest::MoveRule { from, to, reply } => {
+                let _ = reply.send(tracer.move_rule(from, to));
+            },
+            // Served once out of the tracing loop
+            ThreadRequest::Release { reply } => {
+                *self.release.borrow_mut() = Some(reply);
+            },
+            ThreadRequest::Halt => {
+                let signal = self.stop_for_gdb(tracer, StopReason::Signal(Signal::SIGINT), None);
+                self.signal.set(signal);
+            },
+            ThreadRequest::AttachGdb { stops } => {
+                *self.gdb.borrow_mut() = Some(stops);
+            },
+            ThreadRequest::DetachGdb => {
+                *self.gdb.borrow_mut() = None;
+            },
+            // Only meaningful when the tracee waits to be started or resumed
+            ThreadRequest::Start | ThreadRequest::Continue(_) | ThreadRequest::Step(_) => { },
+        }
+    }
+
+    /*
+     * On a breakpoint, report the hit and keep the tracee stopped until the control thread resumes it.
+     * Then the original instruction is restored, single-stepped and the breakpoint re-inserted.
+     */
+    fn handle_breakpoint(&self, pid: Pid, tracer: &mut TracerEngine) -> Result<(), io::Error>
+    {
+        let mut regs = ptrace::getregs(pid)?;
+        let address = regs.rip as usize - BREAKPOINT_OPCODE.len();
+        if !tracer.has_breakpoint(address) {
+            println!("Tracee {} received a SIGTRAP at {:#x} outside of any breakpoint", pid, regs.rip);
+            return Ok(());
+        }
+
+        // Rewind to the breakpoint address
+        regs.rip = address as u64;
+        ptrace::setregs(pid, regs)?;
+
+        println!("Tracee {} hits breakpoint at {:#x}", pid, address);
+        if let Some(notifier) = self.notifier.as_ref() {
+            notifier.notify_breakpoint(pid, address, &regs);
+        }
+        self.report_stop(StopReason::Breakpoint);
+
+        let signal = self.hold(tracer, StopReason::Breakpoint);
+        // Released on the breakpoint address, the original instruction is put back
+        if self.is_released() {
+            return Ok(());
+        }
+
+        // The registers or the breakpoints may have been modified meanwhile
+        let address = ptrace::getregs(pid)?.rip as usize;
+        match tracer.has_breakpoint(address) {
+            true => { self.single_step(pid, tracer, signal)?; },
+            false => self.signal.set(signal),
+        }
+        Ok(())
+    }
+
+    /*
+     * Keep the tracee stopped and serve the requests until it is resumed, the signal to deliver is returned.
+     * Meanwhile a gdb client may single-step the tracee.
+     */
+    fn hold(&self, tracer: &mut TracerEngine, mut reason: StopReason) -> Option<Signal>
+    {
+        let pid = Pid::from_raw(tracer.pid);
+        loop {
+            match self.rx.recv() {
+                Ok(ThreadRequest::Continue(signal)) => return signal,
+                Ok(ThreadRequest::Step(signal)) => {
+                    match self.step(pid, tracer, signal) {
+                        Ok(Some(stop)) => reason = stop,
+                        // Stepping a syscall
+                        Ok(None) => return signal,
+                        Err(err) => println!("Fail to single-step tracee {}: {}", pid, err),
+                    }
+                    self.report_stop(reason);
+                },
+                // Already stopped
+                Ok(ThreadRequest::Halt) => { self.report_stop(reason); },
+                Ok(request) => self.handle_request(tracer, request),
+                Err(_) => return None,
+            }
+            if self.is_released() {
+                return None;
+            }
+        }
+    }
+
+    /*
+     * Hold the tracee if a gdb client is told about the stop, otherwise it resumes with `signal`.
+     */
+    fn stop_for_gdb(&self, tracer: &mut TracerEngine, reason: StopReason, signal: Option<Signal>) -> Option<Signal>
+    {
+        match self.report_stop(reason) {
+            true => self.hold(tracer, reason),
+            false => signal,
+        }
+    }
+
+    /*
+     * Whether a gdb client is told about the stop, it is forgotten once disconnected.
+     */
+    fn report_stop(&self, reason: StopReason) -> bool
+    {
+        let mut gdb = self.gdb.borrow_mut();
+        let reported = gdb.as_ref().is_some_and(|stops| stops.send(reason).is_ok());
+        if !reported {
+            *gdb = None;
+        }
+        reported
+    }
+
+    /*
+     * Single-step the tracee, or let it run until the end of the syscall it is about to make: the syscall
+     * must be traced to be forwarded. Then the step completes in the tracing loop and None is returned.
+     * Note: a syscall instruction under a breakpoint is not recognized and is executed locally.
+     */
+    fn step(&self, pid: Pid, tracer: &mut TracerEngine, signal: Option<Signal>) -> Result<Option<StopReason>, io::Error>
+    {
+        let rip = ptrace::getregs(pid)?.rip;
+        let instruction = ptrace::read(pid, rip as ptrace::AddressType)? as u16;
+        if tracer.is_in_syscall() || instruction.to_le_bytes()[..] == *SYSCALL_OPCODE {
+            self.stepping.set(true);
+            return Ok(None);
+        }
+
+        let stop = match self.single_step(pid, tracer, signal)? {
+            WaitStatus::Stopped(_, Signal::SIGTRAP) => StopReason::Signal(Signal::SIGTRAP),
+            // Delivered if the client resumes with it
+            WaitStatus::Stopped(_, signo) => StopReason::Signal(signo),
+            WaitStatus::Exited(_, status) => StopReason::Exited(status),
+            WaitStatus::Signaled(_, signo, _) => StopReason::Terminated(signo),
+            _ => StopReason::Signal(Signal::SIGTRAP),
+        };
+        Ok(Some(stop))
+    }
+
+    /*
+     * Execute the instruction at the pc, the original one when it is under a breakpoint.
+     */
+    fn single_step(&self, pid: Pid, tracer: &mut TracerEngine, signal: Option<Signal>) -> Result<WaitStatus, io::Error>
+    {
+        let address = ptrace::getregs(pid)?.rip as usize;
+        let breakpoint = tracer.has_breakpoint(address);
+        if breakpoint {
+            tracer.disable_breakpoint(address)?;
+        }
+
+        let mut signal = signal;
+        let status = loop {
+            ptrace::step(pid, signal)?;
+            // A pending SIGSTOP of the control thread may stop the tracee before the instruction is executed
+            match waitpid(pid, None)? {
+                WaitStatus::Stopped(_, Signal::SIGSTOP) if self.interrupts.take() => signal = None,
+                status => break status,
+            }
+        };
+
+        if breakpoint {
+            tracer.enable_breakpoint(address)?;
+        }
+        Ok(status)
+    }
+
+    fn notify(&self, event: Event)
+    {
+        if let Some(notifier) = self.notifier.as_ref() {
+            notifier.notify(event);
+        }
+    }
+
     fn sync_registers(&self, pid: Pid, tracer: &mut TracerEngine) -> Result<(), io::Error>
     {
         let regs: nix::libc::user_regs_struct = ptrace::getregs(pid)?;
@@ -203,11 +637,11 @@ impl TracingThread {
         Ok(())
     }
 
-    fn restart_syscall(&self, pid: Pid) -> Result<Pid, ()>
+    fn restart_syscall(&self, pid: Pid, signal: Option<Signal>) -> Result<Pid, ()>
     {
         // Continue execution
         //ptrace::syscall(pid, None).unwrap();
-        match ptrace::syscall(pid, None) {
+        match ptrace::syscall(pid, signal) {
             Ok(()) => { /* continue */ },
             /*
             Err(ref err) if err.kind() == nix::errno::Errno::ESRCH => {
@@ -222,7 +656,10 @@ impl TracingThread {
         Ok(pid)
     }
 
-    fn wait_for_syscall(&self, pid: Pid) -> Result<Pid, ()>
+    /*
+     * The syscall stops are distinguished from the other SIGTRAP with PTRACE_O_TRACESYSGOOD.
+     */
+    fn wait_for_syscall(&self, pid: Pid) -> Result<TraceeStop, ()>
     {
         match waitpid(pid, None) {
             Err(err) => {
@@ -234,22 +671,40 @@ impl TracingThread {
                     WaitStatus::Stopped(pid, signo) => {
                         match signo {
                             Signal::SIGTRAP => {
-                                return Ok(pid);
+                                Ok(TraceeStop::Trap(pid))
+                            },
+                            // The SIGSTOP sent by the control thread is suppressed when restarting the tracee
+                            Signal::SIGSTOP if self.interrupts.take() => {
+                                Ok(TraceeStop::Interrupted)
                             },
+                            // Delivered like the other signals, the tracee is then killed unless it handles it
                             Signal::SIGSEGV => {
                                 let regs = ptrace::getregs(pid).unwrap();
                                 println!("Tracee {} segfault at {:#x}", pid, regs.rip);
-                                return Err(());
+                                self.notify(Event::SignalReceived { pid: pid.as_raw(), signal: signo.to_string() });
+                                Ok(TraceeStop::Signal(signo))
                             },
-                            // TODO: add support for other signals
                             _ => {
-                                panic!("Tracee {} received signal {} which is not handled", pid, signo);
+                                println!("Tracee {} received signal {}", pid, signo);
+                                self.notify(Event::SignalReceived { pid: pid.as_raw(), signal: signo.to_string() });
+                                Ok(TraceeStop::Signal(signo))
                             },
                         }
                     },
+                    WaitStatus::PtraceSyscall(pid) => {
+                        Ok(TraceeStop::Syscall(pid))
+                    },
                     WaitStatus::Exited(pid, exit_status) => {
                         println!("The tracee {} exits with status {}", pid, exit_status);
-                        return Err(());
+                        self.notify(Event::ProcessExited { pid: pid.as_raw(), status: Some(exit_status), signal: None });
+                        self.report_stop(StopReason::Exited(exit_status));
+                        Err(())
+                    },
+                    WaitStatus::Signaled(pid, signo, _) => {
+                        println!("The tracee {} is killed by signal {}", pid, signo);
+                        self.notify(Event::ProcessExited { pid: pid.as_raw(), status: None, signal: Some(signo.to_string()) });
+                        self.report_stop(StopReason::Terminated(signo));
+                        Err(())
                     },
                     // TODO: add support for other WaitStatus
                     _ => {
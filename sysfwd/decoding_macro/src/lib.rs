@@ -1,6 +1,5 @@
 use proc_macro::TokenStream;
 use quote::quote;
-use syn;
 
 
 
@@ -34,7 +33,7 @@ pub fn decode_exit_derive(input: TokenStream) -> TokenStream
 
     // Get the retval attribute
     let retval_field = fields.iter()
-        .find(|field| field.ident.as_ref().map_or(false, |ident| ident == "retval"))
+        .find(|field| field.ident.as_ref().is_some_and(|ident| ident == "retval"))
         .expect("No field retval in the struct");
 
     // Get the type inside the Option<>
@@ -59,7 +58,7 @@ pub fn decode_exit_derive(input: TokenStream) -> TokenStream
 
     let gen = quote! {
         impl DecodeExit for #name {
-            fn decode_exit(&mut self, value: usize, pid: i32, operation: &Box<Operation>) -> Result<(), std::io::Error>
+            fn decode_exit(&mut self, value: usize, pid: i32, operation: &Operation) -> Result<(), std::io::Error>
             {
                 self.retval = Some( #inner_type::from(value) );
                 Ok(())
@@ -68,3 +67,143 @@ pub fn decode_exit_derive(input: TokenStream) -> TokenStream
     };
     gen.into()
 }
+
+
+/*
+ * Give the fields of type Fd with their index, which is the index of the argument as the fields follow
+ * the order of the arguments, and the retval when it is an Option<Fd>.
+ */
+#[proc_macro_derive(FdArgs)]
+pub fn fd_args_derive(input: TokenStream) -> TokenStream
+{
+    let ast: syn::DeriveInput = syn::parse(input).unwrap();
+
+    // The name of the struct
+    let name = ast.ident;
+
+    // Extract the list of structure fields
+    let fields = match ast.data {
+        syn::Data::Struct(data_struct) => {
+            match data_struct.fields {
+                syn::Fields::Named(fields_named) => fields_named.named,
+                _ => panic!("Expected named fields in struct"),
+            }
+        },
+        _ => panic!("Expected struct"),
+    };
+
+    // The last segment of the type path, e.g., Fd or Option
+    fn type_name(ty: &syn::Type) -> Option<&syn::PathSegment> {
+        match ty {
+            syn::Type::Path(path) => path.path.segments.last(),
+            _ => None,
+        }
+    }
+    let is_fd = |ty: &syn::Type| type_name(ty).is_some_and(|segment| segment.ident == "Fd");
+
+    let mut args = Vec::new();
+    let mut returned = quote! { None };
+    for (index, field) in fields.iter().enumerate() {
+        let ident = field.ident.as_ref().unwrap();
+        if ident == "retval" {
+            // Option<Fd>
+            let inner = type_name(&field.ty).and_then(|segment| match &segment.arguments {
+                syn::PathArguments::AngleBracketed(angle_bracketed) => angle_bracketed.args.first(),
+                _ => None,
+            });
+            if let Some(syn::GenericArgument::Type(ty)) = inner {
+                if is_fd(ty) {
+                    returned = quote! { self.retval.as_mut() };
+                }
+            }
+        } else if is_fd(&field.ty) {
+            args.push(quote! { (#index, &mut self.#ident) });
+        }
+    }
+
+    let gen = quote! {
+        impl crate::syscall::decoder::FdArgs for #name {
+            fn fd_args(&mut self) -> Vec<(usize, &mut crate::syscall::args::Fd)>
+            {
+                vec![ #(#args),* ]
+            }
+
+            fn returned_fd(&mut self) -> Option<&mut crate::syscall::args::Fd>
+            {
+                #returned
+            }
+        }
+    };
+    gen.into()
+}
+
+
+/*
+ * Give the pointer arguments of type Buffer or Struct, including those in a Vec, e.g., the buffers of an iovec.
+ */
+#[proc_macro_derive(PointerArgs)]
+pub fn pointer_args_derive(input: TokenStream) -> TokenStream
+{
+    let ast: syn::DeriveInput = syn::parse(input).unwrap();
+
+    // The name of the struct
+    let name = ast.ident;
+
+    // Extract the list of structure fields
+    let fields = match ast.data {
+        syn::Data::Struct(data_struct) => {
+            match data_struct.fields {
+                syn::Fields::Named(fields_named) => fields_named.named,
+                _ => panic!("Expected named fields in struct"),
+            }
+        },
+        _ => panic!("Expected struct"),
+    };
+
+    // The last segment of the type path, e.g., Buffer or Vec
+    fn type_name(ty: &syn::Type) -> Option<&syn::PathSegment> {
+        match ty {
+            syn::Type::Path(path) => path.path.segments.last(),
+            _ => None,
+        }
+    }
+    let is_pointer = |ty: &syn::Type| type_name(ty).is_some_and(|segment| segment.ident == "Buffer" || segment.ident == "Struct");
+
+    let mut args = Vec::new();
+    for field in fields.iter() {
+        let ident = field.ident.as_ref().unwrap();
+        if is_pointer(&field.ty) {
+            args.push(quote! { pointers.push(&mut self.#ident); });
+            continue;
+        }
+        // Vec<Buffer>
+        let inner = type_name(&field.ty)
+            .filter(|segment| segment.ident == "Vec")
+            .and_then(|segment| match &segment.arguments {
+                syn::PathArguments::AngleBracketed(angle_bracketed) => angle_bracketed.args.first(),
+                _ => None,
+            });
+        if let Some(syn::GenericArgument::Type(ty)) = inner {
+            if is_pointer(ty) {
+                args.push(quote! {
+                    for pointer in self.#ident.iter_mut() {
+                        pointers.push(pointer);
+                    }
+                });
+            }
+        }
+    }
+
+    let gen = quote! {
+        impl crate::syscall::decoder::PointerArgs for #name {
+            fn pointer_args(&mut self) -> Vec<&mut dyn crate::syscall::args::Pointer>
+            {
+                #[allow(unused_mut)]
+                let mut pointers: Vec<&mut dyn crate::syscall::args::Pointer> = Vec::new();
+                #(#args)*
+                pointers
+            }
+        }
+    };
+    gen.into()
+}
@@ -3,6 +3,8 @@
  */
 mod x86_64;
 
+pub use x86_64::{ BREAKPOINT_OPCODE, SYSCALL_OPCODE, SYSCALL_GADGET, REGISTER_NAMES, register_mut, registers_to_map, update_registers };
+
 
 use std::collections::HashMap;
 
@@ -31,7 +33,7 @@ pub struct Architecture {
 impl Architecture {
     pub fn new(name: TargetArch) -> Self {
         Self {
-            name: name,
+            name,
             //register_table: Register::new(&name),
             syscall_table: SyscallTable::new(&name),
         }
@@ -1,6 +1,86 @@
 /*
  */
-use std::collections::HashMap;
+use std::collections::{ BTreeMap, HashMap };
+
+use nix::libc::user_regs_struct;
+
+
+/* int3 */
+pub const BREAKPOINT_OPCODE: &[u8] = &[0xcc];
+
+/* syscall */
+pub const SYSCALL_OPCODE: &[u8] = &[0x0f, 0x05];
+
+/* syscall; ret */
+pub const SYSCALL_GADGET: &[u8] = &[0x0f, 0x05, 0xc3];
+
+/*
+ * Registers exchanged over the control channel, in the order of the gdb amd64 target description.
+ */
+pub const REGISTER_NAMES: [&str; 27] = [
+    "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp",
+    "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15",
+    "rip", "eflags", "cs", "ss", "ds", "es", "fs", "gs",
+    "fs_base", "gs_base", "orig_rax",
+];
+
+pub fn register_mut<'a>(regs: &'a mut user_regs_struct, name: &str) -> Option<&'a mut u64>
+{
+    let reg = match name {
+        "rax" => &mut regs.rax,
+        "rbx" => &mut regs.rbx,
+        "rcx" => &mut regs.rcx,
+        "rdx" => &mut regs.rdx,
+        "rsi" => &mut regs.rsi,
+        "rdi" => &mut regs.rdi,
+        "rbp" => &mut regs.rbp,
+        "rsp" => &mut regs.rsp,
+        "r8" => &mut regs.r8,
+        "r9" => &mut regs.r9,
+        "r10" => &mut regs.r10,
+        "r11" => &mut regs.r11,
+        "r12" => &mut regs.r12,
+        "r13" => &mut regs.r13,
+        "r14" => &mut regs.r14,
+        "r15" => &mut regs.r15,
+        "rip" | "pc" => &mut regs.rip,
+        "eflags" => &mut regs.eflags,
+        "cs" => &mut regs.cs,
+        "ss" => &mut regs.ss,
+        "ds" => &mut regs.ds,
+        "es" => &mut regs.es,
+        "fs" => &mut regs.fs,
+        "gs" => &mut regs.gs,
+        "fs_base" => &mut regs.fs_base,
+        "gs_base" => &mut regs.gs_base,
+        "orig_rax" => &mut regs.orig_rax,
+        _ => return None,
+    };
+    Some(reg)
+}
+
+/*
+ * The registers of a set by name.
+ */
+pub fn registers_to_map(regs: &user_regs_struct) -> BTreeMap<String, u64>
+{
+    let mut regs = *regs;
+    REGISTER_NAMES.iter()
+        .map(|name| (name.to_string(), *register_mut(&mut regs, name).unwrap()))
+        .collect()
+}
+
+/*
+ * Apply the given registers to a register set, the registers not mentioned are left untouched.
+ */
+pub fn update_registers(regs: &mut user_regs_struct, values: &BTreeMap<String, u64>) -> Result<(), String>
+{
+    for (name, value) in values {
+        let reg = register_mut(regs, name).ok_or(format!("Unknown register {:?}", name))?;
+        *reg = *value;
+    }
+    Ok(())
+}
 
 /*
 pub fn create_register_table() -> HashMap<&'static str, u8> {
@@ -0,0 +1,120 @@
+/*
+ * The credentials and the context of a process which decide how its syscalls are permitted,
+ * mirrored from the tracee to the executor child so that a forwarded syscall is checked as the tracee.
+ */
+use std::{
+    collections::BTreeMap,
+    fs,
+    io,
+};
+
+use nix::libc;
+use serde::{ Serialize, Deserialize };
+
+
+
+/*
+ * The syscalls after which the context of the tracee changes.
+ */
+pub const CONTEXT_SYSCALLS: &[&str] = &[
+    "setuid", "setgid", "setreuid", "setregid", "setresuid", "setresgid", "setfsuid", "setfsgid",
+    "setgroups", "chdir", "fchdir", "umask", "setrlimit", "prlimit64",
+];
+
+/* struct rlimit { rlim_t rlim_cur; rlim_t rlim_max; } */
+pub const RLIMIT_SIZE: usize = 16;
+
+/* RLIMIT_CPU to RLIMIT_RTTIME, the same on every Linux architecture */
+const RLIMIT_COUNT: u32 = 16;
+
+
+
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProcessContext {
+    /* The ids used for the permission checks of the file system */
+    pub fsuid: u32,
+    pub fsgid: u32,
+    pub groups: Vec<u32>,
+    pub umask: Option<u32>,
+    pub cwd: Option<String>,
+    /* The soft and hard limits by resource */
+    pub rlimits: BTreeMap<u32, (u64, u64)>,
+}
+
+impl ProcessContext {
+
+    pub fn from_pid(pid: i32) -> Result<Self, io::Error>
+    {
+        let mut context = Self::default();
+        context.read_status(pid)?;
+        context.read_rlimits(pid)?;
+        Ok(context)
+    }
+
+    /*
+     * Read the credentials, the umask and the current directory, which the limits do not depend on.
+     */
+    pub fn read_status(&mut self, pid: i32) -> Result<(), io::Error>
+    {
+        let status = fs::read_to_string(format!("/proc/{}/status", pid))?;
+        for line in status.lines() {
+            let (key, value) = match line.split_once(':') {
+                Some(field) => field,
+                None => continue,
+            };
+            let values: Vec<&str> = value.split_whitespace().collect();
+            match key {
+                // real, effective, saved set and file system ids
+                "Uid" => self.fsuid = parse_id(values.get(3))?,
+                "Gid" => self.fsgid = parse_id(values.get(3))?,
+                "Groups" => {
+                    self.groups = values.iter()
+                        .map(|group| parse_id(Some(group)))
+                        .collect::<Result<Vec<u32>, io::Error>>()?;
+                },
+                // Only since Linux 4.7
+                "Umask" => {
+                    let umask = values.first().and_then(|umask| u32::from_str_radix(umask, 8).ok());
+                    self.umask = umask;
+                },
+                _ => (),
+            }
+        }
+
+        self.cwd = Some(fs::read_link(format!("/proc/{}/cwd", pid))?.to_string_lossy().into_owned());
+        Ok(())
+    }
+
+    fn read_rlimits(&mut self, pid: i32) -> Result<(), io::Error>
+    {
+        for resource in 0..RLIMIT_COUNT {
+            let mut rlimit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+            if unsafe { libc::prlimit(pid, resource, std::ptr::null(), &mut rlimit) } == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            self.rlimits.insert(resource, (rlimit.rlim_cur, rlimit.rlim_max));
+        }
+        Ok(())
+    }
+
+    /*
+     * Change a limit from the struct rlimit given to a syscall.
+     */
+    pub fn set_rlimit(&mut self, resource: u32, rlimit: &[u8]) -> Result<(), io::Error>
+    {
+        if rlimit.len() < RLIMIT_SIZE {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Truncated struct rlimit"));
+        }
+        let cur = u64::from_le_bytes(rlimit[0..8].try_into().unwrap());
+        let max = u64::from_le_bytes(rlimit[8..16].try_into().unwrap());
+        self.rlimits.insert(resource, (cur, max));
+        Ok(())
+    }
+}
+
+fn parse_id(id: Option<&&str>) -> Result<u32, io::Error>
+{
+    id.and_then(|id| id.parse::<u32>().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid id {:?} in the process status", id)))
+}
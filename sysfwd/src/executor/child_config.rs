@@ -0,0 +1,136 @@
+/*
+ * The process hosting the syscalls executed by the executor.
+ * Some devices expect the syscalls from a process with a specific identity, environment or cgroup.
+ */
+use std::{
+    collections::BTreeMap,
+    io,
+    os::unix::process::CommandExt,
+    process::{ Command, Stdio },
+};
+
+use nix::libc;
+use serde::{ Serialize, Deserialize };
+
+use crate::executor::{ SandboxConfig, ReplayConfig };
+
+
+
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug)]
+#[serde(default)]
+pub struct ChildConfig {
+    pub program: String,
+    pub args: Vec<String>,
+    /* Added to the environment of the executor, unless it is cleared */
+    pub env: BTreeMap<String, String>,
+    pub clear_env: bool,
+    pub cwd: Option<String>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /* The execution domain given to personality(2), ADDR_NO_RANDOMIZE is added when disabling ASLR */
+    pub personality: Option<u64>,
+    pub disable_aslr: bool,
+    /*
+     * Execute the syscalls in the executor itself with a NativeInvoker, no child is spawned.
+     * Only for the syscalls which do not depend on the identity of the process.
+     */
+    pub native: bool,
+    /* Confine the child, see SandboxConfig */
+    pub sandbox: Option<SandboxConfig>,
+    /* Write the executed syscalls in this file, see Recorder */
+    pub record: Option<String>,
+    /* Answer the syscalls from a recording instead of executing them, see Replay */
+    pub replay: Option<ReplayConfig>,
+}
+
+impl Default for ChildConfig {
+
+    /*
+     * A process doing nothing by itself, without ASLR so that the executor finds its code.
+     */
+    fn default() -> Self
+    {
+        Self {
+            program: String::from("/bin/true"),
+            args: Vec::new(),
+            env: BTreeMap::new(),
+            clear_env: false,
+            cwd: None,
+            uid: None,
+            gid: None,
+            personality: None,
+            disable_aslr: true,
+            native: false,
+            sandbox: None,
+            record: None,
+            replay: None,
+        }
+    }
+}
+
+impl ChildConfig {
+
+    pub fn new(program: String, args: Vec<String>) -> Self
+    {
+        Self { program, args, ..Self::default() }
+    }
+
+    /*
+     * The command spawning the child, the invoker adds what it needs to control it (e.g., PTRACE_TRACEME).
+     * Once sandboxed, the calling thread spawns its next children in a new pid namespace.
+     */
+    pub fn command(&self) -> Result<Command, io::Error>
+    {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args);
+        command.stdout(Stdio::inherit());
+        command.stderr(Stdio::inherit());
+
+        if self.clear_env {
+            command.env_clear();
+        }
+        command.envs(&self.env);
+        if let Some(cwd) = self.cwd.as_ref() {
+            command.current_dir(cwd);
+        }
+        // The group first, the user may lose the right to change it
+        if let Some(gid) = self.gid {
+            command.gid(gid);
+        }
+        if let Some(uid) = self.uid {
+            command.uid(uid);
+        }
+
+        let persona = self.persona();
+        if let Some(persona) = persona {
+            unsafe {
+                command.pre_exec(move || {
+                    if libc::personality(persona) == -1 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+
+        if let Some(sandbox) = self.sandbox.as_ref() {
+            let uid = self.uid.unwrap_or_else(|| unsafe { libc::geteuid() });
+            let gid = self.gid.unwrap_or_else(|| unsafe { libc::getegid() });
+            sandbox.apply(&mut command, uid, gid, self.cwd.as_deref())?;
+        }
+        Ok(command)
+    }
+
+    fn persona(&self) -> Option<libc::c_ulong>
+    {
+        let aslr = match self.disable_aslr {
+            true => libc::ADDR_NO_RANDOMIZE as libc::c_ulong,
+            false => 0,
+        };
+        match (self.personality, aslr) {
+            (None, 0) => None,
+            (persona, aslr) => Some(persona.unwrap_or(0) as libc::c_ulong | aslr),
+        }
+    }
+}
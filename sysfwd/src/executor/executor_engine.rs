@@ -3,20 +3,32 @@
  */
 use std::{
     io::{ self },
-    sync::Arc,
+    process::Child,
+    time::{ Duration, Instant },
+    sync::{
+        Arc,
+        mpsc::Receiver,
+    },
 };
+use nix::libc;
+use serde_json::Value;
 
 use crate::{
     sync::Event,
     arch::{ TargetArch, Architecture },
-    protocol::data::Server,
+    context::ProcessContext,
+    protocol::{
+        data::{ DataMessage, Server, RemoteMemory, MEMORY_CHUNK_SIZE },
+        control::Notifier,
+        rpc,
+    },
     syscall::{
         Syscall,
-        decoder::DecodeExit,
+        decoder::{ DecodeExit, PointerArgs },
         encoder::EncodeEntry,
     },
-    targets::operation::Operation,
-    executor::Invoker,
+    targets::operation::{ Operation, OperationRequest },
+    executor::{ Invoker, ChildConfig, Scratch, ScratchMemory, Policy, Recorder, Replay, Divergence, Resources, replay },
 };
 
 
@@ -24,11 +36,27 @@ use crate::{
 pub struct ExecutorEngine {
     pub arch: Architecture,
     protocol: Server,
+    remote: RemoteMemory,
 
     syscall: Syscall,
     child_pid: i32,
     operator: Box<Operation>,
     invoker: Box<dyn Invoker>,
+    /* Where the tracee memory given to the syscalls is copied in the child */
+    scratch: Scratch,
+    /* The syscalls accepted by the executor, all of them when none */
+    policy: Option<Policy>,
+    /* Where the executed syscalls are written */
+    recorder: Option<Recorder>,
+    /* The recording answering the syscalls instead of the invoker */
+    replay: Option<Replay>,
+    /* What the syscalls of the current session created in the child */
+    resources: Resources,
+    /* How long a session may stay silent before its resources are released */
+    session_timeout: Option<Duration>,
+    last_message: Instant,
+    requests: Option<Receiver<OperationRequest>>,
+    notifier: Option<Notifier>,
 
     stop: Arc<Event>,
     stopped: Arc<Event>,
@@ -38,25 +66,138 @@ impl ExecutorEngine {
 
     pub fn new(
         target_arch: TargetArch,
-        ipv4_address: &str,
-        executor_port: u16,
-        tracer_port: u16,
+        protocol: Server,
         stop_event: Arc<Event>,
         stopped_event: Arc<Event>,
-        operator: Box<Operation>,
+        mut operator: Box<Operation>,
         invoker: Box<dyn Invoker>,
         child_pid: i32,
     ) -> Self
     {
+        let remote = protocol.remote_memory(stop_event.clone());
+
+        // The encoders write the tracee memory at its address, which is redirected to the scratch memory
+        let scratch = Scratch::new();
+        let Operation { register, memory } = *operator;
+        *operator = Operation {
+            register,
+            memory: Box::new(ScratchMemory::new(memory, scratch.clone())),
+        };
+
         Self {
             arch: Architecture::new(target_arch),
-            protocol: Server::new(ipv4_address, executor_port, tracer_port),
+            protocol,
+            remote,
             syscall: Syscall::new(),
-            operator: operator,
+            operator,
             stop: stop_event,
             stopped: stopped_event,
-            invoker: invoker,
-            child_pid: child_pid,
+            invoker,
+            scratch,
+            policy: None,
+            recorder: None,
+            replay: None,
+            resources: Resources::new(),
+            session_timeout: None,
+            last_message: Instant::now(),
+            requests: None,
+            notifier: None,
+            child_pid,
+        }
+    }
+
+    /*
+     * Same as new() but the child is first spawned by the invoker from its configuration.
+     * The child is returned to be waited or released by the caller.
+     */
+    pub fn spawn(
+        target_arch: TargetArch,
+        protocol: Server,
+        stop_event: Arc<Event>,
+        stopped_event: Arc<Event>,
+        operator: Box<Operation>,
+        mut invoker: Box<dyn Invoker>,
+        config: &ChildConfig,
+    ) -> Result<(Self, Child), io::Error>
+    {
+        let child = invoker.invoke_new_process(config)?;
+        let child_pid = child.id() as i32;
+
+        let executor = Self::new(target_arch, protocol, stop_event, stopped_event, operator, invoker, child_pid);
+        Ok((executor, child))
+    }
+
+    /*
+     * Receive the operations on the child requested by the control thread.
+     * They are served between two syscalls, at least once per read timeout of the data channel.
+     */
+    pub fn set_request_channel(&mut self, requests: Receiver<OperationRequest>)
+    {
+        self.requests = Some(requests);
+    }
+
+    /*
+     * Refuse the syscalls outside of the policy, whatever the tracer decided.
+     */
+    pub fn set_policy(&mut self, policy: Policy)
+    {
+        self.policy = Some(policy);
+    }
+
+    /*
+     * Write the executed syscalls, with their buffers, to be replayed later.
+     */
+    pub fn set_recorder(&mut self, recorder: Recorder)
+    {
+        self.recorder = Some(recorder);
+    }
+
+    /*
+     * Answer the syscalls from a recording, the invoker only executes those diverging from it
+     * when asked to.
+     */
+    pub fn set_replay(&mut self, replay: Replay)
+    {
+        self.replay = Some(replay);
+    }
+
+    /*
+     * Release the resources of a session once the tracer sent nothing, not even a heartbeat, for this long.
+     */
+    pub fn set_session_timeout(&mut self, timeout: Duration)
+    {
+        self.session_timeout = Some(timeout);
+    }
+
+    /*
+     * Report the executed syscalls to the control client.
+     */
+    pub fn set_notifier(&mut self, notifier: Notifier)
+    {
+        self.notifier = Some(notifier);
+    }
+
+    fn notify_executed(&self, pipelined: bool)
+    {
+        if let Some(notifier) = self.notifier.as_ref() {
+            if notifier.is_subscribed("syscall_forwarded") {
+                notifier.notify(rpc::Event::SyscallForwarded {
+                    pid: self.child_pid,
+                    name: self.syscall.name.clone(),
+                    no: self.syscall.raw.no,
+                    retval: self.syscall.raw.retval as isize,
+                    pipelined,
+                });
+            }
+        }
+    }
+
+    fn serve_requests(&self)
+    {
+        if let Some(requests) = self.requests.as_ref() {
+            while let Ok(request) = requests.try_recv() {
+                request.serve(self.child_pid, &self.operator);
+            }
         }
     }
 
@@ -69,18 +210,51 @@ impl ExecutorEngine {
             if self.stop.is_set() {
                 break;
             }
+            self.serve_requests();
 
             /* Wait for new syscall
              * Note:
-             * There is no timeout or keep-alive mechanisms to know when the tracer is finished.
-             * Instead, the executor should run in another thread a listining loop to receive
-             * remote commands (TODO).
-             * For now, it needs to be stopped manually or via a signal.
+             * A session ends when the tracer sends EndSession or stops sending anything, even its
+             * heartbeat, for the session timeout. The executor itself runs until it is stopped through
+             * the control channel.
              */
-            //self.syscall = self.protocol.receive_syscall();
-            match self.protocol.receive_syscall() {
-                Ok(syscall) => {
-                    self.syscall = syscall;
+            let request = self.protocol.receive_request();
+            if request.is_ok() {
+                self.last_message = Instant::now();
+            }
+            match request {
+                Ok(DataMessage::Syscall(syscall)) => {
+                    self.syscall = *syscall;
+                    self.execute_syscall();
+                    self.protocol.return_syscall_exit(&self.syscall);
+                    self.notify_executed(false);
+                },
+                Ok(DataMessage::Batch(syscalls)) => {
+                    // Pipelined syscalls are executed in order and returned all at once
+                    let mut results = Vec::with_capacity(syscalls.len());
+                    for syscall in syscalls {
+                        self.syscall = syscall;
+                        self.execute_syscall();
+                        self.notify_executed(true);
+                        results.push(self.syscall.clone());
+                    }
+                    self.protocol.return_batch_exit(&results);
+                },
+                // The memory mirrored by the tracer, between two syscalls
+                Ok(DataMessage::ReadMemory { address, size }) => {
+                    let mem = self.read_mapped(address, size);
+                    self.protocol.return_memory(mem);
+                },
+                Ok(DataMessage::WriteMemory { address, content }) => self.write_mapped(address, content),
+                Ok(DataMessage::Heartbeat) => (),
+                Ok(DataMessage::EndSession) => self.end_session("the tracee is gone"),
+                Ok(DataMessage::Context(context)) => {
+                    if let Err(err) = self.apply_context(&context) {
+                        eprintln!("[{}] Fail to adopt the context of the tracee: {}", self.child_pid, err);
+                    }
+                },
+                Ok(message) => {
+                    eprintln!("Unexpected message on the data channel: {:?}", message);
                 },
                 /* Unix => WouldBlock ; Windows => TimedOut
                 Err(ref err) if err.kind() == io::ErrorKind::TimedOut => {
@@ -91,6 +265,7 @@ impl ExecutorEngine {
                 // The socket is set with a timeout of 1sec in order to check if the thread should stop.
                 Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
                     //eprintln!("Socket timeout: {:?}", err);
+                    self.check_session_timeout();
                     continue;
                 },
                 Err(err) => {
@@ -98,18 +273,141 @@ impl ExecutorEngine {
                     continue;
                 }
             }
+        }
+
+        self.end_session("the executor stops");
+        self.stopped.set();
+
+    }
 
-            /* Carry out syscall's decision */
-            self.log_entry_syscall();
-            self.invoke_syscall().unwrap();
-            self.log_exit_syscall();
+    /*
+     * Release what the syscalls of the session created, the child is then ready for the next session,
+     * which may come from another tracer.
+     */
+    fn end_session(&mut self, reason: &str)
+    {
+        self.protocol.unpin_sender();
+        if self.resources.is_empty() {
+            return;
+        }
+        println!("[{}] End of the session: {}, release its resources", self.child_pid, reason);
+        if let Err(err) = self.resources.release(self.invoker.as_ref()) {
+            eprintln!("[{}] Fail to release the resources of the session: {}", self.child_pid, err);
+        }
+    }
+
+    /*
+     * Give the child the credentials and the context of the tracee.
+     * Only the ids of the file system change, so that the child can still adopt another context afterwards.
+     * Everything is tried, the error is the first one.
+     */
+    fn apply_context(&mut self, context: &ProcessContext) -> Result<(), io::Error>
+    {
+        println!("[{}] Adopt the context {:?}", self.child_pid, context);
+        let groups: Vec<u8> = context.groups.iter().flat_map(|group| group.to_le_bytes()).collect();
+        let cwd = context.cwd.as_ref().map(|cwd| [cwd.as_bytes(), &[0]].concat());
+        let rlimits: Vec<(u32, Vec<u8>)> = context.rlimits.iter()
+            .map(|(resource, (cur, max))| (*resource, [cur.to_le_bytes(), max.to_le_bytes()].concat()))
+            .collect();
+
+        let mut result = Ok(());
+        let mut check = |outcome: Result<(), io::Error>| {
+            if let Err(err) = outcome {
+                eprintln!("[{}] {}", self.child_pid, err);
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        };
 
-            /* Return syscall */
-            self.protocol.return_syscall_exit(&self.syscall);
+        let address = self.copy_to_child(groups)?;
+        check(self.invoke_checked(libc::SYS_setgroups, [context.groups.len(), address, 0, 0]));
+        // Both return the previous id whatever happens
+        self.invoker.invoke_syscall(libc::SYS_setfsgid as usize, context.fsgid as usize, 0, 0, 0, 0, 0, 0)?;
+        self.invoker.invoke_syscall(libc::SYS_setfsuid as usize, context.fsuid as usize, 0, 0, 0, 0, 0, 0)?;
+        if let Some(umask) = context.umask {
+            check(self.invoke_checked(libc::SYS_umask, [umask as usize, 0, 0, 0]));
+        }
+        if let Some(cwd) = cwd {
+            let address = self.copy_to_child(cwd)?;
+            check(self.invoke_checked(libc::SYS_chdir, [address, 0, 0, 0]));
+        }
+        for (resource, rlimit) in rlimits {
+            let address = self.copy_to_child(rlimit)?;
+            check(self.invoke_checked(libc::SYS_prlimit64, [0, resource as usize, address, 0]));
         }
 
-        self.stopped.set();
+        self.scratch.clear();
+        result
+    }
+
+    /*
+     * Access the memory mapped by the syscalls of the session, the rest of the child is not accessible.
+     */
+    fn read_mapped(&self, address: usize, size: usize) -> Vec<u8>
+    {
+        // The tracer reads by chunks, never allocate more for it
+        let size = std::cmp::min(size, MEMORY_CHUNK_SIZE);
+        if ! self.resources.maps(address, size) {
+            eprintln!("[{}] Refuse to read {} bytes at {:#x} outside of the mappings", self.child_pid, size, address);
+            return Vec::new();
+        }
+        self.operator.memory.read(self.child_pid, address, size)
+    }
 
+    fn write_mapped(&self, address: usize, content: Vec<u8>)
+    {
+        if ! self.resources.maps(address, content.len()) {
+            eprintln!("[{}] Refuse to write {} bytes at {:#x} outside of the mappings", self.child_pid, content.len(), address);
+            return;
+        }
+        let size = content.len();
+        if self.operator.memory.write(self.child_pid, address, content) != size {
+            eprintln!("[{}] Fail to write {} bytes at {:#x}", self.child_pid, size, address);
+        }
+    }
+
+    /*
+     * Copy the data in the scratch memory of the child and return its address there.
+     */
+    fn copy_to_child(&self, data: Vec<u8>) -> Result<usize, io::Error>
+    {
+        if data.is_empty() {
+            return Ok(0);
+        }
+        let address = self.scratch.reserve(data.len())?;
+        self.operator.memory.write(self.child_pid, address, data);
+        Ok(address)
+    }
+
+    /*
+     * Invoke a syscall of the executor itself, a failure is returned as an error.
+     */
+    fn invoke_checked(&self, scno: libc::c_long, args: [usize; 4]) -> Result<(), io::Error>
+    {
+        let (retval, _) = self.invoker.invoke_syscall(scno as usize, args[0], args[1], args[2], args[3], 0, 0, 0)?;
+        if (retval as isize) < 0 {
+            let err = io::Error::from_raw_os_error(-(retval as isize) as i32);
+            return Err(io::Error::new(err.kind(), format!("Syscall {} {:x?} failed: {}", scno, args, err)));
+        }
+        Ok(())
+    }
+
+    fn check_session_timeout(&mut self)
+    {
+        if let Some(timeout) = self.session_timeout {
+            if self.last_message.elapsed() >= timeout {
+                self.end_session("no heartbeat from the tracer");
+            }
+        }
+    }
+
+    fn execute_syscall(&mut self)
+    {
+        /* Carry out syscall's decision */
+        self.log_entry_syscall();
+        self.invoke_syscall().unwrap();
+        self.log_exit_syscall();
     }
 
     fn invoke_syscall(&mut self) -> Result<(), io::Error>
@@ -121,11 +419,67 @@ impl ExecutorEngine {
         //let raw = self.syscall.raw.clone();
         if let Some(decoded_sc) = self.syscall.decoded.as_mut() {
             let raw = self.syscall.raw.clone();
-            self.syscall.raw = decoded_sc.encode_entry(raw, self.child_pid, &self.operator).unwrap();
+            match decoded_sc.encode_entry(raw, self.child_pid, &self.operator, &self.remote, &self.scratch) {
+                Ok(raw) => self.syscall.raw = raw,
+                Err(err) => {
+                    eprintln!("Fail to encode the syscall {}: {}", self.syscall.name, err);
+                    let errno = encode_errno(&err);
+                    self.set_result(-errno as usize, errno as usize);
+                    return Ok(());
+                },
+            }
+        }
+
+        /* What identifies the syscall in a recording, once its buffers are fetched */
+        let signature = match self.recorder.is_some() || self.replay.is_some() {
+            true => Some(replay::signature(&self.syscall)),
+            false => None,
+        };
+
+        self.answer_syscall(signature.as_ref());
+
+        if let (Some(recorder), Some(signature)) = (self.recorder.as_mut(), signature) {
+            if let Err(err) = recorder.record(signature, &self.syscall) {
+                eprintln!("Fail to record the syscall {}: {}", self.syscall.name, err);
+            }
+        }
+        Ok(())
+    }
+
+    /*
+     * Give the encoded syscall its result, from the invoker unless refused or replayed.
+     */
+    fn answer_syscall(&mut self, signature: Option<&Value>)
+    {
+        if ! self.check_policy() {
+            self.set_result(-libc::EPERM as usize, libc::EPERM as usize);
+            return;
+        }
+
+        /* The child outlives the tracee, only the session ends */
+        if self.syscall.raw.no == libc::SYS_exit_group as usize {
+            self.end_session("the tracee exits");
+            self.set_result(0, 0);
+            return;
         }
 
-        /* Invoke the syscall */
-        let (retval, errno)= self.invoker.invoke_syscall(self.syscall.raw.no,
+        /* Answer from the recording, if any */
+        if let Some(signature) = signature.filter(|_| self.replay.is_some()) {
+            match self.replay_syscall(signature) {
+                None => {
+                    self.scratch.clear();
+                    return;
+                },
+                Some(Divergence::Execute) => (),
+                Some(_) => {
+                    self.set_result(-libc::EIO as usize, libc::EIO as usize);
+                    return;
+                },
+            }
+        }
+
+        /* Invoke the syscall, once its arguments are in the child */
+        let (retval, errno) = self.invoker.invoke_syscall(self.syscall.raw.no,
                                  self.syscall.raw.args[0],
                                  self.syscall.raw.args[1],
                                  self.syscall.raw.args[2],
@@ -134,15 +488,79 @@ impl ExecutorEngine {
                                  self.syscall.raw.args[5],
                                  self.syscall.raw.args[6])
                                  .unwrap();
+        let raw = &self.syscall.raw;
+        self.resources.update(raw.no, &raw.args, retval, self.child_pid, self.operator.memory.as_ref());
+        self.set_result(retval, errno);
+    }
+
+    /*
+     * Decode the syscall exit from the result given by the invoker, or by the executor itself.
+     */
+    fn set_result(&mut self, retval: usize, errno: usize)
+    {
         self.syscall.raw.retval = retval;
         self.syscall.raw.errno = errno;
 
-        /* Decode the syscall exit */
         if let Some(decoded_sc) = self.syscall.decoded.as_mut() {
             decoded_sc.decode_exit(self.syscall.raw.retval, self.child_pid, &self.operator).unwrap();
+            // The outputs are shipped back to the tracee, read them before the copies are released
+            if (retval as isize) >= 0 {
+                decoded_sc.read_outputs(self.child_pid, &self.operator);
+            }
         }
+        self.scratch.clear();
+    }
 
-        Ok(())
+    /*
+     * Give the syscall the result of the recorded one, the divergence handling is returned when none matches.
+     * The divergences are reported to the control client.
+     */
+    fn replay_syscall(&mut self, signature: &Value) -> Option<Divergence>
+    {
+        let replay = self.replay.as_mut()?;
+        let reason = match replay.answer(&self.syscall, signature) {
+            Ok(recorded) => match replay::apply_answer(&mut self.syscall, recorded) {
+                Ok(()) => return None,
+                Err(err) => format!("Fail to apply the recorded {}: {}", recorded.name, err),
+            },
+            Err(reason) => reason,
+        };
+        let divergence = replay.divergence();
+
+        eprintln!("Replay: {}", reason);
+        if let Some(notifier) = self.notifier.as_ref() {
+            notifier.notify(rpc::Event::ReplayDiverged {
+                pid: self.child_pid,
+                name: self.syscall.name.clone(),
+                no: self.syscall.raw.no,
+                reason,
+            });
+        }
+        Some(divergence)
+    }
+
+    /*
+     * Whether the syscall can be executed, a refused one is audited and reported to the control client.
+     * The name comes from the number, as the one given by the tracer may not match.
+     */
+    fn check_policy(&self) -> bool
+    {
+        let policy = match self.policy.as_ref() {
+            Some(policy) => policy,
+            None => return true,
+        };
+        let raw = &self.syscall.raw;
+        let name = self.arch.syscall_table.get_syscall_name(&raw.no).unwrap_or(format!("syscall_{}", raw.no));
+
+        let reason = match policy.check(self.child_pid, &name, &raw.args) {
+            Ok(()) => return true,
+            Err(reason) => reason,
+        };
+        policy.audit(self.child_pid, &name, raw.no, &raw.args, &reason);
+        if let Some(notifier) = self.notifier.as_ref() {
+            notifier.notify(rpc::Event::SyscallRefused { pid: self.child_pid, name, no: raw.no, reason });
+        }
+        false
     }
 
     pub fn shutdown(&mut self)
@@ -156,6 +574,35 @@ impl ExecutorEngine {
     fn init(&mut self)
     {
         //self.protocol.init();
+        // The scratch memory refuses every copy, so the syscalls taking a pointer fail to encode
+        if let Err(err) = self.map_scratch() {
+            eprintln!("Fail to map the scratch memory in the child {}, the syscalls taking a pointer are refused: {}", self.child_pid, err);
+        }
+    }
+
+    /*
+     * Map in the child the memory receiving the copies of the pointer arguments.
+     */
+    fn map_scratch(&mut self) -> Result<(), io::Error>
+    {
+        let (base, _) = self.invoker.invoke_syscall(libc::SYS_mmap as usize,
+                                 0,
+                                 Scratch::SIZE,
+                                 (libc::PROT_READ | libc::PROT_WRITE) as usize,
+                                 (libc::MAP_PRIVATE | libc::MAP_ANONYMOUS) as usize,
+                                 usize::MAX,
+                                 0,
+                                 0)?;
+        // A failed mmap returns -errno, a valid mapping is page aligned
+        if (base as isize) < 0 {
+            return Err(io::Error::from_raw_os_error(-(base as isize) as i32));
+        }
+        if base % 0x1000 != 0 {
+            let msg = format!("Unexpected mmap result {:#x}", base);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+        }
+        self.scratch.set_region(base, Scratch::SIZE);
+        Ok(())
     }
 
 
@@ -167,7 +614,23 @@ impl ExecutorEngine {
     fn log_exit_syscall(&self) {
         let json = serde_json::to_string(&self.syscall).unwrap();
         println!("[{}] {}", self.child_pid, json);
-        println!("");
+        println!();
     }
 
-}
\ No newline at end of file
+}
+
+/*
+ * The errno returned to the tracee for a syscall which cannot be encoded.
+ * ENOSYS is kept for the syscalls which cannot be forwarded yet.
+ */
+fn encode_errno(err: &io::Error) -> i32
+{
+    if let Some(errno) = err.raw_os_error() {
+        return errno;
+    }
+    match err.kind() {
+        io::ErrorKind::Unsupported => libc::ENOSYS,
+        io::ErrorKind::OutOfMemory => libc::ENOMEM,
+        _ => libc::EINVAL,
+    }
+}
@@ -3,15 +3,33 @@
  */
 
 mod executor_engine;
+mod child_config;
+mod scratch;
+mod native_invoker;
+mod sandbox;
+mod policy;
+mod replay;
+mod resources;
 
 pub use executor_engine::ExecutorEngine;
+pub use child_config::ChildConfig;
+pub use scratch::{ Scratch, ScratchMemory };
+pub use native_invoker::NativeInvoker;
+pub use sandbox::SandboxConfig;
+pub use policy::{ Policy, AuditEntry };
+pub use replay::{ Recorder, RecordedSyscall, Replay, ReplayConfig, Divergence };
+pub use resources::Resources;
 
 
 use std::{
     io,
     process::Child,
 };
-use nix::unistd::Pid;
+use nix::{
+    unistd::Pid,
+    libc::user_regs_struct,
+};
+use crate::protocol::control::Notifier;
 
 
 
@@ -20,14 +38,43 @@ use nix::unistd::Pid;
  */
 
 pub trait ExecutorCallback {
-    fn spawn_process(&mut self, program: &str, prog_args: &[&str]) -> Result<Pid, io::Error>;
+    fn spawn_process(&mut self, config: ChildConfig) -> Result<Pid, io::Error>;
     fn kill_process(&mut self, pid: Pid) -> Result<(), io::Error>;
+    /* Whether the pid is one of the processes of the executor */
+    fn has_process(&self, pid: Pid) -> bool;
+
+    fn read_memory(&mut self, pid: Pid, address: usize, size: usize) -> Result<Vec<u8>, io::Error>;
+    fn write_memory(&mut self, pid: Pid, address: usize, data: Vec<u8>) -> Result<usize, io::Error>;
+    fn read_registers(&mut self, pid: Pid) -> Result<user_regs_struct, io::Error>;
+    fn write_registers(&mut self, pid: Pid, regs: user_regs_struct) -> Result<(), io::Error>;
+
+    /*
+     * Give the executor a way to report events, such as the forwarded syscalls, to the control client.
+     */
+    fn set_notifier(&mut self, _notifier: Notifier) {}
+
+    /*
+     * Used to switch the role of a debugger holding both a tracer and an executor, see TracerCallback.
+     */
+    fn release_process(&mut self, _pid: Pid) -> Result<Child, io::Error>
+    {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "The executor cannot release its processes"))
+    }
+
+    fn adopt_process(&mut self, _child: Child) -> Result<Pid, io::Error>
+    {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "The executor cannot adopt a process"))
+    }
 }
 
 
 pub trait Invoker {
+    #[allow(clippy::too_many_arguments)]
     fn invoke_syscall(&self, scno: usize, arg1:usize, arg2: usize,
                       arg3: usize, arg4: usize, arg5: usize, arg6: usize,
                       arg7: usize) -> Result<(usize, usize), io::Error>;
-    fn invoke_new_process(&self) -> Result<Child, io::Error>;
+    /*
+     * Spawn the child in which the next syscalls are invoked.
+     */
+    fn invoke_new_process(&mut self, config: &ChildConfig) -> Result<Child, io::Error>;
 }
\ No newline at end of file
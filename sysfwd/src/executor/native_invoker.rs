@@ -0,0 +1,358 @@
+/*
+ * Invoke the syscalls in the executor itself, for the forwarded operations (files, devices, sockets)
+ * which do not depend on the identity of the process issuing them.
+ * The pointer arguments are copied in the scratch memory, which is then mapped in the executor,
+ * so the memory operations of the engine must be targets::local::Local.
+ */
+use std::{
+    cell::RefCell,
+    collections::{ BTreeMap, BTreeSet },
+    io,
+    process::Child,
+};
+
+use nix::{
+    errno::Errno,
+    libc,
+};
+
+use crate::{
+    executor::{
+        Invoker, ChildConfig,
+        resources::{ FD_RETURNING_SYSCALLS, in_mappings, unmap_range },
+    },
+    targets::{ local::Local, operation::MemoryOperation },
+};
+
+
+
+/*
+ * Syscalls known to only act on the fds and the mappings of the session, the others are refused.
+ * The fd arguments must be fds of the session, see fd_arguments.
+ */
+const SESSION_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_read, libc::SYS_write, libc::SYS_pread64, libc::SYS_pwrite64,
+    libc::SYS_readv, libc::SYS_writev, libc::SYS_preadv, libc::SYS_pwritev, libc::SYS_preadv2, libc::SYS_pwritev2,
+    libc::SYS_lseek, libc::SYS_close, libc::SYS_fstat, libc::SYS_fstatfs, libc::SYS_fsync, libc::SYS_fdatasync,
+    libc::SYS_ftruncate, libc::SYS_fallocate, libc::SYS_fadvise64, libc::SYS_sync_file_range,
+    libc::SYS_fchmod, libc::SYS_fchown, libc::SYS_fcntl, libc::SYS_ioctl, libc::SYS_flock,
+    libc::SYS_getdents, libc::SYS_getdents64, libc::SYS_dup, libc::SYS_dup2, libc::SYS_dup3,
+    libc::SYS_fgetxattr, libc::SYS_fsetxattr, libc::SYS_flistxattr, libc::SYS_fremovexattr,
+    libc::SYS_socket, libc::SYS_socketpair, libc::SYS_connect, libc::SYS_bind, libc::SYS_listen,
+    libc::SYS_accept, libc::SYS_accept4, libc::SYS_sendto, libc::SYS_recvfrom, libc::SYS_sendmsg, libc::SYS_recvmsg,
+    libc::SYS_shutdown, libc::SYS_getsockname, libc::SYS_getpeername, libc::SYS_setsockopt, libc::SYS_getsockopt,
+    libc::SYS_pipe, libc::SYS_pipe2, libc::SYS_eventfd, libc::SYS_eventfd2, libc::SYS_memfd_create,
+    libc::SYS_epoll_create, libc::SYS_epoll_create1, libc::SYS_epoll_ctl, libc::SYS_epoll_wait, libc::SYS_epoll_pwait,
+    libc::SYS_timerfd_create, libc::SYS_timerfd_settime, libc::SYS_timerfd_gettime,
+    libc::SYS_inotify_init, libc::SYS_inotify_init1, libc::SYS_inotify_rm_watch,
+    libc::SYS_sendfile, libc::SYS_splice, libc::SYS_tee, libc::SYS_copy_file_range,
+    libc::SYS_mmap, libc::SYS_munmap, libc::SYS_mremap, libc::SYS_msync,
+];
+
+/*
+ * Syscalls resolving a path, the executor files are reachable so they are only executed when a policy
+ * checks them first, see NativeInvoker::with_paths.
+ */
+const PATH_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_open, libc::SYS_creat, libc::SYS_openat, libc::SYS_openat2,
+    libc::SYS_stat, libc::SYS_lstat, libc::SYS_newfstatat, libc::SYS_statx, libc::SYS_statfs,
+    libc::SYS_access, libc::SYS_faccessat, libc::SYS_faccessat2, libc::SYS_readlink, libc::SYS_readlinkat,
+    libc::SYS_mkdir, libc::SYS_mkdirat, libc::SYS_rmdir, libc::SYS_unlink, libc::SYS_unlinkat,
+    libc::SYS_rename, libc::SYS_renameat, libc::SYS_renameat2, libc::SYS_link, libc::SYS_linkat,
+    libc::SYS_symlink, libc::SYS_symlinkat, libc::SYS_truncate, libc::SYS_utimensat,
+    libc::SYS_chmod, libc::SYS_fchmodat, libc::SYS_chown, libc::SYS_lchown, libc::SYS_fchownat,
+    libc::SYS_getxattr, libc::SYS_lgetxattr, libc::SYS_listxattr, libc::SYS_llistxattr,
+];
+
+/*
+ * The arguments holding a fd, AT_FDCWD is accepted for the *at syscalls.
+ */
+fn fd_arguments(scno: libc::c_long) -> &'static [usize]
+{
+    match scno {
+        libc::SYS_read | libc::SYS_write | libc::SYS_pread64 | libc::SYS_pwrite64
+            | libc::SYS_readv | libc::SYS_writev | libc::SYS_preadv | libc::SYS_pwritev
+            | libc::SYS_preadv2 | libc::SYS_pwritev2 | libc::SYS_lseek | libc::SYS_close
+            | libc::SYS_fstat | libc::SYS_fstatfs | libc::SYS_fsync | libc::SYS_fdatasync
+            | libc::SYS_ftruncate | libc::SYS_fallocate | libc::SYS_fadvise64 | libc::SYS_sync_file_range
+            | libc::SYS_fchmod | libc::SYS_fchown | libc::SYS_fcntl | libc::SYS_ioctl | libc::SYS_flock
+            | libc::SYS_getdents | libc::SYS_getdents64 | libc::SYS_dup
+            | libc::SYS_fgetxattr | libc::SYS_fsetxattr | libc::SYS_flistxattr | libc::SYS_fremovexattr
+            | libc::SYS_connect | libc::SYS_bind | libc::SYS_listen | libc::SYS_accept | libc::SYS_accept4
+            | libc::SYS_sendto | libc::SYS_recvfrom | libc::SYS_sendmsg | libc::SYS_recvmsg
+            | libc::SYS_sendmmsg | libc::SYS_recvmmsg | libc::SYS_shutdown
+            | libc::SYS_getsockname | libc::SYS_getpeername | libc::SYS_setsockopt | libc::SYS_getsockopt
+            | libc::SYS_epoll_wait | libc::SYS_epoll_pwait | libc::SYS_epoll_pwait2 | libc::SYS_inotify_add_watch | libc::SYS_inotify_rm_watch
+            | libc::SYS_timerfd_settime | libc::SYS_timerfd_gettime
+            | libc::SYS_openat | libc::SYS_openat2 | libc::SYS_mkdirat | libc::SYS_mknodat | libc::SYS_fchownat
+            | libc::SYS_futimesat | libc::SYS_newfstatat | libc::SYS_unlinkat | libc::SYS_readlinkat
+            | libc::SYS_fchmodat | libc::SYS_faccessat | libc::SYS_faccessat2 | libc::SYS_utimensat
+            | libc::SYS_statx | libc::SYS_name_to_handle_at | libc::SYS_open_by_handle_at => &[0],
+        libc::SYS_epoll_ctl => &[0, 2],
+        libc::SYS_sendfile | libc::SYS_tee => &[0, 1],
+        libc::SYS_splice | libc::SYS_copy_file_range | libc::SYS_renameat | libc::SYS_renameat2
+            | libc::SYS_linkat => &[0, 2],
+        libc::SYS_symlinkat => &[1],
+        libc::SYS_mmap => &[4],
+        _ => &[],
+    }
+}
+
+
+
+/*
+ * Each invoker is a session with its own fds: it only uses the fds it opened, never those of the
+ * executor (e.g., the sockets of the data and control channels), and closes them when dropped.
+ */
+#[derive(Debug, Default)]
+pub struct NativeInvoker {
+    fds: RefCell<BTreeSet<usize>>,
+    /* Only the mappings created by the session can be unmapped, remapped or synchronized */
+    mappings: RefCell<BTreeMap<usize, usize>>,
+    /* Whether the syscalls resolving a path are executed */
+    paths: bool,
+}
+
+impl NativeInvoker {
+
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    /*
+     * Also execute the syscalls resolving a path, only when the engine checks them against a Policy.
+     */
+    pub fn with_paths(mut self) -> Self
+    {
+        self.paths = true;
+        self
+    }
+
+    /*
+     * Why the syscall cannot be executed in the executor, if so.
+     */
+    fn check(&self, scno: libc::c_long, args: &[usize; 6]) -> Result<(), Errno>
+    {
+        match scno {
+            libc::SYS_munmap | libc::SYS_msync => return match in_mappings(&self.mappings.borrow(), args[0], args[1]) {
+                true => Ok(()),
+                false => Err(Errno::EPERM),
+            },
+            // The executor mappings must not be replaced
+            libc::SYS_mremap => return match args[3] as i32 & libc::MREMAP_FIXED == 0
+                                          && in_mappings(&self.mappings.borrow(), args[0], args[1]) {
+                true => Ok(()),
+                false => Err(Errno::EPERM),
+            },
+            _ if empty_path(scno, args) => (),
+            _ if PATH_SYSCALLS.contains(&scno) || unix_address(scno, args) => match self.paths {
+                true => (),
+                false => return Err(Errno::EPERM),
+            },
+            _ if ! SESSION_SYSCALLS.contains(&scno) => return Err(Errno::ENOSYS),
+            // The fds given with the message would not be checked
+            libc::SYS_sendmsg if control_message(args[1]) => return Err(Errno::EPERM),
+            _ => (),
+        }
+
+        let fds = self.fds.borrow();
+        let anonymous = scno == libc::SYS_mmap && args[3] as i32 & libc::MAP_ANONYMOUS != 0;
+        for &index in fd_arguments(scno) {
+            if anonymous || args[index] as i32 == libc::AT_FDCWD {
+                continue;
+            }
+            if ! fds.contains(&args[index]) {
+                return Err(Errno::EBADF);
+            }
+        }
+
+        match scno {
+            // The executor mappings must not be replaced
+            libc::SYS_mmap if args[3] as i32 & (libc::MAP_FIXED | libc::MAP_FIXED_NOREPLACE) != 0 => Err(Errno::EPERM),
+            // The new fd must not be one of the executor
+            libc::SYS_dup2 | libc::SYS_dup3 => {
+                if ! fds.contains(&args[0]) {
+                    return Err(Errno::EBADF);
+                }
+                let used = unsafe { libc::fcntl(args[1] as i32, libc::F_GETFD) } != -1;
+                match used && ! fds.contains(&args[1]) {
+                    true => Err(Errno::EBADF),
+                    false => Ok(()),
+                }
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /*
+     * Keep track of the fds and the mappings created or released by the syscall which succeeded.
+     */
+    fn update_fds(&self, scno: libc::c_long, args: &[usize; 6], retval: usize)
+    {
+        let mut fds = self.fds.borrow_mut();
+        match scno {
+            libc::SYS_mmap => { self.mappings.borrow_mut().insert(retval, args[1]); },
+            libc::SYS_mremap => {
+                let mut mappings = self.mappings.borrow_mut();
+                if args[3] as i32 & libc::MREMAP_DONTUNMAP == 0 {
+                    unmap_range(&mut mappings, args[0], args[1]);
+                }
+                mappings.insert(retval, args[2]);
+            },
+            libc::SYS_munmap => { unmap_range(&mut self.mappings.borrow_mut(), args[0], args[1]); },
+            libc::SYS_close => { fds.remove(&args[0]); },
+            libc::SYS_dup2 | libc::SYS_dup3 => { fds.insert(retval); },
+            libc::SYS_fcntl if matches!(args[1] as i32, libc::F_DUPFD | libc::F_DUPFD_CLOEXEC) => { fds.insert(retval); },
+            // The fds are written in an array of two int
+            libc::SYS_pipe | libc::SYS_pipe2 | libc::SYS_socketpair => {
+                let address = if scno == libc::SYS_socketpair { args[3] } else { args[0] };
+                let pair = Local { }.read(0, address, 8);
+                for fd in pair.chunks_exact(4) {
+                    fds.insert(u32::from_le_bytes(fd.try_into().unwrap()) as usize);
+                }
+            },
+            _ if FD_RETURNING_SYSCALLS.contains(&scno) => { fds.insert(retval); },
+            _ => (),
+        }
+    }
+}
+
+/*
+ * A fstatat or statx of the fd itself, as made by fstat in the libc.
+ */
+fn empty_path(scno: libc::c_long, args: &[usize; 6]) -> bool
+{
+    let flags = match scno {
+        libc::SYS_newfstatat => args[3],
+        libc::SYS_statx => args[2],
+        _ => return false,
+    };
+    args[0] as i32 != libc::AT_FDCWD && flags as i32 & libc::AT_EMPTY_PATH != 0
+        && Local { }.read(0, args[1], 1) == [0]
+}
+
+/*
+ * A bind or connect to a unix socket, which resolves a path.
+ */
+fn unix_address(scno: libc::c_long, args: &[usize; 6]) -> bool
+{
+    if scno != libc::SYS_bind && scno != libc::SYS_connect {
+        return false;
+    }
+    let family = Local { }.read(0, args[1], 2);
+    family.len() == 2 && u16::from_ne_bytes([family[0], family[1]]) == libc::AF_UNIX as u16
+}
+
+/*
+ * Whether the struct msghdr at `address` has ancillary data, e.g., fds.
+ */
+fn control_message(address: usize) -> bool
+{
+    // struct msghdr { name, namelen, iov, iovlen, control, controllen, flags } only for x86_64
+    let msghdr = Local { }.read(0, address, 48);
+    msghdr.len() != 48 || usize::from_ne_bytes(msghdr[40..48].try_into().unwrap()) != 0
+}
+
+impl Invoker for NativeInvoker {
+
+    /*
+     * The result is returned as by the kernel, -errno on failure.
+     */
+    fn invoke_syscall(&self, scno: usize, arg1:usize, arg2: usize,
+                      arg3: usize, arg4: usize, arg5: usize, arg6: usize,
+                      _arg7: usize) -> Result<(usize, usize), io::Error>
+    {
+        let scno = scno as libc::c_long;
+        let args = [arg1, arg2, arg3, arg4, arg5, arg6];
+
+        if let Err(errno) = self.check(scno, &args) {
+            return Ok((-(errno as isize) as usize, errno as usize));
+        }
+
+        let retval = unsafe { libc::syscall(scno, arg1, arg2, arg3, arg4, arg5, arg6) };
+        if retval == -1 {
+            let errno = Errno::last();
+            return Ok((-(errno as isize) as usize, errno as usize));
+        }
+
+        let retval = retval as usize;
+        self.update_fds(scno, &args, retval);
+        Ok((retval, 0))
+    }
+
+    fn invoke_new_process(&mut self, _config: &ChildConfig) -> Result<Child, io::Error>
+    {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "A native invoker executes the syscalls in the executor itself"))
+    }
+}
+
+impl Drop for NativeInvoker {
+
+    fn drop(&mut self)
+    {
+        for fd in self.fds.borrow().iter() {
+            unsafe { libc::close(*fd as i32); }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_the_syscalls_outside_the_session()
+    {
+        let invoker = NativeInvoker::new();
+        assert_eq!(invoker.check(libc::SYS_close_range, &[0, !0, 0, 0, 0, 0]), Err(Errno::ENOSYS));
+        assert_eq!(invoker.check(libc::SYS_prlimit64, &[0; 6]), Err(Errno::ENOSYS));
+        assert_eq!(invoker.check(libc::SYS_pidfd_send_signal, &[0; 6]), Err(Errno::ENOSYS));
+        assert_eq!(invoker.check(libc::SYS_vmsplice, &[0; 6]), Err(Errno::ENOSYS));
+        // The fds of the executor are not those of the session
+        assert_eq!(invoker.check(libc::SYS_write, &[1, 0, 0, 0, 0, 0]), Err(Errno::EBADF));
+        assert_eq!(invoker.check(libc::SYS_munmap, &[0x1000, 0x1000, 0, 0, 0, 0]), Err(Errno::EPERM));
+    }
+
+    #[test]
+    fn resolves_paths_only_when_allowed()
+    {
+        let at_fdcwd = libc::AT_FDCWD as usize;
+        let path = c"/etc/passwd".as_ptr() as usize;
+        let args = [at_fdcwd, path, libc::O_RDONLY as usize, 0, 0, 0];
+
+        assert_eq!(NativeInvoker::new().check(libc::SYS_openat, &args), Err(Errno::EPERM));
+        assert_eq!(NativeInvoker::new().check(libc::SYS_unlink, &[path, 0, 0, 0, 0, 0]), Err(Errno::EPERM));
+        assert_eq!(NativeInvoker::new().with_paths().check(libc::SYS_openat, &args), Ok(()));
+    }
+
+    #[test]
+    fn unmaps_any_range_of_the_session_mappings()
+    {
+        let invoker = NativeInvoker::new();
+        let flags = (libc::MAP_PRIVATE | libc::MAP_ANONYMOUS) as usize;
+        let prot = (libc::PROT_READ | libc::PROT_WRITE) as usize;
+        let (address, _) = invoker.invoke_syscall(libc::SYS_mmap as usize, 0, 0x3000, prot, flags, !0, 0, 0).unwrap();
+
+        assert_eq!(invoker.check(libc::SYS_msync, &[address, 0x3000, libc::MS_SYNC as usize, 0, 0, 0]), Ok(()));
+        assert_eq!(invoker.check(libc::SYS_msync, &[address + 0x2000, 0x2000, 0, 0, 0, 0]), Err(Errno::EPERM));
+        let fixed = (libc::MREMAP_MAYMOVE | libc::MREMAP_FIXED) as usize;
+        assert_eq!(invoker.check(libc::SYS_mremap, &[address, 0x1000, 0x1000, fixed, 0x1000, 0]), Err(Errno::EPERM));
+
+        // The middle page is unmapped, the first and the last are kept
+        let (retval, _) = invoker.invoke_syscall(libc::SYS_munmap as usize, address + 0x1000, 0x1000, 0, 0, 0, 0, 0).unwrap();
+        assert_eq!(retval, 0);
+        assert_eq!(*invoker.mappings.borrow(), BTreeMap::from([(address, 0x1000), (address + 0x2000, 0x1000)]));
+        assert_eq!(invoker.check(libc::SYS_munmap, &[address, 0x2000, 0, 0, 0, 0]), Err(Errno::EPERM));
+
+        let (moved, _) = invoker.invoke_syscall(libc::SYS_mremap as usize, address + 0x2000, 0x1000, 0x4000,
+                                                libc::MREMAP_MAYMOVE as usize, 0, 0, 0).unwrap();
+        assert!((moved as isize) > 0);
+        assert_eq!(*invoker.mappings.borrow(), BTreeMap::from([(address, 0x1000), (moved, 0x4000)]));
+
+        for (start, length) in invoker.mappings.borrow().clone() {
+            unsafe { libc::munmap(start as *mut libc::c_void, length); }
+        }
+    }
+}
@@ -0,0 +1,478 @@
+/*
+ * The syscalls the executor accepts to execute, whatever the tracer asks.
+ * This is a safety layer independent of the filter of the tracer: a syscall outside of the policy
+ * is refused with EPERM and logged in the audit log.
+ */
+use std::{
+    collections::BTreeSet,
+    fs::{ self, File, OpenOptions },
+    io::{ self, Write },
+    os::unix::fs::{ FileExt, FileTypeExt, MetadataExt },
+    path::{ Component, Path, PathBuf },
+    time::{ SystemTime, UNIX_EPOCH },
+};
+
+use nix::libc;
+use serde::{ Serialize, Deserialize };
+
+
+
+/* PATH_MAX */
+const MAX_PATH: usize = 4096;
+/* The symbolic links followed to resolve a path, as MAXSYMLINKS */
+const MAX_LINKS: usize = 40;
+
+/*
+ * The arguments holding a path and the argument holding the fd the path is relative to, if any.
+ */
+fn path_arguments(name: &str) -> &'static [(Option<usize>, usize)]
+{
+    match name {
+        "open" | "creat" | "unlink" | "mkdir" | "rmdir" | "chmod" | "chown" | "lchown" | "truncate"
+            | "stat" | "lstat" | "access" | "readlink" | "execve" | "chdir" | "chroot" | "mknod"
+            | "statfs" | "utime" | "utimes" | "getxattr" | "lgetxattr" | "setxattr" | "lsetxattr"
+            | "listxattr" | "llistxattr" | "removexattr" | "lremovexattr" | "acct" | "swapon" => &[(None, 0)],
+        "openat" | "openat2" | "unlinkat" | "mkdirat" | "fchmodat" | "fchownat" | "newfstatat" | "statx"
+            | "faccessat" | "faccessat2" | "readlinkat" | "execveat" | "mknodat" | "futimesat"
+            | "utimensat" | "name_to_handle_at" => &[(Some(0), 1)],
+        "rename" | "link" => &[(None, 0), (None, 1)],
+        "renameat" | "renameat2" | "linkat" => &[(Some(0), 1), (Some(2), 3)],
+        "symlink" => &[(None, 1)],
+        "symlinkat" => &[(Some(1), 2)],
+        "mount" => &[(None, 0), (None, 1)],
+        "umount2" | "pivot_root" => &[(None, 0)],
+        _ => &[],
+    }
+}
+
+/*
+ * The arguments holding a fd, which may refer to a device.
+ */
+fn fd_arguments(name: &str) -> &'static [usize]
+{
+    match name {
+        "read" | "write" | "pread64" | "pwrite64" | "readv" | "writev" | "preadv" | "pwritev"
+            | "preadv2" | "pwritev2" | "lseek" | "ioctl" | "fcntl" | "fstat" | "fsync" | "fdatasync"
+            | "ftruncate" | "getdents" | "getdents64" | "flock" | "fallocate" => &[0],
+        "sendfile" | "splice" | "copy_file_range" => &[0, 2],
+        "mmap" => &[4],
+        _ => &[],
+    }
+}
+
+
+
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, Default)]
+#[serde(default)]
+pub struct Policy {
+    /* The names of the syscalls executed, all the others are refused */
+    pub syscalls: BTreeSet<String>,
+    /* The paths given to the syscalls, once their symbolic links are followed, must be under one of these */
+    pub paths: Vec<String>,
+    /* The devices, as "major:minor", which may be opened or used wherever their node is */
+    pub devices: Vec<String>,
+    /* Where the refused syscalls are appended as JSON lines, stderr by default */
+    pub audit_log: Option<String>,
+
+    #[serde(skip)]
+    device_numbers: Vec<libc::dev_t>,
+}
+
+/*
+ * An entry of the audit log.
+ */
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug)]
+pub struct AuditEntry {
+    pub time: u64,
+    pub pid: i32,
+    pub name: String,
+    pub no: usize,
+    pub args: Vec<usize>,
+    pub reason: String,
+}
+
+impl Policy {
+
+    /*
+     * Load a policy from a JSON file, e.g.
+     * {"syscalls": ["openat", "read", "write", "close"], "paths": ["/tmp/data"], "devices": ["10:125"]}
+     */
+    pub fn from_file(path: &str) -> Result<Self, io::Error>
+    {
+        let content = fs::read_to_string(path)?;
+        let mut policy: Self = serde_json::from_str(&content)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid policy {}: {}", path, err)))?;
+        policy.device_numbers = policy.devices.iter()
+            .map(|device| parse_device(device))
+            .collect::<Result<Vec<libc::dev_t>, io::Error>>()?;
+        Ok(policy)
+    }
+
+    /*
+     * Why the syscall, about to be executed by `pid` with `args`, is refused.
+     * The arguments are those given to the process, its memory is read to get the paths.
+     */
+    pub fn check(&self, pid: i32, name: &str, args: &[usize]) -> Result<(), String>
+    {
+        if ! self.syscalls.contains(name) {
+            return Err(format!("The syscall {} is not allowed", name));
+        }
+        // A file handle opens any file of the mount, it has no path to check
+        if name == "open_by_handle_at" && ! self.paths.is_empty() {
+            return Err(format!("The syscall {} escapes the allowed paths", name));
+        }
+
+        for &(dirfd, index) in path_arguments(name) {
+            // e.g., utimensat on the fd itself
+            if args[index] == 0 {
+                continue;
+            }
+            let path = read_path(pid, args[index])
+                .map_err(|err| format!("Fail to read the path of {}: {}", name, err))?;
+            let resolved = resolve(pid, dirfd.map(|dirfd| args[dirfd] as i32), &path)
+                .map_err(|err| format!("Fail to resolve the path {:?} of {}: {}", path, name, err))?;
+            self.check_path(pid, &resolved)?;
+
+            // The file a symbolic link points to must be allowed as well, whether the syscall follows it or not
+            let target = canonicalize(pid, &resolved)
+                .map_err(|err| format!("Fail to resolve the path {:?} of {}: {}", path, name, err))?;
+            if target != resolved {
+                self.check_path(pid, &target)?;
+            }
+
+            // A link created in an allowed directory must not point outside of them
+            if name == "symlink" || name == "symlinkat" {
+                let link_target = read_path(pid, args[0])
+                    .map_err(|err| format!("Fail to read the target of {}: {}", name, err))?;
+                let directory = resolved.parent().unwrap_or(Path::new("/"));
+                let link_target = canonicalize(pid, &directory.join(link_target))
+                    .map_err(|err| format!("Fail to resolve the target of {}: {}", name, err))?;
+                self.check_path(pid, &link_target)?;
+            }
+        }
+
+        for &index in fd_arguments(name) {
+            // An anonymous mapping has no fd
+            if name == "mmap" && args[3] as i32 & libc::MAP_ANONYMOUS != 0 {
+                continue;
+            }
+            let fd = format!("/proc/{}/fd/{}", pid, args[index] as i32);
+            if let Ok(metadata) = fs::metadata(&fd) {
+                self.check_device(&metadata, &fd)?;
+            }
+        }
+        Ok(())
+    }
+
+    /*
+     * A device is allowed by its number, any other file by its path.
+     */
+    fn check_path(&self, pid: i32, path: &Path) -> Result<(), String>
+    {
+        // The path as seen by the process, which may be in another mount namespace
+        let host_path = Path::new(&format!("/proc/{}/root", pid)).join(path.strip_prefix("/").unwrap());
+        if let Ok(metadata) = fs::metadata(&host_path) {
+            if is_device(&metadata) {
+                return self.check_device(&metadata, &path.to_string_lossy());
+            }
+        }
+
+        match self.paths.iter().any(|prefix| path.starts_with(prefix)) {
+            true => Ok(()),
+            false => Err(format!("The path {} is not allowed", path.display())),
+        }
+    }
+
+    fn check_device(&self, metadata: &fs::Metadata, path: &str) -> Result<(), String>
+    {
+        if ! is_device(metadata) || self.device_numbers.contains(&metadata.rdev()) {
+            return Ok(());
+        }
+        let (major, minor) = (libc::major(metadata.rdev()), libc::minor(metadata.rdev()));
+        Err(format!("The device {}:{} ({}) is not allowed", major, minor, path))
+    }
+
+    /*
+     * Log a refused syscall.
+     */
+    pub fn audit(&self, pid: i32, name: &str, no: usize, args: &[usize], reason: &str)
+    {
+        let entry = AuditEntry {
+            time: SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0),
+            pid,
+            name: name.to_string(),
+            no,
+            args: args.to_vec(),
+            reason: reason.to_string(),
+        };
+        let line = serde_json::to_string(&entry).unwrap();
+
+        let written = match self.audit_log.as_ref() {
+            Some(path) => OpenOptions::new().create(true).append(true).open(path)
+                .and_then(|mut file| writeln!(file, "{}", line)),
+            None => {
+                eprintln!("[AUDIT] {}", line);
+                Ok(())
+            },
+        };
+        if let Err(err) = written {
+            eprintln!("Fail to write the audit log: {} [AUDIT] {}", err, line);
+        }
+    }
+}
+
+fn parse_device(device: &str) -> Result<libc::dev_t, io::Error>
+{
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("Invalid device {:?}, expected major:minor", device));
+    let (major, minor) = device.split_once(':').ok_or_else(invalid)?;
+    let major = major.trim().parse::<u32>().map_err(|_| invalid())?;
+    let minor = minor.trim().parse::<u32>().map_err(|_| invalid())?;
+    Ok(libc::makedev(major, minor))
+}
+
+fn is_device(metadata: &fs::Metadata) -> bool
+{
+    metadata.file_type().is_char_device() || metadata.file_type().is_block_device()
+}
+
+/*
+ * Read the null-terminated path at `address` in the memory of the process.
+ */
+fn read_path(pid: i32, address: usize) -> Result<PathBuf, io::Error>
+{
+    let mem = File::open(format!("/proc/{}/mem", pid))?;
+    let mut path = vec![0; MAX_PATH];
+    let size = mem.read_at(&mut path, address as u64)?;
+    path.truncate(size);
+
+    match path.iter().position(|byte| *byte == 0) {
+        Some(end) => {
+            path.truncate(end);
+            Ok(PathBuf::from(String::from_utf8_lossy(&path).into_owned()))
+        },
+        None => Err(io::Error::new(io::ErrorKind::InvalidData, "Path not terminated")),
+    }
+}
+
+/*
+ * The absolute path, relative to the directory of `dirfd` or to the current directory of the process.
+ * The symbolic links of its directories are followed, not the one of its last component which some
+ * syscalls do not follow (e.g., unlink or lstat), see canonicalize.
+ */
+fn resolve(pid: i32, dirfd: Option<i32>, path: &Path) -> Result<PathBuf, io::Error>
+{
+    let absolute = match path.is_absolute() {
+        true => path.to_path_buf(),
+        false => {
+            let base = match dirfd {
+                Some(fd) if fd != libc::AT_FDCWD => format!("/proc/{}/fd/{}", pid, fd),
+                _ => format!("/proc/{}/cwd", pid),
+            };
+            fs::read_link(base)?.join(path)
+        },
+    };
+
+    match absolute.components().next_back() {
+        Some(Component::Normal(name)) => {
+            let parent = absolute.parent().unwrap_or(Path::new("/"));
+            Ok(canonicalize(pid, parent)?.join(name))
+        },
+        _ => canonicalize(pid, &absolute),
+    }
+}
+
+/*
+ * The absolute path with all its symbolic links followed, in the root directory of the process.
+ * The components which do not exist yet (e.g., a file to create) are kept as they are.
+ */
+fn canonicalize(pid: i32, absolute: &Path) -> Result<PathBuf, io::Error>
+{
+    let root = PathBuf::from(format!("/proc/{}/root", pid));
+    let mut pending: Vec<PathBuf> = absolute.components().rev()
+        .map(|component| PathBuf::from(component.as_os_str()))
+        .collect();
+    let mut resolved = PathBuf::from("/");
+    let mut links = 0;
+
+    while let Some(component) = pending.pop() {
+        match component.components().next() {
+            Some(Component::RootDir) => resolved = PathBuf::from("/"),
+            Some(Component::ParentDir) => { resolved.pop(); },
+            Some(Component::Normal(name)) => {
+                let next = resolved.join(name);
+                let host_path = root.join(next.strip_prefix("/").unwrap());
+                let is_link = fs::symlink_metadata(&host_path)
+                    .map(|metadata| metadata.file_type().is_symlink())
+                    .unwrap_or(false);
+                if ! is_link {
+                    resolved = next;
+                    continue;
+                }
+
+                links += 1;
+                if links > MAX_LINKS {
+                    return Err(io::Error::from_raw_os_error(libc::ELOOP));
+                }
+                // The target is resolved in place of the link, an absolute one from the root of the process
+                let target = fs::read_link(&host_path)?;
+                pending.extend(target.components().rev().map(|component| PathBuf::from(component.as_os_str())));
+            },
+            _ => (),
+        }
+    }
+    Ok(resolved)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{ ffi::CString, os::unix::fs::symlink };
+
+    /*
+     * A directory with an allowed and a forbidden subdirectory, removed when dropped.
+     */
+    struct Tree {
+        base: PathBuf,
+    }
+
+    impl Tree {
+        fn new(name: &str) -> Self
+        {
+            let base = std::env::temp_dir().join(format!("policy-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&base);
+            fs::create_dir_all(base.join("allowed/sub")).unwrap();
+            fs::create_dir_all(base.join("forbidden/sub")).unwrap();
+            fs::write(base.join("allowed/file"), b"").unwrap();
+            fs::write(base.join("forbidden/secret"), b"").unwrap();
+            Self { base }
+        }
+
+        fn path(&self, path: &str) -> PathBuf
+        {
+            self.base.join(path)
+        }
+
+        fn policy(&self) -> Policy
+        {
+            Policy {
+                syscalls: ["openat", "unlinkat", "symlinkat", "unlink"].iter().map(|name| name.to_string()).collect(),
+                paths: vec![self.path("allowed").to_string_lossy().into_owned()],
+                ..Default::default()
+            }
+        }
+    }
+
+    impl Drop for Tree {
+        fn drop(&mut self)
+        {
+            let _ = fs::remove_dir_all(&self.base);
+        }
+    }
+
+    fn c_path(path: &Path) -> CString
+    {
+        CString::new(path.to_string_lossy().into_owned()).unwrap()
+    }
+
+    fn check_openat(policy: &Policy, path: &Path) -> Result<(), String>
+    {
+        let path = c_path(path);
+        let args = [libc::AT_FDCWD as usize, path.as_ptr() as usize, 0, 0, 0, 0];
+        policy.check(std::process::id() as i32, "openat", &args)
+    }
+
+    #[test]
+    fn refuses_the_syscalls_not_listed()
+    {
+        let tree = Tree::new("syscalls");
+        let path = c_path(&tree.path("allowed/file"));
+        let args = [libc::AT_FDCWD as usize, path.as_ptr() as usize, 0, 0, 0, 0];
+        assert!(tree.policy().check(std::process::id() as i32, "renameat", &args).is_err());
+    }
+
+    #[test]
+    fn allows_the_paths_under_a_prefix()
+    {
+        let tree = Tree::new("prefix");
+        let policy = tree.policy();
+        assert!(check_openat(&policy, &tree.path("allowed/file")).is_ok());
+        assert!(check_openat(&policy, &tree.path("allowed/new")).is_ok());
+        assert!(check_openat(&policy, &tree.path("forbidden/secret")).is_err());
+        assert!(check_openat(&policy, &tree.path("allowed/../forbidden/secret")).is_err());
+    }
+
+    #[test]
+    fn follows_the_symbolic_links()
+    {
+        let tree = Tree::new("links");
+        let policy = tree.policy();
+        symlink(tree.path("forbidden/secret"), tree.path("allowed/link")).unwrap();
+        symlink(tree.path("forbidden/sub"), tree.path("allowed/dirlink")).unwrap();
+
+        assert!(check_openat(&policy, &tree.path("allowed/link")).is_err());
+        assert!(check_openat(&policy, &tree.path("allowed/dirlink/file")).is_err());
+        // Lexically allowed/file, but the parent of dirlink is forbidden
+        assert!(check_openat(&policy, &tree.path("allowed/dirlink/../secret")).is_err());
+    }
+
+    #[test]
+    fn refuses_the_links_pointing_outside()
+    {
+        let tree = Tree::new("symlinkat");
+        let policy = tree.policy();
+        let pid = std::process::id() as i32;
+
+        let linkpath = c_path(&tree.path("allowed/evil"));
+        let outside = c_path(&tree.path("forbidden/secret"));
+        let args = [outside.as_ptr() as usize, libc::AT_FDCWD as usize, linkpath.as_ptr() as usize, 0, 0, 0];
+        assert!(policy.check(pid, "symlinkat", &args).is_err());
+
+        let relative = CString::new("../forbidden/secret").unwrap();
+        let args = [relative.as_ptr() as usize, libc::AT_FDCWD as usize, linkpath.as_ptr() as usize, 0, 0, 0];
+        assert!(policy.check(pid, "symlinkat", &args).is_err());
+
+        let inside = CString::new("sub").unwrap();
+        let args = [inside.as_ptr() as usize, libc::AT_FDCWD as usize, linkpath.as_ptr() as usize, 0, 0, 0];
+        assert!(policy.check(pid, "symlinkat", &args).is_ok());
+    }
+
+    #[test]
+    fn resolves_relative_to_the_dirfd()
+    {
+        let tree = Tree::new("dirfd");
+        let policy = tree.policy();
+        let dir = File::open(tree.path("allowed")).unwrap();
+        let fd = std::os::unix::io::AsRawFd::as_raw_fd(&dir) as usize;
+
+        let inside = CString::new("file").unwrap();
+        let escape = CString::new("../forbidden/secret").unwrap();
+        let pid = std::process::id() as i32;
+        assert!(policy.check(pid, "unlinkat", &[fd, inside.as_ptr() as usize, 0, 0, 0, 0]).is_ok());
+        assert!(policy.check(pid, "unlinkat", &[fd, escape.as_ptr() as usize, 0, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn detects_the_link_loops()
+    {
+        let tree = Tree::new("loop");
+        symlink(tree.path("allowed/b"), tree.path("allowed/a")).unwrap();
+        symlink(tree.path("allowed/a"), tree.path("allowed/b")).unwrap();
+        assert!(check_openat(&tree.policy(), &tree.path("allowed/a")).is_err());
+    }
+
+    #[test]
+    fn refuses_the_file_handles_with_paths()
+    {
+        let tree = Tree::new("handle");
+        let mut policy = tree.policy();
+        policy.syscalls.insert("open_by_handle_at".to_string());
+        let args = [libc::AT_FDCWD as usize, 0, libc::O_RDONLY as usize, 0, 0, 0];
+        assert!(policy.check(std::process::id() as i32, "open_by_handle_at", &args).is_err());
+
+        policy.paths.clear();
+        assert!(policy.check(std::process::id() as i32, "open_by_handle_at", &args).is_ok());
+    }
+}
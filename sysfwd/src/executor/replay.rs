@@ -0,0 +1,342 @@
+/*
+ * Record the syscalls executed during a forwarding session and replay them later without the devices,
+ * e.g., to run the rehosting tests on a machine which does not have them.
+ * A recording is a file of JSON lines, one RecordedSyscall per line.
+ */
+use std::{
+    fs::{ self, File },
+    io::{ self, BufWriter, Write },
+};
+
+use serde::{ Serialize, Deserialize };
+use serde_json::Value;
+
+use crate::syscall::{ Syscall, decoder::DecodedSyscall };
+
+
+
+/*
+ * A syscall as returned to the tracer, with its exit values and buffers.
+ * The signature identifies it on entry, see signature().
+ */
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug)]
+pub struct RecordedSyscall {
+    pub signature: Value,
+    pub syscall: Syscall,
+}
+
+/*
+ * Write each executed syscall in a recording.
+ */
+#[derive(Debug)]
+pub struct Recorder {
+    file: BufWriter<File>,
+}
+
+impl Recorder {
+
+    pub fn create(path: &str) -> Result<Self, io::Error>
+    {
+        Ok(Self { file: BufWriter::new(File::create(path)?) })
+    }
+
+    /*
+     * The recording is flushed after each syscall so that it is complete whenever the session ends.
+     */
+    pub fn record(&mut self, signature: Value, syscall: &Syscall) -> Result<(), io::Error>
+    {
+        let recorded = RecordedSyscall { signature, syscall: syscall.clone() };
+        let line = serde_json::to_string(&recorded)?;
+        writeln!(self.file, "{}", line)?;
+        self.file.flush()
+    }
+}
+
+
+
+/*
+ * What to do with a syscall which does not match the next one of the recording.
+ */
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Divergence {
+    /* Return EIO without moving in the recording */
+    #[default]
+    Error,
+    /* Skip the recorded syscalls up to the next matching one, within the lookahead, or return EIO */
+    Resync,
+    /* Execute the syscall for real without moving in the recording */
+    Execute,
+}
+
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, Default)]
+#[serde(default)]
+pub struct ReplayConfig {
+    /* The recording written by a Recorder */
+    pub trace: String,
+    pub divergence: Divergence,
+    /* How many recorded syscalls may be skipped to resync */
+    pub lookahead: usize,
+}
+
+/*
+ * Answer the syscalls from a recording, in order.
+ * A syscall matches a recorded one with the same signature.
+ */
+#[derive(Debug)]
+pub struct Replay {
+    trace: Vec<RecordedSyscall>,
+    position: usize,
+    config: ReplayConfig,
+}
+
+impl Replay {
+
+    pub fn new(config: ReplayConfig) -> Result<Self, io::Error>
+    {
+        let content = fs::read_to_string(&config.trace)?;
+        let trace = content.lines()
+            .filter(|line| ! line.trim().is_empty())
+            .enumerate()
+            .map(|(i, line)| serde_json::from_str::<RecordedSyscall>(line)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData,
+                                              format!("Invalid syscall {} in {}: {}", i, config.trace, err))))
+            .collect::<Result<Vec<RecordedSyscall>, io::Error>>()?;
+
+        Ok(Self {
+            trace,
+            position: 0,
+            config,
+        })
+    }
+
+    pub fn divergence(&self) -> Divergence
+    {
+        self.config.divergence
+    }
+
+    /*
+     * The recorded syscall answering the one with this signature, or why it diverges from the recording.
+     */
+    pub fn answer(&mut self, syscall: &Syscall, signature: &Value) -> Result<&Syscall, String>
+    {
+        let window = match self.config.divergence {
+            Divergence::Resync => self.config.lookahead + 1,
+            _ => 1,
+        };
+        let found = self.trace[self.position..].iter()
+            .take(window)
+            .position(|recorded| recorded.signature == *signature);
+
+        if let Some(offset) = found {
+            if offset > 0 {
+                eprintln!("Replay: skip {} recorded syscalls at {}", offset, self.position);
+            }
+            self.position += offset + 1;
+            return Ok(&self.trace[self.position - 1].syscall);
+        }
+
+        match self.trace.get(self.position) {
+            Some(recorded) => Err(format!("{} diverges from the recorded {} at {}", syscall.name, recorded.syscall.name, self.position)),
+            None => Err(format!("{} is after the end of the recording", syscall.name)),
+        }
+    }
+}
+
+/*
+ * Give the syscall the exit values of the recorded one.
+ * The contents of its pointer arguments are the recorded ones, at the addresses of this syscall.
+ */
+pub fn apply_answer(syscall: &mut Syscall, recorded: &Syscall) -> Result<(), io::Error>
+{
+    syscall.raw.retval = recorded.raw.retval;
+    syscall.raw.errno = recorded.raw.errno;
+
+    if let (Some(decoded), Some(answer)) = (syscall.decoded.as_ref(), recorded.decoded.as_ref()) {
+        let mut value = serde_json::to_value(decoded)?;
+        merge_exit(&mut value, &serde_json::to_value(answer)?);
+        syscall.decoded = Some(serde_json::from_value::<DecodedSyscall>(value)?);
+    }
+    Ok(())
+}
+
+fn merge_exit(value: &mut Value, answer: &Value)
+{
+    let (value, answer) = match (value, answer) {
+        (Value::Object(value), Value::Object(answer)) => (value, answer),
+        _ => return,
+    };
+    for (key, answer) in answer {
+        match (key.as_str(), value.get_mut(key)) {
+            ("retval", Some(value)) | ("content", Some(value)) => *value = answer.clone(),
+            (_, Some(value)) => merge_exit(value, answer),
+            _ => (),
+        }
+    }
+}
+
+/*
+ * What identifies the syscall on entry, once its arguments are encoded, whatever the run:
+ * the number, the values and the buffers given to the syscall, but not the addresses nor the
+ * structures, which may hold addresses or uninitialized memory.
+ * Only the number of a syscall which is not decoded is known, its arguments may be addresses.
+ */
+pub fn signature(syscall: &Syscall) -> Value
+{
+    let mut decoded = serde_json::to_value(&syscall.decoded).unwrap_or(Value::Null);
+    strip(&mut decoded);
+    serde_json::json!({ "no": syscall.raw.no, "decoded": decoded })
+}
+
+fn strip(value: &mut Value)
+{
+    let object = match value {
+        Value::Object(object) => object,
+        _ => return,
+    };
+    object.remove("retval");
+    if object.contains_key("direction") {
+        object.remove("address");
+        // An Address holds the pointer in its value
+        object.remove("value");
+        // A Struct is the only pointer argument with a name
+        if object.contains_key("name") || ! object.contains_key("size") {
+            object.remove("content");
+        }
+    }
+    for value in object.values_mut() {
+        strip(value);
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall::{
+        RawSyscall,
+        args::Size,
+        syscalls::io::{ Read, Write },
+    };
+
+    fn read(fd: usize, address: usize, count: usize) -> Syscall
+    {
+        let raw = RawSyscall { no: 0, args: vec![fd, address, count, 0, 0, 0], retval: 0, errno: 0 };
+        Syscall { raw: raw.clone(), decoded: Some(DecodedSyscall::Read(Read::new(raw))), name: "read".to_string(), decision: None }
+    }
+
+    fn write(fd: usize, address: usize, content: &[u8]) -> Syscall
+    {
+        let raw = RawSyscall { no: 1, args: vec![fd, address, content.len(), 0, 0, 0], retval: 0, errno: 0 };
+        let mut write = Write::new(raw.clone());
+        write.buf.content = content.to_vec();
+        Syscall { raw, decoded: Some(DecodedSyscall::Write(write)), name: "write".to_string(), decision: None }
+    }
+
+    /*
+     * The syscall once executed, returning `retval` and its buffer filled with `content` for a read.
+     */
+    fn executed(mut syscall: Syscall, retval: usize, content: &[u8]) -> Syscall
+    {
+        syscall.raw.retval = retval;
+        match syscall.decoded.as_mut() {
+            Some(DecodedSyscall::Read(read)) => {
+                read.buf.content = content.to_vec();
+                read.retval = Some(Size::new(retval));
+            },
+            Some(DecodedSyscall::Write(write)) => write.retval = Some(Size::new(retval)),
+            _ => (),
+        }
+        syscall
+    }
+
+    /*
+     * A replay of the syscalls, recorded by a Recorder in a file named after the test.
+     * As the executor does, each syscall is signed on entry and recorded once executed.
+     */
+    fn replay(name: &str, syscalls: &[(Syscall, usize, &[u8])], divergence: Divergence, lookahead: usize) -> Replay
+    {
+        let trace = format!("{}/sysfwd-replay-{}-{}.jsonl", std::env::temp_dir().display(), std::process::id(), name);
+        let mut recorder = Recorder::create(&trace).unwrap();
+        for (syscall, retval, content) in syscalls {
+            recorder.record(signature(syscall), &executed(syscall.clone(), *retval, content)).unwrap();
+        }
+        drop(recorder);
+
+        let replay = Replay::new(ReplayConfig { trace: trace.clone(), divergence, lookahead }).unwrap();
+        fs::remove_file(trace).unwrap();
+        replay
+    }
+
+    #[test]
+    fn signature_ignores_addresses_and_exit_values()
+    {
+        assert_eq!(signature(&read(3, 0x1000, 16)), signature(&executed(read(3, 0x2000, 16), 5, b"")));
+        assert_ne!(signature(&read(3, 0x1000, 16)), signature(&read(4, 0x1000, 16)));
+        assert_ne!(signature(&read(3, 0x1000, 16)), signature(&read(3, 0x1000, 32)));
+
+        // The buffers given to the syscall are part of it
+        assert_eq!(signature(&write(1, 0x1000, b"abc")), signature(&write(1, 0x3000, b"abc")));
+        assert_ne!(signature(&write(1, 0x1000, b"abc")), signature(&write(1, 0x1000, b"abd")));
+
+        let undecoded = Syscall { raw: RawSyscall { no: 39, args: vec![0x1000; 6], retval: 0, errno: 0 }, ..Syscall::new() };
+        assert_eq!(signature(&undecoded), serde_json::json!({ "no": 39, "decoded": null }));
+    }
+
+    #[test]
+    fn answer_keeps_the_addresses_of_the_syscall()
+    {
+        let recorded = executed(read(3, 0x1000, 16), 5, b"hello");
+        let mut syscall = read(3, 0x2000, 16);
+        apply_answer(&mut syscall, &recorded).unwrap();
+
+        assert_eq!(syscall.raw.retval, 5);
+        let read = match syscall.decoded.as_ref() {
+            Some(DecodedSyscall::Read(read)) => read,
+            _ => panic!("Not a read"),
+        };
+        assert_eq!((read.buf.address, read.buf.content.as_slice()), (0x2000, &b"hello"[..]));
+        assert_eq!(read.retval.as_ref().map(|retval| retval.value), Some(5));
+        assert_eq!(read.fd.value, 3);
+    }
+
+    #[test]
+    fn divergence_without_resync()
+    {
+        let trace: [(Syscall, usize, &[u8]); 2] = [(read(3, 0x1000, 16), 5, b"hello"), (write(1, 0x1000, b"hello"), 5, b"")];
+        let mut replay = replay("error", &trace, Divergence::Error, 4);
+
+        let diverging = write(1, 0x1000, b"hello");
+        assert!(replay.answer(&diverging, &signature(&diverging)).is_err());
+
+        // The recording did not move
+        let syscall = read(3, 0x5000, 16);
+        assert_eq!(replay.answer(&syscall, &signature(&syscall)).unwrap().raw.retval, 5);
+        assert_eq!(replay.answer(&diverging, &signature(&diverging)).unwrap().name, "write");
+        assert!(replay.answer(&diverging, &signature(&diverging)).unwrap_err().contains("end of the recording"));
+    }
+
+    #[test]
+    fn resync_within_the_lookahead()
+    {
+        let trace: [(Syscall, usize, &[u8]); 4] = [
+            (read(3, 0x1000, 16), 1, b"a"),
+            (read(4, 0x1000, 16), 1, b"b"),
+            (read(5, 0x1000, 16), 1, b"c"),
+            (read(6, 0x1000, 16), 1, b"d"),
+        ];
+        let mut replay = replay("resync", &trace, Divergence::Resync, 1);
+
+        // One recorded syscall skipped at most
+        let syscall = read(5, 0x1000, 16);
+        assert!(replay.answer(&syscall, &signature(&syscall)).is_err());
+        let syscall = read(4, 0x1000, 16);
+        assert_eq!(replay.answer(&syscall, &signature(&syscall)).unwrap().raw.args[0], 4);
+        let syscall = read(6, 0x1000, 16);
+        assert_eq!(replay.answer(&syscall, &signature(&syscall)).unwrap().raw.args[0], 6);
+        assert!(replay.answer(&syscall, &signature(&syscall)).is_err());
+    }
+}
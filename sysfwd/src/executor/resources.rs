@@ -0,0 +1,175 @@
+/*
+ * What the syscalls of a session created in the executor child, released when the session ends
+ * so that a device allowing a single opener is not left busy by a tracee which crashed.
+ */
+use std::{
+    collections::{ BTreeMap, BTreeSet },
+    io,
+};
+
+use nix::libc;
+
+use crate::{
+    executor::Invoker,
+    targets::operation::MemoryOperation,
+    tracer::mirror::PAGE_SIZE,
+};
+
+
+
+/*
+ * Syscalls returning a new fd.
+ */
+pub(crate) const FD_RETURNING_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_open, libc::SYS_openat, libc::SYS_openat2, libc::SYS_creat, libc::SYS_open_by_handle_at,
+    libc::SYS_socket, libc::SYS_accept, libc::SYS_accept4, libc::SYS_dup,
+    libc::SYS_eventfd, libc::SYS_eventfd2, libc::SYS_epoll_create, libc::SYS_epoll_create1,
+    libc::SYS_memfd_create, libc::SYS_signalfd, libc::SYS_signalfd4, libc::SYS_timerfd_create,
+    libc::SYS_inotify_init, libc::SYS_inotify_init1, libc::SYS_userfaultfd, libc::SYS_perf_event_open,
+];
+
+/* The other fds of the child, e.g., the standard streams, are never closed */
+const FIRST_SESSION_FD: usize = 3;
+
+/*
+ * Whether the memory block lies in one of the mappings, their last page is mapped up to its end.
+ */
+pub(crate) fn in_mappings(mappings: &BTreeMap<usize, usize>, address: usize, size: usize) -> bool
+{
+    match mappings.range(..=address).next_back() {
+        Some((start, length)) => address.saturating_add(size) <= start + length.next_multiple_of(PAGE_SIZE),
+        None => false,
+    }
+}
+
+/*
+ * Remove the range from the mappings, a mapping partly in the range keeps the rest.
+ */
+pub(crate) fn unmap_range(mappings: &mut BTreeMap<usize, usize>, address: usize, length: usize)
+{
+    let end = address.saturating_add(length.next_multiple_of(PAGE_SIZE));
+    let overlapping: Vec<(usize, usize)> = mappings.range(..end)
+        .map(|(start, length)| (*start, start + length.next_multiple_of(PAGE_SIZE)))
+        .filter(|(_, mapping_end)| address < *mapping_end)
+        .collect();
+
+    for (start, mapping_end) in overlapping {
+        mappings.remove(&start);
+        if start < address {
+            mappings.insert(start, address - start);
+        }
+        if end < mapping_end {
+            mappings.insert(end, mapping_end - end);
+        }
+    }
+}
+
+
+
+#[derive(Debug, Default)]
+pub struct Resources {
+    fds: BTreeSet<usize>,
+    /* The address and length of the mappings */
+    mappings: BTreeMap<usize, usize>,
+    processes: BTreeSet<i32>,
+}
+
+impl Resources {
+
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.fds.is_empty() && self.mappings.is_empty() && self.processes.is_empty()
+    }
+
+    /*
+     * Whether the memory block lies in one of the mappings, e.g., before the tracer reads or writes it.
+     */
+    pub fn maps(&self, address: usize, size: usize) -> bool
+    {
+        in_mappings(&self.mappings, address, size)
+    }
+
+    /*
+     * Keep track of what the syscall created or released, once it succeeded.
+     * The memory of the child is read for the fds returned in an array.
+     */
+    pub fn update(&mut self, scno: usize, args: &[usize], retval: usize, pid: i32, memory: &dyn MemoryOperation)
+    {
+        if (retval as isize) < 0 {
+            return;
+        }
+        let scno = scno as libc::c_long;
+
+        match scno {
+            libc::SYS_close => { self.fds.remove(&args[0]); },
+            libc::SYS_dup2 | libc::SYS_dup3 => { self.fds.insert(retval); },
+            libc::SYS_fcntl if matches!(args[1] as i32, libc::F_DUPFD | libc::F_DUPFD_CLOEXEC) => {
+                self.fds.insert(retval);
+            },
+            // The fds are written in an array of two int
+            libc::SYS_pipe | libc::SYS_pipe2 | libc::SYS_socketpair => {
+                let address = if scno == libc::SYS_socketpair { args[3] } else { args[0] };
+                let pair = memory.read(pid, address, 8);
+                for fd in pair.chunks_exact(4) {
+                    self.fds.insert(u32::from_le_bytes(fd.try_into().unwrap()) as usize);
+                }
+            },
+            _ if FD_RETURNING_SYSCALLS.contains(&scno) => { self.fds.insert(retval); },
+
+            libc::SYS_mmap => { self.mappings.insert(retval, args[1]); },
+            libc::SYS_mremap => {
+                if args[3] as i32 & libc::MREMAP_DONTUNMAP == 0 {
+                    unmap_range(&mut self.mappings, args[0], args[1]);
+                }
+                self.mappings.insert(retval, args[2]);
+            },
+            libc::SYS_munmap => { unmap_range(&mut self.mappings, args[0], args[1]); },
+
+            libc::SYS_fork | libc::SYS_vfork | libc::SYS_clone | libc::SYS_clone3 => {
+                self.processes.insert(retval as i32);
+            },
+            libc::SYS_wait4 | libc::SYS_waitid if retval != 0 => { self.processes.remove(&(retval as i32)); },
+            _ => (),
+        }
+        self.fds.retain(|fd| *fd >= FIRST_SESSION_FD);
+    }
+
+    /*
+     * Kill the processes, unmap the memory and close the fds of the session in the child.
+     * Everything is forgotten even when a release fails, the error is the first one.
+     */
+    pub fn release(&mut self, invoker: &dyn Invoker) -> Result<(), io::Error>
+    {
+        let mut result = Ok(());
+        let mut invoke = |scno: libc::c_long, args: [usize; 4]| {
+            let outcome = invoker.invoke_syscall(scno as usize, args[0], args[1], args[2], args[3], 0, 0, 0)
+                .and_then(|(retval, _)| match (retval as isize) < 0 {
+                    true => Err(io::Error::from_raw_os_error(-(retval as isize) as i32)),
+                    false => Ok(()),
+                });
+            if let Err(err) = outcome {
+                eprintln!("Fail to release a resource with syscall {} {:x?}: {}", scno, args, err);
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        };
+
+        for pid in std::mem::take(&mut self.processes) {
+            invoke(libc::SYS_kill, [pid as usize, libc::SIGKILL as usize, 0, 0]);
+            invoke(libc::SYS_wait4, [pid as usize, 0, 0, 0]);
+        }
+        for (address, length) in std::mem::take(&mut self.mappings) {
+            invoke(libc::SYS_munmap, [address, length, 0, 0]);
+        }
+        for fd in std::mem::take(&mut self.fds) {
+            invoke(libc::SYS_close, [fd, 0, 0, 0]);
+        }
+        result
+    }
+}
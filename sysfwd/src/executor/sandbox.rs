@@ -0,0 +1,334 @@
+/*
+ * Confine the executor child so that the forwarded syscalls only reach what is exposed to it.
+ * The child runs in new user, mount, pid and net namespaces, with a root filesystem made of the
+ * configured paths and devices (e.g., /dev/kbuf), and a seccomp filter denying the syscalls which
+ * could undo the confinement.
+ */
+use std::{
+    collections::BTreeSet,
+    ffi::CString,
+    fs,
+    io,
+    os::unix::{ ffi::OsStrExt, process::CommandExt },
+    path::{ Path, PathBuf },
+    process::Command,
+};
+
+use nix::{
+    libc,
+    sched::{ unshare, CloneFlags },
+};
+use serde::{ Serialize, Deserialize };
+
+
+
+/* An empty directory of the host on which each child mounts the root of its sandbox */
+const SANDBOX_ROOT: &str = "/tmp/sysfwd-sandbox";
+
+/* Needed to run the child program, bound read-only when they exist */
+const SYSTEM_PATHS: &[&str] = &[
+    "/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/libx32", "/etc/ld.so.cache",
+];
+
+/* Not in libc */
+const AUDIT_ARCH_X86_64: u32 = 0xc000003e;
+const X32_SYSCALL_BIT: u32 = 0x40000000;
+
+/*
+ * Syscalls denied to the child, for the forwarded ones as for the child itself.
+ */
+const DENIED_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_mount, libc::SYS_umount2, libc::SYS_pivot_root, libc::SYS_chroot,
+    libc::SYS_fsopen, libc::SYS_fsconfig, libc::SYS_fsmount, libc::SYS_fspick,
+    libc::SYS_move_mount, libc::SYS_open_tree, libc::SYS_mount_setattr,
+    libc::SYS_unshare, libc::SYS_setns,
+    libc::SYS_kexec_load, libc::SYS_kexec_file_load, libc::SYS_reboot,
+    libc::SYS_init_module, libc::SYS_finit_module, libc::SYS_delete_module,
+    libc::SYS_swapon, libc::SYS_swapoff, libc::SYS_acct, libc::SYS_quotactl,
+    libc::SYS_bpf, libc::SYS_perf_event_open, libc::SYS_userfaultfd,
+    libc::SYS_keyctl, libc::SYS_add_key, libc::SYS_request_key,
+    libc::SYS_process_vm_writev, libc::SYS_personality,
+    libc::SYS_name_to_handle_at, libc::SYS_open_by_handle_at, libc::SYS_iopl, libc::SYS_ioperm, libc::SYS_syslog,
+    libc::SYS_settimeofday, libc::SYS_clock_settime, libc::SYS_clock_adjtime, libc::SYS_adjtimex,
+];
+
+
+
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug)]
+#[serde(default)]
+pub struct SandboxConfig {
+    /* Host paths (files, directories or devices) bound at the same place in the sandbox */
+    pub binds: Vec<String>,
+    /* Host paths bound read-only, by default those needed to run the child program */
+    pub read_only: Vec<String>,
+}
+
+impl Default for SandboxConfig {
+
+    fn default() -> Self
+    {
+        Self {
+            binds: Vec::new(),
+            read_only: SYSTEM_PATHS.iter()
+                .filter(|path| Path::new(path).exists())
+                .map(|path| path.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl SandboxConfig {
+
+    /*
+     * Spawn the command in the sandbox, as `uid` and `gid` outside of it (root inside).
+     * The pid namespace is created for the children of the calling thread, which needs CAP_SYS_ADMIN.
+     */
+    pub fn apply(&self, command: &mut Command, uid: u32, gid: u32, cwd: Option<&str>) -> Result<(), io::Error>
+    {
+        let setup = Setup::new(self, uid, gid, cwd)?;
+
+        fs::create_dir_all(SANDBOX_ROOT)?;
+        unshare(CloneFlags::CLONE_NEWPID)?;
+
+        unsafe {
+            command.pre_exec(move || setup.enter());
+        }
+        Ok(())
+    }
+}
+
+
+
+/*
+ * What the child does before its exec, prepared by the parent as nothing should be allocated
+ * after the fork of a multi-threaded process.
+ */
+enum Step {
+    Dir(CString),
+    File(CString),
+    Symlink { target: CString, link: CString },
+    Bind { source: CString, target: CString, read_only: bool, flags: libc::c_ulong },
+}
+
+struct Setup {
+    root: CString,
+    old_root: CString,
+    old_root_inside: CString,
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+    cwd: CString,
+    steps: Vec<Step>,
+    filter: Vec<libc::sock_filter>,
+}
+
+fn cstring(path: &Path) -> Result<CString, io::Error>
+{
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid path {:?}", path)))
+}
+
+impl Setup {
+
+    fn new(config: &SandboxConfig, uid: u32, gid: u32, cwd: Option<&str>) -> Result<Self, io::Error>
+    {
+        let root = Path::new(SANDBOX_ROOT);
+        let mut steps = Vec::new();
+        let mut dirs = BTreeSet::new();
+
+        let paths = config.read_only.iter().map(|path| (path, true))
+            .chain(config.binds.iter().map(|path| (path, false)));
+        for (path, read_only) in paths {
+            let source = Path::new(path);
+            if ! source.is_absolute() {
+                let msg = format!("The sandbox path {} is not absolute", path);
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+            }
+            let target = root.join(source.strip_prefix("/").unwrap());
+
+            // The mount points are created in the root, one directory after the other
+            for ancestor in target.ancestors().skip(1).collect::<Vec<&Path>>().into_iter().rev() {
+                if ancestor.starts_with(root) && ancestor != root && dirs.insert(ancestor.to_path_buf()) {
+                    steps.push(Step::Dir(cstring(ancestor)?));
+                }
+            }
+
+            let metadata = fs::symlink_metadata(source)?;
+            if metadata.file_type().is_symlink() {
+                // e.g., /bin -> usr/bin on merged /usr systems
+                let link = fs::read_link(source)?;
+                steps.push(Step::Symlink { target: cstring(&link)?, link: cstring(&target)? });
+                continue;
+            }
+            match metadata.is_dir() {
+                true => if dirs.insert(target.clone()) { steps.push(Step::Dir(cstring(&target)?)) },
+                false => steps.push(Step::File(cstring(&target)?)),
+            }
+            steps.push(Step::Bind {
+                source: cstring(source)?,
+                target: cstring(&target)?,
+                read_only,
+                flags: locked_flags(source)?,
+            });
+        }
+
+        Ok(Self {
+            root: cstring(root)?,
+            old_root: cstring(&root.join(".old"))?,
+            old_root_inside: CString::new("/.old").unwrap(),
+            uid_map: format!("0 {} 1\n", uid).into_bytes(),
+            gid_map: format!("0 {} 1\n", gid).into_bytes(),
+            cwd: cstring(&PathBuf::from(cwd.unwrap_or("/")))?,
+            steps,
+            filter: seccomp_filter(),
+        })
+    }
+
+    /*
+     * Run in the child between its fork and its exec.
+     */
+    fn enter(&self) -> Result<(), io::Error>
+    {
+        unsafe {
+            check(libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::CLONE_NEWNET))?;
+            // Changing the uid made the process not dumpable, with its /proc files owned by root
+            check(libc::prctl(libc::PR_SET_DUMPABLE, 1, 0, 0, 0))?;
+            write_file(c"/proc/self/setgroups", b"deny")?;
+            write_file(c"/proc/self/gid_map", &self.gid_map)?;
+            write_file(c"/proc/self/uid_map", &self.uid_map)?;
+
+            // Nothing mounted in the sandbox propagates to the host
+            check(libc::mount(std::ptr::null(), c"/".as_ptr(), std::ptr::null(),
+                              libc::MS_REC | libc::MS_PRIVATE, std::ptr::null()))?;
+            check(libc::mount(c"tmpfs".as_ptr(), self.root.as_ptr(), c"tmpfs".as_ptr(),
+                              libc::MS_NOSUID, c"mode=0755".as_ptr() as *const libc::c_void))?;
+
+            for step in self.steps.iter() {
+                self.run(step)?;
+            }
+
+            check(libc::mkdir(self.old_root.as_ptr(), 0o700))?;
+            check(libc::syscall(libc::SYS_pivot_root, self.root.as_ptr(), self.old_root.as_ptr()) as libc::c_int)?;
+            check(libc::chdir(c"/".as_ptr()))?;
+            check(libc::umount2(self.old_root_inside.as_ptr(), libc::MNT_DETACH))?;
+            check(libc::rmdir(self.old_root_inside.as_ptr()))?;
+            check(libc::chdir(self.cwd.as_ptr()))?;
+
+            let program = libc::sock_fprog {
+                len: self.filter.len() as libc::c_ushort,
+                filter: self.filter.as_ptr() as *mut libc::sock_filter,
+            };
+            check(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0))?;
+            check(libc::prctl(libc::PR_SET_SECCOMP, libc::SECCOMP_MODE_FILTER, &program as *const libc::sock_fprog))?;
+        }
+        Ok(())
+    }
+
+    unsafe fn run(&self, step: &Step) -> Result<(), io::Error>
+    {
+        match step {
+            // The mount point may come from a directory already bound
+            Step::Dir(path) => {
+                if libc::mkdir(path.as_ptr(), 0o755) == -1 && *libc::__errno_location() != libc::EEXIST {
+                    return Err(io::Error::last_os_error());
+                }
+            },
+            Step::File(path) => {
+                let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CREAT | libc::O_CLOEXEC, 0o644);
+                if fd != -1 {
+                    libc::close(fd);
+                }
+            },
+            Step::Symlink { target, link } => check(libc::symlink(target.as_ptr(), link.as_ptr()))?,
+            Step::Bind { source, target, read_only, flags } => {
+                check(libc::mount(source.as_ptr(), target.as_ptr(), std::ptr::null(),
+                                  libc::MS_BIND | libc::MS_REC, std::ptr::null()))?;
+                if *read_only {
+                    // The flags of the host mount are locked in a user namespace and must be kept
+                    let flags = flags | libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY | libc::MS_NOSUID;
+                    check(libc::mount(std::ptr::null(), target.as_ptr(), std::ptr::null(), flags, std::ptr::null()))?;
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+fn check(ret: libc::c_int) -> Result<(), io::Error>
+{
+    match ret {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+unsafe fn write_file(path: &std::ffi::CStr, content: &[u8]) -> Result<(), io::Error>
+{
+    let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+    check(fd)?;
+    let written = libc::write(fd, content.as_ptr() as *const libc::c_void, content.len());
+    libc::close(fd);
+    match written == content.len() as isize {
+        true => Ok(()),
+        false => Err(io::Error::last_os_error()),
+    }
+}
+
+/*
+ * The mount flags of the host mount of `path`.
+ */
+fn locked_flags(path: &Path) -> Result<libc::c_ulong, io::Error>
+{
+    let path = cstring(path)?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    check(unsafe { libc::statvfs(path.as_ptr(), &mut stat) })?;
+
+    let flags = [
+        (libc::ST_NOSUID, libc::MS_NOSUID),
+        (libc::ST_NODEV, libc::MS_NODEV),
+        (libc::ST_NOEXEC, libc::MS_NOEXEC),
+        (libc::ST_NOATIME, libc::MS_NOATIME),
+        (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+        (libc::ST_RELATIME, libc::MS_RELATIME),
+    ];
+    Ok(flags.iter()
+        .filter(|(st, _)| stat.f_flag & st != 0)
+        .fold(0, |acc, (_, ms)| acc | ms))
+}
+
+/*
+ * Return EPERM for the denied syscalls and kill the child on any other architecture or ABI.
+ * ptrace is denied except PTRACE_TRACEME, which the invoker calls once the filter is installed.
+ */
+fn seccomp_filter() -> Vec<libc::sock_filter>
+{
+    let deny = libc::SECCOMP_RET_ERRNO | libc::EPERM as u32;
+    let count = DENIED_SYSCALLS.len() as u8;
+
+    unsafe {
+        let mut filter = vec![
+            libc::BPF_STMT((libc::BPF_LD | libc::BPF_W | libc::BPF_ABS) as u16, 4),
+            libc::BPF_JUMP((libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K) as u16, AUDIT_ARCH_X86_64, 1, 0),
+            libc::BPF_STMT((libc::BPF_RET | libc::BPF_K) as u16, libc::SECCOMP_RET_KILL_PROCESS),
+            libc::BPF_STMT((libc::BPF_LD | libc::BPF_W | libc::BPF_ABS) as u16, 0),
+            libc::BPF_JUMP((libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K) as u16, X32_SYSCALL_BIT, 0, 1),
+            libc::BPF_STMT((libc::BPF_RET | libc::BPF_K) as u16, libc::SECCOMP_RET_KILL_PROCESS),
+            // The request is the whole first argument, its high word then its low word
+            libc::BPF_JUMP((libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K) as u16, libc::SYS_ptrace as u32, 0, 6),
+            libc::BPF_STMT((libc::BPF_LD | libc::BPF_W | libc::BPF_ABS) as u16, 20),
+            libc::BPF_JUMP((libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K) as u16, 0, 0, 3),
+            libc::BPF_STMT((libc::BPF_LD | libc::BPF_W | libc::BPF_ABS) as u16, 16),
+            libc::BPF_JUMP((libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K) as u16, libc::PTRACE_TRACEME, 0, 1),
+            libc::BPF_STMT((libc::BPF_RET | libc::BPF_K) as u16, libc::SECCOMP_RET_ALLOW),
+            libc::BPF_STMT((libc::BPF_RET | libc::BPF_K) as u16, deny),
+        ];
+        for (index, scno) in DENIED_SYSCALLS.iter().enumerate() {
+            // Jump to the deny return after the remaining checks and the allow return
+            let remaining = count - 1 - index as u8;
+            filter.push(libc::BPF_JUMP((libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K) as u16, *scno as u32, remaining + 1, 0));
+        }
+        filter.push(libc::BPF_STMT((libc::BPF_RET | libc::BPF_K) as u16, libc::SECCOMP_RET_ALLOW));
+        filter.push(libc::BPF_STMT((libc::BPF_RET | libc::BPF_K) as u16, deny));
+        filter
+    }
+}
@@ -0,0 +1,261 @@
+/*
+ * Memory mapped in the executor child to hold the copies of the tracee memory given to the syscalls,
+ * so that the pointer arguments do not depend on the layout of the child.
+ */
+use std::{
+    io,
+    sync::{ Arc, Mutex },
+};
+
+use crate::targets::operation::MemoryOperation;
+
+
+/* Copies are aligned as any object the kernel may read */
+const ALIGNMENT: usize = 16;
+
+
+
+/*
+ * A tracee memory block copied in the scratch region.
+ */
+#[derive(Clone, Copy, Debug)]
+struct Copy {
+    remote: usize,
+    local: usize,
+    size: usize,
+}
+
+#[derive(Debug, Default)]
+struct Region {
+    base: usize,
+    size: usize,
+    used: usize,
+    copies: Vec<Copy>,
+}
+
+/*
+ * The copies of a syscall are allocated one after the other in the region and dropped once it returned.
+ * Shared by the encoders, which allocate the copies, and the memory of the child, which redirects to them.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct Scratch {
+    region: Arc<Mutex<Region>>,
+}
+
+impl Scratch {
+
+    /* Large enough for the biggest buffers of the forwarded syscalls */
+    pub const SIZE: usize = 16 * 1024 * 1024;
+
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    /*
+     * Use the region mapped in the child at `base`.
+     */
+    pub fn set_region(&self, base: usize, size: usize)
+    {
+        let mut region = self.region.lock().unwrap();
+        *region = Region { base, size, ..Region::default() };
+    }
+
+    /*
+     * Reserve a copy of `size` bytes of the tracee memory at `remote` and return its address in the child.
+     * A NULL pointer stays NULL. Without a scratch region the pointer is refused: the tracee address
+     * may be anything in the child, writing the copy there would corrupt it.
+     */
+    pub fn alloc(&self, remote: usize, size: usize) -> Result<usize, io::Error>
+    {
+        if remote == 0 {
+            return Ok(0);
+        }
+
+        let mut region = self.region.lock().unwrap();
+        if region.base == 0 {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "No scratch memory mapped in the child"));
+        }
+        // The same block given twice to a syscall gets a single copy
+        if let Some(copy) = region.copies.iter_mut().find(|copy| copy.remote == remote && copy.size >= size) {
+            return Ok(copy.local);
+        }
+
+        let offset = region.used.next_multiple_of(ALIGNMENT);
+        if offset.saturating_add(size) > region.size {
+            let msg = format!("Scratch memory exhausted by a copy of {:#x} bytes", size);
+            return Err(io::Error::new(io::ErrorKind::OutOfMemory, msg));
+        }
+        let local = region.base + offset;
+        region.used = offset + size;
+        region.copies.push(Copy { remote, local, size });
+        Ok(local)
+    }
+
+    /*
+     * Reserve `size` bytes which are not a copy of the tracee memory, e.g., for the data given by the executor
+     * itself to a syscall. The block is written at its address in the child.
+     */
+    pub fn reserve(&self, size: usize) -> Result<usize, io::Error>
+    {
+        let mut region = self.region.lock().unwrap();
+        if region.base == 0 {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "No scratch memory mapped in the child"));
+        }
+
+        let offset = region.used.next_multiple_of(ALIGNMENT);
+        if offset.saturating_add(size) > region.size {
+            let msg = format!("Scratch memory exhausted by a block of {:#x} bytes", size);
+            return Err(io::Error::new(io::ErrorKind::OutOfMemory, msg));
+        }
+        let local = region.base + offset;
+        region.used = offset + size;
+        region.copies.push(Copy { remote: local, local, size });
+        Ok(local)
+    }
+
+    /*
+     * The address in the child of a tracee address inside one of the copies.
+     */
+    pub fn translate(&self, remote: usize) -> Option<usize>
+    {
+        let region = self.region.lock().unwrap();
+        region.copies.iter()
+            .rev()
+            .find(|copy| copy.remote <= remote && remote < copy.remote + copy.size.max(1))
+            .map(|copy| copy.local + (remote - copy.remote))
+    }
+
+    /*
+     * Drop the copies once the syscall returned.
+     */
+    pub fn clear(&self)
+    {
+        let mut region = self.region.lock().unwrap();
+        region.used = 0;
+        region.copies.clear();
+    }
+}
+
+
+
+/*
+ * The memory of the child, seen through the tracee addresses of the syscall being executed.
+ * Accesses to the tracee memory copied in the scratch region are redirected to the copies,
+ * the other addresses are left as they are.
+ */
+pub struct ScratchMemory {
+    memory: Box<dyn MemoryOperation>,
+    scratch: Scratch,
+}
+
+impl ScratchMemory {
+
+    pub fn new(memory: Box<dyn MemoryOperation>, scratch: Scratch) -> Self
+    {
+        Self { memory, scratch }
+    }
+}
+
+impl MemoryOperation for ScratchMemory {
+
+    fn read(&self, pid: i32, addr: usize, size: usize) -> Vec<u8>
+    {
+        let addr = self.scratch.translate(addr).unwrap_or(addr);
+        self.memory.read(pid, addr, size)
+    }
+
+    fn write(&self, pid: i32, addr: usize, mem: Vec<u8>) -> usize
+    {
+        let addr = self.scratch.translate(addr).unwrap_or(addr);
+        self.memory.write(pid, addr, mem)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: usize = 0x7000_0000;
+
+    /*
+     * The child memory, recording the addresses accessed.
+     */
+    struct Accesses(Arc<Mutex<Vec<usize>>>);
+
+    impl MemoryOperation for Accesses {
+        fn read(&self, _pid: i32, addr: usize, size: usize) -> Vec<u8>
+        {
+            self.0.lock().unwrap().push(addr);
+            vec![0; size]
+        }
+
+        fn write(&self, _pid: i32, addr: usize, mem: Vec<u8>) -> usize
+        {
+            self.0.lock().unwrap().push(addr);
+            mem.len()
+        }
+    }
+
+    #[test]
+    fn refused_without_region()
+    {
+        let scratch = Scratch::new();
+        assert_eq!(scratch.alloc(0, 8).unwrap(), 0);
+        assert_eq!(scratch.alloc(0x1000, 8).unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert_eq!(scratch.reserve(8).unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert_eq!(scratch.translate(0x1000), None);
+    }
+
+    #[test]
+    fn copies_are_aligned_and_shared()
+    {
+        let scratch = Scratch::new();
+        scratch.set_region(BASE, 0x100);
+
+        assert_eq!(scratch.alloc(0x1000, 3).unwrap(), BASE);
+        assert_eq!(scratch.alloc(0x2000, 8).unwrap(), BASE + ALIGNMENT);
+        // The same block, or a part at its start, gets the same copy
+        assert_eq!(scratch.alloc(0x1000, 2).unwrap(), BASE);
+        assert_eq!(scratch.reserve(4).unwrap(), BASE + 2 * ALIGNMENT);
+        assert_eq!(scratch.alloc(0x1000, 16).unwrap(), BASE + 3 * ALIGNMENT);
+
+        assert_eq!(scratch.alloc(0x3000, 0x100).unwrap_err().kind(), io::ErrorKind::OutOfMemory);
+        scratch.clear();
+        assert_eq!(scratch.alloc(0x3000, 0x100).unwrap(), BASE);
+    }
+
+    #[test]
+    fn translate_inside_the_copies()
+    {
+        let scratch = Scratch::new();
+        scratch.set_region(BASE, 0x100);
+        scratch.alloc(0x1000, 8).unwrap();
+        scratch.alloc(0x2000, 0).unwrap();
+
+        assert_eq!(scratch.translate(0x1000), Some(BASE));
+        assert_eq!(scratch.translate(0x1007), Some(BASE + 7));
+        assert_eq!(scratch.translate(0x1008), None);
+        // An empty block still has its address translated
+        assert_eq!(scratch.translate(0x2000), Some(BASE + ALIGNMENT));
+
+        scratch.clear();
+        assert_eq!(scratch.translate(0x1000), None);
+    }
+
+    #[test]
+    fn memory_redirected_to_the_copies()
+    {
+        let scratch = Scratch::new();
+        scratch.set_region(BASE, 0x100);
+        scratch.alloc(0x1000, 8).unwrap();
+
+        let accesses = Arc::new(Mutex::new(Vec::new()));
+        let memory = ScratchMemory::new(Box::new(Accesses(accesses.clone())), scratch);
+        memory.read(1, 0x1004, 4);
+        memory.write(1, 0x5000, vec![0; 4]);
+        assert_eq!(*accesses.lock().unwrap(), vec![BASE + 4, 0x5000]);
+    }
+}
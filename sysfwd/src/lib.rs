@@ -6,6 +6,7 @@ pub mod sync;
 pub mod arch;
 pub mod protocol; // Should be private?
 pub mod memory;
+pub mod context;
 
 pub mod syscall;
 pub mod tracer;
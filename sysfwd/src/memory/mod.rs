@@ -1,61 +1,214 @@
 /*
- * 
+ *
  */
 use std::{
-    fs::File,
-    io::{BufRead, BufReader},
-    path::Path,
+    fmt,
+    fs,
+    io,
+    os::unix::fs::FileExt,
 };
 
+use serde::{ Serialize, Deserialize };
+
+
+
+/*
+ * The permissions of a region, as the "rwxp" column of /proc/<pid>/maps.
+ */
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Permissions {
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+    pub shared: bool,
+}
+
+impl Permissions {
+
+    pub fn parse(perms: &str) -> Result<Self, io::Error>
+    {
+        let perms = perms.as_bytes();
+        if perms.len() != 4 {
+            let msg = format!("Invalid permissions {:?}", String::from_utf8_lossy(perms));
+            return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+        }
+
+        Ok(Self {
+            read: perms[0] == b'r',
+            write: perms[1] == b'w',
+            execute: perms[2] == b'x',
+            shared: perms[3] == b's',
+        })
+    }
+}
+
+impl fmt::Display for Permissions {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{}{}{}{}",
+               if self.read { 'r' } else { '-' },
+               if self.write { 'w' } else { '-' },
+               if self.execute { 'x' } else { '-' },
+               if self.shared { 's' } else { 'p' })
+    }
+}
+
+
+
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug)]
 pub struct MemoryRegion {
-    start: u64,
-    end: u64,
-    permissions: String,
-    offset: u64,
-    device: String,
-    inode: u64,
-    pathname: String,
+    pub start: u64,
+    pub end: u64,
+    pub permissions: Permissions,
+    pub offset: u64,
+    pub device: String,
+    pub inode: u64,
+    pub pathname: String,
 }
 
-pub fn read_process_memory_maps(pid: u32) -> Vec<MemoryRegion>
+impl MemoryRegion {
+
+    /*
+     * Parse a line of /proc/<pid>/maps.
+     * The pathname is everything after the inode column and may contain spaces.
+     */
+    pub fn parse(line: &str) -> Result<Self, io::Error>
+    {
+        let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid {} in maps entry {:?}", what, line));
+
+        let mut rest = line;
+        let range = next_field(&mut rest).ok_or_else(|| invalid("range"))?;
+        let permissions = next_field(&mut rest).ok_or_else(|| invalid("permissions"))?;
+        let offset = next_field(&mut rest).ok_or_else(|| invalid("offset"))?;
+        let device = next_field(&mut rest).ok_or_else(|| invalid("device"))?;
+        let inode = next_field(&mut rest).ok_or_else(|| invalid("inode"))?;
+        let pathname = rest.trim_start();
+
+        let (start, end) = range.split_once('-').ok_or_else(|| invalid("range"))?;
+        let start = u64::from_str_radix(start, 16).map_err(|_| invalid("range"))?;
+        let end = u64::from_str_radix(end, 16).map_err(|_| invalid("range"))?;
+        let offset = u64::from_str_radix(offset, 16).map_err(|_| invalid("offset"))?;
+        let inode = inode.parse::<u64>().map_err(|_| invalid("inode"))?;
+
+        Ok(Self {
+            start,
+            end,
+            permissions: Permissions::parse(permissions)?,
+            offset,
+            device: device.to_string(),
+            inode,
+            pathname: pathname.to_string(),
+        })
+    }
+
+    pub fn contains(&self, address: u64) -> bool
+    {
+        self.start <= address && address < self.end
+    }
+
+    pub fn size(&self) -> u64
+    {
+        self.end - self.start
+    }
+}
+
+/*
+ * Return the next field separated by spaces and move `rest` after it.
+ */
+fn next_field<'a>(rest: &mut &'a str) -> Option<&'a str>
 {
-    let maps_path = format!("/proc/{}/maps", pid);
-    let file = File::open(&Path::new(&maps_path)).expect("Failed to open maps file");
-
-    let reader = BufReader::new(file);
-    let mut regions = Vec::new();
-
-    for line in reader.lines() {
-        if let Ok(line) = line {
-            let mut parts = line.split_whitespace();    // may be wrong here
-            let range = parts.next().expect("Invalid maps entry");
-            let permissions = parts.next().expect("Invalid maps entry");
-            let offset = parts.next().expect("Invalid maps entry");
-            let device = parts.next().expect("Invalid maps entry");
-            let inode = parts.next().expect("Invalid maps entry");
-            let pathname = parts.next().unwrap_or("");
-
-            let range_parts: Vec<&str> = range.split('-').collect();
-            let start = u64::from_str_radix(range_parts[0], 16).expect("Invalid memory range");
-            let end = u64::from_str_radix(range_parts[1], 16).expect("Invalid memory range");
-            let offset = u64::from_str_radix(offset, 16).expect("Invalid offset");
-            let inode = u64::from_str_radix(inode, 10).expect("Invalid inode");
-
-            let region = MemoryRegion {
-                start,
-                end,
-                permissions: permissions.to_string(),
-                offset,
-                device: device.to_string(),
-                inode,
-                pathname: pathname.to_string(),
-            };
-
-            regions.push(region);
+    let line = rest.trim_start();
+    if line.is_empty() {
+        return None;
+    }
+    let end = line.find(char::is_whitespace).unwrap_or(line.len());
+    let (field, remaining) = line.split_at(end);
+    *rest = remaining;
+    Some(field)
+}
+
+
+
+/*
+ * The memory layout of a process, the regions are sorted by address as in /proc/<pid>/maps.
+ */
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, Default)]
+pub struct MemoryMap {
+    pub regions: Vec<MemoryRegion>,
+}
+
+impl MemoryMap {
+
+    pub fn from_pid(pid: i32) -> Result<Self, io::Error>
+    {
+        let maps = fs::read_to_string(format!("/proc/{}/maps", pid))?;
+        Self::parse(&maps)
+    }
+
+    pub fn parse(maps: &str) -> Result<Self, io::Error>
+    {
+        let regions = maps.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(MemoryRegion::parse)
+            .collect::<Result<Vec<MemoryRegion>, io::Error>>()?;
+        Ok(Self { regions })
+    }
+
+    /*
+     * The region containing the address.
+     */
+    pub fn find(&self, address: u64) -> Option<&MemoryRegion>
+    {
+        let index = self.regions.partition_point(|region| region.end <= address);
+        self.regions.get(index).filter(|region| region.contains(address))
+    }
+
+    /*
+     * The regions mapping the given file.
+     */
+    pub fn find_pathname<'a>(&'a self, pathname: &'a str) -> impl Iterator<Item = &'a MemoryRegion>
+    {
+        self.regions.iter().filter(move |region| region.pathname == pathname)
+    }
+}
+
+
+
+/*
+ * The address of the first occurrence of `code` in the executable regions of the process.
+ * The vDSO is looked up first as it is mapped in every process, whatever the program.
+ */
+pub fn find_code(pid: i32, code: &[u8]) -> Result<u64, io::Error>
+{
+    let map = MemoryMap::from_pid(pid)?;
+    let mem = fs::File::open(format!("/proc/{}/mem", pid))?;
+
+    let mut regions: Vec<&MemoryRegion> = map.regions.iter()
+        .filter(|region| region.permissions.read && region.permissions.execute)
+        .collect();
+    regions.sort_by_key(|region| region.pathname != "[vdso]");
+
+    for region in regions {
+        let mut content = vec![0; region.size() as usize];
+        // Some special regions cannot be read even when mapped readable
+        if mem.read_exact_at(&mut content, region.start).is_err() {
+            continue;
+        }
+        if let Some(offset) = content.windows(code.len()).position(|window| window == code) {
+            return Ok(region.start + offset as u64);
         }
     }
+    let msg = format!("No executable code {:02x?} in process {}", code, pid);
+    Err(io::Error::new(io::ErrorKind::NotFound, msg))
+}
 
-    regions
+pub fn read_process_memory_maps(pid: u32) -> Vec<MemoryRegion>
+{
+    MemoryMap::from_pid(pid as i32).expect("Failed to read maps file").regions
 }
 
 pub fn print_memory_regions(regions: &[MemoryRegion]) {
@@ -66,3 +219,59 @@ pub fn print_memory_regions(regions: &[MemoryRegion]) {
         );
     }
 }
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAPS: &str = "\
+55d0c2a00000-55d0c2a02000 r--p 00000000 fd:01 1311237                    /usr/bin/my program
+55d0c2a02000-55d0c2a06000 r-xp 00002000 fd:01 1311237                    /usr/bin/my program
+7f3a1c000000-7f3a1c021000 rw-s 00000000 00:05 4242                       /dev/shm/region  two  spaces (deleted)
+7f3a1c200000-7f3a1c221000 rw-p 00000000 00:00 0 
+7ffd5b3f1000-7ffd5b3f3000 r-xp 00000000 00:00 0                          [vdso]
+";
+
+    #[test]
+    fn pathnames_keep_their_spaces()
+    {
+        let map = MemoryMap::parse(MAPS).unwrap();
+        assert_eq!(map.regions.len(), 5);
+        assert_eq!(map.regions[0].pathname, "/usr/bin/my program");
+        assert_eq!(map.regions[2].pathname, "/dev/shm/region  two  spaces (deleted)");
+        assert_eq!(map.regions[3].pathname, "");
+        assert_eq!(map.regions[4].pathname, "[vdso]");
+        assert_eq!(map.find_pathname("/usr/bin/my program").count(), 2);
+    }
+
+    #[test]
+    fn region_fields()
+    {
+        let region = MemoryRegion::parse(MAPS.lines().nth(1).unwrap()).unwrap();
+        assert_eq!((region.start, region.end, region.offset), (0x55d0c2a02000, 0x55d0c2a06000, 0x2000));
+        assert_eq!((region.device.as_str(), region.inode), ("fd:01", 1311237));
+        assert_eq!(region.permissions.to_string(), "r-xp");
+        assert!(region.permissions.execute && !region.permissions.write);
+        assert_eq!(Permissions::parse("rw-s").unwrap().to_string(), "rw-s");
+    }
+
+    #[test]
+    fn find_by_address()
+    {
+        let map = MemoryMap::parse(MAPS).unwrap();
+        assert_eq!(map.find(0x55d0c2a02000).unwrap().offset, 0x2000);
+        assert_eq!(map.find(0x55d0c2a01fff).unwrap().offset, 0);
+        assert!(map.find(0x55d0c2a06000).is_none());
+        assert!(map.find(0).is_none());
+    }
+
+    #[test]
+    fn invalid_entries()
+    {
+        assert!(MemoryRegion::parse("55d0c2a00000 r--p 00000000 fd:01 1 /bin/true").is_err());
+        assert!(MemoryRegion::parse("55d0c2a00000-55d0c2a02000 r-p 00000000 fd:01 1 /bin/true").is_err());
+        assert!(MemoryRegion::parse("55d0c2a00000-55d0c2a02000 r--p 00000000 fd:01").is_err());
+    }
+}
@@ -0,0 +1,673 @@
+/*
+ *
+ */
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{ self, Read },
+    sync::{
+        Arc, Mutex,
+        atomic::{ AtomicU64, Ordering },
+    },
+    time::Duration,
+};
+
+use hmac::{ Hmac, Mac };
+use sha2::Sha256;
+
+use crate::protocol::data::Transport;
+
+
+type HmacSha256 = Hmac<Sha256>;
+
+const MAC_SIZE: usize = 32;
+const RANDOM_SIZE: usize = 16;
+const NONCE_SIZE: usize = 8;
+
+/* Type of the frames exchanged over an authenticated data channel */
+const FRAME_HELLO: u8 = 0;
+const FRAME_WELCOME: u8 = 1;
+const FRAME_DATA: u8 = 2;
+
+/* How many times the tracer sends its hello before giving up on the executor */
+const HANDSHAKE_ATTEMPTS: usize = 10;
+
+/* How many randoms of past hellos the executor remembers to refuse them again */
+const SEEN_HELLOS: usize = 64;
+
+
+/*
+ * Load a pre-shared key from a file. Trailing whitespaces are ignored to allow keys written by hand.
+ */
+pub fn read_key_file(path: &str) -> io::Result<Vec<u8>>
+{
+    let mut key = Vec::new();
+    File::open(path)?.read_to_end(&mut key)?;
+
+    while key.last().is_some_and(|c| c.is_ascii_whitespace()) {
+        key.pop();
+    }
+    if key.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Empty pre-shared key"));
+    }
+    Ok(key)
+}
+
+pub fn compute_mac(key: &[u8], parts: &[&[u8]]) -> [u8; MAC_SIZE]
+{
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any size");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/*
+ * Constant time comparison of the tag with the MAC of the parts.
+ */
+pub fn verify_mac(key: &[u8], parts: &[&[u8]], tag: &[u8]) -> bool
+{
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any size");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.verify_slice(tag).is_ok()
+}
+
+pub fn random_bytes() -> io::Result<[u8; RANDOM_SIZE]>
+{
+    let mut random = [0u8; RANDOM_SIZE];
+    File::open("/dev/urandom")?.read_exact(&mut random)?;
+    Ok(random)
+}
+
+/*
+ * The session key binds the MACs to the randoms of both sides, so frames of a previous session cannot be replayed.
+ */
+pub fn session_key(key: &[u8], first: &[u8], second: &[u8]) -> [u8; MAC_SIZE]
+{
+    compute_mac(key, &[b"sysfwd-session", first, second])
+}
+
+pub fn to_hex(bytes: &[u8]) -> String
+{
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn from_hex(hex: &str) -> Option<Vec<u8>>
+{
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+
+
+/*
+ * The state shared by the clones of an authenticated transport once the handshake is done.
+ * Nonces are counters which must strictly increase in each direction.
+ */
+struct Session {
+    key: [u8; MAC_SIZE],
+    send_nonce: AtomicU64,
+    recv_nonce: AtomicU64,
+    pinned: Mutex<bool>,
+}
+
+impl Session {
+
+    fn new(key: [u8; MAC_SIZE]) -> Self
+    {
+        Self { key, send_nonce: AtomicU64::new(0), recv_nonce: AtomicU64::new(0), pinned: Mutex::new(false) }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Role {
+    Tracer,
+    Executor,
+}
+
+impl Role {
+
+    fn label(&self) -> &'static [u8]
+    {
+        match self {
+            Role::Tracer => b"tracer",
+            Role::Executor => b"executor",
+        }
+    }
+
+    fn peer(&self) -> Role
+    {
+        match self {
+            Role::Tracer => Role::Executor,
+            Role::Executor => Role::Tracer,
+        }
+    }
+}
+
+
+
+/*
+ * A session offered by the executor in its welcome, not proven by the tracer yet.
+ */
+struct Pending {
+    remote_random: Vec<u8>,
+    welcome: Vec<u8>,
+    session: Arc<Session>,
+}
+
+/*
+ * Wrap a transport to authenticate every frame with a pre-shared key.
+ *
+ * Handshake: the tracer sends HELLO(random_t, mac) and the executor answers WELCOME(random_e, mac).
+ * Afterwards each frame is DATA(nonce, mac, payload), the MAC covering the role of the sender, the nonce and the payload.
+ * The session key depends on random_e, the challenge of the executor: the executor only switches to a new session
+ * on its first valid data frame, so a replayed hello never replaces the current session. The randoms of the
+ * last hellos are refused as well.
+ * The executor pins the sender address on the first valid data frame of a session and drops anything else.
+ */
+pub struct AuthenticatedTransport {
+    inner: Box<dyn Transport>,
+    psk: Arc<Vec<u8>>,
+    role: Role,
+    session: Arc<Mutex<Option<Arc<Session>>>>,
+    pending: Arc<Mutex<Option<Pending>>>,
+    seen_hellos: Arc<Mutex<VecDeque<Vec<u8>>>>,
+}
+
+impl AuthenticatedTransport {
+
+    /*
+     * Tracer side: the handshake is done immediately.
+     */
+    pub fn connect(inner: Box<dyn Transport>, psk: &[u8]) -> io::Result<Self>
+    {
+        let transport = Self::new(inner, psk, Role::Tracer);
+
+        let random = random_bytes()?;
+        let tag = compute_mac(psk, &[b"sysfwd-hello", &random]);
+        let hello = [&[FRAME_HELLO][..], &random, &tag].concat();
+
+        transport.inner.set_read_timeout(Some(Duration::new(1, 0)))?;
+        for _ in 0..HANDSHAKE_ATTEMPTS {
+            transport.inner.send(&hello)?;
+
+            let frame = match transport.inner.receive() {
+                Ok((frame, _)) => frame,
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(err) => return Err(err),
+            };
+
+            if frame.len() != 1 + RANDOM_SIZE + MAC_SIZE || frame[0] != FRAME_WELCOME {
+                continue;
+            }
+            let remote_random = &frame[1..1 + RANDOM_SIZE];
+            if !verify_mac(psk, &[b"sysfwd-welcome", &random, remote_random], &frame[1 + RANDOM_SIZE..]) {
+                eprintln!("Drop welcome with an invalid MAC");
+                continue;
+            }
+
+            let session = Session::new(session_key(psk, &random, remote_random));
+            *session.pinned.lock().unwrap() = true;
+            transport.inner.pin_sender();
+            *transport.session.lock().unwrap() = Some(Arc::new(session));
+
+            transport.inner.set_read_timeout(None)?;
+            return Ok(transport);
+        }
+
+        Err(io::Error::new(io::ErrorKind::TimedOut, "No authenticated executor answered"))
+    }
+
+    /*
+     * Executor side: the handshake is done when the tracer sends its hello.
+     */
+    pub fn accept(inner: Box<dyn Transport>, psk: &[u8]) -> Self
+    {
+        Self::new(inner, psk, Role::Executor)
+    }
+
+    fn new(inner: Box<dyn Transport>, psk: &[u8], role: Role) -> Self
+    {
+        Self {
+            inner,
+            psk: Arc::new(psk.to_vec()),
+            role,
+            session: Arc::new(Mutex::new(None)),
+            pending: Arc::new(Mutex::new(None)),
+            seen_hellos: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    fn handle_hello(&self, frame: &[u8]) -> io::Result<()>
+    {
+        if self.role != Role::Executor || frame.len() != 1 + RANDOM_SIZE + MAC_SIZE {
+            return Ok(());
+        }
+        let remote_random = &frame[1..1 + RANDOM_SIZE];
+        if !verify_mac(&self.psk, &[b"sysfwd-hello", remote_random], &frame[1 + RANDOM_SIZE..]) {
+            eprintln!("Drop hello with an invalid MAC");
+            return Ok(());
+        }
+
+        let mut pending = self.pending.lock().unwrap();
+        // The tracer sends its hello again when the welcome is lost
+        if let Some(pending) = pending.as_ref().filter(|pending| pending.remote_random == remote_random) {
+            return self.inner.send(&pending.welcome);
+        }
+        let mut seen_hellos = self.seen_hellos.lock().unwrap();
+        if seen_hellos.iter().any(|seen| seen == remote_random) {
+            eprintln!("Drop replayed hello");
+            return Ok(());
+        }
+        if seen_hellos.len() == SEEN_HELLOS {
+            seen_hellos.pop_front();
+        }
+        seen_hellos.push_back(remote_random.to_vec());
+
+        let random = random_bytes()?;
+        let tag = compute_mac(&self.psk, &[b"sysfwd-welcome", remote_random, &random]);
+        let welcome = [&[FRAME_WELCOME][..], &random, &tag].concat();
+        self.inner.send(&welcome)?;
+
+        let session = Arc::new(Session::new(session_key(&self.psk, remote_random, &random)));
+        *pending = Some(Pending { remote_random: remote_random.to_vec(), welcome, session });
+        Ok(())
+    }
+
+    /*
+     * Return the payload of a valid data frame, None if the frame must be dropped.
+     */
+    fn open_data(&self, frame: &[u8]) -> Option<Vec<u8>>
+    {
+        if frame.len() < 1 + NONCE_SIZE + MAC_SIZE {
+            return None;
+        }
+
+        let nonce_bytes = &frame[1..1 + NONCE_SIZE];
+        let tag = &frame[1 + NONCE_SIZE..1 + NONCE_SIZE + MAC_SIZE];
+        let payload = &frame[1 + NONCE_SIZE + MAC_SIZE..];
+        let parts = [self.role.peer().label(), nonce_bytes, payload];
+
+        let current = self.session.lock().unwrap().clone();
+        let session = match current.filter(|session| verify_mac(&session.key, &parts, tag)) {
+            Some(session) => session,
+            None => {
+                // The tracer answered the challenge of the welcome, the pending session replaces the current one
+                let mut pending = self.pending.lock().unwrap();
+                match pending.take_if(|pending| verify_mac(&pending.session.key, &parts, tag)) {
+                    Some(pending) => {
+                        *self.session.lock().unwrap() = Some(pending.session.clone());
+                        pending.session
+                    },
+                    None => {
+                        eprintln!("Drop frame with an invalid MAC");
+                        return None;
+                    },
+                }
+            },
+        };
+
+        let nonce = u64::from_be_bytes(nonce_bytes.try_into().unwrap());
+        if session.recv_nonce.fetch_max(nonce, Ordering::SeqCst) >= nonce {
+            eprintln!("Drop replayed frame with nonce {}", nonce);
+            return None;
+        }
+
+        let mut pinned = session.pinned.lock().unwrap();
+        if !*pinned {
+            self.inner.pin_sender();
+            *pinned = true;
+        }
+
+        Some(payload.to_vec())
+    }
+}
+
+impl Transport for AuthenticatedTransport {
+
+    fn send(&self, data: &[u8]) -> io::Result<()>
+    {
+        let session = match self.session.lock().unwrap().clone() {
+            Some(session) => session,
+            None => return Err(io::Error::new(io::ErrorKind::NotConnected, "No authenticated session")),
+        };
+
+        let nonce = (session.send_nonce.fetch_add(1, Ordering::SeqCst) + 1).to_be_bytes();
+        let tag = compute_mac(&session.key, &[self.role.label(), &nonce, data]);
+        self.inner.send(&[&[FRAME_DATA][..], &nonce, &tag, data].concat())
+    }
+
+    fn receive(&self) -> io::Result<(Vec<u8>, usize)>
+    {
+        loop {
+            let (frame, _) = self.inner.receive()?;
+
+            match frame.first() {
+                Some(&FRAME_HELLO) => self.handle_hello(&frame)?,
+                Some(&FRAME_DATA) => {
+                    if let Some(payload) = self.open_data(&frame) {
+                        let size = payload.len();
+                        return Ok((payload, size));
+                    }
+                },
+                _ => eprintln!("Drop unexpected frame of {} bytes", frame.len()),
+            }
+        }
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()>
+    {
+        self.inner.set_read_timeout(dur)
+    }
+
+    fn try_clone(&self) -> io::Result<Box<dyn Transport>>
+    {
+        Ok(Box::new(Self {
+            inner: self.inner.try_clone()?,
+            psk: self.psk.clone(),
+            role: self.role,
+            session: self.session.clone(),
+            pending: self.pending.clone(),
+            seen_hellos: self.seen_hellos.clone(),
+        }))
+    }
+
+    fn pin_sender(&self)
+    {
+        self.inner.pin_sender()
+    }
+
+    /*
+     * The current session pins its sender again on its next frame, unless another tracer replaces it first.
+     */
+    fn unpin_sender(&self)
+    {
+        if let Some(session) = self.session.lock().unwrap().as_ref() {
+            *session.pinned.lock().unwrap() = false;
+        }
+        self.inner.unpin_sender()
+    }
+}
+
+
+
+/*
+ * Authentication of the control channel, a line based TCP protocol.
+ *
+ * On connection the server sends "CHALLENGE <random_s>", the client answers "AUTH <random_c> <mac>" and gets
+ * a JSON-RPC response with a null id, true on success.
+ * Afterwards each command line is prefixed with "<nonce> <mac>", the MAC covering "<nonce> <command>".
+ * The lines of the server, starting with the response to AUTH, are prefixed the same way by a LineSigner,
+ * with their own nonces and the MAC covering "debugger" as well.
+ */
+pub struct ControlAuth {
+    psk: Vec<u8>,
+    challenge: [u8; RANDOM_SIZE],
+    session: Option<[u8; MAC_SIZE]>,
+    last_nonce: u64,
+}
+
+impl ControlAuth {
+
+    pub fn new(psk: &[u8]) -> io::Result<Self>
+    {
+        Ok(Self { psk: psk.to_vec(), challenge: random_bytes()?, session: None, last_nonce: 0 })
+    }
+
+    pub fn challenge(&self) -> String
+    {
+        format!("CHALLENGE {}\n", to_hex(&self.challenge))
+    }
+
+    /*
+     * Check the answer to the challenge and open the session.
+     */
+    pub fn authenticate(&mut self, line: &str) -> Result<(), String>
+    {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.len() != 3 || words[0] != "AUTH" {
+            return Err(format!("Expected AUTH, received {:?}", line));
+        }
+
+        let random = from_hex(words[1]).ok_or("Invalid random in AUTH")?;
+        let tag = from_hex(words[2]).ok_or("Invalid MAC in AUTH")?;
+        if !verify_mac(&self.psk, &[b"sysfwd-control", &self.challenge, &random], &tag) {
+            return Err("Invalid MAC in AUTH".to_string());
+        }
+
+        self.session = Some(session_key(&self.psk, &self.challenge, &random));
+        Ok(())
+    }
+
+    /*
+     * Check the MAC and the nonce of a command line and return the command.
+     */
+    pub fn open_command<'a>(&mut self, line: &'a str) -> Result<&'a str, String>
+    {
+        let session = self.session.ok_or("Not authenticated")?;
+
+        let line = line.trim();
+        let mut parts = line.splitn(3, ' ');
+        let nonce_str = parts.next().unwrap_or("");
+        let tag = parts.next().and_then(from_hex).ok_or("Missing MAC")?;
+        let command = parts.next().unwrap_or("");
+
+        let nonce: u64 = nonce_str.parse().map_err(|_| format!("Invalid nonce {:?}", nonce_str))?;
+        let signed = format!("{} {}", nonce, command);
+        if !verify_mac(&session, &[signed.as_bytes()], &tag) {
+            return Err("Invalid MAC for command".to_string());
+        }
+        if nonce <= self.last_nonce {
+            return Err(format!("Replayed command with nonce {}", nonce));
+        }
+        self.last_nonce = nonce;
+
+        Ok(command)
+    }
+
+    /*
+     * The signer of the lines sent by the server, once authenticated.
+     */
+    pub fn signer(&self) -> Option<LineSigner>
+    {
+        self.session.map(|key| LineSigner { key, nonce: 0 })
+    }
+}
+
+#[derive(Debug)]
+pub struct LineSigner {
+    key: [u8; MAC_SIZE],
+    nonce: u64,
+}
+
+impl LineSigner {
+
+    /*
+     * Prefix the line with the next nonce and the MAC of both.
+     */
+    pub fn sign(&mut self, line: &str) -> String
+    {
+        self.nonce += 1;
+        let signed = format!("{} {}", self.nonce, line.trim_end());
+        let tag = compute_mac(&self.key, &[b"debugger", signed.as_bytes()]);
+        format!("{} {} {}\n", self.nonce, to_hex(&tag), line.trim_end())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::mpsc::{ channel, Receiver, Sender },
+        thread,
+    };
+
+    const PSK: &[u8] = b"pre-shared key";
+
+    type Frames = Arc<Mutex<Vec<Vec<u8>>>>;
+
+    /*
+     * One end of an in-memory link, every frame sent is recorded to be replayed.
+     */
+    struct Link {
+        tx: Sender<Vec<u8>>,
+        rx: Mutex<Receiver<Vec<u8>>>,
+        sent: Frames,
+    }
+
+    impl Transport for Link {
+        fn send(&self, data: &[u8]) -> io::Result<()>
+        {
+            self.sent.lock().unwrap().push(data.to_vec());
+            self.tx.send(data.to_vec()).map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+        }
+
+        fn receive(&self) -> io::Result<(Vec<u8>, usize)>
+        {
+            let frame = self.rx.lock().unwrap().recv_timeout(Duration::from_secs(1))
+                .map_err(|_| io::Error::from(io::ErrorKind::WouldBlock))?;
+            let size = frame.len();
+            Ok((frame, size))
+        }
+
+        fn set_read_timeout(&self, _dur: Option<Duration>) -> io::Result<()>
+        {
+            Ok(())
+        }
+
+        fn try_clone(&self) -> io::Result<Box<dyn Transport>>
+        {
+            Err(io::Error::from(io::ErrorKind::Unsupported))
+        }
+    }
+
+    /*
+     * A connected tracer and executor, with the frames sent by the tracer and a way to inject frames to the executor.
+     */
+    fn connect() -> (AuthenticatedTransport, AuthenticatedTransport, Frames, Sender<Vec<u8>>)
+    {
+        let (to_executor, executor_rx) = channel();
+        let (to_tracer, tracer_rx) = channel();
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let tracer_link = Link { tx: to_executor.clone(), rx: Mutex::new(tracer_rx), sent: sent.clone() };
+        let executor_link = Link { tx: to_tracer, rx: Mutex::new(executor_rx), sent: Arc::new(Mutex::new(Vec::new())) };
+
+        let executor = thread::spawn(move || {
+            let executor = AuthenticatedTransport::accept(Box::new(executor_link), PSK);
+            let (payload, _) = executor.receive().unwrap();
+            assert_eq!(payload, b"first");
+            executor
+        });
+        let tracer = AuthenticatedTransport::connect(Box::new(tracer_link), PSK).unwrap();
+        tracer.send(b"first").unwrap();
+        (tracer, executor.join().unwrap(), sent, to_executor)
+    }
+
+    #[test]
+    fn exchanges_authenticated_frames()
+    {
+        let (tracer, executor, _, _) = connect();
+        executor.send(b"reply").unwrap();
+        assert_eq!(tracer.receive().unwrap().0, b"reply");
+        tracer.send(b"second").unwrap();
+        assert_eq!(executor.receive().unwrap().0, b"second");
+    }
+
+    #[test]
+    fn drops_replayed_and_forged_frames()
+    {
+        let (tracer, executor, sent, inject) = connect();
+        let data = sent.lock().unwrap().last().unwrap().clone();
+
+        let mut forged = data.clone();
+        *forged.last_mut().unwrap() ^= 1;
+        inject.send(forged).unwrap();
+        inject.send(data).unwrap();
+        tracer.send(b"second").unwrap();
+        assert_eq!(executor.receive().unwrap().0, b"second");
+    }
+
+    #[test]
+    fn keeps_the_session_on_a_replayed_hello()
+    {
+        let (tracer, executor, sent, inject) = connect();
+        let hello = sent.lock().unwrap()[0].clone();
+        assert_eq!(hello[0], FRAME_HELLO);
+
+        inject.send(hello).unwrap();
+        tracer.send(b"second").unwrap();
+        assert_eq!(executor.receive().unwrap().0, b"second");
+        executor.send(b"reply").unwrap();
+        assert_eq!(tracer.receive().unwrap().0, b"reply");
+    }
+
+    /*
+     * Answer the challenge of the server, returning the client random.
+     */
+    fn answer(auth: &mut ControlAuth, psk: &[u8]) -> Result<[u8; RANDOM_SIZE], String>
+    {
+        let challenge = from_hex(auth.challenge().trim().strip_prefix("CHALLENGE ").unwrap()).unwrap();
+        let random = random_bytes().unwrap();
+        let tag = compute_mac(psk, &[b"sysfwd-control", &challenge, &random]);
+        auth.authenticate(&format!("AUTH {} {}\n", to_hex(&random), to_hex(&tag)))?;
+        Ok(random)
+    }
+
+    fn sign_command(key: &[u8], nonce: u64, command: &str) -> String
+    {
+        let tag = compute_mac(key, &[format!("{} {}", nonce, command).as_bytes()]);
+        format!("{} {} {}\n", nonce, to_hex(&tag), command)
+    }
+
+    #[test]
+    fn control_refuses_a_wrong_key()
+    {
+        let mut auth = ControlAuth::new(PSK).unwrap();
+        assert!(answer(&mut auth, b"another key").is_err());
+        assert!(auth.open_command("1 00 {}").is_err());
+        assert!(auth.signer().is_none());
+    }
+
+    #[test]
+    fn control_checks_the_commands()
+    {
+        let mut auth = ControlAuth::new(PSK).unwrap();
+        let random = answer(&mut auth, PSK).unwrap();
+        let challenge = from_hex(auth.challenge().trim().strip_prefix("CHALLENGE ").unwrap()).unwrap();
+        let key = session_key(PSK, &challenge, &random);
+
+        let command = sign_command(&key, 1, r#"{"jsonrpc":"2.0","method":"list_rules","id":1}"#);
+        assert_eq!(auth.open_command(&command), Ok(r#"{"jsonrpc":"2.0","method":"list_rules","id":1}"#));
+        assert!(auth.open_command(&command).is_err(), "replayed command");
+        assert!(auth.open_command(&sign_command(&key, 2, "{}").replace("{}", "[]")).is_err(), "forged command");
+        assert!(auth.open_command(&sign_command(&key, 3, "{}")).is_ok());
+    }
+
+    #[test]
+    fn control_signs_the_lines_of_the_server()
+    {
+        let mut auth = ControlAuth::new(PSK).unwrap();
+        let random = answer(&mut auth, PSK).unwrap();
+        let challenge = from_hex(auth.challenge().trim().strip_prefix("CHALLENGE ").unwrap()).unwrap();
+        let key = session_key(PSK, &challenge, &random);
+
+        let mut signer = auth.signer().unwrap();
+        for nonce in 1..3 {
+            let line = signer.sign("{\"result\":true}\n");
+            let mut parts = line.trim_end().splitn(3, ' ');
+            assert_eq!(parts.next(), Some(nonce.to_string().as_str()));
+            let tag = from_hex(parts.next().unwrap()).unwrap();
+            let signed = format!("{} {}", nonce, parts.next().unwrap());
+            assert!(verify_mac(&key, &[b"debugger", signed.as_bytes()], &tag));
+            // A line of the server is never a valid command
+            assert!(!verify_mac(&key, &[signed.as_bytes()], &tag));
+        }
+    }
+}
@@ -2,60 +2,258 @@
  *
  */
 use std::{
-    str::FromStr,
     //collections::{ HashMap },
     //thread::{ Builder, JoinHandle },
     //os::unix::process::{ CommandExt },
     //process::{ exit, Child, Command },
     //sync::{ Arc, Barrier },
-    io::{ self, BufRead, BufReader, BufWriter, Write },
-    net::{TcpListener, TcpStream, Ipv4Addr },
+    io::{ self, BufRead, BufReader, Write },
+    collections::HashSet,
+    sync::{ Arc, Mutex },
+    net::{TcpListener, TcpStream, Ipv4Addr, IpAddr },
 };
 
 use nix::{
+    libc::user_regs_struct,
     unistd::Pid,
     sys::signal::Signal,
 };
+use serde::{ Serialize, Deserialize };
+use serde_json::Value;
 
 use crate::{
     tracer::TracerCallback,
-    executor::ExecutorCallback,
+    executor::{ ExecutorCallback, ChildConfig },
+    arch::{ registers_to_map, update_registers },
+    memory::MemoryMap,
+    protocol::data::MEMORY_CHUNK_SIZE,
+    protocol::auth::{ ControlAuth, LineSigner, to_hex, from_hex },
+    protocol::rpc::{
+        self,
+        Response, RpcError, Event, parse_params,
+        PidParams, SpawnProcessParams, ContTracingParams, ReadMemoryParams, WriteMemoryParams,
+        WriteRegistersParams, BreakpointParams, SpawnProcessResult, ReadMemoryResult,
+        SubscribeParams, SubscribeResult, SwitchParams, SwitchResult, LoadRuleParams, UnloadRuleParams, MoveRuleParams, ListRulesResult,
+        GdbServerParams, GdbServerResult,
+    },
 };
 
 
 
+fn to_result<T: Serialize>(result: T) -> Result<Value, RpcError>
+{
+    serde_json::to_value(result).map_err(|err| RpcError::new(rpc::INTERNAL_ERROR, err.to_string()))
+}
+
+/*
+ * 0 means no signal.
+ */
+fn parse_signal(signal: i32) -> Result<Option<Signal>, RpcError>
+{
+    match signal {
+        0 => Ok(None),
+        _ => Signal::try_from(signal).map(Some).map_err(|_| RpcError::invalid_params(format!("Invalid signal {}", signal))),
+    }
+}
+
+
+
 /*
  * The control thread listen for commands from avatar.
- * It can be configured either as a tracer or executor and changed during runtime (TODO).
+ * It can be configured either as a tracer or executor and changed during runtime
+ * when the debugger implements both.
  */
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
 pub enum Configuration {
     Tracer = 0,
     Executor = 1,
 }
 
 
+/* The connection of the client and the signer of the lines sent once authenticated */
+type ClientStream = (TcpStream, Option<LineSigner>);
+
+/*
+ * Used by the debugger threads to report events to the control client asynchronously,
+ * as JSON-RPC notifications interleaved with the responses.
+ * The responses are written through the notifier as well, each line under its lock so that they never mix.
+ * Each connection starts subscribed to the breakpoint hits only, once authenticated.
+ */
+#[derive(Clone, Debug)]
+pub struct Notifier {
+    stream: Arc<Mutex<Option<ClientStream>>>,
+    subscriptions: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Notifier {
+
+    pub fn new() -> Self
+    {
+        Self {
+            stream: Arc::new(Mutex::new(None)),
+            subscriptions: Arc::new(Mutex::new(Self::default_subscriptions())),
+        }
+    }
+
+    fn default_subscriptions() -> HashSet<String>
+    {
+        HashSet::from([String::from("breakpoint")])
+    }
+
+    /*
+     * No event is sent on a new connection until start_events.
+     */
+    fn set_stream(&self, stream: Option<TcpStream>)
+    {
+        *self.stream.lock().unwrap() = stream.map(|stream| (stream, None));
+        self.subscriptions.lock().unwrap().clear();
+    }
+
+    /*
+     * Authenticate the next lines sent to the client.
+     */
+    fn sign_with(&self, signer: Option<LineSigner>)
+    {
+        if let Some((_, current)) = self.stream.lock().unwrap().as_mut() {
+            *current = signer;
+        }
+    }
+
+    fn start_events(&self)
+    {
+        *self.subscriptions.lock().unwrap() = Self::default_subscriptions();
+    }
+
+    /*
+     * Write a whole line, either an event or a response.
+     */
+    fn send_line(&self, line: &str) -> io::Result<()>
+    {
+        match self.stream.lock().unwrap().as_mut() {
+            Some((stream, Some(signer))) => stream.write_all(signer.sign(line).as_bytes()),
+            Some((stream, None)) => stream.write_all(line.as_bytes()),
+            None => Err(io::Error::new(io::ErrorKind::NotConnected, "No control client")),
+        }
+    }
+
+    /*
+     * Allow the debuggers to skip building the events nobody listens to.
+     */
+    pub fn is_subscribed(&self, event: &str) -> bool
+    {
+        self.stream.lock().unwrap().is_some() && self.subscriptions.lock().unwrap().contains(event)
+    }
+
+    pub fn notify(&self, event: Event)
+    {
+        if !self.subscriptions.lock().unwrap().contains(event.name()) {
+            return;
+        }
+
+        if let Err(err) = self.send_line(&event.to_line()) {
+            eprintln!("Fail to notify {}: {}", event.name(), err);
+        }
+    }
+
+    /*
+     * The tracee stays stopped until cont_tracing.
+     */
+    pub fn notify_breakpoint(&self, pid: Pid, address: usize, regs: &user_regs_struct)
+    {
+        self.notify(Event::Breakpoint {
+            pid: pid.as_raw(),
+            address,
+            registers: registers_to_map(regs),
+        });
+    }
+
+    /*
+     * Add or remove event names, "*" standing for all of them.
+     * Return the events subscribed to.
+     */
+    fn update_subscriptions(&self, events: &[String], subscribe: bool) -> Result<Vec<String>, RpcError>
+    {
+        let mut names: Vec<&str> = Vec::new();
+        for event in events {
+            match event.as_str() {
+                "*" => names.extend(Event::NAMES),
+                name if Event::NAMES.contains(&name) => names.push(name),
+                name => return Err(RpcError::invalid_params(format!("Unknown event {:?}", name))),
+            }
+        }
+
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        for name in names {
+            match subscribe {
+                true => subscriptions.insert(name.to_string()),
+                false => subscriptions.remove(name),
+            };
+        }
+
+        let mut events: Vec<String> = subscriptions.iter().cloned().collect();
+        events.sort();
+        Ok(events)
+    }
+}
+
+impl Default for Notifier {
+
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+
 pub struct ControlChannel {
     configuration: Configuration,
     tracer: Option<Box<dyn TracerCallback>>,        // USe callbakc closure or trait ???
     executor: Option<Box<dyn ExecutorCallback>>,
     reader: Option<BufReader<TcpStream>>,
-    writer: Option<BufWriter<TcpStream>>,
+    notifier: Notifier,
+
+    /* Pre-shared key mode */
+    key: Option<Vec<u8>>,
+    auth: Option<ControlAuth>,
+    pinned_peer: Option<IpAddr>,
 }
 
 impl ControlChannel {
 
-    pub fn new(configuration: Configuration, tracer: Option<Box<dyn TracerCallback>>, executor: Option<Box<dyn ExecutorCallback>>) -> Self
+    pub fn new(configuration: Configuration, mut tracer: Option<Box<dyn TracerCallback>>, mut executor: Option<Box<dyn ExecutorCallback>>) -> Self
     {
+        let notifier = Notifier::new();
+        if let Some(tracer) = tracer.as_mut() {
+            tracer.set_notifier(notifier.clone());
+        }
+        if let Some(executor) = executor.as_mut() {
+            executor.set_notifier(notifier.clone());
+        }
+
         Self {
-            configuration: configuration,
-            tracer: tracer,
-            executor: executor,
+            configuration,
+            tracer,
+            executor,
             //stream: None,
             reader: None,
-            writer: None,
+            notifier,
+            key: None,
+            auth: None,
+            pinned_peer: None,
         }
     }
 
+    /*
+     * Only accept connections answering a challenge with the pre-shared key and commands authenticated with it.
+     * The address of the first authenticated peer is pinned for the next connections.
+     */
+    pub fn require_key(&mut self, key: Vec<u8>)
+    {
+        self.key = Some(key);
+    }
+
     pub fn listen(&mut self, ip: Ipv4Addr, port: u16)
     {
         println!("Listen for connections...");
@@ -77,9 +275,20 @@ impl ControlChannel {
 
     fn handle_connection(&mut self, stream: TcpStream)
     {
+        let peer = stream.peer_addr().map(|addr| addr.ip()).ok();
         self.reader = Some(BufReader::new(stream.try_clone().unwrap()));
-        self.writer = Some(BufWriter::new(stream));
-
+        self.notifier.set_stream(Some(stream));
+
+        if self.key.is_some() {
+            if let Err(err) = self.authenticate_peer(peer) {
+                eprintln!("Authentication failed: {}", err);
+                let error = RpcError::new(rpc::AUTHENTICATION_FAILED, err);
+                self.send_response(Response::error(Value::Null, error));
+                return;
+            }
+            self.send_response(Response::result(Value::Null, Value::Bool(true)));
+        }
+        self.notifier.start_events();
 
         /* The main loop of the listening thread */
         loop {
@@ -100,6 +309,35 @@ impl ControlChannel {
                 }
             }
         }
+        self.notifier.set_stream(None);
+    }
+
+    fn authenticate_peer(&mut self, peer: Option<IpAddr>) -> Result<(), String>
+    {
+        if self.pinned_peer.is_some() && self.pinned_peer != peer {
+            return Err(format!("Connection from {:?} while pinned to {:?}", peer, self.pinned_peer));
+        }
+
+        let mut auth = ControlAuth::new(self.key.as_ref().unwrap()).map_err(|err| err.to_string())?;
+        let challenge = auth.challenge();
+        self.notifier.send_line(&challenge).map_err(|err| err.to_string())?;
+
+        let mut buffer = String::new();
+        self.reader.as_mut().unwrap().read_line(&mut buffer).map_err(|err| err.to_string())?;
+        auth.authenticate(&buffer)?;
+
+        self.notifier.sign_with(auth.signer());
+        self.auth = Some(auth);
+        self.pinned_peer = peer;
+        Ok(())
+    }
+
+    fn send_response(&mut self, response: Response)
+    {
+        let line = response.to_line();
+        if let Err(err) = self.notifier.send_line(&line) {
+            eprintln!("Fail to send response {}: {}", line.trim_end(), err);
+        }
     }
 
     fn receive_message(&mut self, buffer: &mut String) -> io::Result<usize>
@@ -110,374 +348,490 @@ impl ControlChannel {
         result
     }
 
+    /*
+     * Parse a request, run it and send back its response.
+     * Malformed requests are answered with an error, they never bring down the debugger.
+     */
     fn dispatch_message(&mut self, message: String)
     {
-        let command: Vec<&str> = message.split_whitespace().collect();
-
-        match self.dispatch_command(command) {
-            Ok(_) => {
-                // TODO: Send Ack to avatar2 ?
+        let message = match self.auth.as_mut() {
+            Some(auth) => match auth.open_command(&message) {
+                Ok(command) => command.to_string(),
+                Err(msg) => {
+                    eprintln!("Reject command: {}", msg);
+                    let error = RpcError::new(rpc::AUTHENTICATION_FAILED, msg);
+                    self.send_response(Response::error(Value::Null, error));
+                    return;
+                },
             },
-            Err(msg) => {
-                eprintln!("{}", msg);
-                // TODO: Send back error message to avatar2
-            }
+            None => message,
+        };
+
+        let request = match rpc::parse_request(&message) {
+            Ok(request) => request,
+            Err((id, error)) => {
+                self.send_response(Response::error(id, error));
+                return;
+            },
+        };
+
+        let result = self.dispatch_command(&request.method, request.params);
+        if let Err(error) = result.as_ref() {
+            eprintln!("{}: {}", request.method, error.message);
+        }
+
+        // No response to notifications
+        if let Some(id) = request.id {
+            let response = match result {
+                Ok(result) => Response::result(id, result),
+                Err(error) => Response::error(id, error),
+            };
+            self.send_response(response);
         }
     }
 
-    fn dispatch_command(&mut self, command: Vec<&str>) -> Result<(), String>
+    fn dispatch_command(&mut self, method: &str, params: Value) -> Result<Value, RpcError>
     {
-        match command[0] {
-            "switch" => self.switch_configuration(command),
+        match method {
+            "switch" => self.switch_configuration(params),
+
+            "read_mem" => self.read_memory(params),
+            "write_mem" => self.write_memory(params),
+
+            "read_regs" => self.read_registers(params),
+            "write_regs" => self.write_registers(params),
 
-            "read_mem" => self.read_memory(command),
-            "write_mem" => self.write_memory(command),
+            "set_breakpoint" => self.set_breakpoint(params),
+            "remove_breakpoint" => self.remove_breakpoint(params),
 
-            "read_regs" => self.read_registers(command),
-            "write_regs" => self.write_registers(command),
+            "get_procmaps" => self.get_procmaps(params),
 
-            "set_breakpoint" => self.set_breakpoint(command),
-            "remove_breakpoint" => self.remove_breakpoint(command),
+            "subscribe" => self.subscribe(params, true),
+            "unsubscribe" => self.subscribe(params, false),
 
-            "get_procmaps" => Err(format!("Not implemented")),
-            //"" => Err(format!("Not implemented")),
-            
             // Check for configuration specific commands
             _ => {
                 match self.configuration {
-                    Configuration::Tracer => self.dispatch_tracer(command),
-                    Configuration::Executor => self.dispatch_executor(command),
+                    Configuration::Tracer => self.dispatch_tracer(method, params),
+                    Configuration::Executor => self.dispatch_executor(method, params),
                 }
             }
         }
 
     }
 
-    fn dispatch_tracer(&mut self, command: Vec<&str>) -> Result<(), String>
+    fn dispatch_tracer(&mut self, method: &str, params: Value) -> Result<Value, RpcError>
     {
-        match command[0] {
-            "spawn_process" => self.tracer_spawn_process(command),
-            "kill_process" => self.tracer_kill_process(command),
-            "start_tracing" => self.tracer_start_tracing(command),
-            "cont_tracing" => self.tracer_cont_tracing(command),
-            "stop_tracing" => self.tracer_stop_tracing(command),
-            //"" => Err(format!("Not implemented")),
-            _ => {
-                let msg = format!("[TRACER] Command not implemented: {}", command[0]);
-                Err(msg)
-            }
+        match method {
+            "spawn_process" => self.tracer_spawn_process(params),
+            "kill_process" => self.tracer_kill_process(params),
+            "start_tracing" => self.tracer_start_tracing(params),
+            "cont_tracing" => self.tracer_cont_tracing(params),
+            "stop_tracing" => self.tracer_stop_tracing(params),
+            "list_rules" => self.tracer_list_rules(params),
+            "load_rule" => self.tracer_load_rule(params),
+            "unload_rule" => self.tracer_unload_rule(params),
+            "move_rule" => self.tracer_move_rule(params),
+            "gdb_server" => self.tracer_gdb_server(params),
+            _ => Err(RpcError::method_not_found(method)),
         }
     }
 
-    fn dispatch_executor(&mut self, command: Vec<&str>) -> Result<(), String>
+    fn dispatch_executor(&mut self, method: &str, params: Value) -> Result<Value, RpcError>
     {
-        match command[0] {
-            "spawn_process" => self.executor_spawn_process(command),
-            "kill_process" => self.executor_kill_process(command),
-            //"" => Err(format!("Not implemented")),
-            _ => {
-                let msg = format!("[EXECUTOR] Command not implemented: {}", command[0]);
-                Err(msg)
-            }
+        match method {
+            "spawn_process" => self.executor_spawn_process(params),
+            "kill_process" => self.executor_kill_process(params),
+            _ => Err(RpcError::method_not_found(method)),
         }
     }
 
-
-    /* Function in common */
-
-    fn switch_configuration(&mut self, _command: Vec<&str>) -> Result<(), String>
+    fn tracer(&mut self) -> Result<&mut Box<dyn TracerCallback>, RpcError>
     {
-        let msg = format!("Switching between Tracer and Executor not supported yet :(");
-        Err(msg)
+        self.tracer.as_mut().ok_or(RpcError::not_supported("No tracer configured".to_string()))
     }
 
-    fn read_registers(&mut self, _command: Vec<&str>) -> Result<(), String>
+    fn executor(&mut self) -> Result<&mut Box<dyn ExecutorCallback>, RpcError>
     {
-        // TODO
-        let msg = format!("Command read register not implemented yet");
-        Err(msg)
+        self.executor.as_mut().ok_or(RpcError::not_supported("No executor configured".to_string()))
     }
 
-    fn write_registers(&mut self, _command: Vec<&str>) -> Result<(), String>
-    {
-        // TODO
-        let msg = format!("Command write register not implemented yet");
-        Err(msg)
-    }
 
-    fn read_memory(&mut self, _command: Vec<&str>) -> Result<(), String>
-    {
-        // TODO
-        let msg = format!("Command read memory not implemented yet");
-        Err(msg)
-    }
+    /* Function in common */
 
-    fn write_memory(&mut self, _command: Vec<&str>) -> Result<(), String>
+    /*
+     * switch {pids} -> {configuration}
+     * Change between the tracer and executor roles, the processes are released by the current role
+     * and adopted by the new one. They keep their pid.
+     */
+    fn switch_configuration(&mut self, params: Value) -> Result<Value, RpcError>
     {
-        // TODO
-        let msg = format!("Command write memory not implemented yet");
-        Err(msg)
-    }
+        let params: SwitchParams = parse_params(params)?;
 
-    fn set_breakpoint(&mut self, _command: Vec<&str>) -> Result<(), String>
-    {
-        // TODO
-        let msg = format!("Command set breakpoint not implemented yet");
-        Err(msg)
+        if self.tracer.is_none() || self.executor.is_none() {
+            return Err(RpcError::not_supported("Switching needs both a tracer and an executor".to_string()));
+        }
+
+        for pid in params.pids {
+            let pid = Pid::from_raw(pid);
+            let adopted = match self.configuration {
+                Configuration::Tracer => {
+                    let child = self.tracer()?.release_process(pid).map_err(|err| RpcError::failed(err.to_string()))?;
+                    self.executor()?.adopt_process(child)
+                },
+                Configuration::Executor => {
+                    let child = self.executor()?.release_process(pid).map_err(|err| RpcError::failed(err.to_string()))?;
+                    self.tracer()?.adopt_process(child)
+                },
+            };
+            // The process is left stopped and without debugger
+            adopted.map_err(|err| RpcError::failed(format!("Process {} released but not adopted: {}", pid, err)))?;
+        }
+
+        self.configuration = match self.configuration {
+            Configuration::Tracer => Configuration::Executor,
+            Configuration::Executor => Configuration::Tracer,
+        };
+        println!("Switched to {:?}", self.configuration);
+        to_result(SwitchResult { configuration: self.configuration })
     }
 
-    fn remove_breakpoint(&mut self, _command: Vec<&str>) -> Result<(), String>
+    /*
+     * read_regs {pid} -> {name: value}
+     */
+    fn read_registers(&mut self, params: Value) -> Result<Value, RpcError>
     {
-        // TODO
-        let msg = format!("Command remove breakpoint not implemented yet");
-        Err(msg)
+        let params: PidParams = parse_params(params)?;
+        let pid = Pid::from_raw(params.pid);
+
+        let regs = match self.configuration {
+            Configuration::Tracer => self.tracer()?.read_registers(pid),
+            Configuration::Executor => self.executor()?.read_registers(pid),
+        }.map_err(|err| RpcError::failed(format!("Fail to read registers of {}: {}", pid, err)))?;
+
+        to_result(registers_to_map(&regs))
     }
-    
 
-    /* Tracer related functions */
-    
-    fn tracer_spawn_process(&mut self, command: Vec<&str>) -> Result<(), String>
+    /*
+     * write_regs {pid, registers: {name: value}}
+     * Only the given registers are modified.
+     */
+    fn write_registers(&mut self, params: Value) -> Result<Value, RpcError>
     {
-        let program = command[1].to_string();
-        let args_str: Vec<&str> = command[2..].to_vec();
-        let args: Vec<String> = args_str.iter().map(|&s| s.to_string()).collect();
+        let params: WriteRegistersParams = parse_params(params)?;
+        let pid = Pid::from_raw(params.pid);
 
-        match self.tracer.as_mut() {
-            Some(tracer) => {
-                // TODO: match on the value return by spawn_process
-                let pid = tracer.spawn_process(program, args).unwrap();
+        let mut regs = match self.configuration {
+            Configuration::Tracer => self.tracer()?.read_registers(pid),
+            Configuration::Executor => self.executor()?.read_registers(pid),
+        }.map_err(|err| RpcError::failed(format!("Fail to read registers of {}: {}", pid, err)))?;
 
-                let buffer = pid.as_raw().to_be_bytes();
-                self.writer.as_mut().unwrap().write(&buffer).unwrap();
-            },
-            None => {
-                let mut ack = String::new();
-                ack.push_str("ERR");
-                let buffer = ack.as_bytes();
-                self.writer.as_mut().unwrap().write(&buffer).unwrap();
-            }
-        }
+        update_registers(&mut regs, &params.registers).map_err(RpcError::invalid_params)?;
 
-        self.writer.as_mut().unwrap().flush().unwrap();
+        match self.configuration {
+            Configuration::Tracer => self.tracer()?.write_registers(pid, regs),
+            Configuration::Executor => self.executor()?.write_registers(pid, regs),
+        }.map_err(|err| RpcError::failed(format!("Fail to write registers of {}: {}", pid, err)))?;
 
-        // when should we return an error ?
-        Ok(())
+        Ok(Value::Null)
     }
 
-    fn tracer_kill_process(&mut self, command: Vec<&str>) -> Result<(), String>
+    /*
+     * read_mem {pid, address, size} -> {data}
+     * The memory is returned as an hexadecimal string, at most MEMORY_CHUNK_SIZE bytes at once.
+     */
+    fn read_memory(&mut self, params: Value) -> Result<Value, RpcError>
     {
-        let pid = command[1];
-        let pid = FromStr::from_str(pid).unwrap();
-        let pid = Pid::from_raw(pid);
+        let params: ReadMemoryParams = parse_params(params)?;
+        let pid = Pid::from_raw(params.pid);
+        if params.size > MEMORY_CHUNK_SIZE {
+            let msg = format!("Cannot read {} bytes, at most {} at once", params.size, MEMORY_CHUNK_SIZE);
+            return Err(RpcError::invalid_params(msg));
+        }
 
-        match self.tracer.as_mut() {
-            Some(tracer) => {
-                // TODO: match on the value return by spawn_process
-                tracer.kill_process(pid).unwrap();
+        let mem = match self.configuration {
+            Configuration::Tracer => self.tracer()?.read_memory(pid, params.address, params.size),
+            Configuration::Executor => self.executor()?.read_memory(pid, params.address, params.size),
+        }.map_err(|err| RpcError::failed(format!("Fail to read memory of {}: {}", pid, err)))?;
 
-                let mut ack = String::new();
-                ack.push_str("ACK");
-                let buffer = ack.as_bytes();
-                self.writer.as_mut().unwrap().write(&buffer).unwrap();
-            },
-            None => {
-                let mut ack = String::new();
-                ack.push_str("ERR");
-                let buffer = ack.as_bytes();
-                self.writer.as_mut().unwrap().write(&buffer).unwrap();
-            }
+        if mem.len() != params.size {
+            let msg = format!("Only {} bytes over {} readable at {:#x}", mem.len(), params.size, params.address);
+            return Err(RpcError::failed(msg));
         }
+        to_result(ReadMemoryResult { data: to_hex(&mem) })
+    }
 
-        self.writer.as_mut().unwrap().flush().unwrap();
+    /*
+     * write_mem {pid, address, data}
+     */
+    fn write_memory(&mut self, params: Value) -> Result<Value, RpcError>
+    {
+        let params: WriteMemoryParams = parse_params(params)?;
+        let pid = Pid::from_raw(params.pid);
+        let data = from_hex(&params.data).ok_or(RpcError::invalid_params("Invalid hexadecimal data".to_string()))?;
+        let size = data.len();
+
+        let written = match self.configuration {
+            Configuration::Tracer => self.tracer()?.write_memory(pid, params.address, data),
+            Configuration::Executor => self.executor()?.write_memory(pid, params.address, data),
+        }.map_err(|err| RpcError::failed(format!("Fail to write memory of {}: {}", pid, err)))?;
+
+        if written != size {
+            let msg = format!("Only {} bytes over {} written at {:#x}", written, size, params.address);
+            return Err(RpcError::failed(msg));
+        }
+        Ok(Value::Null)
+    }
 
-        // when should we return an error ?
-        Ok(())
+    /*
+     * get_procmaps {pid} -> MemoryMap
+     * The maps file is read directly, both the tracer and the executor run on the host of their processes.
+     * Only the processes of the debugger are readable, not any process of its host.
+     */
+    fn get_procmaps(&mut self, params: Value) -> Result<Value, RpcError>
+    {
+        let params: PidParams = parse_params(params)?;
+        let pid = Pid::from_raw(params.pid);
+        let known = match self.configuration {
+            Configuration::Tracer => self.tracer()?.has_process(pid),
+            Configuration::Executor => self.executor()?.has_process(pid),
+        };
+        if !known {
+            return Err(RpcError::failed(format!("No such pid: {}", pid)));
+        }
+
+        let maps = MemoryMap::from_pid(params.pid)
+            .map_err(|err| RpcError::failed(format!("Fail to read memory maps of {}: {}", params.pid, err)))?;
+        to_result(maps)
     }
 
-    fn tracer_start_tracing(&mut self, command: Vec<&str>) -> Result<(), String>
+    /*
+     * subscribe {events} -> {events}
+     * unsubscribe {events} -> {events}
+     * The events are sent as notifications on this connection, see rpc::Event.
+     */
+    fn subscribe(&mut self, params: Value, subscribe: bool) -> Result<Value, RpcError>
     {
-        let pid = command[1];
-        let pid = FromStr::from_str(pid).unwrap();
-        let pid = Pid::from_raw(pid);
+        let params: SubscribeParams = parse_params(params)?;
 
-        match self.tracer.as_mut() {
-            Some(tracer) => {
-                // TODO: match on the value return by spawn_process
-                tracer.start_tracing(pid).unwrap();
+        let events = self.notifier.update_subscriptions(&params.events, subscribe)?;
+        to_result(SubscribeResult { events })
+    }
 
-                let mut ack = String::new();
-                ack.push_str("ACK");
-                let buffer = ack.as_bytes();
-                self.writer.as_mut().unwrap().write(&buffer).unwrap();
-            },
-            None => {
-                let mut ack = String::new();
-                ack.push_str("ERR");
-                let buffer = ack.as_bytes();
-                self.writer.as_mut().unwrap().write(&buffer).unwrap();
-            }
+    /*
+     * set_breakpoint {pid, address}
+     * The hits are reported with a "breakpoint" notification and the tracee stays stopped until cont_tracing.
+     */
+    fn set_breakpoint(&mut self, params: Value) -> Result<Value, RpcError>
+    {
+        let params: BreakpointParams = parse_params(params)?;
+        let pid = Pid::from_raw(params.pid);
+
+        match self.configuration {
+            Configuration::Tracer => self.tracer()?.set_breakpoint(pid, params.address)
+                .map_err(|err| RpcError::failed(format!("Fail to set breakpoint at {:#x}: {}", params.address, err)))?,
+            Configuration::Executor => return Err(RpcError::not_supported("Breakpoints are only supported by the tracer".to_string())),
         }
+        Ok(Value::Null)
+    }
 
-        self.writer.as_mut().unwrap().flush().unwrap();
+    /*
+     * remove_breakpoint {pid, address}
+     */
+    fn remove_breakpoint(&mut self, params: Value) -> Result<Value, RpcError>
+    {
+        let params: BreakpointParams = parse_params(params)?;
+        let pid = Pid::from_raw(params.pid);
 
-        // when should we return an error ?
-        Ok(())
+        match self.configuration {
+            Configuration::Tracer => self.tracer()?.remove_breakpoint(pid, params.address)
+                .map_err(|err| RpcError::failed(format!("Fail to remove breakpoint at {:#x}: {}", params.address, err)))?,
+            Configuration::Executor => return Err(RpcError::not_supported("Breakpoints are only supported by the tracer".to_string())),
+        }
+        Ok(Value::Null)
     }
 
-    fn tracer_cont_tracing(&mut self, command: Vec<&str>) -> Result<(), String>
-    {
-        let pid = command[1];
-        let pid = FromStr::from_str(pid).unwrap();
-        let pid = Pid::from_raw(pid);
-
-        let sig = command[2];
-        let sig = FromStr::from_str(sig).unwrap();
-        let signal: Option<Signal>;
-        // Linux signals
-        match sig {
-            1 => signal = Some(Signal::SIGHUP),
-            2 => signal = Some(Signal::SIGINT),
-            3 => signal = Some(Signal::SIGQUIT),
-            4 => signal = Some(Signal::SIGKILL),
-            5 => signal = Some(Signal::SIGTRAP),
-            6 => signal = Some(Signal::SIGABRT),
-            7 => signal = Some(Signal::SIGBUS),
-            8 => signal = Some(Signal::SIGFPE),
-            9 => signal = Some(Signal::SIGKILL),
-            10 => signal = Some(Signal::SIGUSR1),
-            11 => signal = Some(Signal::SIGSEGV),
-            12 => signal = Some(Signal::SIGUSR2),
-            13 => signal = Some(Signal::SIGPIPE),
-            14 => signal = Some(Signal::SIGALRM),
-            15 => signal = Some(Signal::SIGTERM),
-            //16
-            17 => signal = Some(Signal::SIGCHLD),
-            18 => signal = Some(Signal::SIGCONT),
-            19 => signal = Some(Signal::SIGSTOP),
-            20 => signal = Some(Signal::SIGTSTP),
-            21 => signal = Some(Signal::SIGTTIN),
-            22 => signal = Some(Signal::SIGTTOU),
-            23 => signal = Some(Signal::SIGURG),
-            24 => signal = Some(Signal::SIGXCPU),
-            25 => signal = Some(Signal::SIGXFSZ),
-            26 => signal = Some(Signal::SIGVTALRM),
-            27 => signal = Some(Signal::SIGPROF),
-            28 => signal = Some(Signal::SIGWINCH),
-            29 => signal = Some(Signal::SIGIO),
-            30 => signal = Some(Signal::SIGPWR),
-            31 => signal = Some(Signal::SIGSYS),
-            _ => signal = None,
-        }
 
-        match self.tracer.as_mut() {
-            Some(tracer) => {
-                tracer.cont_tracing(pid, signal).unwrap();
+    /* Tracer related functions */
 
-                let mut ack = String::new();
-                ack.push_str("ACK");
-                let buffer = ack.as_bytes();
-                self.writer.as_mut().unwrap().write(&buffer).unwrap();
-            },
-            None => {
-                let mut ack = String::new();
-                ack.push_str("ERR");
-                let buffer = ack.as_bytes();
-                self.writer.as_mut().unwrap().write(&buffer).unwrap();
-            }
-        }
+    /*
+     * spawn_process {program, args} -> {pid}
+     */
+    fn tracer_spawn_process(&mut self, params: Value) -> Result<Value, RpcError>
+    {
+        let params: SpawnProcessParams = parse_params(params)?;
 
-        Ok(())
+        let pid = self.tracer()?.spawn_process(params.program.clone(), params.args)
+            .map_err(|err| RpcError::failed(format!("Fail to spawn {}: {}", params.program, err)))?;
+        to_result(SpawnProcessResult { pid: pid.as_raw() })
     }
-    
-    fn tracer_stop_tracing(&mut self, command: Vec<&str>) -> Result<(), String>
+
+    /*
+     * kill_process {pid}
+     */
+    fn tracer_kill_process(&mut self, params: Value) -> Result<Value, RpcError>
     {
-        let pid = command[1];
-        let pid = FromStr::from_str(pid).unwrap();
-        let pid = Pid::from_raw(pid);
+        let params: PidParams = parse_params(params)?;
+        let pid = Pid::from_raw(params.pid);
 
-        match self.tracer.as_mut() {
-            Some(tracer) => {
-                // TODO: match on the value return by spawn_process
-                tracer.stop_tracing(pid).unwrap();
+        self.tracer()?.kill_process(pid)
+            .map_err(|err| RpcError::failed(format!("Fail to kill {}: {}", pid, err)))?;
+        Ok(Value::Null)
+    }
 
-                let mut ack = String::new();
-                ack.push_str("ACK");
-                let buffer = ack.as_bytes();
-                self.writer.as_mut().unwrap().write(&buffer).unwrap();
-            },
-            None => {
-                let mut ack = String::new();
-                ack.push_str("ERR");
-                let buffer = ack.as_bytes();
-                self.writer.as_mut().unwrap().write(&buffer).unwrap();
-            }
-        }
+    /*
+     * start_tracing {pid}
+     */
+    fn tracer_start_tracing(&mut self, params: Value) -> Result<Value, RpcError>
+    {
+        let params: PidParams = parse_params(params)?;
+        let pid = Pid::from_raw(params.pid);
 
-        self.writer.as_mut().unwrap().flush().unwrap();
+        self.tracer()?.start_tracing(pid)
+            .map_err(|err| RpcError::failed(format!("Fail to start tracing {}: {}", pid, err)))?;
+        Ok(Value::Null)
+    }
 
-        // when should we return an error ?
-        Ok(())
+    /*
+     * cont_tracing {pid, signal}
+     */
+    fn tracer_cont_tracing(&mut self, params: Value) -> Result<Value, RpcError>
+    {
+        let params: ContTracingParams = parse_params(params)?;
+        let pid = Pid::from_raw(params.pid);
+        let signal = parse_signal(params.signal)?;
+
+        self.tracer()?.cont_tracing(pid, signal)
+            .map_err(|err| RpcError::failed(format!("Fail to continue {}: {}", pid, err)))?;
+        Ok(Value::Null)
     }
 
+    /*
+     * stop_tracing {pid}
+     */
+    fn tracer_stop_tracing(&mut self, params: Value) -> Result<Value, RpcError>
+    {
+        let params: PidParams = parse_params(params)?;
+        let pid = Pid::from_raw(params.pid);
 
-    /* Executor related functions */
+        self.tracer()?.stop_tracing(pid)
+            .map_err(|err| RpcError::failed(format!("Fail to stop tracing {}: {}", pid, err)))?;
+        Ok(Value::Null)
+    }
 
-    fn executor_spawn_process(&mut self, command: Vec<&str>) -> Result<(), String>
+    /*
+     * list_rules {pid} -> {rules: [{index, name, params}], available: [name]}
+     */
+    fn tracer_list_rules(&mut self, params: Value) -> Result<Value, RpcError>
     {
-        let program = command[1];
-        let args = &command[2..];
+        let params: PidParams = parse_params(params)?;
+        let pid = Pid::from_raw(params.pid);
 
-        match self.executor.as_mut() {
-            Some(executor) => {
-                // TODO: match on the value return by spawn_process
-                let pid = executor.spawn_process(program, args).unwrap();
+        let tracer = self.tracer()?;
+        let rules = tracer.list_rules(pid)
+            .map_err(|err| RpcError::failed(format!("Fail to list the rules of {}: {}", pid, err)))?;
+        to_result(ListRulesResult { rules, available: tracer.available_rules() })
+    }
 
-                let buffer = pid.as_raw().to_be_bytes();
-                self.writer.as_mut().unwrap().write(&buffer).unwrap();
-            },
-            None => {
-                let mut ack = String::new();
-                ack.push_str("ERR");
-                let buffer = ack.as_bytes();
-                self.writer.as_mut().unwrap().write(&buffer).unwrap();
-            }
-        }
+    /*
+     * load_rule {pid, index, name, params}
+     * The rule is created by the rule registry of the tracer from its name and params.
+     */
+    fn tracer_load_rule(&mut self, params: Value) -> Result<Value, RpcError>
+    {
+        let params: LoadRuleParams = parse_params(params)?;
+        let pid = Pid::from_raw(params.pid);
+        let name = params.name.clone();
+
+        self.tracer()?.load_rule(pid, params.index, params.name, params.params)
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::NotFound | io::ErrorKind::InvalidInput => RpcError::invalid_params(err.to_string()),
+                _ => RpcError::failed(format!("Fail to load rule {} in {}: {}", name, pid, err)),
+            })?;
+        Ok(Value::Null)
+    }
 
-        self.writer.as_mut().unwrap().flush().unwrap();
+    /*
+     * unload_rule {pid, index}
+     */
+    fn tracer_unload_rule(&mut self, params: Value) -> Result<Value, RpcError>
+    {
+        let params: UnloadRuleParams = parse_params(params)?;
+        let pid = Pid::from_raw(params.pid);
+
+        self.tracer()?.unload_rule(pid, params.index)
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::InvalidInput => RpcError::invalid_params(err.to_string()),
+                _ => RpcError::failed(format!("Fail to unload rule {} of {}: {}", params.index, pid, err)),
+            })?;
+        Ok(Value::Null)
+    }
 
-        // when should we return an error ?
-        Ok(())
+    /*
+     * move_rule {pid, from, to}
+     */
+    fn tracer_move_rule(&mut self, params: Value) -> Result<Value, RpcError>
+    {
+        let params: MoveRuleParams = parse_params(params)?;
+        let pid = Pid::from_raw(params.pid);
+
+        self.tracer()?.move_rule(pid, params.from, params.to)
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::InvalidInput => RpcError::invalid_params(err.to_string()),
+                _ => RpcError::failed(format!("Fail to move rule {} of {}: {}", params.from, pid, err)),
+            })?;
+        Ok(Value::Null)
     }
 
-    fn executor_kill_process(&mut self, command: Vec<&str>) -> Result<(), String>
+
+    /*
+     * gdb_server {pid, port} -> {port}
+     * Start a gdb server for the tracee, the tracee keeps forwarding its syscalls while gdb is attached.
+     */
+    fn tracer_gdb_server(&mut self, params: Value) -> Result<Value, RpcError>
     {
-        let pid = command[1];
-        let pid = FromStr::from_str(pid).unwrap();
-        let pid = Pid::from_raw(pid);
+        let params: GdbServerParams = parse_params(params)?;
+        let pid = Pid::from_raw(params.pid);
+
+        let port = self.tracer()?.start_gdb_server(pid, params.port)
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::Unsupported => RpcError::not_supported(err.to_string()),
+                _ => RpcError::failed(format!("Fail to start a gdb server for {}: {}", pid, err)),
+            })?;
+        to_result(GdbServerResult { port })
+    }
 
-        match self.executor.as_mut() {
-            Some(executor) => {
-                // TODO: match on the value return by spawn_process
-                executor.kill_process(pid).unwrap();
 
-                let mut ack = String::new();
-                ack.push_str("ACK");
-                let buffer = ack.as_bytes();
-                self.writer.as_mut().unwrap().write(&buffer).unwrap();
-            },
-            None => {
-                let mut ack = String::new();
-                ack.push_str("ERR");
-                let buffer = ack.as_bytes();
-                self.writer.as_mut().unwrap().write(&buffer).unwrap();
-            }
-        }
+    /* Executor related functions */
 
-        self.writer.as_mut().unwrap().flush().unwrap();
+    /*
+     * spawn_process {program, args, env, clear_env, cwd, uid, gid, personality, disable_aslr, native, sandbox,
+     *                record, replay} -> {pid}
+     * All optional, see ChildConfig for the defaults.
+     * A native session has no child, its pid is the one of the thread executing its syscalls.
+     */
+    fn executor_spawn_process(&mut self, params: Value) -> Result<Value, RpcError>
+    {
+        let config: ChildConfig = parse_params(params)?;
+        let program = config.program.clone();
 
-        // when should we return an error ?
-        Ok(())
+        let pid = self.executor()?.spawn_process(config)
+            .map_err(|err| RpcError::failed(format!("Fail to spawn {}: {}", program, err)))?;
+        to_result(SpawnProcessResult { pid: pid.as_raw() })
     }
 
+    /*
+     * kill_process {pid}
+     */
+    fn executor_kill_process(&mut self, params: Value) -> Result<Value, RpcError>
+    {
+        let params: PidParams = parse_params(params)?;
+        let pid = Pid::from_raw(params.pid);
+
+        self.executor()?.kill_process(pid)
+            .map_err(|err| RpcError::failed(format!("Fail to kill {}: {}", pid, err)))?;
+        Ok(Value::Null)
+    }
 
-}
\ No newline at end of file
+
+}
@@ -2,13 +2,25 @@
  *
  */
 use std::{
-    time::Duration,
+    time::{ Duration, Instant },
     io,
+    sync::{ Arc, Mutex },
     net::{ SocketAddr, UdpSocket, Ipv4Addr },
+    thread::{ self, JoinHandle },
 };
 
-
-use crate::syscall::Syscall;
+use serde::{ Serialize, Deserialize };
+
+use crate::{
+    sync::Event,
+    context::ProcessContext,
+    syscall::Syscall,
+    targets::operation::{ Operation, MemoryOperation },
+    protocol::{
+        shm::SharedMemory,
+        auth::AuthenticatedTransport,
+    },
+};
 
 
 /* 
@@ -17,6 +29,85 @@ use crate::syscall::Syscall;
  */
 const HEADER_SIZE: usize = 8;
 
+/* The largest UDP datagram, a header announcing more is wrong */
+const MAX_DATAGRAM_SIZE: usize = 65536;
+
+/*
+ * The tracer proves it is alive at this interval, the executor releases the resources of a session
+ * silent for longer than its timeout, see ExecutorEngine::set_session_timeout.
+ */
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+
+/*
+ * The default size of the memory block of a message, so that it fits in a datagram once serialized.
+ */
+pub const MEMORY_CHUNK_SIZE: usize = 4096;
+
+/* How long the executor waits for the tracer to serve a chunk of the tracee memory */
+const MEMORY_REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+
+/*
+ * The messages exchanged over the data channel.
+ * Besides the syscall itself, the executor can send reverse requests to the tracer
+ * while the syscall is being encoded or invoked, e.g., to fetch tracee memory on demand.
+ * Between two syscalls, the tracer reads and writes the memory mapped by the syscalls in the child
+ * with the same requests, see MirrorTable.
+ */
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug)]
+pub enum DataMessage {
+    Syscall(Box<Syscall>),
+    Batch(Vec<Syscall>),
+    ReadMemory { address: usize, size: usize },
+    Memory(Vec<u8>),
+    /* No reply */
+    WriteMemory { address: usize, content: Vec<u8> },
+    /* Sent periodically by the tracer, no reply */
+    Heartbeat,
+    /* The tracee is gone, the executor releases what its syscalls created, no reply */
+    EndSession,
+    /* The credentials and context the child adopts for the next syscalls, no reply */
+    Context(ProcessContext),
+}
+
+
+
+/*
+ * A transport moves the raw messages between the tracer and the executor.
+ * Peer is the default over UDP, SharedMemory is used when both run on the same host.
+ */
+pub trait Transport: Send {
+    fn send(&self, data: &[u8]) -> Result<(), io::Error>;
+    fn receive(&self) -> Result<(Vec<u8>, usize), io::Error>;
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()>;
+    fn try_clone(&self) -> io::Result<Box<dyn Transport>>;
+
+    /*
+     * Only accept the messages coming from the sender of the last received message.
+     * Nothing to do when the transport is already point to point.
+     */
+    fn pin_sender(&self) {}
+
+    /*
+     * Accept again the messages of any sender, e.g., once the session of the pinned one ended.
+     */
+    fn unpin_sender(&self) {}
+
+    fn send_message(&self, message: &DataMessage) -> Result<(), io::Error>
+    {
+        let data: String = serde_json::to_string(message).expect("Fail to serialize message to JSON");
+        self.send(data.as_bytes())
+    }
+
+    fn receive_message(&self) -> Result<DataMessage, io::Error>
+    {
+        let (buffer, _len): (Vec<u8>, usize) = self.receive()?;
+        serde_json::from_slice(&buffer).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
 
 
 /*
@@ -26,16 +117,26 @@ const HEADER_SIZE: usize = 8;
 pub struct Peer {
     local_socket: UdpSocket,
     remote_address: SocketAddr,
+    last_sender: Arc<Mutex<Option<SocketAddr>>>,
+    pinned_sender: Arc<Mutex<Option<SocketAddr>>>,
 }
 
 impl Peer {
 
     pub fn new(local_addr: SocketAddr, remote_address: SocketAddr) -> Self {
         let local_socket = UdpSocket::bind(local_addr).unwrap();
-        Self { local_socket, remote_address }
+        Self {
+            local_socket,
+            remote_address,
+            last_sender: Arc::new(Mutex::new(None)),
+            pinned_sender: Arc::new(Mutex::new(None)),
+        }
     }
+}
 
-    pub fn send(&self, data: &[u8]) -> Result<(), std::io::Error> 
+impl Transport for Peer {
+
+    fn send(&self, data: &[u8]) -> Result<(), std::io::Error> 
     {
         // There is a bug if data > 2^16 which is the maximum payload size for a UDP packet.
         // For example with cat which read 0x20000 bytes
@@ -50,27 +151,67 @@ impl Peer {
         Ok(())
     }
 
-    pub fn receive(&self) -> Result<(Vec<u8>, usize), std::io::Error>
+    fn receive(&self) -> Result<(Vec<u8>, usize), std::io::Error>
     {
-        // Read header containing the size of the payload
-        let mut header = [0u8; HEADER_SIZE];
-        let (count, _addr): (usize, SocketAddr) = self.local_socket.peek_from(&mut header)?;
-        if count != HEADER_SIZE { panic!("Fail to read header"); }
-        let size = usize::from_be_bytes(header);
-
-        // Read the payload
-        let mut message: Vec<u8> = vec![0u8; HEADER_SIZE + size];
-        let (size, _addr): (usize, SocketAddr) = self.local_socket.recv_from(&mut message)?;
-        //println!("Received {} bytes", size);
-        let payload = message.split_off(HEADER_SIZE);
-        Ok((payload, size))
+        loop {
+            // Read header containing the size of the payload
+            let mut header = [0u8; HEADER_SIZE];
+            let (count, addr): (usize, SocketAddr) = self.local_socket.peek_from(&mut header)?;
+            if count != HEADER_SIZE {
+                // Drop the datagram, otherwise it is peeked again
+                self.local_socket.recv_from(&mut header)?;
+                let msg = format!("Drop a datagram of {} bytes from {}, shorter than its header", count, addr);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+            }
+            let size = std::cmp::min(usize::from_be_bytes(header), MAX_DATAGRAM_SIZE);
+
+            // Read the payload
+            let mut message: Vec<u8> = vec![0u8; HEADER_SIZE + size];
+            let (size, addr): (usize, SocketAddr) = self.local_socket.recv_from(&mut message)?;
+            message.truncate(size);
+            //println!("Received {} bytes", size);
+
+            // Drop the datagrams from anyone else than the pinned sender
+            if let Some(pinned) = *self.pinned_sender.lock().unwrap() {
+                if pinned != addr {
+                    eprintln!("Drop {} bytes from {}", size, addr);
+                    continue;
+                }
+            }
+            *self.last_sender.lock().unwrap() = Some(addr);
+
+            let payload = message.split_off(HEADER_SIZE);
+            return Ok((payload, size));
+        }
     }
 
-    pub fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()>
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()>
     {
         self.local_socket.set_read_timeout(dur)
     }
 
+    fn try_clone(&self) -> io::Result<Box<dyn Transport>>
+    {
+        let local_socket = self.local_socket.try_clone()?;
+        Ok(Box::new(Self {
+            local_socket,
+            remote_address: self.remote_address,
+            last_sender: self.last_sender.clone(),
+            pinned_sender: self.pinned_sender.clone(),
+        }))
+    }
+
+    fn pin_sender(&self)
+    {
+        let last_sender = *self.last_sender.lock().unwrap();
+        *self.pinned_sender.lock().unwrap() = last_sender;
+    }
+
+    fn unpin_sender(&self)
+    {
+        *self.pinned_sender.lock().unwrap() = None;
+    }
+
 }
 
 
@@ -79,7 +220,9 @@ impl Peer {
  * The dispatcher...
  */
 pub struct Client { 
-    connection: Peer,
+    connection: Box<dyn Transport>,
+    /* Serialize the sends shared with the heartbeat thread, the transports do not all support concurrent sends */
+    sending: Arc<Mutex<()>>,
 }
 
 impl Client {
@@ -97,24 +240,189 @@ impl Client {
         let remote_addr = SocketAddr::new(ip.into(), executor_port);
 
         let connection = Peer::new(local_addr, remote_addr);
-        Client { connection }
+        Client::with_transport(Box::new(connection))
     }
 
-    pub fn send_syscall_entry(&self, syscall: &Syscall) -> Result<Syscall, std::io::Error>
+    /*
+     * Connect to an executor on the same host sharing its ring buffers through the UNIX socket at `path`.
+     */
+    pub fn new_shared_memory(path: &str) -> Result<Self, io::Error>
     {
-        // Craft the message
-        let data: String = serde_json::to_string(syscall).expect("Fail to serialize syscall to JSON");
-        //println!("[TRACER] Send syscall: {:?}", data);
+        let connection = SharedMemory::connect(path)?;
+        Ok(Client::with_transport(Box::new(connection)))
+    }
+
+    pub fn with_transport(connection: Box<dyn Transport>) -> Self
+    {
+        Client { connection, sending: Arc::new(Mutex::new(())) }
+    }
+
+    /*
+     * Authenticate every message with a pre-shared key, the handshake with the executor is done immediately.
+     */
+    pub fn with_key(self, key: &[u8]) -> Result<Self, io::Error>
+    {
+        let connection = AuthenticatedTransport::connect(self.connection, key)?;
+        Ok(Client::with_transport(Box::new(connection)))
+    }
 
+    /*
+     * Send the syscall to the executor and wait for its exit.
+     */
+    pub fn send_syscall_entry(&self, syscall: &Syscall, pid: i32, operation: &Operation) -> Result<Syscall, std::io::Error>
+    {
         // Send the message
-        self.connection.send(&data.as_bytes()).expect("Fail to send syscall entry");
+        let message = DataMessage::Syscall(Box::new(syscall.clone()));
+        self.send_message(&message)?;
 
         // Wait for the reply
-        let (buffer, _len): (Vec<u8>, usize) = self.connection.receive().expect("Error receiving syscall reply message");
-        //println!("[TRACER] Received {} bytes: {:?}", len, buffer);
+        match self.wait_reply(pid, operation)? {
+            DataMessage::Syscall(remote_syscall) => Ok(*remote_syscall),
+            message => {
+                let msg = format!("Expected a syscall, received {:?}", message);
+                Err(io::Error::new(io::ErrorKind::InvalidData, msg))
+            },
+        }
+    }
+
+    /*
+     * Send several syscalls at once which are executed in order by the executor.
+     */
+    pub fn send_syscall_batch(&self, syscalls: &[Syscall], pid: i32, operation: &Operation) -> Result<Vec<Syscall>, std::io::Error>
+    {
+        let message = DataMessage::Batch(syscalls.to_vec());
+        self.send_message(&message)?;
+
+        match self.wait_reply(pid, operation)? {
+            DataMessage::Batch(remote_syscalls) => Ok(remote_syscalls),
+            message => {
+                let msg = format!("Expected a batch of syscalls, received {:?}", message);
+                Err(io::Error::new(io::ErrorKind::InvalidData, msg))
+            },
+        }
+    }
 
-        let remote_syscall: Syscall = serde_json::from_slice(&buffer).expect("Fail to deserialize Syscall from JSON");
-        Ok(remote_syscall)
+    /*
+     * Wait for the reply of the executor.
+     * Meanwhile, serve the memory requests of the executor by reading the memory of the tracee `pid`.
+     */
+    fn wait_reply(&self, pid: i32, operation: &Operation) -> Result<DataMessage, std::io::Error>
+    {
+        loop {
+            match self.connection.receive_message()? {
+                DataMessage::ReadMemory { address, size } => {
+                    // The executor reads by chunks, never allocate more for it
+                    let size = std::cmp::min(size, MEMORY_CHUNK_SIZE);
+                    let mut mem = operation.memory.read(pid, address, size);
+                    mem.truncate(size);
+                    self.send_message(&DataMessage::Memory(mem))?;
+                },
+                message => return Ok(message),
+            }
+        }
+    }
+
+    /*
+     * Read the memory mapped in the executor child, by chunks.
+     */
+    pub fn read_remote_memory(&self, address: usize, size: usize) -> Result<Vec<u8>, io::Error>
+    {
+        let mut mem = Vec::with_capacity(size);
+        while mem.len() < size {
+            let chunk = std::cmp::min(size - mem.len(), MEMORY_CHUNK_SIZE);
+            self.send_message(&DataMessage::ReadMemory { address: address + mem.len(), size: chunk })?;
+            match self.connection.receive_message()? {
+                DataMessage::Memory(content) if content.len() == chunk => mem.extend(content),
+                DataMessage::Memory(content) => {
+                    let msg = format!("Read {} bytes out of {} at {:#x} in the child", content.len(), chunk, address + mem.len());
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, msg));
+                },
+                message => {
+                    let msg = format!("Expected child memory, received {:?}", message);
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+                },
+            }
+        }
+        Ok(mem)
+    }
+
+    /*
+     * Write the memory mapped in the executor child, by chunks.
+     */
+    pub fn write_remote_memory(&self, address: usize, content: &[u8]) -> Result<(), io::Error>
+    {
+        for (index, chunk) in content.chunks(MEMORY_CHUNK_SIZE).enumerate() {
+            let address = address + index * MEMORY_CHUNK_SIZE;
+            self.send_message(&DataMessage::WriteMemory { address, content: chunk.to_vec() })?;
+        }
+        Ok(())
+    }
+
+    /*
+     * Make the executor child adopt the context of the tracee before the next syscall.
+     */
+    pub fn send_context(&self, context: &ProcessContext) -> Result<(), io::Error>
+    {
+        self.send_message(&DataMessage::Context(context.clone()))
+    }
+
+    /*
+     * Tell the executor the tracee is gone.
+     */
+    pub fn end_session(&self) -> Result<(), io::Error>
+    {
+        self.send_message(&DataMessage::EndSession)
+    }
+
+    /*
+     * Send a heartbeat at each interval from another thread, until the returned handle is dropped.
+     * The tracing thread may block for long in a local syscall of the tracee.
+     */
+    pub fn start_heartbeat(&self, interval: Duration) -> Result<Heartbeat, io::Error>
+    {
+        let connection = self.connection.try_clone()?;
+        let sending = self.sending.clone();
+        let stop = Arc::new(Event::new());
+        let stopped = stop.clone();
+
+        let handler = thread::Builder::new()
+            .name(String::from("heartbeat"))
+            .spawn(move || {
+                while ! stopped.wait_timeout(interval) {
+                    let _sending = sending.lock().unwrap();
+                    // Nobody may listen yet, the executor only needs the heartbeats during a session
+                    let _ = connection.send_message(&DataMessage::Heartbeat);
+                }
+            })?;
+        Ok(Heartbeat { stop, handler: Some(handler) })
+    }
+
+    fn send_message(&self, message: &DataMessage) -> Result<(), io::Error>
+    {
+        let _sending = self.sending.lock().unwrap();
+        self.connection.send_message(message)
+    }
+
+}
+
+
+
+/*
+ * Stop the heartbeats of a Client when dropped.
+ */
+pub struct Heartbeat {
+    stop: Arc<Event>,
+    handler: Option<JoinHandle<()>>,
+}
+
+impl Drop for Heartbeat {
+
+    fn drop(&mut self)
+    {
+        self.stop.set();
+        if let Some(handler) = self.handler.take() {
+            let _ = handler.join();
+        }
     }
 
 }
@@ -125,7 +433,7 @@ impl Client {
  * The worker...
  */
 pub struct Server { 
-    connection: Peer,
+    connection: Box<dyn Transport>,
 }
 
 impl Server {
@@ -143,33 +451,167 @@ impl Server {
         let remote_addr = SocketAddr::new(ip.into(), tracer_port);
 
         let connection = Peer::new(local_addr, remote_addr);
+        Server::with_transport(Box::new(connection))
+    }
 
+    /*
+     * Share ring buffers with a tracer on the same host, handed over the UNIX socket at `path`.
+     */
+    pub fn new_shared_memory(path: &str) -> Result<Self, io::Error>
+    {
+        let connection = SharedMemory::listen(path)?;
+        Ok(Server::with_transport(Box::new(connection)))
+    }
+
+    pub fn with_transport(connection: Box<dyn Transport>) -> Self
+    {
+        // The timeout is used by the executor to check if it should stop
         let duration = Duration::new(1, 0);
         connection.set_read_timeout(Some(duration)).unwrap();
 
         Server { connection }
     }
 
+    /*
+     * Only accept the messages authenticated with a pre-shared key, the handshake is done when the tracer connects.
+     */
+    pub fn with_key(self, key: &[u8]) -> Self
+    {
+        let connection = AuthenticatedTransport::accept(self.connection, key);
+        Server { connection: Box::new(connection) }
+    }
 
-    pub fn receive_syscall(&self) -> Result<Syscall, std::io::Error>
+    /*
+     * Let another tracer reach the executor, once the session ended.
+     */
+    pub fn unpin_sender(&self)
     {
-        // Read socket
-        let (buffer, _len): (Vec<u8>, usize)  = self.connection.receive()?;
+        self.connection.unpin_sender();
+    }
 
-        // Parse syscall
-        let syscall = serde_json::from_slice(&buffer).expect("Fail to deserialize Syscall from JSON");
-        Ok(syscall)
+
+    /*
+     * Wait for a syscall or a batch of syscalls to execute, a memory access, or a message about the session.
+     */
+    pub fn receive_request(&self) -> Result<DataMessage, std::io::Error>
+    {
+        match self.connection.receive_message()? {
+            message @ (DataMessage::Syscall(_) | DataMessage::Batch(_)
+                       | DataMessage::ReadMemory { .. } | DataMessage::WriteMemory { .. }
+                       | DataMessage::Heartbeat | DataMessage::EndSession | DataMessage::Context(_)) => Ok(message),
+            message => {
+                let msg = format!("Expected a syscall, received {:?}", message);
+                Err(io::Error::new(io::ErrorKind::InvalidData, msg))
+            },
+        }
     }
 
 
     pub fn return_syscall_exit(&self, syscall: &Syscall)
     {
-        // Craft the message
-        let message: String = serde_json::to_string(syscall).expect("Fail to serialize syscall to JSON");
-        let data: &[u8] = message.as_bytes();
-        //println!("[EXECUTOR] Send syscall: {:?}", message);
+        let message = DataMessage::Syscall(Box::new(syscall.clone()));
+        self.connection.send_message(&message).expect("Failt to return syscall exit");
+    }
 
-        // Send the message
-        self.connection.send(&data).expect("Failt to return syscall exit");
+    pub fn return_batch_exit(&self, syscalls: &[Syscall])
+    {
+        let message = DataMessage::Batch(syscalls.to_vec());
+        self.connection.send_message(&message).expect("Failt to return batch exit");
+    }
+
+    pub fn return_memory(&self, mem: Vec<u8>)
+    {
+        let message = DataMessage::Memory(mem);
+        self.connection.send_message(&message).expect("Fail to return memory");
+    }
+
+    /*
+     * A memory operation reading the tracee memory through the data channel, until `stop` is set.
+     */
+    pub fn remote_memory(&self, stop: Arc<Event>) -> RemoteMemory
+    {
+        let connection = self.connection.try_clone().expect("Fail to clone the data channel socket");
+        RemoteMemory { connection, stop }
+    }
+}
+
+
+
+/*
+ * Used by the executor to fetch the tracee memory on demand, for instance while encoding
+ * the buffers or the nested pointers of a syscall.
+ * The tracer only serves requests for the tracee of the syscall being forwarded, therefore the pid is ignored.
+ */
+pub struct RemoteMemory {
+    connection: Box<dyn Transport>,
+    stop: Arc<Event>,
+}
+
+impl RemoteMemory {
+
+    /*
+     * Fetch one chunk, the tracer serves at most MEMORY_CHUNK_SIZE bytes per request.
+     */
+    fn read_chunk(&self, addr: usize, size: usize) -> Result<Vec<u8>, io::Error>
+    {
+        let request = DataMessage::ReadMemory { address: addr, size };
+        self.connection.send_message(&request)?;
+
+        let deadline = Instant::now() + MEMORY_REPLY_TIMEOUT;
+        loop {
+            match self.connection.receive_message() {
+                Ok(DataMessage::Memory(mem)) => return Ok(mem),
+                // Sent by the heartbeat thread of the tracer meanwhile
+                Ok(DataMessage::Heartbeat) => (),
+                // The socket has a read timeout to check if the executor should stop
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => (),
+                Ok(message) => {
+                    let msg = format!("Expected tracee memory, received {:?}", message);
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+                },
+                Err(err) => return Err(err),
+            }
+
+            if self.stop.is_set() {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "The executor stops"));
+            }
+            if Instant::now() >= deadline {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "The tracer does not serve the memory"));
+            }
+        }
+    }
+}
+
+impl MemoryOperation for RemoteMemory {
+
+    fn read(&self, _pid: i32, addr: usize, size: usize) -> Vec<u8>
+    {
+        let mut mem = Vec::with_capacity(std::cmp::min(size, MEMORY_CHUNK_SIZE));
+        while mem.len() < size {
+            let chunk = std::cmp::min(size - mem.len(), MEMORY_CHUNK_SIZE);
+            let address = addr + mem.len();
+            match self.read_chunk(address, chunk) {
+                Ok(content) => {
+                    let complete = content.len() == chunk;
+                    mem.extend(content);
+                    // The rest is not readable either
+                    if ! complete {
+                        break;
+                    }
+                },
+                Err(err) => {
+                    eprintln!("Fail to read {} bytes at {:#x} from the tracer: {}", chunk, address, err);
+                    break;
+                },
+            }
+        }
+        mem.truncate(size);
+        mem
+    }
+
+    fn write(&self, _pid: i32, addr: usize, _mem: Vec<u8>) -> usize
+    {
+        eprintln!("Writing the tracee memory at {:#x} from the executor is not supported", addr);
+        0
     }
 }
\ No newline at end of file
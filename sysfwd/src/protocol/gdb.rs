@@ -0,0 +1,808 @@
+/*
+ * GDB remote serial protocol server, to inspect a tracee with gdb while its syscalls keep being forwarded.
+ *
+ * The packets needed by gdb to debug a single-threaded x86_64 process are supported: registers (g/G),
+ * memory (m/M), software breakpoints (Z0/z0), execution (c/C/s/S and the interrupt) and the memory map
+ * and libraries (qXfer). The other packets get an empty reply, which tells gdb they are not supported.
+ */
+use std::{
+    fs::{ self, File },
+    io::{ self, Read, Write },
+    net::TcpStream,
+    time::Duration,
+};
+
+use nix::{
+    libc::user_regs_struct,
+    sys::signal::Signal,
+    unistd::Pid,
+};
+
+use crate::{
+    arch::{ REGISTER_NAMES, register_mut },
+    memory::MemoryMap,
+    protocol::auth::{ to_hex, from_hex },
+};
+
+
+/* The registers of the 'g' packet, up to gs, the other registers of the amd64 description are unavailable */
+const GDB_REGISTERS: usize = 24;
+/* rax to rip are 64-bit, eflags and the segment registers 32-bit */
+const GDB_WIDE_REGISTERS: usize = 17;
+
+/* How long to wait for a stop before checking for an interrupt from gdb */
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+const INTERRUPT: u8 = 0x03;
+
+/* The largest packet accepted and sent, advertised in hexadecimal in the qSupported reply */
+const PACKET_SIZE: usize = 0x4000;
+
+/* GDB_SIGNAL_UNKNOWN */
+const GDB_SIGNAL_UNKNOWN: u8 = 143;
+
+/*
+ * gdb has its own signal numbers, which differ from the Linux ones past SIGTERM.
+ */
+const GDB_SIGNALS: [(Signal, u8); 30] = [
+    (Signal::SIGHUP, 1), (Signal::SIGINT, 2), (Signal::SIGQUIT, 3), (Signal::SIGILL, 4),
+    (Signal::SIGTRAP, 5), (Signal::SIGABRT, 6), (Signal::SIGFPE, 8), (Signal::SIGKILL, 9),
+    (Signal::SIGBUS, 10), (Signal::SIGSEGV, 11), (Signal::SIGSYS, 12), (Signal::SIGPIPE, 13),
+    (Signal::SIGALRM, 14), (Signal::SIGTERM, 15), (Signal::SIGURG, 16), (Signal::SIGSTOP, 17),
+    (Signal::SIGTSTP, 18), (Signal::SIGCONT, 19), (Signal::SIGCHLD, 20), (Signal::SIGTTIN, 21),
+    (Signal::SIGTTOU, 22), (Signal::SIGIO, 23), (Signal::SIGXCPU, 24), (Signal::SIGXFSZ, 25),
+    (Signal::SIGVTALRM, 26), (Signal::SIGPROF, 27), (Signal::SIGWINCH, 28), (Signal::SIGUSR1, 30),
+    (Signal::SIGUSR2, 31), (Signal::SIGPWR, 32),
+];
+
+fn to_gdb_signal(signal: Signal) -> u8
+{
+    GDB_SIGNALS.iter().find(|(sig, _)| *sig == signal).map_or(GDB_SIGNAL_UNKNOWN, |(_, no)| *no)
+}
+
+fn from_gdb_signal(no: u8) -> Option<Signal>
+{
+    GDB_SIGNALS.iter().find(|(_, n)| *n == no).map(|(sig, _)| *sig)
+}
+
+
+
+/*
+ * Why the tracee stopped, reported to gdb once it resumed it.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StopReason {
+    /* Interrupted, single-stepped or stopped by a signal delivered on resume */
+    Signal(Signal),
+    /* On a software breakpoint, the pc is rewound to its address */
+    Breakpoint,
+    Exited(i32),
+    Terminated(Signal),
+}
+
+impl StopReason {
+
+    fn reply(&self, pid: Pid) -> String
+    {
+        match self {
+            Self::Signal(signal) => format!("T{:02x}thread:{:x};", to_gdb_signal(*signal), pid.as_raw()),
+            Self::Breakpoint => format!("T05swbreak:;thread:{:x};", pid.as_raw()),
+            Self::Exited(status) => format!("W{:02x}", status & 0xff),
+            Self::Terminated(signal) => format!("X{:02x}", to_gdb_signal(*signal)),
+        }
+    }
+
+    fn is_exit(&self) -> bool
+    {
+        matches!(self, Self::Exited(_) | Self::Terminated(_))
+    }
+}
+
+
+/*
+ * The tracee as seen by the server.
+ * The requests other than interrupt are only sent while the tracee is stopped.
+ */
+pub trait GdbTarget {
+    fn pid(&self) -> Pid;
+
+    /* Stop the tracee, the stop is then returned by wait_stop */
+    fn interrupt(&mut self) -> io::Result<()>;
+    fn resume(&mut self, signal: Option<Signal>) -> io::Result<()>;
+    fn step(&mut self, signal: Option<Signal>) -> io::Result<()>;
+    /* None when the tracee is still running after the timeout */
+    fn wait_stop(&mut self, timeout: Duration) -> io::Result<Option<StopReason>>;
+
+    fn read_registers(&mut self) -> io::Result<user_regs_struct>;
+    fn write_registers(&mut self, regs: user_regs_struct) -> io::Result<()>;
+    fn read_memory(&mut self, address: usize, size: usize) -> io::Result<Vec<u8>>;
+    fn write_memory(&mut self, address: usize, data: Vec<u8>) -> io::Result<usize>;
+    fn set_breakpoint(&mut self, address: usize) -> io::Result<()>;
+    fn remove_breakpoint(&mut self, address: usize) -> io::Result<()>;
+
+    /* Resume the tracee without reporting its stops anymore, it is still traced */
+    fn detach(&mut self) -> io::Result<()>;
+    fn kill(&mut self) -> io::Result<()>;
+}
+
+
+
+/*
+ * What to do once a packet is handled.
+ */
+enum Action {
+    Reply(String),
+    /* Wait for the tracee to stop before replying */
+    Resumed,
+    /* Reply and end the session */
+    Close(Option<String>),
+}
+
+/*
+ * A session with a gdb client, serving one tracee.
+ */
+pub struct GdbServer<T: GdbTarget> {
+    target: T,
+    stream: TcpStream,
+    no_ack: bool,
+    /* The last stop, reported to '?' */
+    stop: StopReason,
+}
+
+impl<T: GdbTarget> GdbServer<T> {
+
+    pub fn new(target: T, stream: TcpStream) -> Self
+    {
+        Self {
+            target,
+            stream,
+            no_ack: false,
+            stop: StopReason::Signal(Signal::SIGINT),
+        }
+    }
+
+    /*
+     * Stop the tracee, as gdb expects when connecting, and serve the client until it detaches or disconnects.
+     * The tracee resumes when the connection is lost.
+     */
+    pub fn serve(mut self) -> io::Result<()>
+    {
+        let pid = self.target.pid();
+        println!("GDB client {} debugging {}", self.stream.peer_addr()?, pid);
+
+        self.target.interrupt()?;
+        self.stop = self.wait_stop()?;
+
+        loop {
+            let packet = match self.read_packet()? {
+                Some(packet) => packet,
+                None => {
+                    println!("GDB client of {} disconnected", pid);
+                    if !self.stop.is_exit() {
+                        self.target.detach()?;
+                    }
+                    return Ok(());
+                },
+            };
+
+            match self.handle_packet(&packet)? {
+                Action::Reply(reply) => self.write_packet(reply.as_bytes())?,
+                Action::Resumed => {
+                    self.stop = self.wait_stop()?;
+                    let reply = self.stop.reply(pid);
+                    self.write_packet(reply.as_bytes())?;
+                },
+                Action::Close(reply) => {
+                    if let Some(reply) = reply {
+                        self.write_packet(reply.as_bytes())?;
+                    }
+                    println!("GDB client of {} detached", pid);
+                    return Ok(());
+                },
+            }
+        }
+    }
+
+    /*
+     * Wait for the tracee to stop while the client may interrupt it.
+     */
+    fn wait_stop(&mut self) -> io::Result<StopReason>
+    {
+        self.stream.set_nonblocking(true)?;
+        let result = loop {
+            match self.target.wait_stop(POLL_INTERVAL) {
+                Ok(Some(stop)) => break Ok(stop),
+                Ok(None) => { },
+                Err(err) => break Err(err),
+            }
+
+            let mut byte = [0u8; 1];
+            match self.stream.read(&mut byte) {
+                Ok(0) => break Err(io::Error::new(io::ErrorKind::ConnectionAborted, "GDB client disconnected")),
+                Ok(_) if byte[0] == INTERRUPT => {
+                    if let Err(err) = self.target.interrupt() {
+                        break Err(err);
+                    }
+                },
+                // Acknowledgments
+                Ok(_) => { },
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => { },
+                Err(err) => break Err(err),
+            }
+        };
+        self.stream.set_nonblocking(false)?;
+        result
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>>
+    {
+        let mut byte = [0u8; 1];
+        match self.stream.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    /*
+     * Read the next packet "$data#checksum", acknowledged unless the no-ack mode is on.
+     * A packet longer than PACKET_SIZE is dropped and rejected. None when the connection is closed.
+     */
+    fn read_packet(&mut self) -> io::Result<Option<String>>
+    {
+        loop {
+            // Skip the acknowledgments and the interrupts sent while the tracee is already stopped
+            match self.read_byte()? {
+                Some(b'$') => { },
+                Some(_) => continue,
+                None => return Ok(None),
+            }
+
+            let mut data = Vec::new();
+            let mut sum: u8 = 0;
+            loop {
+                match self.read_byte()? {
+                    Some(b'#') => break,
+                    Some(byte) => {
+                        sum = sum.wrapping_add(byte);
+                        if data.len() <= PACKET_SIZE {
+                            data.push(byte);
+                        }
+                    },
+                    None => return Ok(None),
+                }
+            }
+
+            let mut checksum = [0u8; 2];
+            self.stream.read_exact(&mut checksum)?;
+            let checksum = std::str::from_utf8(&checksum).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+            let valid = checksum == Some(sum) && data.len() <= PACKET_SIZE;
+            if !self.no_ack {
+                let ack: &[u8] = if valid { b"+" } else { b"-" };
+                self.stream.write_all(ack)?;
+            }
+            if valid {
+                return Ok(Some(String::from_utf8_lossy(&unescape(&data)).into_owned()));
+            }
+        }
+    }
+
+    /*
+     * Over TCP the acknowledgments of the client are not waited for, they are skipped when reading.
+     */
+    fn write_packet(&mut self, data: &[u8]) -> io::Result<()>
+    {
+        let sum = data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        let mut packet = Vec::with_capacity(data.len() + 4);
+        packet.push(b'$');
+        packet.extend_from_slice(data);
+        packet.extend_from_slice(format!("#{:02x}", sum).as_bytes());
+        self.stream.write_all(&packet)
+    }
+
+    fn handle_packet(&mut self, packet: &str) -> io::Result<Action>
+    {
+        let pid = self.target.pid();
+        let (command, args) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+
+        let reply = match command {
+            "?" => self.stop.reply(pid),
+            "g" => self.read_registers(),
+            "G" => self.write_registers(args),
+            "m" => self.read_memory(args),
+            "M" => self.write_memory(args),
+            "Z" => self.breakpoint(args, true),
+            "z" => self.breakpoint(args, false),
+            "c" => return self.resume(None, args, false),
+            "s" => return self.resume(None, args, true),
+            "C" | "S" => {
+                let (signal, address) = args.split_once(';').unwrap_or((args, ""));
+                let signal = match u8::from_str_radix(signal, 16) {
+                    Ok(signal) => from_gdb_signal(signal),
+                    Err(_) => return Ok(Action::Reply(String::from("E01"))),
+                };
+                return self.resume(signal, address, command == "S");
+            },
+            "D" => {
+                self.target.detach()?;
+                return Ok(Action::Close(Some(String::from("OK"))));
+            },
+            "k" => {
+                self.target.kill()?;
+                return Ok(Action::Close(None));
+            },
+            // Single thread, the thread of the tracee
+            "H" | "T" => String::from("OK"),
+            "q" | "Q" | "v" => return self.handle_query(packet),
+            _ => String::new(),
+        };
+        Ok(Action::Reply(reply))
+    }
+
+    fn handle_query(&mut self, packet: &str) -> io::Result<Action>
+    {
+        let pid = self.target.pid();
+
+        let reply = if packet.starts_with("qSupported") {
+            format!("PacketSize={:x};QStartNoAckMode+;swbreak+;qXfer:memory-map:read+;qXfer:libraries:read+", PACKET_SIZE)
+        } else if packet == "QStartNoAckMode" {
+            // The packet itself is acknowledged, not the following ones
+            self.no_ack = true;
+            String::from("OK")
+        } else if packet.starts_with("qAttached") {
+            // gdb detaches instead of killing the tracee when quitting
+            String::from("1")
+        } else if packet == "qC" {
+            format!("QC{:x}", pid.as_raw())
+        } else if packet == "qfThreadInfo" {
+            format!("m{:x}", pid.as_raw())
+        } else if packet == "qsThreadInfo" {
+            String::from("l")
+        } else if packet == "qSymbol::" {
+            String::from("OK")
+        } else if let Some(args) = packet.strip_prefix("qXfer:memory-map:read::") {
+            self.transfer(args, Self::memory_map)
+        } else if let Some(args) = packet.strip_prefix("qXfer:libraries:read::") {
+            self.transfer(args, Self::libraries)
+        } else if packet.starts_with("vKill") {
+            self.target.kill()?;
+            return Ok(Action::Close(Some(String::from("OK"))));
+        } else {
+            String::new()
+        };
+        Ok(Action::Reply(reply))
+    }
+
+    fn resume(&mut self, signal: Option<Signal>, address: &str, step: bool) -> io::Result<Action>
+    {
+        if !address.is_empty() {
+            let address = match u64::from_str_radix(address, 16) {
+                Ok(address) => address,
+                Err(_) => return Ok(Action::Reply(String::from("E01"))),
+            };
+            let mut regs = self.target.read_registers()?;
+            regs.rip = address;
+            self.target.write_registers(regs)?;
+        }
+
+        match step {
+            true => self.target.step(signal)?,
+            false => self.target.resume(signal)?,
+        }
+        Ok(Action::Resumed)
+    }
+
+    /*
+     * g -> the registers in the order of the amd64 description, little-endian.
+     */
+    fn read_registers(&mut self) -> String
+    {
+        let mut regs = match self.target.read_registers() {
+            Ok(regs) => regs,
+            Err(err) => return error_reply(&err),
+        };
+
+        REGISTER_NAMES[..GDB_REGISTERS].iter().enumerate()
+            .map(|(i, name)| {
+                let value = *register_mut(&mut regs, name).unwrap();
+                match i < GDB_WIDE_REGISTERS {
+                    true => to_hex(&value.to_le_bytes()),
+                    false => to_hex(&(value as u32).to_le_bytes()),
+                }
+            })
+            .collect()
+    }
+
+    /*
+     * G<registers> -> OK, the registers missing at the end are left untouched.
+     */
+    fn write_registers(&mut self, args: &str) -> String
+    {
+        let data = match from_hex(args) {
+            Some(data) => data,
+            None => return String::from("E01"),
+        };
+        let mut regs = match self.target.read_registers() {
+            Ok(regs) => regs,
+            Err(err) => return error_reply(&err),
+        };
+
+        let mut data = data.as_slice();
+        for (i, name) in REGISTER_NAMES[..GDB_REGISTERS].iter().enumerate() {
+            let size = if i < GDB_WIDE_REGISTERS { 8 } else { 4 };
+            if data.len() < size {
+                break;
+            }
+            let (value, rest) = data.split_at(size);
+            let mut bytes = [0u8; 8];
+            bytes[..size].copy_from_slice(value);
+            *register_mut(&mut regs, name).unwrap() = u64::from_le_bytes(bytes);
+            data = rest;
+        }
+
+        match self.target.write_registers(regs) {
+            Ok(()) => String::from("OK"),
+            Err(err) => error_reply(&err),
+        }
+    }
+
+    /*
+     * m<address>,<length> -> the bytes read, possibly less than asked.
+     * The length is clamped to what fits hex-encoded in a packet.
+     */
+    fn read_memory(&mut self, args: &str) -> String
+    {
+        let (address, size) = match parse_range(args) {
+            Some((address, size)) => (address, size.min((PACKET_SIZE - 4) / 2)),
+            None => return String::from("E01"),
+        };
+        match self.target.read_memory(address, size) {
+            Ok(mem) if mem.is_empty() && size > 0 => String::from("E14"),
+            Ok(mem) => to_hex(&mem),
+            Err(err) => error_reply(&err),
+        }
+    }
+
+    /*
+     * M<address>,<length>:<bytes> -> OK
+     */
+    fn write_memory(&mut self, args: &str) -> String
+    {
+        let (range, data) = match args.split_once(':') {
+            Some(split) => split,
+            None => return String::from("E01"),
+        };
+        let (address, data) = match (parse_range(range), from_hex(data)) {
+            (Some((address, size)), Some(data)) if data.len() == size => (address, data),
+            _ => return String::from("E01"),
+        };
+
+        let size = data.len();
+        match self.target.write_memory(address, data) {
+            Ok(written) if written == size => String::from("OK"),
+            Ok(_) => String::from("E14"),
+            Err(err) => error_reply(&err),
+        }
+    }
+
+    /*
+     * Z0,<address>,<kind> and z0,<address>,<kind>, only software breakpoints.
+     */
+    fn breakpoint(&mut self, args: &str, insert: bool) -> String
+    {
+        let mut fields = args.split(',');
+        if fields.next() != Some("0") {
+            return String::new();
+        }
+        let address = match fields.next().and_then(|address| usize::from_str_radix(address, 16).ok()) {
+            Some(address) => address,
+            None => return String::from("E01"),
+        };
+
+        let result = match insert {
+            true => self.target.set_breakpoint(address),
+            false => self.target.remove_breakpoint(address),
+        };
+        match result {
+            Ok(()) => String::from("OK"),
+            Err(err) => error_reply(&err),
+        }
+    }
+
+    /*
+     * qXfer:<object>:read::<offset>,<length> -> the part of the document, 'l' when it is the last one.
+     */
+    fn transfer(&mut self, args: &str, document: fn(&mut Self) -> io::Result<String>) -> String
+    {
+        let (offset, length) = match parse_range(args) {
+            Some(range) => range,
+            None => return String::from("E01"),
+        };
+        let document = match document(self) {
+            Ok(document) => document,
+            Err(err) => return error_reply(&err),
+        };
+
+        let document = document.as_bytes();
+        let start = offset.min(document.len());
+        let end = offset.saturating_add(length).min(document.len());
+        let marker = if end < document.len() { 'm' } else { 'l' };
+        format!("{}{}", marker, String::from_utf8_lossy(&escape(&document[start..end])))
+    }
+
+    fn memory_map(&mut self) -> io::Result<String>
+    {
+        let map = MemoryMap::from_pid(self.target.pid().as_raw())?;
+
+        let mut xml = String::from("<?xml version=\"1.0\"?>\n\
+            <!DOCTYPE memory-map PUBLIC \"+//IDN gnu.org//DTD GDB Memory Map V1.0//EN\" \
+            \"http://sourceware.org/gdb/gdb-memory-map.dtd\">\n<memory-map>\n");
+        for region in map.regions.iter() {
+            xml.push_str(&format!("  <memory type=\"ram\" start=\"{:#x}\" length=\"{:#x}\"/>\n", region.start, region.size()));
+        }
+        xml.push_str("</memory-map>\n");
+        Ok(xml)
+    }
+
+    /*
+     * The ELF files mapped by the tracee other than its executable, at the address of their first segment.
+     */
+    fn libraries(&mut self) -> io::Result<String>
+    {
+        let pid = self.target.pid().as_raw();
+        let map = MemoryMap::from_pid(pid)?;
+        let executable = fs::read_link(format!("/proc/{}/exe", pid))?;
+
+        let mut xml = String::from("<library-list>\n");
+        for region in map.regions.iter().filter(|region| region.offset == 0 && region.pathname.starts_with('/')) {
+            if region.pathname == executable.to_string_lossy() || !is_elf(&region.pathname) {
+                continue;
+            }
+            xml.push_str(&format!("  <library name=\"{}\"><segment address=\"{:#x}\"/></library>\n",
+                                  escape_xml(&region.pathname), region.start));
+        }
+        xml.push_str("</library-list>\n");
+        Ok(xml)
+    }
+}
+
+
+/*
+ * <address>,<length> in hexadecimal.
+ */
+fn parse_range(args: &str) -> Option<(usize, usize)>
+{
+    let (address, length) = args.split_once(',')?;
+    Some((usize::from_str_radix(address, 16).ok()?, usize::from_str_radix(length, 16).ok()?))
+}
+
+fn error_reply(err: &io::Error) -> String
+{
+    format!("E{:02x}", err.raw_os_error().unwrap_or(1) & 0xff)
+}
+
+/*
+ * In binary data '#', '$', '}' and '*' are escaped with '}' followed by the byte xor 0x20.
+ */
+fn escape(data: &[u8]) -> Vec<u8>
+{
+    let mut escaped = Vec::with_capacity(data.len());
+    for byte in data {
+        match byte {
+            b'#' | b'$' | b'}' | b'*' => escaped.extend_from_slice(&[b'}', byte ^ 0x20]),
+            _ => escaped.push(*byte),
+        }
+    }
+    escaped
+}
+
+fn unescape(data: &[u8]) -> Vec<u8>
+{
+    let mut unescaped = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'}' => unescaped.extend(bytes.next().map(|byte| byte ^ 0x20)),
+            _ => unescaped.push(*byte),
+        }
+    }
+    unescaped
+}
+
+fn escape_xml(text: &str) -> String
+{
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn is_elf(pathname: &str) -> bool
+{
+    let mut magic = [0u8; 4];
+    File::open(pathname).and_then(|mut file| file.read_exact(&mut magic)).is_ok() && &magic == b"\x7fELF"
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        net::TcpListener,
+        sync::{ Arc, atomic::{ AtomicBool, Ordering } },
+        thread,
+    };
+
+    const BASE: usize = 0x1000;
+
+    /*
+     * A stopped tracee with a few bytes of memory at BASE.
+     */
+    struct FakeTarget {
+        memory: Vec<u8>,
+        detached: Arc<AtomicBool>,
+    }
+
+    impl GdbTarget for FakeTarget {
+        fn pid(&self) -> Pid { Pid::from_raw(42) }
+        fn interrupt(&mut self) -> io::Result<()> { Ok(()) }
+        fn resume(&mut self, _signal: Option<Signal>) -> io::Result<()> { Ok(()) }
+        fn step(&mut self, _signal: Option<Signal>) -> io::Result<()> { Ok(()) }
+        fn wait_stop(&mut self, _timeout: Duration) -> io::Result<Option<StopReason>>
+        {
+            Ok(Some(StopReason::Signal(Signal::SIGTRAP)))
+        }
+
+        fn read_registers(&mut self) -> io::Result<user_regs_struct>
+        {
+            Err(io::Error::from_raw_os_error(nix::libc::ESRCH))
+        }
+        fn write_registers(&mut self, _regs: user_regs_struct) -> io::Result<()> { Ok(()) }
+        fn read_memory(&mut self, address: usize, size: usize) -> io::Result<Vec<u8>>
+        {
+            let start = address.saturating_sub(BASE).min(self.memory.len());
+            let end = (start + size).min(self.memory.len());
+            Ok(self.memory[start..end].to_vec())
+        }
+        fn write_memory(&mut self, address: usize, data: Vec<u8>) -> io::Result<usize>
+        {
+            let start = address - BASE;
+            self.memory[start..start + data.len()].copy_from_slice(&data);
+            Ok(data.len())
+        }
+        fn set_breakpoint(&mut self, _address: usize) -> io::Result<()> { Ok(()) }
+        fn remove_breakpoint(&mut self, _address: usize) -> io::Result<()> { Ok(()) }
+        fn detach(&mut self) -> io::Result<()>
+        {
+            self.detached.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+        fn kill(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    fn packet(data: &str) -> Vec<u8>
+    {
+        let sum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        format!("${}#{:02x}", data, sum).into_bytes()
+    }
+
+    fn connected() -> (TcpStream, TcpStream)
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        (server, client)
+    }
+
+    fn read_exact(stream: &mut TcpStream, size: usize) -> String
+    {
+        let mut data = vec![0u8; size];
+        stream.read_exact(&mut data).unwrap();
+        String::from_utf8(data).unwrap()
+    }
+
+    #[test]
+    fn packets_are_acknowledged_by_checksum()
+    {
+        let (server, mut client) = connected();
+        let mut server = GdbServer::new(FakeTarget { memory: Vec::new(), detached: Arc::default() }, server);
+
+        // Leading acknowledgments and interrupts are skipped, a bad checksum is nacked
+        client.write_all(b"+\x03$m1000,4#00").unwrap();
+        client.write_all(&packet("m1000,4")).unwrap();
+        assert_eq!(server.read_packet().unwrap().as_deref(), Some("m1000,4"));
+        assert_eq!(read_exact(&mut client, 2), "-+");
+
+        server.no_ack = true;
+        client.write_all(&packet("X1000,2:}]}\x03")).unwrap();
+        assert_eq!(server.read_packet().unwrap().as_deref(), Some("X1000,2:}#"));
+
+        drop(client);
+        assert_eq!(server.read_packet().unwrap(), None);
+    }
+
+    #[test]
+    fn oversized_packets_are_dropped()
+    {
+        let (server, mut client) = connected();
+        let mut server = GdbServer::new(FakeTarget { memory: Vec::new(), detached: Arc::default() }, server);
+
+        let oversized = format!("M1000,{:x}:{}", PACKET_SIZE, "00".repeat(PACKET_SIZE));
+        client.write_all(&packet(&oversized)).unwrap();
+        client.write_all(&packet("?")).unwrap();
+        assert_eq!(server.read_packet().unwrap().as_deref(), Some("?"));
+        assert_eq!(read_exact(&mut client, 2), "-+");
+    }
+
+    #[test]
+    fn memory_reads_fit_in_a_packet()
+    {
+        let (server, _client) = connected();
+        let target = FakeTarget { memory: vec![0u8; PACKET_SIZE], detached: Arc::default() };
+        let mut server = GdbServer::new(target, server);
+
+        let reply = server.read_memory(&format!("1000,{:x}", PACKET_SIZE));
+        assert_eq!(reply.len(), PACKET_SIZE - 4);
+    }
+
+    #[test]
+    fn replies_are_framed_with_their_checksum()
+    {
+        let (server, mut client) = connected();
+        let mut server = GdbServer::new(FakeTarget { memory: Vec::new(), detached: Arc::default() }, server);
+
+        server.write_packet(b"OK").unwrap();
+        server.write_packet(b"").unwrap();
+        assert_eq!(read_exact(&mut client, 10), "$OK#9a$#00");
+    }
+
+    #[test]
+    fn session_until_detach()
+    {
+        let (server, mut client) = connected();
+        let detached = Arc::new(AtomicBool::new(false));
+        let target = FakeTarget { memory: b"\x01\x02\x03\x04".to_vec(), detached: detached.clone() };
+        let session = thread::spawn(move || GdbServer::new(target, server).serve());
+
+        let mut exchange = |request: &str, reply: &str| {
+            client.write_all(&packet(request)).unwrap();
+            let expected = format!("+{}", String::from_utf8(packet(reply)).unwrap());
+            assert_eq!(read_exact(&mut client, expected.len()), expected, "reply to {}", request);
+        };
+        exchange("?", "T05thread:2a;");
+        exchange("m1000,8", "01020304");
+        exchange("m2000,1", "E14");
+        exchange("M1001,2:aabb", "OK");
+        exchange("m1000,4", "01aabb04");
+        exchange("g", "E03");
+        exchange("Hg0", "OK");
+        exchange("vMustReplyEmpty", "");
+        exchange("D", "OK");
+
+        session.join().unwrap().unwrap();
+        assert!(detached.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn binary_data_escaping()
+    {
+        let data = b"a#b$c}d*e";
+        assert_eq!(escape(data), b"a}\x03b}\x04c}]d}\x0ae".to_vec());
+        assert_eq!(unescape(&escape(data)), data.to_vec());
+    }
+
+    #[test]
+    fn signals_and_stop_replies()
+    {
+        assert_eq!(to_gdb_signal(Signal::SIGUSR1), 30);
+        assert_eq!(from_gdb_signal(30), Some(Signal::SIGUSR1));
+        assert_eq!(to_gdb_signal(Signal::SIGSTKFLT), GDB_SIGNAL_UNKNOWN);
+        assert_eq!(from_gdb_signal(GDB_SIGNAL_UNKNOWN), None);
+
+        let pid = Pid::from_raw(0x2a);
+        assert_eq!(StopReason::Breakpoint.reply(pid), "T05swbreak:;thread:2a;");
+        assert_eq!(StopReason::Exited(256 + 3).reply(pid), "W03");
+        assert_eq!(StopReason::Terminated(Signal::SIGKILL).reply(pid), "X09");
+        assert_eq!(parse_range("7fff0000,10"), Some((0x7fff0000, 0x10)));
+        assert_eq!(parse_range("7fff0000"), None);
+    }
+}
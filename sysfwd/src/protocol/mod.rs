@@ -8,8 +8,28 @@
  */
 pub mod control;
 
+/*
+ * JSON-RPC 2.0 messages of the control channel.
+ */
+pub mod rpc;
+
 /*
  * The data channel is used between tracing and executor threads to exchange syscall data with UDP.
  */
 pub mod data;
 
+/*
+ * Alternative transport for the data channel when the tracer and the executor are on the same host.
+ */
+pub mod shm;
+
+/*
+ * GDB remote serial protocol server, giving gdb access to a tracee through its tracer.
+ */
+pub mod gdb;
+
+
+/*
+ * Optional authentication of the data and control channels with a pre-shared key.
+ */
+pub mod auth;
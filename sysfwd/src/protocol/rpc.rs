@@ -0,0 +1,457 @@
+/*
+ *
+ */
+use std::collections::BTreeMap;
+
+use serde::{ Serialize, Deserialize, de::DeserializeOwned };
+use serde_json::Value;
+
+use crate::{
+    protocol::control::Configuration,
+    tracer::filtering::{ Decision, RuleInfo },
+};
+
+
+/*
+ * JSON-RPC 2.0 messages of the control channel, one message per line.
+ *
+ * The client sends requests {"jsonrpc": "2.0", "id": 1, "method": "read_regs", "params": {"pid": 42}}
+ * and receives a response with the same id, either {"result": ...} or {"error": {"code": ..., "message": ...}}.
+ * The debugger sends notifications, without id, to report asynchronous events such as breakpoint hits.
+ */
+pub const JSONRPC_VERSION: &str = "2.0";
+
+/* Error codes defined by the specification */
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+/* Error codes of the debugger */
+pub const OPERATION_FAILED: i64 = -32000;
+pub const NOT_SUPPORTED: i64 = -32001;
+pub const AUTHENTICATION_FAILED: i64 = -32002;
+
+
+
+#[derive(Deserialize, Debug)]
+pub struct Request {
+    pub jsonrpc: String,
+    /* A request without id is a notification and gets no response */
+    #[serde(default)]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(flatten)]
+    outcome: Outcome,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "lowercase")]
+enum Outcome {
+    Result(Value),
+    Error(RpcError),
+}
+
+impl Response {
+
+    pub fn result(id: Value, result: Value) -> Self
+    {
+        Self { jsonrpc: JSONRPC_VERSION, id, outcome: Outcome::Result(result) }
+    }
+
+    pub fn error(id: Value, error: RpcError) -> Self
+    {
+        Self { jsonrpc: JSONRPC_VERSION, id, outcome: Outcome::Error(error) }
+    }
+
+    /*
+     * The response serialized on a single line, ending with a newline.
+     */
+    pub fn to_line(&self) -> String
+    {
+        to_line(self)
+    }
+}
+
+fn to_line<T: Serialize>(message: &T) -> String
+{
+    let mut line = serde_json::to_string(message).unwrap_or_else(|err| {
+        // Only happens with maps whose keys are not strings
+        let error = RpcError::new(INTERNAL_ERROR, format!("Fail to serialize message: {}", err));
+        serde_json::to_string(&Response::error(Value::Null, error)).unwrap()
+    });
+    line.push('\n');
+    line
+}
+
+
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+
+    pub fn new(code: i64, message: String) -> Self
+    {
+        Self { code, message }
+    }
+
+    pub fn invalid_params(message: String) -> Self
+    {
+        Self::new(INVALID_PARAMS, message)
+    }
+
+    pub fn method_not_found(method: &str) -> Self
+    {
+        Self::new(METHOD_NOT_FOUND, format!("Method not found: {}", method))
+    }
+
+    pub fn failed(message: String) -> Self
+    {
+        Self::new(OPERATION_FAILED, message)
+    }
+
+    pub fn not_supported(message: String) -> Self
+    {
+        Self::new(NOT_SUPPORTED, message)
+    }
+}
+
+/*
+ * Parse a request line.
+ * On error, the id to answer with, null when the request has none or is not even JSON.
+ */
+pub fn parse_request(message: &str) -> Result<Request, (Value, RpcError)>
+{
+    let message: Value = serde_json::from_str(message).map_err(|err| {
+        (Value::Null, RpcError::new(PARSE_ERROR, format!("Parse error: {}", err)))
+    })?;
+
+    // Answer invalid requests with their id when it can be found
+    let id = message.get("id").cloned().unwrap_or(Value::Null);
+    let request: Request = serde_json::from_value(message).map_err(|err| {
+        (id.clone(), RpcError::new(INVALID_REQUEST, format!("Invalid request: {}", err)))
+    })?;
+    if request.jsonrpc != JSONRPC_VERSION {
+        let error = RpcError::new(INVALID_REQUEST, format!("Unsupported jsonrpc version {:?}", request.jsonrpc));
+        return Err((id, error));
+    }
+    Ok(request)
+}
+
+/*
+ * Deserialize the params of a request into the typed params of its method.
+ */
+pub fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError>
+{
+    serde_json::from_value(params).map_err(|err| RpcError::invalid_params(format!("Invalid params: {}", err)))
+}
+
+
+
+/*
+ * Params of the methods.
+ * Addresses and sizes are numbers, memory contents are hexadecimal strings.
+ */
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PidParams {
+    pub pid: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SpawnProcessParams {
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ContTracingParams {
+    pub pid: i32,
+    /* Signal delivered to the process, 0 for none */
+    #[serde(default)]
+    pub signal: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReadMemoryParams {
+    pub pid: i32,
+    pub address: usize,
+    pub size: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WriteMemoryParams {
+    pub pid: i32,
+    pub address: usize,
+    pub data: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WriteRegistersParams {
+    pub pid: i32,
+    /* Only the given registers are modified */
+    pub registers: BTreeMap<String, u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BreakpointParams {
+    pub pid: i32,
+    pub address: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SwitchParams {
+    /* The processes taken along to the new role */
+    #[serde(default)]
+    pub pids: Vec<i32>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GdbServerParams {
+    pub pid: i32,
+    /* Any free port when missing */
+    #[serde(default)]
+    pub port: u16,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LoadRuleParams {
+    pub pid: i32,
+    /* Lowest priority when missing */
+    #[serde(default)]
+    pub index: Option<usize>,
+    pub name: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UnloadRuleParams {
+    pub pid: i32,
+    pub index: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MoveRuleParams {
+    pub pid: i32,
+    pub from: usize,
+    pub to: usize,
+}
+
+
+/*
+ * Results of the methods, the methods without result answer null.
+ */
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SpawnProcessResult {
+    pub pid: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReadMemoryResult {
+    pub data: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SwitchResult {
+    /* The new role of the debugger */
+    pub configuration: Configuration,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GdbServerResult {
+    /* The port to give to gdb "target remote" */
+    pub port: u16,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListRulesResult {
+    /* The rules of the tracee by priority */
+    pub rules: Vec<RuleInfo>,
+    /* The names of the rules which can be loaded */
+    pub available: Vec<String>,
+}
+
+
+/*
+ * The events reported by the debuggers as notifications, the method being the name of the event.
+ * A control client only receives the events it subscribed to.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum Event {
+    /* The tracee stays stopped until cont_tracing */
+    Breakpoint {
+        pid: i32,
+        address: usize,
+        registers: BTreeMap<String, u64>,
+    },
+    /* Either the exit status or the signal which killed the process */
+    ProcessExited {
+        pid: i32,
+        status: Option<i32>,
+        signal: Option<String>,
+    },
+    /* The signal is delivered to the process when it resumes */
+    SignalReceived {
+        pid: i32,
+        signal: String,
+    },
+    SyscallForwarded {
+        pid: i32,
+        name: String,
+        no: usize,
+        retval: isize,
+        pipelined: bool,
+    },
+    /* A rule took the decision on the entry of a syscall */
+    RuleFired {
+        pid: i32,
+        index: usize,
+        rule: String,
+        syscall: String,
+        decision: Decision,
+    },
+    ExecutorDisconnected {
+        pid: i32,
+        reason: String,
+    },
+    /* The policy of the executor, or the tracer for a process it cannot trace, refused the syscall, which returned EPERM */
+    SyscallRefused {
+        pid: i32,
+        name: String,
+        no: usize,
+        reason: String,
+    },
+    /* The syscall does not match the recording replayed by the executor */
+    ReplayDiverged {
+        pid: i32,
+        name: String,
+        no: usize,
+        reason: String,
+    },
+}
+
+impl Event {
+
+    pub const NAMES: [&'static str; 8] = [
+        "breakpoint", "process_exited", "signal_received",
+        "syscall_forwarded", "rule_fired", "executor_disconnected",
+        "syscall_refused", "replay_diverged",
+    ];
+
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            Self::Breakpoint { .. } => "breakpoint",
+            Self::ProcessExited { .. } => "process_exited",
+            Self::SignalReceived { .. } => "signal_received",
+            Self::SyscallForwarded { .. } => "syscall_forwarded",
+            Self::RuleFired { .. } => "rule_fired",
+            Self::ExecutorDisconnected { .. } => "executor_disconnected",
+            Self::SyscallRefused { .. } => "syscall_refused",
+            Self::ReplayDiverged { .. } => "replay_diverged",
+        }
+    }
+
+    /*
+     * The event as a JSON-RPC notification on a single line.
+     */
+    pub fn to_line(&self) -> String
+    {
+        #[derive(Serialize)]
+        struct EventNotification<'a> {
+            jsonrpc: &'static str,
+            #[serde(flatten)]
+            event: &'a Event,
+        }
+        to_line(&EventNotification { jsonrpc: JSONRPC_VERSION, event: self })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SubscribeParams {
+    /* Event names, "*" for all of them */
+    pub events: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SubscribeResult {
+    /* The events now subscribed to */
+    pub events: Vec<String>,
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_request_with_params()
+    {
+        let request = parse_request(r#"{"jsonrpc": "2.0", "id": 7, "method": "read_regs", "params": {"pid": 42}}"#).unwrap();
+        assert_eq!(request.id, Some(json!(7)));
+        assert_eq!(request.method, "read_regs");
+        let params: PidParams = parse_params(request.params).unwrap();
+        assert_eq!(params.pid, 42);
+    }
+
+    #[test]
+    fn parse_notification_without_params()
+    {
+        let request = parse_request(r#"{"jsonrpc": "2.0", "method": "list_processes"}"#).unwrap();
+        assert_eq!(request.id, None);
+        assert_eq!(request.params, Value::Null);
+    }
+
+    #[test]
+    fn parse_errors_keep_the_id_when_found()
+    {
+        let (id, error) = parse_request("{not json").unwrap_err();
+        assert_eq!((id, error.code), (Value::Null, PARSE_ERROR));
+
+        let (id, error) = parse_request(r#"{"jsonrpc": "2.0", "id": "a", "params": {}}"#).unwrap_err();
+        assert_eq!((id, error.code), (json!("a"), INVALID_REQUEST));
+
+        let (id, error) = parse_request(r#"{"jsonrpc": "1.0", "id": 3, "method": "read_regs"}"#).unwrap_err();
+        assert_eq!((id, error.code), (json!(3), INVALID_REQUEST));
+    }
+
+    #[test]
+    fn invalid_params()
+    {
+        let error = parse_params::<ReadMemoryParams>(json!({"pid": 1, "address": "0x10"})).unwrap_err();
+        assert_eq!(error.code, INVALID_PARAMS);
+    }
+
+    #[test]
+    fn response_and_event_lines()
+    {
+        let line = Response::error(json!(1), RpcError::method_not_found("foo")).to_line();
+        assert!(line.ends_with('\n') && !line.trim_end().contains('\n'));
+        let response: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(response, json!({"jsonrpc": "2.0", "id": 1, "error": {"code": METHOD_NOT_FOUND, "message": "Method not found: foo"}}));
+
+        let event = Event::SignalReceived { pid: 5, signal: "SIGUSR1".to_string() };
+        let notification: Value = serde_json::from_str(&event.to_line()).unwrap();
+        assert_eq!(notification, json!({"jsonrpc": "2.0", "method": "signal_received", "params": {"pid": 5, "signal": "SIGUSR1"}}));
+        assert!(Event::NAMES.contains(&event.name()));
+    }
+}
@@ -0,0 +1,796 @@
+/*
+ *
+ */
+use std::{
+    cell::RefCell,
+    ffi::CString,
+    io::{ self, IoSlice, IoSliceMut },
+    os::unix::{
+        io::{ AsRawFd, RawFd },
+        net::{ UnixListener, UnixStream },
+    },
+    sync::{
+        Arc, Mutex,
+        atomic::{ AtomicBool, AtomicUsize, Ordering },
+    },
+    thread,
+    time::{ Duration, Instant },
+};
+
+use nix::{
+    cmsg_space,
+    errno::Errno,
+    poll::{ poll, PollFd, PollFlags },
+    sys::{
+        eventfd::{ eventfd, EfdFlags },
+        memfd::{ memfd_create, MemFdCreateFlag },
+        mman::{ mmap, munmap, MapFlags, ProtFlags },
+        socket::{ sendmsg, recvmsg, ControlMessage, ControlMessageOwned, MsgFlags, UnixAddr },
+    },
+    unistd::{ close, ftruncate, read, write },
+};
+
+use crate::protocol::data::{ DataMessage, Transport };
+
+
+/*
+ * Layout of the shared region: two rings, one for each direction.
+ * Each ring starts with its producer and consumer counters, on separate cache lines,
+ * followed by the data. The counters only grow, the position in the ring is the counter modulo the capacity.
+ */
+const RING_HEADER_SIZE: usize = 128;
+const RING_TAIL_OFFSET: usize = 64;
+const RING_CAPACITY: usize = 1 << 20;
+const RING_SIZE: usize = RING_HEADER_SIZE + RING_CAPACITY;
+const REGION_SIZE: usize = 2 * RING_SIZE;
+
+/*
+ * Each message starts with two headers: the size of its JSON and the size of its blobs,
+ * the content of the large buffers written before the JSON.
+ */
+const HEADER_SIZE: usize = 8;
+
+/* Ring written by the tracer (client) and read by the executor (server), and conversely */
+const CLIENT_TO_SERVER: usize = 0;
+const SERVER_TO_CLIENT: usize = 1;
+
+/* How long the tracer waits for the executor to listen on the socket */
+const CONNECT_TIMEOUT: Duration = Duration::new(10, 0);
+
+/* How long a sender waits for the other side to make room in a full ring */
+const SEND_TIMEOUT: Duration = Duration::new(10, 0);
+
+
+fn to_io_error(errno: Errno) -> io::Error
+{
+    io::Error::from_raw_os_error(errno as i32)
+}
+
+
+
+/*
+ * The memfd shared by the tracer and the executor, with one eventfd per ring to signal new messages.
+ */
+struct SharedRegion {
+    base: *mut u8,
+    memfd: RawFd,
+    events: [RawFd; 2],
+}
+
+/* The region is only accessed through the atomic counters of the rings */
+unsafe impl Send for SharedRegion {}
+unsafe impl Sync for SharedRegion {}
+
+impl SharedRegion {
+
+    fn create() -> io::Result<Self>
+    {
+        let name = CString::new("sysfwd").unwrap();
+        let memfd = memfd_create(&name, MemFdCreateFlag::MFD_CLOEXEC).map_err(to_io_error)?;
+        ftruncate(memfd, REGION_SIZE as i64).map_err(to_io_error)?;
+
+        let event0 = eventfd(0, EfdFlags::EFD_CLOEXEC).map_err(to_io_error)?;
+        let event1 = eventfd(0, EfdFlags::EFD_CLOEXEC).map_err(to_io_error)?;
+
+        Self::map(memfd, [event0, event1])
+    }
+
+    fn map(memfd: RawFd, events: [RawFd; 2]) -> io::Result<Self>
+    {
+        let base = unsafe {
+            mmap(std::ptr::null_mut(),
+                 REGION_SIZE,
+                 ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                 MapFlags::MAP_SHARED,
+                 memfd,
+                 0)
+        }.map_err(to_io_error)?;
+
+        Ok(Self { base: base as *mut u8, memfd, events })
+    }
+
+    fn ring(&self, index: usize) -> *mut u8
+    {
+        unsafe { self.base.add(index * RING_SIZE) }
+    }
+
+    fn head(&self, index: usize) -> &AtomicUsize
+    {
+        unsafe { &*(self.ring(index) as *const AtomicUsize) }
+    }
+
+    fn tail(&self, index: usize) -> &AtomicUsize
+    {
+        unsafe { &*(self.ring(index).add(RING_TAIL_OFFSET) as *const AtomicUsize) }
+    }
+
+    /*
+     * Copy data into the ring at the given counter, wrapping around the end of the ring.
+     */
+    fn write_bytes(&self, index: usize, counter: usize, data: &[u8])
+    {
+        let data_start = unsafe { self.ring(index).add(RING_HEADER_SIZE) };
+        let position = counter % RING_CAPACITY;
+        let first = data.len().min(RING_CAPACITY - position);
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), data_start.add(position), first);
+            std::ptr::copy_nonoverlapping(data[first..].as_ptr(), data_start, data.len() - first);
+        }
+    }
+
+    fn read_bytes(&self, index: usize, counter: usize, data: &mut [u8])
+    {
+        let data_start = unsafe { self.ring(index).add(RING_HEADER_SIZE) };
+        let position = counter % RING_CAPACITY;
+        let first = data.len().min(RING_CAPACITY - position);
+        let len = data.len();
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(data_start.add(position), data.as_mut_ptr(), first);
+            std::ptr::copy_nonoverlapping(data_start, data[first..].as_mut_ptr(), len - first);
+        }
+    }
+
+    /*
+     * Wait for the other side to consume the ring up to `end` minus its capacity.
+     */
+    fn wait_room(&self, index: usize, end: usize, deadline: Instant) -> io::Result<()>
+    {
+        // The tail comes from the other side, a tail past `end` never leaves room
+        while end.wrapping_sub(self.tail(index).load(Ordering::Acquire)) > RING_CAPACITY {
+            if Instant::now() >= deadline {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "Shared ring full"));
+            }
+            thread::sleep(Duration::from_micros(100));
+        }
+        Ok(())
+    }
+
+    fn signal(&self, index: usize) -> io::Result<()>
+    {
+        write(self.events[index], &1u64.to_ne_bytes()).map_err(to_io_error)?;
+        Ok(())
+    }
+
+    /*
+     * Wait for the eventfd of the ring to be signaled.
+     * Return false on timeout.
+     */
+    fn wait(&self, index: usize, timeout: Option<Duration>) -> io::Result<bool>
+    {
+        let timeout: i32 = match timeout {
+            Some(dur) => dur.as_millis().min(i32::MAX as u128) as i32,
+            None => -1,
+        };
+
+        let mut fds = [PollFd::new(self.events[index], PollFlags::POLLIN)];
+        match poll(&mut fds, timeout) {
+            Ok(0) => Ok(false),
+            Ok(_) => {
+                let mut counter = [0u8; 8];
+                read(self.events[index], &mut counter).map_err(to_io_error)?;
+                Ok(true)
+            },
+            Err(Errno::EINTR) => Ok(true),
+            Err(errno) => Err(to_io_error(errno)),
+        }
+    }
+}
+
+impl Drop for SharedRegion {
+
+    fn drop(&mut self)
+    {
+        unsafe { munmap(self.base as *mut std::ffi::c_void, REGION_SIZE).ok(); }
+        close(self.memfd).ok();
+        close(self.events[0]).ok();
+        close(self.events[1]).ok();
+    }
+}
+
+
+
+/*
+ * The blobs of the message being serialized or deserialized on this thread, see `shared_bytes`.
+ */
+struct Blobs {
+    region: Arc<SharedRegion>,
+    ring: usize,
+    /* Sending: the head of the message being written. Receiving: the start of its blobs */
+    start: usize,
+    /* Sending: where the next blob is written. Receiving: the end of the blobs */
+    cursor: usize,
+    deadline: Instant,
+}
+
+thread_local! {
+    static BLOBS: RefCell<Option<Blobs>> = const { RefCell::new(None) };
+}
+
+/*
+ * Serialize the content of the large buffers as a range of the ring instead of a JSON array of bytes.
+ * The bytes are written in the ring in front of the JSON of the message, the receiver copies them from there.
+ * Outside of the shared memory transport, or behind the authentication since the MAC must cover every byte,
+ * the content is serialized inline as usual.
+ */
+pub(crate) mod shared_bytes {
+
+    use serde::{ de, ser, Deserialize, Deserializer, Serialize, Serializer };
+
+    use super::{ BLOBS, RING_CAPACITY };
+
+    /* Smaller contents are cheaper inline */
+    const MIN_SHARED_SIZE: usize = 256;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum Content {
+        Inline(Vec<u8>),
+        Shared { offset: usize, size: usize },
+    }
+
+    pub fn serialize<S: Serializer>(content: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+    {
+        if content.len() < MIN_SHARED_SIZE {
+            return serializer.collect_seq(content);
+        }
+
+        let shared = BLOBS.with(|blobs| {
+            let mut blobs = blobs.borrow_mut();
+            let blobs = match blobs.as_mut() {
+                Some(blobs) => blobs,
+                None => return Ok(None),
+            };
+
+            if blobs.cursor + content.len() - blobs.start > RING_CAPACITY {
+                return Err(format!("Buffer of {} bytes does not fit in the shared ring", content.len()));
+            }
+            blobs.region.wait_room(blobs.ring, blobs.cursor + content.len(), blobs.deadline)
+                .map_err(|err| err.to_string())?;
+            blobs.region.write_bytes(blobs.ring, blobs.cursor, content);
+
+            let offset = blobs.cursor;
+            blobs.cursor += content.len();
+            Ok(Some(offset))
+        }).map_err(ser::Error::custom)?;
+
+        match shared {
+            Some(offset) => Content::Shared { offset, size: content.len() }.serialize(serializer),
+            None => serializer.collect_seq(content),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error>
+    {
+        let (offset, size) = match Content::deserialize(deserializer)? {
+            Content::Inline(content) => return Ok(content),
+            Content::Shared { offset, size } => (offset, size),
+        };
+
+        BLOBS.with(|blobs| {
+            let blobs = blobs.borrow();
+            let blobs = match blobs.as_ref() {
+                Some(blobs) => blobs,
+                None => return Err("Buffer shared outside of the shared memory transport".to_string()),
+            };
+
+            // The range comes from the other side, it must stay within the blobs of the message
+            let in_range = offset >= blobs.start
+                && offset.checked_add(size).map(|end| end <= blobs.cursor).unwrap_or(false);
+            if !in_range {
+                return Err(format!("Shared buffer of {} bytes at {} outside of the message", size, offset));
+            }
+
+            let mut content = vec![0u8; size];
+            blobs.region.read_bytes(blobs.ring, offset, &mut content);
+            Ok(content)
+        }).map_err(de::Error::custom)
+    }
+}
+
+
+
+/*
+ * A transport for a tracer and an executor living on the same host.
+ * Messages are written once in a memfd-backed ring buffer and the other side is woken up with an eventfd,
+ * therefore there is no socket copy and no limit on the payload size other than the ring capacity.
+ *
+ * The executor creates a region for each tracer connecting and hands the file descriptors over a UNIX socket
+ * (SCM_RIGHTS), so it also works when both sides run as different users or in different containers sharing the socket.
+ * The executor talks with the last tracer connected, until the sender is pinned for the length of a session.
+ */
+pub struct SharedMemory {
+    region: Arc<Mutex<Option<Arc<SharedRegion>>>>,
+    pinned: Arc<AtomicBool>,
+    tx: usize,
+    rx: usize,
+    read_timeout: Mutex<Option<Duration>>,
+}
+
+impl SharedMemory {
+
+    /*
+     * Executor side: serve a new region to each tracer connecting on the socket at `path`.
+     */
+    pub fn listen(path: &str) -> io::Result<Self>
+    {
+        // Remove a socket left by a previous executor
+        let _ = std::fs::remove_file(path);
+        let listener = UnixListener::bind(path)?;
+
+        let shared = Self::new(None, SERVER_TO_CLIENT, CLIENT_TO_SERVER);
+        let current = shared.region.clone();
+        let pinned = shared.pinned.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        eprintln!("Fail to accept shared memory client: {}", err);
+                        continue;
+                    },
+                };
+
+                // Closing the stream without any region makes the tracer fail to connect
+                if pinned.load(Ordering::Acquire) {
+                    eprintln!("Refuse a shared memory client, the executor is already in a session");
+                    continue;
+                }
+
+                let region = match SharedRegion::create() {
+                    Ok(region) => region,
+                    Err(err) => {
+                        eprintln!("Fail to create shared memory: {}", err);
+                        continue;
+                    },
+                };
+
+                let fds = [region.memfd, region.events[0], region.events[1]];
+                let iov = [IoSlice::new(b"sysfwd")];
+                let cmsg = [ControlMessage::ScmRights(&fds)];
+                match sendmsg::<UnixAddr>(stream.as_raw_fd(), &iov, &cmsg, MsgFlags::empty(), None) {
+                    Ok(_) => *current.lock().unwrap() = Some(Arc::new(region)),
+                    Err(err) => eprintln!("Fail to send shared memory to client: {}", err),
+                }
+            }
+        });
+
+        Ok(shared)
+    }
+
+    /*
+     * Tracer side: receive the region created by the executor listening at `path`.
+     */
+    pub fn connect(path: &str) -> io::Result<Self>
+    {
+        let start = Instant::now();
+        let stream = loop {
+            match UnixStream::connect(path) {
+                Ok(stream) => break stream,
+                Err(err) if start.elapsed() >= CONNECT_TIMEOUT => return Err(err),
+                Err(_) => thread::sleep(Duration::from_millis(100)),
+            }
+        };
+
+        let mut buffer = [0u8; 16];
+        let mut iov = [IoSliceMut::new(&mut buffer)];
+        let mut cmsg_buffer = cmsg_space!([RawFd; 3]);
+        let message = recvmsg::<UnixAddr>(stream.as_raw_fd(), &mut iov, Some(&mut cmsg_buffer), MsgFlags::empty())
+            .map_err(to_io_error)?;
+
+        let mut fds: Vec<RawFd> = Vec::new();
+        for cmsg in message.cmsgs() {
+            if let ControlMessageOwned::ScmRights(received) = cmsg {
+                fds.extend(received);
+            }
+        }
+        if fds.len() != 3 {
+            let msg = format!("Expected 3 file descriptors from the executor, received {}", fds.len());
+            return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+        }
+
+        let region = Arc::new(SharedRegion::map(fds[0], [fds[1], fds[2]])?);
+        Ok(Self::new(Some(region), CLIENT_TO_SERVER, SERVER_TO_CLIENT))
+    }
+
+    fn new(region: Option<Arc<SharedRegion>>, tx: usize, rx: usize) -> Self
+    {
+        Self {
+            region: Arc::new(Mutex::new(region)),
+            pinned: Arc::new(AtomicBool::new(false)),
+            tx,
+            rx,
+            read_timeout: Mutex::new(None),
+        }
+    }
+
+    fn region(&self) -> Option<Arc<SharedRegion>>
+    {
+        self.region.lock().unwrap().clone()
+    }
+
+    /*
+     * Write a message in the ring: its JSON after the blobs already written from `head` up to `cursor`.
+     */
+    fn publish(&self, region: &SharedRegion, head: usize, cursor: usize, json: &[u8], deadline: Instant) -> io::Result<()>
+    {
+        let blobs_size = cursor - head - 2 * HEADER_SIZE;
+        let total = 2 * HEADER_SIZE + blobs_size + json.len();
+        if total > RING_CAPACITY {
+            let msg = format!("Message of {} bytes does not fit in the shared ring", total);
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+        }
+
+        region.wait_room(self.tx, head + total, deadline)?;
+
+        region.write_bytes(self.tx, head, &json.len().to_be_bytes());
+        region.write_bytes(self.tx, head + HEADER_SIZE, &blobs_size.to_be_bytes());
+        region.write_bytes(self.tx, cursor, json);
+        region.head(self.tx).store(head + total, Ordering::Release);
+
+        region.signal(self.tx)
+    }
+
+    /*
+     * Wait for the next message and hand its JSON and the range of its blobs to `parse`.
+     */
+    fn receive_with<T>(&self, parse: impl FnOnce(Vec<u8>, Blobs) -> io::Result<T>) -> io::Result<T>
+    {
+        let timeout = *self.read_timeout.lock().unwrap();
+
+        loop {
+            let region = match self.region() {
+                Some(region) => region,
+                None => {
+                    // No tracer yet, same error as a socket read timeout
+                    thread::sleep(timeout.unwrap_or(Duration::from_millis(100)));
+                    match timeout {
+                        Some(_) => return Err(io::Error::new(io::ErrorKind::WouldBlock, "No shared memory client")),
+                        None => continue,
+                    }
+                },
+            };
+
+            // Only this side moves the tail of its ring
+            let tail = region.tail(self.rx).load(Ordering::Relaxed);
+            let head = region.head(self.rx).load(Ordering::Acquire);
+
+            // The head comes from the other side, it is never behind the tail nor a whole ring ahead of it
+            let published = head.wrapping_sub(tail);
+            if published > RING_CAPACITY {
+                region.tail(self.rx).store(head, Ordering::Release);
+                let msg = format!("Drop the shared ring, head {:#x} out of the ring from tail {:#x}", head, tail);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+            }
+
+            if published < 2 * HEADER_SIZE && head != tail {
+                region.tail(self.rx).store(head, Ordering::Release);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "Drop the shared ring, message shorter than its headers"));
+            }
+
+            if head != tail {
+                let mut header = [0u8; HEADER_SIZE];
+                region.read_bytes(self.rx, tail, &mut header);
+                let json_size = usize::from_be_bytes(header);
+                region.read_bytes(self.rx, tail + HEADER_SIZE, &mut header);
+                let blobs_size = usize::from_be_bytes(header);
+
+                // The sizes come from the other side, never allocate more than what it published
+                let available = published - 2 * HEADER_SIZE;
+                if json_size > available || blobs_size > available - json_size {
+                    region.tail(self.rx).store(head, Ordering::Release);
+                    let msg = format!("Drop the shared ring, message of {} bytes over the {} bytes published",
+                                      json_size.saturating_add(blobs_size), available);
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+                }
+
+                let start = tail + 2 * HEADER_SIZE;
+                let mut json = vec![0u8; json_size];
+                region.read_bytes(self.rx, start + blobs_size, &mut json);
+
+                let blobs = Blobs {
+                    region: region.clone(),
+                    ring: self.rx,
+                    start,
+                    cursor: start + blobs_size,
+                    deadline: Instant::now(),
+                };
+                let parsed = parse(json, blobs);
+                region.tail(self.rx).store(start + blobs_size + json_size, Ordering::Release);
+
+                return parsed;
+            }
+
+            // Same error as a socket read timeout
+            if !region.wait(self.rx, timeout)? {
+                return Err(io::Error::new(io::ErrorKind::WouldBlock, "Shared memory read timeout"));
+            }
+        }
+    }
+
+    fn not_connected() -> io::Error
+    {
+        io::Error::new(io::ErrorKind::NotConnected, "No shared memory client")
+    }
+}
+
+impl Transport for SharedMemory {
+
+    fn send(&self, data: &[u8]) -> io::Result<()>
+    {
+        let region = self.region().ok_or_else(Self::not_connected)?;
+
+        // Only this side moves the head of its ring
+        let head = region.head(self.tx).load(Ordering::Relaxed);
+        self.publish(&region, head, head + 2 * HEADER_SIZE, data, Instant::now() + SEND_TIMEOUT)
+    }
+
+    fn receive(&self) -> io::Result<(Vec<u8>, usize)>
+    {
+        self.receive_with(|json, _blobs| {
+            let len = json.len();
+            Ok((json, len))
+        })
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()>
+    {
+        *self.read_timeout.lock().unwrap() = dur;
+        Ok(())
+    }
+
+    fn try_clone(&self) -> io::Result<Box<dyn Transport>>
+    {
+        let timeout = *self.read_timeout.lock().unwrap();
+        Ok(Box::new(Self {
+            region: self.region.clone(),
+            pinned: self.pinned.clone(),
+            tx: self.tx,
+            rx: self.rx,
+            read_timeout: Mutex::new(timeout),
+        }))
+    }
+
+    /*
+     * Stop serving regions to new tracers.
+     */
+    fn pin_sender(&self)
+    {
+        self.pinned.store(true, Ordering::Release);
+    }
+
+    /*
+     * Serve regions to new tracers again.
+     */
+    fn unpin_sender(&self)
+    {
+        self.pinned.store(false, Ordering::Release);
+    }
+
+    fn send_message(&self, message: &DataMessage) -> io::Result<()>
+    {
+        let region = self.region().ok_or_else(Self::not_connected)?;
+        let head = region.head(self.tx).load(Ordering::Relaxed);
+
+        let blobs = Blobs {
+            region: region.clone(),
+            ring: self.tx,
+            start: head,
+            cursor: head + 2 * HEADER_SIZE,
+            deadline: Instant::now() + SEND_TIMEOUT,
+        };
+        BLOBS.with(|current| *current.borrow_mut() = Some(blobs));
+        let json = serde_json::to_vec(message);
+        let blobs = BLOBS.with(|current| current.borrow_mut().take()).unwrap();
+
+        let json = json.map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        self.publish(&region, head, blobs.cursor, &json, blobs.deadline)
+    }
+
+    fn receive_message(&self) -> io::Result<DataMessage>
+    {
+        self.receive_with(|json, blobs| {
+            BLOBS.with(|current| *current.borrow_mut() = Some(blobs));
+            let message = serde_json::from_slice(&json);
+            BLOBS.with(|current| current.borrow_mut().take());
+
+            message.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        })
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall::{
+        RawSyscall, Syscall,
+        decoder::DecodedSyscall,
+        syscalls::io::Write,
+    };
+
+    fn socket_path(name: &str) -> String
+    {
+        format!("{}/sysfwd-shm-{}-{}.sock", std::env::temp_dir().display(), std::process::id(), name)
+    }
+
+    /*
+     * An executor and a tracer connected through the socket at `name`.
+     */
+    fn connect(name: &str) -> (SharedMemory, SharedMemory)
+    {
+        let path = socket_path(name);
+        let executor = SharedMemory::listen(&path).unwrap();
+        executor.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+        let tracer = SharedMemory::connect(&path).unwrap();
+        (executor, tracer)
+    }
+
+    /* The region is only installed by the executor once handed over */
+    fn receive(executor: &SharedMemory) -> io::Result<DataMessage>
+    {
+        loop {
+            match executor.receive_message() {
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => continue,
+                result => return result,
+            }
+        }
+    }
+
+    fn write_syscall(content: Vec<u8>) -> DataMessage
+    {
+        let raw = RawSyscall { no: 1, args: vec![1, 0x1000, content.len(), 0, 0, 0], retval: 0, errno: 0 };
+        let mut write = Write::new(raw.clone());
+        write.buf.content = content;
+        let syscall = Syscall { raw, decoded: Some(DecodedSyscall::Write(write)), name: "write".to_string(), decision: None };
+        DataMessage::Syscall(Box::new(syscall))
+    }
+
+    #[test]
+    fn large_buffers_are_passed_by_offset()
+    {
+        let (executor, tracer) = connect("offset");
+        let content: Vec<u8> = (0..8192).map(|i| i as u8).collect();
+        tracer.send_message(&write_syscall(content.clone())).unwrap();
+
+        // The JSON only holds the range of the content in the ring
+        let region = loop {
+            if let Some(region) = executor.region() { break region; }
+            thread::sleep(Duration::from_millis(10));
+        };
+        let mut header = [0u8; HEADER_SIZE];
+        region.read_bytes(CLIENT_TO_SERVER, 0, &mut header);
+        assert!(usize::from_be_bytes(header) < 1024);
+
+        match receive(&executor).unwrap() {
+            DataMessage::Syscall(syscall) => match syscall.decoded {
+                Some(DecodedSyscall::Write(write)) => assert_eq!(write.buf.content, content),
+                decoded => panic!("Unexpected syscall {:?}", decoded),
+            },
+            message => panic!("Unexpected message {:?}", message),
+        }
+    }
+
+    #[test]
+    fn small_buffers_are_inline()
+    {
+        let (executor, tracer) = connect("inline");
+        tracer.send_message(&write_syscall(b"hello".to_vec())).unwrap();
+
+        match receive(&executor).unwrap() {
+            DataMessage::Syscall(syscall) => match syscall.decoded {
+                Some(DecodedSyscall::Write(write)) => assert_eq!(write.buf.content, b"hello"),
+                decoded => panic!("Unexpected syscall {:?}", decoded),
+            },
+            message => panic!("Unexpected message {:?}", message),
+        }
+    }
+
+    #[test]
+    fn each_tracer_has_its_own_region()
+    {
+        let path = socket_path("regions");
+        let executor = SharedMemory::listen(&path).unwrap();
+        executor.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+
+        let first = SharedMemory::connect(&path).unwrap();
+        let second = SharedMemory::connect(&path).unwrap();
+        assert!(!Arc::ptr_eq(&first.region().unwrap(), &second.region().unwrap()));
+
+        // The first tracer no longer reaches the executor
+        first.send_message(&DataMessage::Heartbeat).unwrap();
+        second.send_message(&DataMessage::EndSession).unwrap();
+        thread::sleep(Duration::from_millis(100));
+        assert!(matches!(receive(&executor).unwrap(), DataMessage::EndSession));
+    }
+
+    #[test]
+    fn pinned_executor_refuses_new_tracers()
+    {
+        let (executor, tracer) = connect("pinned");
+        tracer.send_message(&DataMessage::Heartbeat).unwrap();
+        assert!(matches!(receive(&executor).unwrap(), DataMessage::Heartbeat));
+        executor.pin_sender();
+
+        assert!(SharedMemory::connect(&socket_path("pinned")).is_err());
+    }
+
+    #[test]
+    fn unpinned_executor_accepts_new_tracers()
+    {
+        let (executor, tracer) = connect("unpinned");
+        tracer.send_message(&DataMessage::Heartbeat).unwrap();
+        assert!(matches!(receive(&executor).unwrap(), DataMessage::Heartbeat));
+        executor.pin_sender();
+        executor.unpin_sender();
+
+        let next = SharedMemory::connect(&socket_path("unpinned")).unwrap();
+        thread::sleep(Duration::from_millis(100));
+        next.send_message(&DataMessage::EndSession).unwrap();
+        assert!(matches!(receive(&executor).unwrap(), DataMessage::EndSession));
+    }
+
+    #[test]
+    fn oversized_header_is_dropped()
+    {
+        let (executor, tracer) = connect("oversized");
+        let region = tracer.region().unwrap();
+
+        // A message claiming far more than what is published
+        region.write_bytes(CLIENT_TO_SERVER, 0, &usize::MAX.to_be_bytes());
+        region.write_bytes(CLIENT_TO_SERVER, HEADER_SIZE, &0usize.to_be_bytes());
+        region.head(CLIENT_TO_SERVER).store(2 * HEADER_SIZE + 4, Ordering::Release);
+        region.signal(CLIENT_TO_SERVER).unwrap();
+
+        let err = receive(&executor).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // The ring is usable again
+        tracer.send_message(&DataMessage::Heartbeat).unwrap();
+        assert!(matches!(receive(&executor).unwrap(), DataMessage::Heartbeat));
+    }
+
+    #[test]
+    fn corrupted_head_is_dropped()
+    {
+        let (executor, tracer) = connect("corrupted");
+        tracer.send_message(&DataMessage::Heartbeat).unwrap();
+        assert!(matches!(receive(&executor).unwrap(), DataMessage::Heartbeat));
+
+        // A head behind the tail, then a head more than a ring ahead of it
+        let region = tracer.region().unwrap();
+        for head in [0, 2 * RING_CAPACITY] {
+            region.head(CLIENT_TO_SERVER).store(head, Ordering::Release);
+            region.signal(CLIENT_TO_SERVER).unwrap();
+
+            let err = receive(&executor).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+
+        // The ring is usable again
+        tracer.send_message(&DataMessage::Heartbeat).unwrap();
+        assert!(matches!(receive(&executor).unwrap(), DataMessage::Heartbeat));
+    }
+}
@@ -1,7 +1,10 @@
 /*
  *
  */
-use std::sync::{ Arc, Condvar, Mutex };
+use std::{
+    sync::{ Arc, Condvar, Mutex },
+    time::Duration,
+};
 
 
 
@@ -10,6 +13,12 @@ pub struct Event {
     mutex: Arc<(Mutex<bool>, Condvar)>,
 }
 
+impl Default for Event {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Event {
 
     pub fn new() -> Self
@@ -21,7 +30,7 @@ impl Event {
 
     pub fn set(&self) 
     {
-        let &(ref lock, ref cvar) = &*self.mutex;
+        let (lock, cvar) = &*self.mutex;
         let mut triggered = lock.lock().unwrap();
         *triggered = true;
         cvar.notify_all();
@@ -29,16 +38,27 @@ impl Event {
 
     pub fn is_set(&self) -> bool 
     {
-        let &(ref lock, _) = &*self.mutex;
+        let (lock, _) = &*self.mutex;
         *lock.lock().unwrap()
     }
 
     pub fn wait(&self) 
     {
-        let &(ref lock, ref cvar) = &*self.mutex;
+        let (lock, cvar) = &*self.mutex;
         let mut triggered = lock.lock().unwrap();
         while !*triggered {
             triggered = cvar.wait(triggered).unwrap();
         }
     }
-}
\ No newline at end of file
+
+    /*
+     * Wait until the event is set or the timeout elapses, return whether it is set.
+     */
+    pub fn wait_timeout(&self, timeout: Duration) -> bool
+    {
+        let (lock, cvar) = &*self.mutex;
+        let triggered = lock.lock().unwrap();
+        let (triggered, _) = cvar.wait_timeout_while(triggered, timeout, |triggered| !*triggered).unwrap();
+        *triggered
+    }
+}
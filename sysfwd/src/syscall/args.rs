@@ -5,6 +5,7 @@ use core::{fmt, panic};
 use std::convert::From;
 
 //use nix::libc::printf;
+use nix::libc;
 use serde::{ Serialize, Deserialize };
 
 use crate::{
@@ -12,7 +13,8 @@ use crate::{
         decoder::DecodeArg,
         encoder::EncodeArg,
     },
-    targets::operation::Operation,
+    targets::operation::{ Operation, MemoryOperation },
+    executor::Scratch,
 };
 
 
@@ -35,7 +37,7 @@ pub enum ArgType {
 }
 
 impl DecodeArg for ArgType {
-    fn decode(&mut self, pid: i32, operation: &Box<Operation>) -> Result<(), std::io::Error> {
+    fn decode(&mut self, pid: i32, operation: &Operation) -> Result<(), std::io::Error> {
         match self {
             ArgType::Integer(integer)   => integer.decode(pid, operation),
             ArgType::Fd(fd)                  => fd.decode(pid, operation),
@@ -67,14 +69,14 @@ pub struct Integer {
 
 impl Integer {
     pub fn new(value: usize) -> Self {
-        Self { value: value }
+        Self { value }
     }
 }
 
 impl From<usize> for Integer {
     fn from(value: usize) -> Self
     {
-        Self { value: value }
+        Self { value }
     }
 }
 
@@ -96,14 +98,14 @@ pub struct Fd {
 
 impl Fd {
     pub fn new(value: usize) -> Self {
-        Self { value: value }
+        Self { value }
     }
 }
 
 impl From<usize> for Fd {
     fn from(value: usize) -> Self
     {
-        Self { value: value }
+        Self { value }
     }
 }
 
@@ -129,7 +131,7 @@ pub struct Size {
 impl Size {
     pub fn new(value: usize) -> Self {
         Self { 
-            value: value,
+            value,
         }
     }
 }
@@ -137,7 +139,7 @@ impl Size {
 impl From<usize> for Size {
     fn from(value: usize) -> Self
     {
-        Self { value: value }
+        Self { value }
     }
 }
 
@@ -160,7 +162,7 @@ pub struct Offset {
 impl Offset {
     pub fn new(value: usize) -> Self {
         Self {
-            value: value,
+            value,
         }
     }
 }
@@ -168,7 +170,7 @@ impl Offset {
 impl From<usize> for Offset {
     fn from(value: usize) -> Self
     {
-        Self { value: value }
+        Self { value }
     }
 }
 
@@ -321,8 +323,8 @@ pub struct Address {
 impl Address {
     pub fn new(value: usize, direction: Direction) -> Self {
         Self { 
-            value: value,
-            direction: direction,
+            value,
+            direction,
             content: 0,
         }
     }
@@ -332,7 +334,7 @@ impl From<usize> for Address {
     fn from(value: usize) -> Self
     {
         Self {
-            value: value,
+            value,
             direction: Direction::InOut,
             content: 0,
         }
@@ -349,9 +351,9 @@ impl DecodeArg for Address {
 
 impl EncodeArg for Address {
 
-    fn encode(&mut self, _pid: i32, _operation: &Box<Operation>) -> Result<(), std::io::Error> {
+    fn encode(&mut self, _pid: i32, _operation: &Operation, _remote: &dyn MemoryOperation, _scratch: &Scratch) -> Result<usize, std::io::Error> {
         // TODO: if content is really used, write it
-        Ok(())
+        Ok(self.value)
     }
 }
 
@@ -359,6 +361,57 @@ impl EncodeArg for Address {
 
 /* Pointer arguments */
 
+/*
+ * A pointer argument whose content may be filled by the syscall, i.e., with the direction In or InOut
+ * (Out is only given to the syscall). `size` bytes are read back once the syscall returned,
+ * see decoder::PointerArgs.
+ */
+pub trait Pointer {
+    fn is_output(&self) -> bool;
+
+    /*
+     * Read the content filled by the syscall in the process which executed it.
+     */
+    fn read_output(&mut self, pid: i32, operation: &Operation);
+
+    /*
+     * Give the content filled by the syscall to the process which made it.
+     */
+    fn write_output(&self, pid: i32, operation: &Operation);
+}
+
+/*
+ * The shared implementation of Pointer, for the arguments with an address, a size and a content.
+ */
+macro_rules! impl_pointer {
+    ($name:ident) => {
+        impl Pointer for $name {
+            fn is_output(&self) -> bool
+            {
+                matches!(self.direction, Direction::In | Direction::InOut)
+            }
+
+            fn read_output(&mut self, pid: i32, operation: &Operation)
+            {
+                if !self.is_output() || self.address == 0 || self.size == 0 {
+                    return;
+                }
+                self.content = operation.memory.read(pid, self.address, self.size);
+                self.content.truncate(self.size);
+            }
+
+            fn write_output(&self, pid: i32, operation: &Operation)
+            {
+                if !self.is_output() || self.address == 0 || self.content.is_empty() {
+                    return;
+                }
+                let size = std::cmp::min(self.size, self.content.len());
+                operation.memory.write(pid, self.address, self.content[..size].to_vec());
+            }
+        }
+    };
+}
+
 /*
  * A memory block
  */
@@ -368,15 +421,17 @@ pub struct Buffer {
     pub address: usize,
     pub direction: Direction,
     pub size: usize,
+    /* Passed by offset in the ring with the shared memory transport */
+    #[serde(with = "crate::protocol::shm::shared_bytes")]
     pub content: Vec<u8>,
 }
 
 impl Buffer {
     pub fn new(address: usize, direction: Direction, size: usize) -> Self {
         Self { 
-            address: address,
-            direction: direction,
-            size: size,
+            address,
+            direction,
+            size,
             content: Vec::new(),  // TODO: initialize with a default size?
         }
     }
@@ -396,8 +451,13 @@ impl From<usize> for Buffer {
 
 impl DecodeArg for Buffer {
 
-    fn decode(&mut self, pid: i32, operation: &Box<Operation>) -> Result<(), std::io::Error> { 
+    fn decode(&mut self, pid: i32, operation: &Operation) -> Result<(), std::io::Error> { 
+        // A buffer filled by the syscall (e.g., read) has no meaningful content on entry.
+        if let Direction::In = self.direction {
+            return Ok(());
+        }
         self.content = operation.memory.read(pid, self.address, self.size);
+        self.content.truncate(self.size);
         Ok(())
     }
 
@@ -409,12 +469,27 @@ impl DecodeArg for Buffer {
     }
 }
 
+impl_pointer!(Buffer);
+
 impl EncodeArg for Buffer {
 
-    fn encode(&mut self, pid: i32, operation: &Box<Operation>) -> Result<(), std::io::Error> {
+    fn encode(&mut self, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<usize, std::io::Error> {
+        let local = scratch.alloc(self.address, self.size)?;
+        // A buffer filled by the syscall only needs room in the scratch memory
+        if local == 0 || matches!(self.direction, Direction::In) {
+            return Ok(local);
+        }
+        // Fetch the content which was not shipped with the syscall
+        if self.content.len() < self.size {
+            self.content = remote.read(pid, self.address, self.size);
+            // Not readable by the tracee either
+            if self.content.len() < self.size {
+                return Err(std::io::Error::from_raw_os_error(libc::EFAULT));
+            }
+        }
         let mem = self.content.clone();
         operation.memory.write(pid, self.address, mem);
-        Ok(())
+        Ok(local)
     }
 }
 
@@ -435,8 +510,8 @@ pub struct NullBuffer {
 impl NullBuffer {
     pub fn new(address: usize, direction: Direction) -> Self {
         Self { 
-            address: address,
-            direction: direction,
+            address,
+            direction,
             size: 0,
             content: Vec::new(),  // TODO: initialize with a default size?
         }
@@ -457,25 +532,26 @@ impl From<usize> for NullBuffer {
 
 impl DecodeArg for NullBuffer {
 
-    fn decode(&mut self, pid: i32, operation: &Box<Operation>) -> Result<(), std::io::Error> { 
-        //TODO: does not work when the Null terminated buffer is greater than READ_SIZE bytes.
-        #[allow(non_snake_case)]
-        let READ_SIZE = 1024;
-        let buf = operation.memory.read(pid, self.address, READ_SIZE);
-
-        let mut iter = buf.iter();
-        loop {
-            match iter.next() {
-                Some(x) => {
-                    match x {
-                        0 => break,
-                        _ => {
-                            self.size += 1;
-                            self.content.push(x.clone());
-                        },
-                    }
+    fn decode(&mut self, pid: i32, operation: &Operation) -> Result<(), std::io::Error> { 
+        // Read small chunks until the null byte instead of a large fixed block.
+        const CHUNK_SIZE: usize = 64;
+        const MAX_SIZE: usize = 4096;   // PATH_MAX
+
+        self.size = 0;
+        self.content.clear();
+
+        while self.size < MAX_SIZE {
+            let chunk = operation.memory.read(pid, self.address + self.size, CHUNK_SIZE);
+            if chunk.is_empty() {
+                break;
+            }
+
+            for x in chunk.iter().take(CHUNK_SIZE) {
+                if *x == 0 {
+                    return Ok(());
                 }
-                None => break,
+                self.size += 1;
+                self.content.push(*x);
             }
         }
         Ok(())
@@ -491,15 +567,16 @@ impl DecodeArg for NullBuffer {
 
 impl EncodeArg for NullBuffer {
 
-    fn encode(&mut self, pid: i32, operation: &Box<Operation>) -> Result<(), std::io::Error> {
+    fn encode(&mut self, pid: i32, operation: &Operation, _remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<usize, std::io::Error> {
         let mut mem = self.content.clone();
         // adjust the memory block to write to be a multiple of 4
         let count = 4 - mem.len() % 4;
-        for _ in 0..count {
-            mem.push(0);
+        mem.resize(mem.len() + count, 0);
+        let local = scratch.alloc(self.address, mem.len())?;
+        if local != 0 {
+            operation.memory.write(pid, self.address, mem);
         }
-        operation.memory.write(pid, self.address, mem);
-        Ok(())
+        Ok(local)
     }
 }
 
@@ -523,9 +600,9 @@ pub struct Array {
 impl Array {
     pub fn new(address: usize, direction: Direction, count: usize) -> Self {
         Self {
-            address: address,
-            direction: direction,
-            count: count,
+            address,
+            direction,
+            count,
             content: Vec::new(),
         }
     }
@@ -545,14 +622,14 @@ impl From<usize> for Array {
 
 impl DecodeArg for Array {
 
-    fn decode(&mut self, _pid: i32, _operation: &Box<Operation>) -> Result<(), std::io::Error> { 
+    fn decode(&mut self, _pid: i32, _operation: &Operation) -> Result<(), std::io::Error> { 
        panic!("To implement"); 
     }
 }
 
 impl EncodeArg for Array {
 
-    fn encode(&mut self, _pid: i32, _operation: &Box<Operation>) -> Result<(), std::io::Error> {
+    fn encode(&mut self, _pid: i32, _operation: &Operation, _remote: &dyn MemoryOperation, _scratch: &Scratch) -> Result<usize, std::io::Error> {
        panic!("To implement"); 
     }
 }
@@ -576,8 +653,8 @@ impl Struct {
     //pub fn new(address: usize, name: &str) -> Self {    TODO: use name during creation
     pub fn new(address: usize, direction: Direction) -> Self {
         Self { 
-            address: address,
-            direction: direction,
+            address,
+            direction,
             size: 0,
             //name: name.to_string(),
             name: String::new(),
@@ -601,10 +678,14 @@ impl From<usize> for Struct {
 
 impl DecodeArg for Struct {
 
-    fn decode(&mut self, pid: i32, operation: &Box<Operation>) -> Result<(), std::io::Error> { 
-       // The best would be to know the structure for each struct and read / parse it.
-       // For now read 4kB
-        self.content = operation.memory.read(pid, self.address, 4096);
+    fn decode(&mut self, pid: i32, operation: &Operation) -> Result<(), std::io::Error> { 
+        // The best would be to know the structure for each struct and read / parse it.
+        // For now only read it when its size is known, otherwise the executor fetches it on demand.
+        // A structure filled by the syscall is read at exit.
+        if self.size > 0 && !matches!(self.direction, Direction::In) {
+            self.content = operation.memory.read(pid, self.address, self.size);
+            self.content.truncate(self.size);
+        }
         Ok(())
     }
 
@@ -617,11 +698,30 @@ impl DecodeArg for Struct {
     }
 }
 
+impl_pointer!(Struct);
+
 impl EncodeArg for Struct {
 
-    fn encode(&mut self, pid: i32, operation: &Box<Operation>) -> Result<(), std::io::Error> {
+    fn encode(&mut self, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<usize, std::io::Error> {
+        // Without its size the structure can neither be copied nor given room in the scratch memory
+        if self.address != 0 && self.size == 0 {
+            let msg = format!("Cannot encode the structure at {:#x} of unknown size", self.address);
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, msg));
+        }
+        let local = scratch.alloc(self.address, self.size)?;
+        // A structure filled by the syscall only needs room in the scratch memory
+        if local == 0 || matches!(self.direction, Direction::In) {
+            return Ok(local);
+        }
+        if self.content.len() < self.size {
+            self.content = remote.read(pid, self.address, self.size);
+            // Not readable by the tracee either
+            if self.content.len() < self.size {
+                return Err(std::io::Error::from_raw_os_error(libc::EFAULT));
+            }
+        }
         let mem = self.content.clone();
         operation.memory.write(pid, self.address, mem);
-        Ok(())
+        Ok(local)
     }
 }
@@ -7,7 +7,7 @@ use serde::{Serialize, Deserialize};
 
 use crate::{
     arch::Architecture,
-    syscall::{ Syscall, syscalls },
+    syscall::{ Syscall, syscalls, args::{ Fd, Pointer } },
     targets::operation::Operation,
 };
 
@@ -21,11 +21,11 @@ impl Decoder {
     pub fn new(arch: Arc<Architecture>) -> Self {
         Self { 
             //arch: Architecture::new(arch),
-            arch: arch,
+            arch,
         }
     }
 
-    pub fn decode_entry(&self, syscall: &mut Syscall, pid: i32, operation: &Box<Operation>) {
+    pub fn decode_entry(&self, syscall: &mut Syscall, pid: i32, operation: &Operation) {
 
         // TODO: improve the match by using number instead of strings
         match self.arch.syscall_table.get_syscall_name(&syscall.raw.no) {
@@ -45,7 +45,7 @@ impl Decoder {
         //syscall.entry_decoded = true;
     }
 
-    fn decode_args(&self, syscall: &mut Syscall, pid: i32, operation: &Box<Operation>) {
+    fn decode_args(&self, syscall: &mut Syscall, pid: i32, operation: &Operation) {
         if let Some(decoded_sc) = &mut syscall.decoded {
                 decoded_sc.decode_entry(pid, operation);
             }
@@ -83,6 +83,7 @@ impl Decoder {
             "pread"     => { decode_syscall!(Pread, io) },
             "pread64"   => { decode_syscall!(Pread, io) },
             "pwrite"    => { decode_syscall!(Pwrite, io) },
+            "pwrite64"  => { decode_syscall!(Pwrite, io) },
             "preadv"    => { decode_syscall!(Preadv, io) },
             "pwritev"   => { decode_syscall!(Pwritev, io) },
             "preadv2"   => { decode_syscall!(Preadv2, io) },
@@ -90,6 +91,14 @@ impl Decoder {
 
             "ioctl"     => { decode_syscall!(Ioctl, ioctl) },
 
+            "dup"       => { decode_syscall!(Dup, dup) },
+            "dup2"      => { decode_syscall!(Dup2, dup) },
+            "dup3"      => { decode_syscall!(Dup3, dup) },
+            "fcntl"     => { decode_syscall!(Fcntl, fcntl) },
+
+            "accept"    => { decode_syscall!(Accept, socket) },
+            "accept4"   => { decode_syscall!(Accept4, socket) },
+
             "lseek"     => { decode_syscall!(Lseek, lseek) },
             "llseek"     => { decode_syscall!(Llseek, lseek) },
 
@@ -108,6 +117,8 @@ impl Decoder {
             "renameat2" => { decode_syscall!(Renameat2, renameat) },
             "truncate" => { decode_syscall!(Truncate, truncate) },
             "ftruncate" => { decode_syscall!(Ftruncate, truncate) },
+            "fsync"     => { decode_syscall!(Fsync, fsync) },
+            "fdatasync" => { decode_syscall!(Fdatasync, fsync) },
 
             "execve"    => { decode_syscall!(Execve, execve) },
             "execveat"  => { decode_syscall!(Execveat, execve) },
@@ -122,6 +133,7 @@ impl Decoder {
             "munmap"    => { decode_syscall!(Munmap, mmap) },
             "mprotect"  => { decode_syscall!(Mprotect, mmap) },
             "madvise"   => { decode_syscall!(Madvise, mmap) },
+            "msync"     => { decode_syscall!(Msync, mmap) },
 
             "getdents"  => { decode_syscall!(Getdents, dirent) },
             "getdents64"=> { decode_syscall!(Getdents64, dirent) },
@@ -145,12 +157,12 @@ impl Decoder {
 
             "getrandom" => { decode_syscall!(Getrandom, getrandom) },
 
-            "epollcreate"   => { decode_syscall!(EpollCreate, epoll) },
-            "epollcreate1"  => { decode_syscall!(EpollCreate1, epoll) },
-            "epollctl"      => { decode_syscall!(EpollCtl, epoll) },
-            "epollwait"     => { decode_syscall!(EpollWait, epoll) },
-            "epollpwait"    => { decode_syscall!(EpollPwait, epoll) },
-            "epollpwait2"   => { decode_syscall!(EpollPwait2, epoll) },
+            "epoll_create"  => { decode_syscall!(EpollCreate, epoll) },
+            "epoll_create1" => { decode_syscall!(EpollCreate1, epoll) },
+            "epoll_ctl"     => { decode_syscall!(EpollCtl, epoll) },
+            "epoll_wait"    => { decode_syscall!(EpollWait, epoll) },
+            "epoll_pwait"   => { decode_syscall!(EpollPwait, epoll) },
+            "epoll_pwait2"  => { decode_syscall!(EpollPwait2, epoll) },
 
             "get_robust_list" => { decode_syscall!(GetRobustList, robust_list) },
             "set_robust_list" => { decode_syscall!(SetRobustList, robust_list) },
@@ -164,11 +176,14 @@ impl Decoder {
     }
 
 
-    pub fn decode_exit(&self, syscall: &mut Syscall, pid: i32, operation: &Box<Operation>) {
+    pub fn decode_exit(&self, syscall: &mut Syscall, pid: i32, operation: &Operation) {
 
-        /* Decode return value */
+        /* Decode return value, then what the syscall filled */
         if let Some(decoded_sc) = &mut syscall.decoded {
             decoded_sc.decode_exit(syscall.raw.retval, pid, operation).unwrap();
+            if (syscall.raw.retval as isize) >= 0 {
+                decoded_sc.read_outputs(pid, operation);
+            }
         }
 
     }
@@ -177,7 +192,7 @@ impl Decoder {
 
 
 pub trait DecodeArg {
-    fn decode(&mut self, _pid: i32, _operation: &Box<Operation>) -> Result<(), std::io::Error> { 
+    fn decode(&mut self, _pid: i32, _operation: &Operation) -> Result<(), std::io::Error> { 
         Ok(())
     }
     fn print(&self) { }
@@ -186,16 +201,54 @@ pub trait DecodeArg {
 pub trait DecodeEntry {
     //fn as_any(&self) -> &dyn Any;
     #[allow(unused_variables)]
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) { }
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) { }
     fn print(&self) { }
 }
 
 pub trait DecodeExit {
-    fn decode_exit(&mut self, _value: usize, _pid: i32, _operation: &Box<Operation>) -> Result<(), std::io::Error> { 
+    fn decode_exit(&mut self, _value: usize, _pid: i32, _operation: &Operation) -> Result<(), std::io::Error> { 
         Ok(())
     }
 }
 
+/*
+ * The fd arguments of a syscall, with the index of the argument, and the fd it returns if any.
+ * Derived from the fields of type Fd, see decoding_macro::FdArgs.
+ */
+pub trait FdArgs {
+    fn fd_args(&mut self) -> Vec<(usize, &mut Fd)>;
+    fn returned_fd(&mut self) -> Option<&mut Fd>;
+}
+
+/*
+ * The pointer arguments of a syscall, whose content filled by the syscall is given back to the tracee.
+ * Derived from the fields of type Buffer and Struct, see decoding_macro::PointerArgs.
+ * Their size is the part filled by the syscall, e.g., bounded by the returned value in decode_exit().
+ */
+pub trait PointerArgs {
+    fn pointer_args(&mut self) -> Vec<&mut dyn Pointer>;
+
+    /*
+     * Read the content of the pointer arguments once the syscall succeeded, in the process which executed it.
+     */
+    fn read_outputs(&mut self, pid: i32, operation: &Operation)
+    {
+        for pointer in self.pointer_args() {
+            pointer.read_output(pid, operation);
+        }
+    }
+
+    /*
+     * Write this content in the process which made the syscall.
+     */
+    fn write_outputs(&mut self, pid: i32, operation: &Operation)
+    {
+        for pointer in self.pointer_args() {
+            pointer.write_output(pid, operation);
+        }
+    }
+}
+
 
 
 #[derive(Serialize, Deserialize)]
@@ -240,6 +293,9 @@ pub enum DecodedSyscall {
     Truncate(syscalls::truncate::Truncate),
     Ftruncate(syscalls::truncate::Ftruncate),
 
+    Fsync(syscalls::fsync::Fsync),
+    Fdatasync(syscalls::fsync::Fdatasync),
+
     Access(syscalls::access::Access),
     Faccessat(syscalls::access::Faccessat),
     Faccessat2(syscalls::access::Faccessat2),
@@ -252,6 +308,7 @@ pub enum DecodedSyscall {
     Munmap(syscalls::mmap::Munmap),
     Mprotect(syscalls::mmap::Mprotect),
     Madvise(syscalls::mmap::Madvise),
+    Msync(syscalls::mmap::Msync),
 
     Execve(syscalls::execve::Execve),
     Execveat(syscalls::execve::Execveat),
@@ -295,6 +352,14 @@ pub enum DecodedSyscall {
     Lseek(syscalls::lseek::Lseek),
     Llseek(syscalls::lseek::Llseek),
 
+    Dup(syscalls::dup::Dup),
+    Dup2(syscalls::dup::Dup2),
+    Dup3(syscalls::dup::Dup3),
+    Fcntl(syscalls::fcntl::Fcntl),
+
+    Accept(syscalls::socket::Accept),
+    Accept4(syscalls::socket::Accept4),
+
     ExitGroup(syscalls::exit::ExitGroup),
 
     /* ... */
@@ -304,81 +369,90 @@ impl DecodedSyscall {
     pub fn get_syscall(&self) -> &dyn std::any::Any
     {
         match self {
-            DecodedSyscall::Close(sc) => return sc,
-            DecodedSyscall::Creat(sc) => return sc,
-            DecodedSyscall::Open(sc) => return sc,
-            DecodedSyscall::Openat(sc) => return sc,
-            DecodedSyscall::Openat2(sc) => return sc,
-            DecodedSyscall::Read(sc) => return sc,
-            DecodedSyscall::Write(sc) => return sc,
-            DecodedSyscall::Readv(sc) => return sc,
-            DecodedSyscall::Writev(sc) => return sc,
-            DecodedSyscall::Pread(sc) => return sc,
-            DecodedSyscall::Pwrite(sc) => return sc,
-            DecodedSyscall::Preadv(sc) => return sc,
-            DecodedSyscall::Pwritev(sc) => return sc,
-            DecodedSyscall::Preadv2(sc) => return sc,
-            DecodedSyscall::Pwritev2(sc) => return sc,
-            DecodedSyscall::Ioctl(sc) => return sc,
-            DecodedSyscall::Brk(sc) => return sc,
-            DecodedSyscall::Sbrk(sc) => return sc,
-            DecodedSyscall::Mmap(sc) => return sc,
-            DecodedSyscall::Mremap(sc) => return sc,
-            DecodedSyscall::Munmap(sc) => return sc,
-            DecodedSyscall::Mprotect(sc) => return sc,
-            DecodedSyscall::Madvise(sc) => return sc,
-            DecodedSyscall::Execve(sc) => return sc,
-            DecodedSyscall::Execveat(sc) => return sc,
-            DecodedSyscall::Fallocate(sc) => return sc,
-            DecodedSyscall::NameToHandleAt(sc) => return sc,
-            DecodedSyscall::OpenByHandleAt(sc) => return sc,
-            DecodedSyscall::MemfdCreate(sc) => return sc,
-            DecodedSyscall::Mknod(sc) => return sc,
-            DecodedSyscall::Mknodat(sc) => return sc,
-            DecodedSyscall::Rename(sc) => return sc,
-            DecodedSyscall::Renameat(sc) => return sc,
-            DecodedSyscall::Renameat2(sc) => return sc,
-            DecodedSyscall::Truncate(sc) => return sc,
-            DecodedSyscall::Ftruncate(sc) => return sc,
-            DecodedSyscall::Access(sc) => return sc,
-            DecodedSyscall::Faccessat(sc) => return sc,
-            DecodedSyscall::Faccessat2(sc) => return sc,
-            DecodedSyscall::Prctl(sc) => return sc,
-            DecodedSyscall::ArchPrctl(sc) => return sc,
-            DecodedSyscall::Getdents(sc) => return sc,
-            DecodedSyscall::Getdents64(sc) => return sc,
-            DecodedSyscall::Readdir(sc) => return sc,
-            DecodedSyscall::Stat(sc) => return sc,
-            DecodedSyscall::Fstat(sc) => return sc,
-            DecodedSyscall::Lstat(sc) => return sc,
-            DecodedSyscall::Fstatat(sc) => return sc,
-            DecodedSyscall::Statx(sc) => return sc,
-            DecodedSyscall::Getrlimit(sc) => return sc,
-            DecodedSyscall::Setrlimit(sc) => return sc,
-            DecodedSyscall::Prlimit(sc) => return sc,
-            DecodedSyscall::Prlimit64(sc) => return sc,
-            DecodedSyscall::Getrusage(sc) => return sc,
-            DecodedSyscall::Rseq(sc) => return sc,
-            DecodedSyscall::Getrandom(sc) => return sc,
-            DecodedSyscall::EpollCreate(sc) => return sc,
-            DecodedSyscall::EpollCreate1(sc) => return sc,
-            DecodedSyscall::EpollCtl(sc) => return sc,
-            DecodedSyscall::EpollWait(sc) => return sc,
-            DecodedSyscall::EpollPwait(sc) => return sc,
-            DecodedSyscall::EpollPwait2(sc) => return sc,
-            DecodedSyscall::SetTidAddress(sc) => return sc,
-            DecodedSyscall::GetRobustList(sc) => return sc,
-            DecodedSyscall::SetRobustList(sc) => return sc,
-            DecodedSyscall::Lseek(sc) => return sc,
-            DecodedSyscall::Llseek(sc) => return sc,
-            DecodedSyscall::ExitGroup(sc) => return sc,
+            DecodedSyscall::Close(sc) => sc,
+            DecodedSyscall::Creat(sc) => sc,
+            DecodedSyscall::Open(sc) => sc,
+            DecodedSyscall::Openat(sc) => sc,
+            DecodedSyscall::Openat2(sc) => sc,
+            DecodedSyscall::Read(sc) => sc,
+            DecodedSyscall::Write(sc) => sc,
+            DecodedSyscall::Readv(sc) => sc,
+            DecodedSyscall::Writev(sc) => sc,
+            DecodedSyscall::Pread(sc) => sc,
+            DecodedSyscall::Pwrite(sc) => sc,
+            DecodedSyscall::Preadv(sc) => sc,
+            DecodedSyscall::Pwritev(sc) => sc,
+            DecodedSyscall::Preadv2(sc) => sc,
+            DecodedSyscall::Pwritev2(sc) => sc,
+            DecodedSyscall::Ioctl(sc) => sc,
+            DecodedSyscall::Brk(sc) => sc,
+            DecodedSyscall::Sbrk(sc) => sc,
+            DecodedSyscall::Mmap(sc) => sc,
+            DecodedSyscall::Mremap(sc) => sc,
+            DecodedSyscall::Munmap(sc) => sc,
+            DecodedSyscall::Mprotect(sc) => sc,
+            DecodedSyscall::Madvise(sc) => sc,
+            DecodedSyscall::Msync(sc) => sc,
+            DecodedSyscall::Execve(sc) => sc,
+            DecodedSyscall::Execveat(sc) => sc,
+            DecodedSyscall::Fallocate(sc) => sc,
+            DecodedSyscall::NameToHandleAt(sc) => sc,
+            DecodedSyscall::OpenByHandleAt(sc) => sc,
+            DecodedSyscall::MemfdCreate(sc) => sc,
+            DecodedSyscall::Mknod(sc) => sc,
+            DecodedSyscall::Mknodat(sc) => sc,
+            DecodedSyscall::Rename(sc) => sc,
+            DecodedSyscall::Renameat(sc) => sc,
+            DecodedSyscall::Renameat2(sc) => sc,
+            DecodedSyscall::Truncate(sc) => sc,
+            DecodedSyscall::Ftruncate(sc) => sc,
+            DecodedSyscall::Fsync(sc) => sc,
+            DecodedSyscall::Fdatasync(sc) => sc,
+            DecodedSyscall::Access(sc) => sc,
+            DecodedSyscall::Faccessat(sc) => sc,
+            DecodedSyscall::Faccessat2(sc) => sc,
+            DecodedSyscall::Prctl(sc) => sc,
+            DecodedSyscall::ArchPrctl(sc) => sc,
+            DecodedSyscall::Getdents(sc) => sc,
+            DecodedSyscall::Getdents64(sc) => sc,
+            DecodedSyscall::Readdir(sc) => sc,
+            DecodedSyscall::Stat(sc) => sc,
+            DecodedSyscall::Fstat(sc) => sc,
+            DecodedSyscall::Lstat(sc) => sc,
+            DecodedSyscall::Fstatat(sc) => sc,
+            DecodedSyscall::Statx(sc) => sc,
+            DecodedSyscall::Getrlimit(sc) => sc,
+            DecodedSyscall::Setrlimit(sc) => sc,
+            DecodedSyscall::Prlimit(sc) => sc,
+            DecodedSyscall::Prlimit64(sc) => sc,
+            DecodedSyscall::Getrusage(sc) => sc,
+            DecodedSyscall::Rseq(sc) => sc,
+            DecodedSyscall::Getrandom(sc) => sc,
+            DecodedSyscall::EpollCreate(sc) => sc,
+            DecodedSyscall::EpollCreate1(sc) => sc,
+            DecodedSyscall::EpollCtl(sc) => sc,
+            DecodedSyscall::EpollWait(sc) => sc,
+            DecodedSyscall::EpollPwait(sc) => sc,
+            DecodedSyscall::EpollPwait2(sc) => sc,
+            DecodedSyscall::SetTidAddress(sc) => sc,
+            DecodedSyscall::GetRobustList(sc) => sc,
+            DecodedSyscall::SetRobustList(sc) => sc,
+            DecodedSyscall::Lseek(sc) => sc,
+            DecodedSyscall::Llseek(sc) => sc,
+            DecodedSyscall::Dup(sc) => sc,
+            DecodedSyscall::Dup2(sc) => sc,
+            DecodedSyscall::Dup3(sc) => sc,
+            DecodedSyscall::Fcntl(sc) => sc,
+            DecodedSyscall::Accept(sc) => sc,
+            DecodedSyscall::Accept4(sc) => sc,
+            DecodedSyscall::ExitGroup(sc) => sc,
         }
 
     }
 }
 
 impl DecodeEntry for DecodedSyscall {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         /* Why not match on the syscall name? */
         match self {
             DecodedSyscall::Close(x) => x.decode_entry(pid, operation),
@@ -404,6 +478,7 @@ impl DecodeEntry for DecodedSyscall {
             DecodedSyscall::Munmap(x) => x.decode_entry(pid, operation),
             DecodedSyscall::Mprotect(x) => x.decode_entry(pid, operation),
             DecodedSyscall::Madvise(x) => x.decode_entry(pid, operation),
+            DecodedSyscall::Msync(x) => x.decode_entry(pid, operation),
             DecodedSyscall::Execve(x) => x.decode_entry(pid, operation),
             DecodedSyscall::Execveat(x) => x.decode_entry(pid, operation),
             DecodedSyscall::Fallocate(x) => x.decode_entry(pid, operation),
@@ -417,6 +492,8 @@ impl DecodeEntry for DecodedSyscall {
             DecodedSyscall::Renameat2(x) => x.decode_entry(pid, operation),
             DecodedSyscall::Truncate(x) => x.decode_entry(pid, operation),
             DecodedSyscall::Ftruncate(x) => x.decode_entry(pid, operation),
+            DecodedSyscall::Fsync(x) => x.decode_entry(pid, operation),
+            DecodedSyscall::Fdatasync(x) => x.decode_entry(pid, operation),
             DecodedSyscall::Access(x) => x.decode_entry(pid, operation),
             DecodedSyscall::Faccessat(x) => x.decode_entry(pid, operation),
             DecodedSyscall::Faccessat2(x) => x.decode_entry(pid, operation),
@@ -448,6 +525,12 @@ impl DecodeEntry for DecodedSyscall {
             DecodedSyscall::SetRobustList(x) => x.decode_entry(pid, operation),
             DecodedSyscall::Lseek(x) => x.decode_entry(pid, operation),
             DecodedSyscall::Llseek(x) => x.decode_entry(pid, operation),
+            DecodedSyscall::Dup(x) => x.decode_entry(pid, operation),
+            DecodedSyscall::Dup2(x) => x.decode_entry(pid, operation),
+            DecodedSyscall::Dup3(x) => x.decode_entry(pid, operation),
+            DecodedSyscall::Fcntl(x) => x.decode_entry(pid, operation),
+            DecodedSyscall::Accept(x) => x.decode_entry(pid, operation),
+            DecodedSyscall::Accept4(x) => x.decode_entry(pid, operation),
             DecodedSyscall::ExitGroup(x) => x.decode_entry(pid, operation),
             //DecodedSyscall::(x) => x.decode_entry(pid, operation),
         }
@@ -455,7 +538,7 @@ impl DecodeEntry for DecodedSyscall {
 }
 
 impl DecodeExit for DecodedSyscall {
-    fn decode_exit(&mut self, value: usize, pid: i32, operation: &Box<Operation>) -> Result<(), std::io::Error> { 
+    fn decode_exit(&mut self, value: usize, pid: i32, operation: &Operation) -> Result<(), std::io::Error> { 
         match self {
             DecodedSyscall::Open(x) => x.decode_exit(value, pid, operation),
             DecodedSyscall::Close(x) => x.decode_exit(value, pid, operation),
@@ -480,6 +563,7 @@ impl DecodeExit for DecodedSyscall {
             DecodedSyscall::Munmap(x) => x.decode_exit(value, pid, operation),
             DecodedSyscall::Mprotect(x) => x.decode_exit(value, pid, operation),
             DecodedSyscall::Madvise(x) => x.decode_exit(value, pid, operation),
+            DecodedSyscall::Msync(x) => x.decode_exit(value, pid, operation),
             DecodedSyscall::Execve(x) => x.decode_exit(value, pid, operation),
             DecodedSyscall::Execveat(x) => x.decode_exit(value, pid, operation),
             DecodedSyscall::Fallocate(x) => x.decode_exit(value, pid, operation),
@@ -493,6 +577,8 @@ impl DecodeExit for DecodedSyscall {
             DecodedSyscall::Renameat2(x) => x.decode_exit(value, pid, operation),
             DecodedSyscall::Truncate(x) => x.decode_exit(value, pid, operation),
             DecodedSyscall::Ftruncate(x) => x.decode_exit(value, pid, operation),
+            DecodedSyscall::Fsync(x) => x.decode_exit(value, pid, operation),
+            DecodedSyscall::Fdatasync(x) => x.decode_exit(value, pid, operation),
             DecodedSyscall::Access(x) => x.decode_exit(value, pid, operation),
             DecodedSyscall::Faccessat(x) => x.decode_exit(value, pid, operation),
             DecodedSyscall::Faccessat2(x) => x.decode_exit(value, pid, operation),
@@ -524,9 +610,265 @@ impl DecodeExit for DecodedSyscall {
             DecodedSyscall::SetRobustList(x) => x.decode_exit(value, pid, operation),
             DecodedSyscall::Lseek(x) => x.decode_exit(value, pid, operation),
             DecodedSyscall::Llseek(x) => x.decode_exit(value, pid, operation),
+            DecodedSyscall::Dup(x) => x.decode_exit(value, pid, operation),
+            DecodedSyscall::Dup2(x) => x.decode_exit(value, pid, operation),
+            DecodedSyscall::Dup3(x) => x.decode_exit(value, pid, operation),
+            DecodedSyscall::Fcntl(x) => x.decode_exit(value, pid, operation),
+            DecodedSyscall::Accept(x) => x.decode_exit(value, pid, operation),
+            DecodedSyscall::Accept4(x) => x.decode_exit(value, pid, operation),
             DecodedSyscall::ExitGroup(_) => Ok(()),
             //_ => panic!("oops"),
             //DecodedSyscall::(x) => x.decode_exit(value, pid, operation),
         }
     }
-}
\ No newline at end of file
+}
+
+impl FdArgs for DecodedSyscall {
+    fn fd_args(&mut self) -> Vec<(usize, &mut Fd)> {
+        match self {
+            DecodedSyscall::Close(x) => x.fd_args(),
+            DecodedSyscall::Creat(x) => x.fd_args(),
+            DecodedSyscall::Open(x) => x.fd_args(),
+            DecodedSyscall::Openat(x) => x.fd_args(),
+            DecodedSyscall::Openat2(x) => x.fd_args(),
+            DecodedSyscall::Read(x) => x.fd_args(),
+            DecodedSyscall::Write(x) => x.fd_args(),
+            DecodedSyscall::Readv(x) => x.fd_args(),
+            DecodedSyscall::Writev(x) => x.fd_args(),
+            DecodedSyscall::Pread(x) => x.fd_args(),
+            DecodedSyscall::Pwrite(x) => x.fd_args(),
+            DecodedSyscall::Preadv(x) => x.fd_args(),
+            DecodedSyscall::Pwritev(x) => x.fd_args(),
+            DecodedSyscall::Preadv2(x) => x.fd_args(),
+            DecodedSyscall::Pwritev2(x) => x.fd_args(),
+            DecodedSyscall::Ioctl(x) => x.fd_args(),
+            DecodedSyscall::Brk(x) => x.fd_args(),
+            DecodedSyscall::Sbrk(x) => x.fd_args(),
+            DecodedSyscall::Mmap(x) => x.fd_args(),
+            DecodedSyscall::Mremap(x) => x.fd_args(),
+            DecodedSyscall::Munmap(x) => x.fd_args(),
+            DecodedSyscall::Mprotect(x) => x.fd_args(),
+            DecodedSyscall::Madvise(x) => x.fd_args(),
+            DecodedSyscall::Msync(x) => x.fd_args(),
+            DecodedSyscall::Execve(x) => x.fd_args(),
+            DecodedSyscall::Execveat(x) => x.fd_args(),
+            DecodedSyscall::Fallocate(x) => x.fd_args(),
+            DecodedSyscall::NameToHandleAt(x) => x.fd_args(),
+            DecodedSyscall::OpenByHandleAt(x) => x.fd_args(),
+            DecodedSyscall::MemfdCreate(x) => x.fd_args(),
+            DecodedSyscall::Mknod(x) => x.fd_args(),
+            DecodedSyscall::Mknodat(x) => x.fd_args(),
+            DecodedSyscall::Rename(x) => x.fd_args(),
+            DecodedSyscall::Renameat(x) => x.fd_args(),
+            DecodedSyscall::Renameat2(x) => x.fd_args(),
+            DecodedSyscall::Truncate(x) => x.fd_args(),
+            DecodedSyscall::Ftruncate(x) => x.fd_args(),
+            DecodedSyscall::Fsync(x) => x.fd_args(),
+            DecodedSyscall::Fdatasync(x) => x.fd_args(),
+            DecodedSyscall::Access(x) => x.fd_args(),
+            DecodedSyscall::Faccessat(x) => x.fd_args(),
+            DecodedSyscall::Faccessat2(x) => x.fd_args(),
+            DecodedSyscall::Prctl(x) => x.fd_args(),
+            DecodedSyscall::ArchPrctl(x) => x.fd_args(),
+            DecodedSyscall::Getdents(x) => x.fd_args(),
+            DecodedSyscall::Getdents64(x) => x.fd_args(),
+            DecodedSyscall::Readdir(x) => x.fd_args(),
+            DecodedSyscall::Stat(x) => x.fd_args(),
+            DecodedSyscall::Fstat(x) => x.fd_args(),
+            DecodedSyscall::Lstat(x) => x.fd_args(),
+            DecodedSyscall::Fstatat(x) => x.fd_args(),
+            DecodedSyscall::Statx(x) => x.fd_args(),
+            DecodedSyscall::Getrlimit(x) => x.fd_args(),
+            DecodedSyscall::Setrlimit(x) => x.fd_args(),
+            DecodedSyscall::Prlimit(x) => x.fd_args(),
+            DecodedSyscall::Prlimit64(x) => x.fd_args(),
+            DecodedSyscall::Getrusage(x) => x.fd_args(),
+            DecodedSyscall::Rseq(x) => x.fd_args(),
+            DecodedSyscall::Getrandom(x) => x.fd_args(),
+            DecodedSyscall::EpollCreate(x) => x.fd_args(),
+            DecodedSyscall::EpollCreate1(x) => x.fd_args(),
+            DecodedSyscall::EpollCtl(x) => x.fd_args(),
+            DecodedSyscall::EpollWait(x) => x.fd_args(),
+            DecodedSyscall::EpollPwait(x) => x.fd_args(),
+            DecodedSyscall::EpollPwait2(x) => x.fd_args(),
+            DecodedSyscall::SetTidAddress(x) => x.fd_args(),
+            DecodedSyscall::GetRobustList(x) => x.fd_args(),
+            DecodedSyscall::SetRobustList(x) => x.fd_args(),
+            DecodedSyscall::Lseek(x) => x.fd_args(),
+            DecodedSyscall::Llseek(x) => x.fd_args(),
+            DecodedSyscall::Dup(x) => x.fd_args(),
+            DecodedSyscall::Dup2(x) => x.fd_args(),
+            DecodedSyscall::Dup3(x) => x.fd_args(),
+            DecodedSyscall::Fcntl(x) => x.fd_args(),
+            DecodedSyscall::Accept(x) => x.fd_args(),
+            DecodedSyscall::Accept4(x) => x.fd_args(),
+            DecodedSyscall::ExitGroup(x) => x.fd_args(),
+        }
+    }
+
+    fn returned_fd(&mut self) -> Option<&mut Fd> {
+        match self {
+            DecodedSyscall::Close(x) => x.returned_fd(),
+            DecodedSyscall::Creat(x) => x.returned_fd(),
+            DecodedSyscall::Open(x) => x.returned_fd(),
+            DecodedSyscall::Openat(x) => x.returned_fd(),
+            DecodedSyscall::Openat2(x) => x.returned_fd(),
+            DecodedSyscall::Read(x) => x.returned_fd(),
+            DecodedSyscall::Write(x) => x.returned_fd(),
+            DecodedSyscall::Readv(x) => x.returned_fd(),
+            DecodedSyscall::Writev(x) => x.returned_fd(),
+            DecodedSyscall::Pread(x) => x.returned_fd(),
+            DecodedSyscall::Pwrite(x) => x.returned_fd(),
+            DecodedSyscall::Preadv(x) => x.returned_fd(),
+            DecodedSyscall::Pwritev(x) => x.returned_fd(),
+            DecodedSyscall::Preadv2(x) => x.returned_fd(),
+            DecodedSyscall::Pwritev2(x) => x.returned_fd(),
+            DecodedSyscall::Ioctl(x) => x.returned_fd(),
+            DecodedSyscall::Brk(x) => x.returned_fd(),
+            DecodedSyscall::Sbrk(x) => x.returned_fd(),
+            DecodedSyscall::Mmap(x) => x.returned_fd(),
+            DecodedSyscall::Mremap(x) => x.returned_fd(),
+            DecodedSyscall::Munmap(x) => x.returned_fd(),
+            DecodedSyscall::Mprotect(x) => x.returned_fd(),
+            DecodedSyscall::Madvise(x) => x.returned_fd(),
+            DecodedSyscall::Msync(x) => x.returned_fd(),
+            DecodedSyscall::Execve(x) => x.returned_fd(),
+            DecodedSyscall::Execveat(x) => x.returned_fd(),
+            DecodedSyscall::Fallocate(x) => x.returned_fd(),
+            DecodedSyscall::NameToHandleAt(x) => x.returned_fd(),
+            DecodedSyscall::OpenByHandleAt(x) => x.returned_fd(),
+            DecodedSyscall::MemfdCreate(x) => x.returned_fd(),
+            DecodedSyscall::Mknod(x) => x.returned_fd(),
+            DecodedSyscall::Mknodat(x) => x.returned_fd(),
+            DecodedSyscall::Rename(x) => x.returned_fd(),
+            DecodedSyscall::Renameat(x) => x.returned_fd(),
+            DecodedSyscall::Renameat2(x) => x.returned_fd(),
+            DecodedSyscall::Truncate(x) => x.returned_fd(),
+            DecodedSyscall::Ftruncate(x) => x.returned_fd(),
+            DecodedSyscall::Fsync(x) => x.returned_fd(),
+            DecodedSyscall::Fdatasync(x) => x.returned_fd(),
+            DecodedSyscall::Access(x) => x.returned_fd(),
+            DecodedSyscall::Faccessat(x) => x.returned_fd(),
+            DecodedSyscall::Faccessat2(x) => x.returned_fd(),
+            DecodedSyscall::Prctl(x) => x.returned_fd(),
+            DecodedSyscall::ArchPrctl(x) => x.returned_fd(),
+            DecodedSyscall::Getdents(x) => x.returned_fd(),
+            DecodedSyscall::Getdents64(x) => x.returned_fd(),
+            DecodedSyscall::Readdir(x) => x.returned_fd(),
+            DecodedSyscall::Stat(x) => x.returned_fd(),
+            DecodedSyscall::Fstat(x) => x.returned_fd(),
+            DecodedSyscall::Lstat(x) => x.returned_fd(),
+            DecodedSyscall::Fstatat(x) => x.returned_fd(),
+            DecodedSyscall::Statx(x) => x.returned_fd(),
+            DecodedSyscall::Getrlimit(x) => x.returned_fd(),
+            DecodedSyscall::Setrlimit(x) => x.returned_fd(),
+            DecodedSyscall::Prlimit(x) => x.returned_fd(),
+            DecodedSyscall::Prlimit64(x) => x.returned_fd(),
+            DecodedSyscall::Getrusage(x) => x.returned_fd(),
+            DecodedSyscall::Rseq(x) => x.returned_fd(),
+            DecodedSyscall::Getrandom(x) => x.returned_fd(),
+            DecodedSyscall::EpollCreate(x) => x.returned_fd(),
+            DecodedSyscall::EpollCreate1(x) => x.returned_fd(),
+            DecodedSyscall::EpollCtl(x) => x.returned_fd(),
+            DecodedSyscall::EpollWait(x) => x.returned_fd(),
+            DecodedSyscall::EpollPwait(x) => x.returned_fd(),
+            DecodedSyscall::EpollPwait2(x) => x.returned_fd(),
+            DecodedSyscall::SetTidAddress(x) => x.returned_fd(),
+            DecodedSyscall::GetRobustList(x) => x.returned_fd(),
+            DecodedSyscall::SetRobustList(x) => x.returned_fd(),
+            DecodedSyscall::Lseek(x) => x.returned_fd(),
+            DecodedSyscall::Llseek(x) => x.returned_fd(),
+            DecodedSyscall::Dup(x) => x.returned_fd(),
+            DecodedSyscall::Dup2(x) => x.returned_fd(),
+            DecodedSyscall::Dup3(x) => x.returned_fd(),
+            DecodedSyscall::Fcntl(x) => x.returned_fd(),
+            DecodedSyscall::Accept(x) => x.returned_fd(),
+            DecodedSyscall::Accept4(x) => x.returned_fd(),
+            DecodedSyscall::ExitGroup(x) => x.returned_fd(),
+        }
+    }
+}
+
+impl PointerArgs for DecodedSyscall {
+    fn pointer_args(&mut self) -> Vec<&mut dyn Pointer> {
+        match self {
+            DecodedSyscall::Close(x) => x.pointer_args(),
+            DecodedSyscall::Creat(x) => x.pointer_args(),
+            DecodedSyscall::Open(x) => x.pointer_args(),
+            DecodedSyscall::Openat(x) => x.pointer_args(),
+            DecodedSyscall::Openat2(x) => x.pointer_args(),
+            DecodedSyscall::Read(x) => x.pointer_args(),
+            DecodedSyscall::Write(x) => x.pointer_args(),
+            DecodedSyscall::Readv(x) => x.pointer_args(),
+            DecodedSyscall::Writev(x) => x.pointer_args(),
+            DecodedSyscall::Pread(x) => x.pointer_args(),
+            DecodedSyscall::Pwrite(x) => x.pointer_args(),
+            DecodedSyscall::Preadv(x) => x.pointer_args(),
+            DecodedSyscall::Pwritev(x) => x.pointer_args(),
+            DecodedSyscall::Preadv2(x) => x.pointer_args(),
+            DecodedSyscall::Pwritev2(x) => x.pointer_args(),
+            DecodedSyscall::Ioctl(x) => x.pointer_args(),
+            DecodedSyscall::Brk(x) => x.pointer_args(),
+            DecodedSyscall::Sbrk(x) => x.pointer_args(),
+            DecodedSyscall::Mmap(x) => x.pointer_args(),
+            DecodedSyscall::Mremap(x) => x.pointer_args(),
+            DecodedSyscall::Munmap(x) => x.pointer_args(),
+            DecodedSyscall::Mprotect(x) => x.pointer_args(),
+            DecodedSyscall::Madvise(x) => x.pointer_args(),
+            DecodedSyscall::Msync(x) => x.pointer_args(),
+            DecodedSyscall::Execve(x) => x.pointer_args(),
+            DecodedSyscall::Execveat(x) => x.pointer_args(),
+            DecodedSyscall::Fallocate(x) => x.pointer_args(),
+            DecodedSyscall::NameToHandleAt(x) => x.pointer_args(),
+            DecodedSyscall::OpenByHandleAt(x) => x.pointer_args(),
+            DecodedSyscall::MemfdCreate(x) => x.pointer_args(),
+            DecodedSyscall::Mknod(x) => x.pointer_args(),
+            DecodedSyscall::Mknodat(x) => x.pointer_args(),
+            DecodedSyscall::Rename(x) => x.pointer_args(),
+            DecodedSyscall::Renameat(x) => x.pointer_args(),
+            DecodedSyscall::Renameat2(x) => x.pointer_args(),
+            DecodedSyscall::Truncate(x) => x.pointer_args(),
+            DecodedSyscall::Ftruncate(x) => x.pointer_args(),
+            DecodedSyscall::Fsync(x) => x.pointer_args(),
+            DecodedSyscall::Fdatasync(x) => x.pointer_args(),
+            DecodedSyscall::Access(x) => x.pointer_args(),
+            DecodedSyscall::Faccessat(x) => x.pointer_args(),
+            DecodedSyscall::Faccessat2(x) => x.pointer_args(),
+            DecodedSyscall::Prctl(x) => x.pointer_args(),
+            DecodedSyscall::ArchPrctl(x) => x.pointer_args(),
+            DecodedSyscall::Getdents(x) => x.pointer_args(),
+            DecodedSyscall::Getdents64(x) => x.pointer_args(),
+            DecodedSyscall::Readdir(x) => x.pointer_args(),
+            DecodedSyscall::Stat(x) => x.pointer_args(),
+            DecodedSyscall::Fstat(x) => x.pointer_args(),
+            DecodedSyscall::Lstat(x) => x.pointer_args(),
+            DecodedSyscall::Fstatat(x) => x.pointer_args(),
+            DecodedSyscall::Statx(x) => x.pointer_args(),
+            DecodedSyscall::Getrlimit(x) => x.pointer_args(),
+            DecodedSyscall::Setrlimit(x) => x.pointer_args(),
+            DecodedSyscall::Prlimit(x) => x.pointer_args(),
+            DecodedSyscall::Prlimit64(x) => x.pointer_args(),
+            DecodedSyscall::Getrusage(x) => x.pointer_args(),
+            DecodedSyscall::Rseq(x) => x.pointer_args(),
+            DecodedSyscall::Getrandom(x) => x.pointer_args(),
+            DecodedSyscall::EpollCreate(x) => x.pointer_args(),
+            DecodedSyscall::EpollCreate1(x) => x.pointer_args(),
+            DecodedSyscall::EpollCtl(x) => x.pointer_args(),
+            DecodedSyscall::EpollWait(x) => x.pointer_args(),
+            DecodedSyscall::EpollPwait(x) => x.pointer_args(),
+            DecodedSyscall::EpollPwait2(x) => x.pointer_args(),
+            DecodedSyscall::SetTidAddress(x) => x.pointer_args(),
+            DecodedSyscall::GetRobustList(x) => x.pointer_args(),
+            DecodedSyscall::SetRobustList(x) => x.pointer_args(),
+            DecodedSyscall::Lseek(x) => x.pointer_args(),
+            DecodedSyscall::Llseek(x) => x.pointer_args(),
+            DecodedSyscall::Dup(x) => x.pointer_args(),
+            DecodedSyscall::Dup2(x) => x.pointer_args(),
+            DecodedSyscall::Dup3(x) => x.pointer_args(),
+            DecodedSyscall::Fcntl(x) => x.pointer_args(),
+            DecodedSyscall::Accept(x) => x.pointer_args(),
+            DecodedSyscall::Accept4(x) => x.pointer_args(),
+            DecodedSyscall::ExitGroup(x) => x.pointer_args(),
+        }
+    }
+}
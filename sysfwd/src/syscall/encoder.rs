@@ -10,28 +10,36 @@ use crate::{
         RawSyscall,
         decoder::DecodedSyscall,
     },
-    targets::operation::Operation,
+    targets::operation::{ Operation, MemoryOperation },
+    executor::Scratch,
 };
 
 
 
 
-/* The trait implemented by each syscalls */
+/*
+ * The trait implemented by each syscalls.
+ * `operation` gives access to the process executing the syscall, while `remote` is used to fetch
+ * on demand the memory of the tracee which was not shipped with the syscall.
+ * The tracee memory is copied in the `scratch` memory of the process: the raw pointer arguments
+ * are rewritten to point to the copies while the decoded arguments keep the tracee addresses.
+ */
 
 pub trait EncodeArg {
-    fn encode(&mut self, _pid: i32, _operation: &Box<Operation>) -> Result<(), std::io::Error> { 
-        Ok(())
-    }
+    /*
+     * Return the value of the argument in the process executing the syscall.
+     */
+    fn encode(&mut self, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<usize, std::io::Error>;
 }
 
 pub trait EncodeEntry {
-    fn encode_entry(&mut self, raw: RawSyscall, _pid: i32, _operation: &Box<Operation>) -> Result<RawSyscall, std::io::Error> {
+    fn encode_entry(&mut self, raw: RawSyscall, _pid: i32, _operation: &Operation, _remote: &dyn MemoryOperation, _scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
         Ok(raw)
     }
 }
 
 pub trait EncodeExit {
-    fn encode_exit(&mut self, _value: usize, _pid: i32, _operation: &Box<Operation>) -> Result<(), std::io::Error> { 
+    fn encode_exit(&mut self, _value: usize, _pid: i32, _operation: &Operation) -> Result<(), std::io::Error> { 
         Ok(())
     }
 }
@@ -42,18 +50,52 @@ pub trait EncodeExit {
 
 impl EncodeEntry for DecodedSyscall {
 
-    fn encode_entry(&mut self, raw: RawSyscall, pid: i32, operation: &Box<Operation>) -> Result<RawSyscall, std::io::Error> {
+    fn encode_entry(&mut self, raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
         match self {
-            DecodedSyscall::Close(x) => x.encode_entry(raw, pid, operation),
-            DecodedSyscall::Creat(x) => x.encode_entry(raw, pid, operation),
-            DecodedSyscall::Open(x) => x.encode_entry(raw, pid, operation),
-            DecodedSyscall::Openat(x) => x.encode_entry(raw, pid, operation),
-            DecodedSyscall::Openat2(x) => x.encode_entry(raw, pid, operation),
-            DecodedSyscall::Read(x) => x.encode_entry(raw, pid, operation),
-            DecodedSyscall::Write(x) => x.encode_entry(raw, pid, operation),
-            DecodedSyscall::Lseek(x) => x.encode_entry(raw, pid, operation),
-            //DecodedSyscall::(sysforward/src/tracer/decision_handler.rsx) => x.encode_entry(raw, pid, operation),
-            _ => Err(std::io::Error::new(std::io::ErrorKind::Other, "Encode trait not implemented for this syscall")),
+            DecodedSyscall::Close(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Creat(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Open(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Openat(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Openat2(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Read(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Write(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Writev(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Lseek(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Pread(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Pwrite(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Ftruncate(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Fallocate(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::MemfdCreate(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::EpollCreate(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::EpollCreate1(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::EpollCtl(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Dup(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Dup2(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Dup3(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Fcntl(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Accept(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Accept4(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Mmap(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Munmap(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Msync(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Readv(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Preadv(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Pwritev(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Preadv2(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Pwritev2(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Fsync(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Fdatasync(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Ioctl(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Fstat(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Fstatat(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Statx(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Getdents64(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::EpollWait(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::EpollPwait(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::EpollPwait2(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            DecodedSyscall::Mremap(x) => x.encode_entry(raw, pid, operation, remote, scratch),
+            //DecodedSyscall::(sysforward/src/tracer/decision_handler.rsx) => x.encode_entry(raw, pid, operation, remote, scratch),
+            _ => Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "Encode trait not implemented for this syscall")),
         }
     }
 }
\ No newline at end of file
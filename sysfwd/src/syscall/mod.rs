@@ -34,6 +34,12 @@ pub struct Syscall {
     pub decision: Option<Decision>,
 }
 
+impl Default for Syscall {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Syscall {
     pub fn new() -> Self {
         Self {
@@ -61,6 +67,12 @@ pub struct RawSyscall {
     pub errno: usize,
 }
 
+impl Default for RawSyscall {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RawSyscall {
     pub fn new() -> Self {
         Self {
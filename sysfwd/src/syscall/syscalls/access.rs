@@ -2,7 +2,7 @@
  *
  */
 use serde::{ Serialize, Deserialize };
-use decoding_macro::DecodeExit;
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
 use crate::{
     syscall::RawSyscall,
     syscall::args::{ Direction, Integer, Fd, Flag, NullBuffer },
@@ -17,6 +17,8 @@ use crate::{
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Access {
     pub pathname: NullBuffer,
     pub mode: Integer,
@@ -31,7 +33,7 @@ impl Access {
     }
 }
 impl DecodeEntry for Access {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.pathname.decode(pid, operation).unwrap();
         self.mode.decode(pid, operation).unwrap();
     }
@@ -42,6 +44,8 @@ impl DecodeEntry for Access {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Faccessat {
     pub dirfd: Fd,
     pub pathname: NullBuffer,
@@ -60,7 +64,7 @@ impl Faccessat {
     }
 }
 impl DecodeEntry for Faccessat {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.dirfd.decode(pid, operation).unwrap();
         self.pathname.decode(pid, operation).unwrap();
         self.mode.decode(pid, operation).unwrap();
@@ -73,6 +77,8 @@ impl DecodeEntry for Faccessat {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Faccessat2 {
     pub dirfd: Fd,
     pub pathname: NullBuffer,
@@ -91,7 +97,7 @@ impl Faccessat2 {
     }
 }
 impl DecodeEntry for Faccessat2 {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.dirfd.decode(pid, operation).unwrap();
         self.pathname.decode(pid, operation).unwrap();
         self.mode.decode(pid, operation).unwrap();
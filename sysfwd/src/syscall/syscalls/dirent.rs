@@ -2,12 +2,16 @@
  *
  */
 use serde::{ Serialize, Deserialize };
-use decoding_macro::DecodeExit;
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
 use crate::{
     syscall::RawSyscall,
     syscall::args::{ Direction, Integer, Fd, Size, Struct },
-    syscall::decoder::{ DecodeArg, DecodeEntry, DecodeExit },
-    targets::operation::Operation,
+    syscall::{
+        decoder::{ DecodeArg, DecodeEntry, DecodeExit },
+        encoder::{ EncodeArg, EncodeEntry },
+    },
+    targets::operation::{ Operation, MemoryOperation },
+    executor::Scratch,
 };
 
 
@@ -15,6 +19,8 @@ use crate::{
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Getdents {
     pub fd: Fd,
     pub dirp: Struct,
@@ -31,7 +37,7 @@ impl Getdents {
     }
 }
 impl DecodeEntry for Getdents {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.fd.decode(pid, operation).unwrap();
         self.dirp.decode(pid, operation).unwrap();
         self.count.decode(pid, operation).unwrap();
@@ -42,7 +48,8 @@ impl DecodeEntry for Getdents {
 // ssize_t getdents64(int fd, void dirp[.count], size_t count)
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
-#[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Getdents64 {
     pub fd: Fd,
     pub dirp: Struct,
@@ -52,25 +59,47 @@ pub struct Getdents64 {
 impl Getdents64 {
     pub fn new(raw: RawSyscall) -> Self {
         let fd = Fd::new(raw.args[0]);
-        let dirp = Struct::new(raw.args[1], Direction::In);
+        let mut dirp = Struct::new(raw.args[1], Direction::In);
+        dirp.size = raw.args[2];
         let count = Integer::new(raw.args[2]);
         let retval = None;
         Self { fd, dirp, count, retval }
     }
 }
 impl DecodeEntry for Getdents64 {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.fd.decode(pid, operation).unwrap();
         self.dirp.decode(pid, operation).unwrap();
         self.count.decode(pid, operation).unwrap();
     }
 }
+impl EncodeEntry for Getdents64 {
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.fd.value;
+        raw.args[1] = self.dirp.encode(pid, operation, remote, scratch)?;
+        raw.args[2] = self.count.value;
+        Ok(raw)
+    }
+}
+impl DecodeExit for Getdents64 {
+    fn decode_exit(&mut self, value: usize, _pid: i32, _operation: &Operation) -> Result<(), std::io::Error> {
+        self.retval = Some(Size::from(value));
+
+        // Only the entries read are filled by the syscall
+        if (value as isize) >= 0 {
+            self.dirp.size = std::cmp::min(value, self.count.value);
+        }
+        Ok(())
+    }
+}
 
 
 // int syscall(SYS_readdir, unsigned int fd, struct old_linux_dirent *dirp, unsigned int count)
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Readdir {
     pub fd: Fd,
     pub dirp: Struct,
@@ -87,7 +116,7 @@ impl Readdir {
     }
 }
 impl DecodeEntry for Readdir {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.fd.decode(pid, operation).unwrap();
         self.dirp.decode(pid, operation).unwrap();
         self.count.decode(pid, operation).unwrap();
@@ -0,0 +1,117 @@
+/*
+ *
+ */
+use serde::{ Serialize, Deserialize };
+
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
+use crate::{
+    syscall::RawSyscall,
+    syscall::args::{ Integer, Fd },
+    syscall::{
+        decoder::{ DecodeArg, DecodeEntry, DecodeExit },
+        encoder::EncodeEntry,
+    },
+    targets::operation::{ Operation, MemoryOperation },
+    executor::Scratch,
+};
+
+
+
+// int dup(int oldfd);
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug)]
+#[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
+pub struct Dup {
+    pub oldfd: Fd,
+    pub retval: Option<Fd>,
+}
+impl Dup {
+    pub fn new(raw: RawSyscall) -> Self {
+        let oldfd = Fd::new(raw.args[0]);
+        let retval = None;
+        Self { oldfd, retval }
+    }
+}
+impl DecodeEntry for Dup {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
+        self.oldfd.decode(pid, operation).unwrap();
+    }
+}
+impl EncodeEntry for Dup {
+    fn encode_entry(&mut self, mut raw: RawSyscall, _pid: i32, _operation: &Operation, _remote: &dyn MemoryOperation, _scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.oldfd.value;
+        Ok(raw)
+    }
+}
+
+// int dup2(int oldfd, int newfd);
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug)]
+#[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
+pub struct Dup2 {
+    pub oldfd: Fd,
+    pub newfd: Fd,
+    pub retval: Option<Fd>,
+}
+impl Dup2 {
+    pub fn new(raw: RawSyscall) -> Self {
+        let oldfd = Fd::new(raw.args[0]);
+        let newfd = Fd::new(raw.args[1]);
+        let retval = None;
+        Self { oldfd, newfd, retval }
+    }
+}
+impl DecodeEntry for Dup2 {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
+        self.oldfd.decode(pid, operation).unwrap();
+        self.newfd.decode(pid, operation).unwrap();
+    }
+}
+impl EncodeEntry for Dup2 {
+    fn encode_entry(&mut self, mut raw: RawSyscall, _pid: i32, _operation: &Operation, _remote: &dyn MemoryOperation, _scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.oldfd.value;
+        raw.args[1] = self.newfd.value;
+        Ok(raw)
+    }
+}
+
+// int dup3(int oldfd, int newfd, int flags);
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug)]
+#[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
+pub struct Dup3 {
+    pub oldfd: Fd,
+    pub newfd: Fd,
+    pub flags: Integer,
+    pub retval: Option<Fd>,
+}
+impl Dup3 {
+    pub fn new(raw: RawSyscall) -> Self {
+        let oldfd = Fd::new(raw.args[0]);
+        let newfd = Fd::new(raw.args[1]);
+        let flags = Integer::new(raw.args[2]);
+        let retval = None;
+        Self { oldfd, newfd, flags, retval }
+    }
+}
+impl DecodeEntry for Dup3 {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
+        self.oldfd.decode(pid, operation).unwrap();
+        self.newfd.decode(pid, operation).unwrap();
+        self.flags.decode(pid, operation).unwrap();
+    }
+}
+impl EncodeEntry for Dup3 {
+    fn encode_entry(&mut self, mut raw: RawSyscall, _pid: i32, _operation: &Operation, _remote: &dyn MemoryOperation, _scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.oldfd.value;
+        raw.args[1] = self.newfd.value;
+        raw.args[2] = self.flags.value;
+        Ok(raw)
+    }
+}
@@ -3,23 +3,33 @@
  */
 use serde::{ Serialize, Deserialize };
 
-use decoding_macro::DecodeExit;
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
 use crate::{
     syscall::RawSyscall,
     syscall::args::{ Direction, Integer, Fd, Struct },
-    syscall::decoder::{ DecodeArg, DecodeEntry, DecodeExit },
-    targets::operation::Operation,
+    syscall::{
+        decoder::{ DecodeArg, DecodeEntry, DecodeExit },
+        encoder::{ EncodeArg, EncodeEntry },
+    },
+    targets::operation::{ Operation, MemoryOperation },
+    executor::Scratch,
 };
 
+/* struct epoll_event { uint32_t events; epoll_data_t data; } packed on x86_64 */
+const EPOLL_EVENT_SIZE: usize = 12;
+/* struct timespec only for x86_64 */
+const TIMESPEC_SIZE: usize = 16;
 
 
 // int epoll_create(int size);
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct EpollCreate {
     pub size: Integer,
-    pub retval: Option<Integer>,
+    pub retval: Option<Fd>,
 }
 impl EpollCreate {
     pub fn new(raw: RawSyscall) -> Self {
@@ -29,18 +39,26 @@ impl EpollCreate {
     }
 }
 impl DecodeEntry for EpollCreate {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.size.decode(pid, operation).unwrap();
     }
 }
+impl EncodeEntry for EpollCreate {
+    fn encode_entry(&mut self, mut raw: RawSyscall, _pid: i32, _operation: &Operation, _remote: &dyn MemoryOperation, _scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.size.value;
+        Ok(raw)
+    }
+}
 
 // int epoll_create1(int size);
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct EpollCreate1 {
     pub size: Integer,
-    pub retval: Option<Integer>,
+    pub retval: Option<Fd>,
 }
 impl EpollCreate1 {
     pub fn new(raw: RawSyscall) -> Self {
@@ -50,15 +68,23 @@ impl EpollCreate1 {
     }
 }
 impl DecodeEntry for EpollCreate1 {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.size.decode(pid, operation).unwrap();
     }
 }
+impl EncodeEntry for EpollCreate1 {
+    fn encode_entry(&mut self, mut raw: RawSyscall, _pid: i32, _operation: &Operation, _remote: &dyn MemoryOperation, _scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.size.value;
+        Ok(raw)
+    }
+}
 
 // int epoll_ctl(int epfd, int op, int fd, struct epoll_event *_Nullable event);
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct EpollCtl {
     pub epfd: Fd,
     pub op: Integer,
@@ -71,24 +97,37 @@ impl EpollCtl {
         let epfd = Fd::new(raw.args[0]);
         let op = Integer::new(raw.args[1]);
         let fd = Fd::new(raw.args[2]);
-        let event = Struct::new(raw.args[3], Direction::InOut);
+        let mut event = Struct::new(raw.args[3], Direction::Out);
+        event.size = EPOLL_EVENT_SIZE;
         let retval = None;
         Self { epfd, op, fd, event, retval }
     }
 }
 impl DecodeEntry for EpollCtl {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.epfd.decode(pid, operation).unwrap();
         self.op.decode(pid, operation).unwrap();
         self.fd.decode(pid, operation).unwrap();
         self.event.decode(pid, operation).unwrap();
     }
 }
+impl EncodeEntry for EpollCtl {
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.epfd.value;
+        raw.args[1] = self.op.value;
+        raw.args[2] = self.fd.value;
+        // The event is ignored by EPOLL_CTL_DEL, it may be NULL
+        self.event.size = EPOLL_EVENT_SIZE;
+        raw.args[3] = self.event.encode(pid, operation, remote, scratch)?;
+        Ok(raw)
+    }
+}
 
 // int epoll_wait(int epfd, struct epoll_event *events, int maxevents, int timeout);
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
-#[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct EpollWait {
     pub epfd: Fd,
     pub events: Struct,
@@ -99,7 +138,8 @@ pub struct EpollWait {
 impl EpollWait {
     pub fn new(raw: RawSyscall) -> Self {
         let epfd = Fd::new(raw.args[0]);
-        let events = Struct::new(raw.args[1], Direction::InOut);
+        let mut events = Struct::new(raw.args[1], Direction::In);
+        events.size = raw.args[2] * EPOLL_EVENT_SIZE;
         let maxevents = Integer::new(raw.args[2]);
         let timeout = Integer::new(raw.args[3]);
         let retval = None;
@@ -107,18 +147,35 @@ impl EpollWait {
     }
 }
 impl DecodeEntry for EpollWait {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.epfd.decode(pid, operation).unwrap();
         self.events.decode(pid, operation).unwrap();
         self.maxevents.decode(pid, operation).unwrap();
         self.timeout.decode(pid, operation).unwrap();
     }
 }
+impl EncodeEntry for EpollWait {
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.epfd.value;
+        raw.args[1] = self.events.encode(pid, operation, remote, scratch)?;
+        raw.args[2] = self.maxevents.value;
+        raw.args[3] = self.timeout.value;
+        Ok(raw)
+    }
+}
+impl DecodeExit for EpollWait {
+    fn decode_exit(&mut self, value: usize, _pid: i32, _operation: &Operation) -> Result<(), std::io::Error> {
+        self.retval = Some(Integer::from(value));
+        bound_events(&mut self.events, value);
+        Ok(())
+    }
+}
 
 // int epoll_pwait(int epfd, struct epoll_event *events, int maxevents, int timeout, const sigset_t *_Nullable sigmask);
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
-#[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct EpollPwait {
     pub epfd: Fd,
     pub events: Struct,
@@ -130,16 +187,18 @@ pub struct EpollPwait {
 impl EpollPwait {
     pub fn new(raw: RawSyscall) -> Self {
         let epfd = Fd::new(raw.args[0]);
-        let events = Struct::new(raw.args[1], Direction::InOut);
+        let mut events = Struct::new(raw.args[1], Direction::In);
+        events.size = raw.args[2] * EPOLL_EVENT_SIZE;
         let maxevents = Integer::new(raw.args[2]);
         let timeout = Integer::new(raw.args[3]);
-        let sigmask = Struct::new(raw.args[4], Direction::In);
+        let mut sigmask = Struct::new(raw.args[4], Direction::Out);
+        sigmask.size = raw.args[5];
         let retval = None;
         Self { epfd, events, maxevents, timeout, sigmask, retval }
     }
 }
 impl DecodeEntry for EpollPwait {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.epfd.decode(pid, operation).unwrap();
         self.events.decode(pid, operation).unwrap();
         self.maxevents.decode(pid, operation).unwrap();
@@ -147,11 +206,29 @@ impl DecodeEntry for EpollPwait {
         self.sigmask.decode(pid, operation).unwrap();
     }
 }
+impl EncodeEntry for EpollPwait {
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.epfd.value;
+        raw.args[1] = self.events.encode(pid, operation, remote, scratch)?;
+        raw.args[2] = self.maxevents.value;
+        raw.args[3] = self.timeout.value;
+        raw.args[4] = self.sigmask.encode(pid, operation, remote, scratch)?;
+        Ok(raw)
+    }
+}
+impl DecodeExit for EpollPwait {
+    fn decode_exit(&mut self, value: usize, _pid: i32, _operation: &Operation) -> Result<(), std::io::Error> {
+        self.retval = Some(Integer::from(value));
+        bound_events(&mut self.events, value);
+        Ok(())
+    }
+}
 
 // int epoll_pwait2(int epfd, struct epoll_event *events, int maxevents, const struct timespec *_Nullable timeout, const sigset_t *_Nullable sigmask);
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
-#[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct EpollPwait2 {
     pub epfd: Fd,
     pub events: Struct,
@@ -163,16 +240,19 @@ pub struct EpollPwait2 {
 impl EpollPwait2 {
     pub fn new(raw: RawSyscall) -> Self {
         let epfd = Fd::new(raw.args[0]);
-        let events = Struct::new(raw.args[1], Direction::InOut);
+        let mut events = Struct::new(raw.args[1], Direction::In);
+        events.size = raw.args[2] * EPOLL_EVENT_SIZE;
         let maxevents = Integer::new(raw.args[2]);
-        let timeout = Struct::new(raw.args[3], Direction::In);
-        let sigmask = Struct::new(raw.args[4], Direction::In);
+        let mut timeout = Struct::new(raw.args[3], Direction::Out);
+        timeout.size = TIMESPEC_SIZE;
+        let mut sigmask = Struct::new(raw.args[4], Direction::Out);
+        sigmask.size = raw.args[5];
         let retval = None;
         Self { epfd, events, maxevents, timeout, sigmask, retval }
     }
 }
 impl DecodeEntry for EpollPwait2 {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.epfd.decode(pid, operation).unwrap();
         self.events.decode(pid, operation).unwrap();
         self.maxevents.decode(pid, operation).unwrap();
@@ -180,3 +260,32 @@ impl DecodeEntry for EpollPwait2 {
         self.sigmask.decode(pid, operation).unwrap();
     }
 }
+impl EncodeEntry for EpollPwait2 {
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.epfd.value;
+        raw.args[1] = self.events.encode(pid, operation, remote, scratch)?;
+        raw.args[2] = self.maxevents.value;
+        raw.args[3] = self.timeout.encode(pid, operation, remote, scratch)?;
+        raw.args[4] = self.sigmask.encode(pid, operation, remote, scratch)?;
+        Ok(raw)
+    }
+}
+impl DecodeExit for EpollPwait2 {
+    fn decode_exit(&mut self, value: usize, _pid: i32, _operation: &Operation) -> Result<(), std::io::Error> {
+        self.retval = Some(Integer::from(value));
+        bound_events(&mut self.events, value);
+        Ok(())
+    }
+}
+
+
+
+/*
+ * Only the events returned are filled by the syscall.
+ */
+fn bound_events(events: &mut Struct, count: usize)
+{
+    if (count as isize) >= 0 {
+        events.size = std::cmp::min(events.size, count * EPOLL_EVENT_SIZE);
+    }
+}
@@ -2,7 +2,7 @@
  *
  */
 use serde::{ Serialize, Deserialize };
-use decoding_macro::DecodeExit;
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
 use crate::{
     syscall::RawSyscall,
     syscall::args::{ Direction, Integer, Fd, Flag, Address, NullBuffer },
@@ -16,6 +16,8 @@ use crate::{
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Execve {
     pub pathname: NullBuffer,
     pub argv: Address,
@@ -32,7 +34,7 @@ impl Execve {
     }
 }
 impl DecodeEntry for Execve {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.pathname.decode(pid, operation).unwrap();
         self.argv.decode(pid, operation).unwrap();
         self.envp.decode(pid, operation).unwrap();
@@ -43,6 +45,8 @@ impl DecodeEntry for Execve {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Execveat {
     pub dirfd: Fd,
     pub pathname: NullBuffer,
@@ -54,16 +58,16 @@ pub struct Execveat {
 impl Execveat {
     pub fn new(raw: RawSyscall) -> Self {
         let dirfd = Fd::new(raw.args[0]);
-        let pathname = NullBuffer::new(raw.args[0], Direction::In);
-        let argv = Address::new(raw.args[1], Direction::In);
-        let envp = Address::new(raw.args[2], Direction::In);
+        let pathname = NullBuffer::new(raw.args[1], Direction::In);
+        let argv = Address::new(raw.args[2], Direction::In);
+        let envp = Address::new(raw.args[3], Direction::In);
         let flags = Flag::new(raw.args[4]);
         let retval = None;
         Self { dirfd, pathname, argv, envp, flags, retval }
     }
 }
 impl DecodeEntry for Execveat {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.dirfd.decode(pid, operation).unwrap();
         self.pathname.decode(pid, operation).unwrap();
         self.argv.decode(pid, operation).unwrap();
@@ -5,6 +5,7 @@
 use serde::{ Serialize, Deserialize };
 
 //use decoding_macro::DecodeExit;
+use decoding_macro::{ FdArgs, PointerArgs };
 use crate::{
     syscall::RawSyscall,
     syscall::args::Integer,
@@ -18,6 +19,8 @@ use crate::{
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 //#[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct ExitGroup {
     pub status: Integer,
     pub retval: Option<Integer>,
@@ -30,7 +33,7 @@ impl ExitGroup {
     }
 }
 impl DecodeEntry for ExitGroup {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.status.decode(pid, operation).unwrap();
     }
 }
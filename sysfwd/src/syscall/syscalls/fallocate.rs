@@ -2,12 +2,16 @@
  */
 use serde::{ Serialize, Deserialize };
 
-use decoding_macro::DecodeExit;
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
 use crate::{
     syscall::RawSyscall,
     syscall::args::{ Integer, Fd, Offset},
-    syscall::decoder::{ DecodeArg, DecodeEntry, DecodeExit },
-    targets::operation::Operation,
+    syscall::{
+        decoder::{ DecodeArg, DecodeEntry, DecodeExit },
+        encoder::EncodeEntry,
+    },
+    targets::operation::{ Operation, MemoryOperation },
+    executor::Scratch,
 };
 
 
@@ -16,6 +20,8 @@ use crate::{
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Fallocate {
     pub fd: Fd,
     pub mode: Integer,
@@ -36,10 +42,19 @@ impl Fallocate {
 }
 
 impl DecodeEntry for Fallocate {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.fd.decode(pid, operation).unwrap();
         self.mode.decode(pid, operation).unwrap();
         self.offset.decode(pid, operation).unwrap();
         self.len.decode(pid, operation).unwrap();
     }
-}
\ No newline at end of file
+}
+impl EncodeEntry for Fallocate {
+    fn encode_entry(&mut self, mut raw: RawSyscall, _pid: i32, _operation: &Operation, _remote: &dyn MemoryOperation, _scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.fd.value;
+        raw.args[1] = self.mode.value;
+        raw.args[2] = self.offset.value;
+        raw.args[3] = self.len.value;
+        Ok(raw)
+    }
+}
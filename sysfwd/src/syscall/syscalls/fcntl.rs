@@ -0,0 +1,73 @@
+/*
+ *
+ */
+use nix::libc;
+use serde::{ Serialize, Deserialize };
+
+use decoding_macro::{ DecodeExit, PointerArgs };
+use crate::{
+    syscall::RawSyscall,
+    syscall::args::{ Integer, Fd },
+    syscall::{
+        decoder::{ DecodeArg, DecodeEntry, DecodeExit, FdArgs },
+        encoder::EncodeEntry,
+    },
+    targets::operation::{ Operation, MemoryOperation },
+    executor::Scratch,
+};
+
+
+
+// int fcntl(int fd, int cmd, ... /* arg */ );
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug)]
+#[derive(DecodeExit)]
+#[derive(PointerArgs)]
+pub struct Fcntl {
+    pub fd: Fd,
+    pub cmd: Integer,
+    pub arg: Integer,
+    pub retval: Option<Fd>,
+}
+impl Fcntl {
+    pub fn new(raw: RawSyscall) -> Self {
+        let fd = Fd::new(raw.args[0]);
+        let cmd = Integer::new(raw.args[1]);
+        let arg = Integer::new(raw.args[2]);
+        let retval = None;
+        Self { fd, cmd, arg, retval }
+    }
+
+    /* Only F_DUPFD and F_DUPFD_CLOEXEC return a fd, the other commands a value */
+    fn is_dup(&self) -> bool
+    {
+        matches!(self.cmd.value as i32, libc::F_DUPFD | libc::F_DUPFD_CLOEXEC)
+    }
+}
+impl DecodeEntry for Fcntl {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
+        self.fd.decode(pid, operation).unwrap();
+        self.cmd.decode(pid, operation).unwrap();
+        self.arg.decode(pid, operation).unwrap();
+    }
+}
+impl EncodeEntry for Fcntl {
+    fn encode_entry(&mut self, mut raw: RawSyscall, _pid: i32, _operation: &Operation, _remote: &dyn MemoryOperation, _scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.fd.value;
+        raw.args[1] = self.cmd.value;
+        raw.args[2] = self.arg.value;
+        Ok(raw)
+    }
+}
+impl FdArgs for Fcntl {
+    fn fd_args(&mut self) -> Vec<(usize, &mut Fd)> {
+        vec![(0, &mut self.fd)]
+    }
+
+    fn returned_fd(&mut self) -> Option<&mut Fd> {
+        match self.is_dup() {
+            true => self.retval.as_mut(),
+            false => None,
+        }
+    }
+}
@@ -2,7 +2,7 @@
  */
 use serde::{ Serialize, Deserialize };
 
-use decoding_macro::DecodeExit;
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
 use crate::{
     syscall::RawSyscall,
     syscall::args::{ Direction, Integer, Fd, Flag, Address, NullBuffer, Struct },
@@ -17,6 +17,8 @@ use crate::{
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct NameToHandleAt {
     pub dirfd: Fd,
     pub pathname: NullBuffer,
@@ -37,7 +39,7 @@ impl NameToHandleAt {
     }
 }
 impl DecodeEntry for NameToHandleAt {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.dirfd.decode(pid, operation).unwrap();
         self.pathname.decode(pid, operation).unwrap();
         self.handle.decode(pid, operation).unwrap();
@@ -50,6 +52,8 @@ impl DecodeEntry for NameToHandleAt {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct OpenByHandleAt {
     pub mount_fd: Fd,
     pub handle: Struct,
@@ -66,7 +70,7 @@ impl OpenByHandleAt {
     }
 }
 impl DecodeEntry for OpenByHandleAt {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.mount_fd.decode(pid, operation).unwrap();
         self.handle.decode(pid, operation).unwrap();
         self.flags.decode(pid, operation).unwrap();
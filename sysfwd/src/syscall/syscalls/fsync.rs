@@ -0,0 +1,81 @@
+/*
+ *
+ */
+use serde::{ Serialize, Deserialize };
+
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
+use crate::{
+    syscall::RawSyscall,
+    syscall::args::{ Integer, Fd },
+    syscall::{
+        decoder::{ DecodeArg, DecodeEntry, DecodeExit },
+        encoder::EncodeEntry,
+    },
+    targets::operation::{ Operation, MemoryOperation },
+    executor::Scratch,
+};
+
+
+
+// int fsync(int fd)
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug)]
+#[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
+pub struct Fsync {
+    pub fd: Fd,
+    pub retval: Option<Integer>,
+}
+
+impl Fsync {
+    pub fn new(raw: RawSyscall) -> Self {
+        let fd = Fd::new(raw.args[0]);
+        let retval = None;
+        Self { fd, retval }
+    }
+}
+
+impl DecodeEntry for Fsync {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
+        self.fd.decode(pid, operation).unwrap();
+    }
+}
+impl EncodeEntry for Fsync {
+    fn encode_entry(&mut self, mut raw: RawSyscall, _pid: i32, _operation: &Operation, _remote: &dyn MemoryOperation, _scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.fd.value;
+        Ok(raw)
+    }
+}
+
+
+// int fdatasync(int fd)
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug)]
+#[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
+pub struct Fdatasync {
+    pub fd: Fd,
+    pub retval: Option<Integer>,
+}
+
+impl Fdatasync {
+    pub fn new(raw: RawSyscall) -> Self {
+        let fd = Fd::new(raw.args[0]);
+        let retval = None;
+        Self { fd, retval }
+    }
+}
+
+impl DecodeEntry for Fdatasync {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
+        self.fd.decode(pid, operation).unwrap();
+    }
+}
+impl EncodeEntry for Fdatasync {
+    fn encode_entry(&mut self, mut raw: RawSyscall, _pid: i32, _operation: &Operation, _remote: &dyn MemoryOperation, _scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.fd.value;
+        Ok(raw)
+    }
+}
@@ -3,7 +3,7 @@
  */
 use serde::{ Serialize, Deserialize };
 
-use decoding_macro::DecodeExit;
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
 use crate::{
     syscall::RawSyscall,
     syscall::args::{ Direction, Integer, Buffer, Size, Flag },
@@ -17,6 +17,8 @@ use crate::{
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Getrandom {
     pub buf: Buffer,
     pub buflen: Size,
@@ -26,7 +28,7 @@ pub struct Getrandom {
 
 impl Getrandom {
     pub fn new(raw: RawSyscall) -> Self {
-        let buf = Buffer::new(raw.args[0], Direction::Out, raw.args[1]);
+        let buf = Buffer::new(raw.args[0], Direction::In, raw.args[1]);
         let buflen = Size::new(raw.args[1]);
         let flags = Flag::new(raw.args[2]);
         let retval = None;
@@ -35,7 +37,7 @@ impl Getrandom {
 }
 
 impl DecodeEntry for Getrandom {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.buf.decode(pid, operation).unwrap();
         self.buflen.decode(pid, operation).unwrap();
         self.flags.decode(pid, operation).unwrap();
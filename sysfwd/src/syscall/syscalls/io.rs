@@ -3,7 +3,7 @@
  */
 use serde::{ Serialize, Deserialize };
 
-use decoding_macro::DecodeExit;
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
 use crate::{
     syscall::RawSyscall,
     syscall::args::{ Direction, Integer, Fd, Size, Offset, Flag, Buffer, Struct },
@@ -11,14 +11,16 @@ use crate::{
         decoder::{ DecodeArg, DecodeEntry, DecodeExit },
         encoder::{ EncodeArg, EncodeEntry },
     },
-    targets::operation::Operation,
+    targets::operation::{ Operation, MemoryOperation },
+    executor::Scratch,
 };
 
 
 // ssize_t read(int fd, void buf[.count], size_t count)
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
-#[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Read{
     pub fd: Fd,
     pub buf: Buffer,
@@ -35,26 +37,38 @@ impl Read {
     }
 }
 impl DecodeEntry for Read {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.fd.decode(pid, operation).unwrap();
         self.buf.decode(pid, operation).unwrap();
         self.count.decode(pid, operation).unwrap();
     }
 }
 impl EncodeEntry for Read {
-    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Box<Operation>) -> Result<RawSyscall, std::io::Error> {
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
         raw.args[0] = self.fd.value;
-        raw.args[1] = self.buf.address;
-        self.buf.encode(pid, operation).unwrap();
+        raw.args[1] = self.buf.encode(pid, operation, remote, scratch)?;
         raw.args[2] = self.count.value;
         Ok(raw)
     }
 }
+impl DecodeExit for Read {
+    fn decode_exit(&mut self, value: usize, _pid: i32, _operation: &Operation) -> Result<(), std::io::Error> {
+        self.retval = Some(Size::from(value));
+
+        // Only the bytes read are filled by the syscall
+        if (value as isize) >= 0 {
+            self.buf.size = std::cmp::min(value, self.count.value);
+        }
+        Ok(())
+    }
+}
 
 // ssize_t write(int fd, const void buf[.count], size_t count)
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Write{
     pub fd: Fd,
     pub buf: Buffer,
@@ -71,17 +85,16 @@ impl Write {
     }
 }
 impl DecodeEntry for Write {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.fd.decode(pid, operation).unwrap();
         self.buf.decode(pid, operation).unwrap();
         self.count.decode(pid, operation).unwrap();
     }
 }
 impl EncodeEntry for Write {
-    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Box<Operation>) -> Result<RawSyscall, std::io::Error> {
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
         raw.args[0] = self.fd.value;
-        raw.args[1] = self.buf.address;
-        self.buf.encode(pid, operation).unwrap();
+        raw.args[1] = self.buf.encode(pid, operation, remote, scratch)?;
         raw.args[2] = self.count.value;
         Ok(raw)
     }
@@ -90,34 +103,55 @@ impl EncodeEntry for Write {
 // ssize_t readv(int fd, const struct iovec *iov, int iovcnt)
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
-#[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Readv {
     pub fd: Fd,
     pub iov: Struct,
     pub iovcnt: Integer,
+    /* The buffers of the iovec array, known once encoded */
+    pub bufs: Vec<Buffer>,
     pub retval: Option<Size>,
 }
 impl Readv {
     pub fn new(raw: RawSyscall) -> Self {
         let fd = Fd::new(raw.args[0]);
-        let iov = Struct::new(raw.args[1], Direction::InOut);
+        let iov = Struct::new(raw.args[1], Direction::Out);
         let iovcnt = Integer::new(raw.args[2]);
+        let bufs = Vec::new();
         let retval = None;
-        Self { fd, iov, iovcnt, retval }
+        Self { fd, iov, iovcnt, bufs, retval }
     }
 }
 impl DecodeEntry for Readv {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.fd.decode(pid, operation).unwrap();
         self.iov.decode(pid, operation).unwrap();
         self.iovcnt.decode(pid, operation).unwrap();
     }
 }
+impl EncodeEntry for Readv {
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.fd.value;
+        (raw.args[1], self.bufs) = encode_iovecs(&mut self.iov, self.iovcnt.value, Direction::In, pid, operation, remote, scratch)?;
+        raw.args[2] = self.iovcnt.value;
+        Ok(raw)
+    }
+}
+impl DecodeExit for Readv {
+    fn decode_exit(&mut self, value: usize, _pid: i32, _operation: &Operation) -> Result<(), std::io::Error> {
+        self.retval = Some(Size::from(value));
+        fill_iovecs(&mut self.bufs, value);
+        Ok(())
+    }
+}
 
 // ssize_t writev(int fd, const struct iovec *iov, int iovcnt)
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Writev {
     pub fd: Fd,
     pub iov: Struct,
@@ -127,26 +161,35 @@ pub struct Writev {
 impl Writev {
     pub fn new(raw: RawSyscall) -> Self {
         let fd = Fd::new(raw.args[0]);
-        let iov = Struct::new(raw.args[1], Direction::InOut);
+        let iov = Struct::new(raw.args[1], Direction::Out);
         let iovcnt = Integer::new(raw.args[2]);
         let retval = None;
         Self { fd, iov, iovcnt, retval }
     }
 }
 impl DecodeEntry for Writev {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.fd.decode(pid, operation).unwrap();
         self.iov.decode(pid, operation).unwrap();
         self.iovcnt.decode(pid, operation).unwrap();
     }
 }
+impl EncodeEntry for Writev {
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.fd.value;
+        (raw.args[1], _) = encode_iovecs(&mut self.iov, self.iovcnt.value, Direction::Out, pid, operation, remote, scratch)?;
+        raw.args[2] = self.iovcnt.value;
+        Ok(raw)
+    }
+}
 
 
 
 // ssize_t pread(int fd, void *buf, size_t nbyte, off_t offset)
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
-#[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Pread {
     pub fd: Fd,
     pub buf: Buffer,
@@ -165,18 +208,40 @@ impl Pread {
     }
 }
 impl DecodeEntry for Pread {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.fd.decode(pid, operation).unwrap();
         self.buf.decode(pid, operation).unwrap();
         self.nbytes.decode(pid, operation).unwrap();
         self.offset.decode(pid, operation).unwrap();
     }
 }
+impl EncodeEntry for Pread {
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.fd.value;
+        raw.args[1] = self.buf.encode(pid, operation, remote, scratch)?;
+        raw.args[2] = self.nbytes.value;
+        raw.args[3] = self.offset.value;
+        Ok(raw)
+    }
+}
+impl DecodeExit for Pread {
+    fn decode_exit(&mut self, value: usize, _pid: i32, _operation: &Operation) -> Result<(), std::io::Error> {
+        self.retval = Some(Size::from(value));
+
+        // Only the bytes read are filled by the syscall
+        if (value as isize) >= 0 {
+            self.buf.size = std::cmp::min(value, self.nbytes.value);
+        }
+        Ok(())
+    }
+}
 
 // ssize_t pwrite(int fd, const void *buf, size_t nbyte, off_t offset)
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Pwrite {
     pub fd: Fd,
     pub buf: Buffer,
@@ -195,49 +260,80 @@ impl Pwrite {
     }
 }
 impl DecodeEntry for Pwrite {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.fd.decode(pid, operation).unwrap();
         self.buf.decode(pid, operation).unwrap();
         self.nbytes.decode(pid, operation).unwrap();
         self.offset.decode(pid, operation).unwrap();
     }
 }
+impl EncodeEntry for Pwrite {
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.fd.value;
+        raw.args[1] = self.buf.encode(pid, operation, remote, scratch)?;
+        raw.args[2] = self.nbytes.value;
+        raw.args[3] = self.offset.value;
+        Ok(raw)
+    }
+}
 
 
 // ssize_t preadv(int fd, const struct iovec *iov, int iovcnt, off_t offset)
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
-#[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Preadv {
     pub fd: Fd,
     pub iov: Struct,
     pub iovcnt: Integer,
     pub offset: Offset,
+    /* The buffers of the iovec array, known once encoded */
+    pub bufs: Vec<Buffer>,
     pub retval: Option<Size>,
 }
 impl Preadv {
     pub fn new(raw: RawSyscall) -> Self {
         let fd = Fd::new(raw.args[0]);
-        let iov = Struct::new(raw.args[1], Direction::In);
+        let iov = Struct::new(raw.args[1], Direction::Out);
         let iovcnt = Integer::new(raw.args[2]);
         let offset = Offset::new(raw.args[3]);
+        let bufs = Vec::new();
         let retval = None;
-        Self { fd, iov, iovcnt, offset, retval}
+        Self { fd, iov, iovcnt, offset, bufs, retval }
     }
 }
 impl DecodeEntry for Preadv {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.fd.decode(pid, operation).unwrap();
         self.iov.decode(pid, operation).unwrap();
         self.iovcnt.decode(pid, operation).unwrap();
         self.offset.decode(pid, operation).unwrap();
     }
 }
+impl EncodeEntry for Preadv {
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.fd.value;
+        (raw.args[1], self.bufs) = encode_iovecs(&mut self.iov, self.iovcnt.value, Direction::In, pid, operation, remote, scratch)?;
+        raw.args[2] = self.iovcnt.value;
+        raw.args[3] = self.offset.value;
+        Ok(raw)
+    }
+}
+impl DecodeExit for Preadv {
+    fn decode_exit(&mut self, value: usize, _pid: i32, _operation: &Operation) -> Result<(), std::io::Error> {
+        self.retval = Some(Size::from(value));
+        fill_iovecs(&mut self.bufs, value);
+        Ok(())
+    }
+}
 
 // ssize_t pwritev(int fd, const struct iovec *iov, int iovcnt, off_t offset)
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Pwritev {
     pub fd: Fd,
     pub iov: Struct,
@@ -248,7 +344,7 @@ pub struct Pwritev {
 impl Pwritev {
     pub fn new(raw: RawSyscall) -> Self {
         let fd = Fd::new(raw.args[0]);
-        let iov = Struct::new(raw.args[1], Direction::InOut);
+        let iov = Struct::new(raw.args[1], Direction::Out);
         let iovcnt = Integer::new(raw.args[2]);
         let offset = Offset::new(raw.args[3]);
         let retval = None;
@@ -256,41 +352,54 @@ impl Pwritev {
     }
 }
 impl DecodeEntry for Pwritev {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.fd.decode(pid, operation).unwrap();
         self.iov.decode(pid, operation).unwrap();
         self.iovcnt.decode(pid, operation).unwrap();
         self.offset.decode(pid, operation).unwrap();
     }
 }
+impl EncodeEntry for Pwritev {
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.fd.value;
+        (raw.args[1], _) = encode_iovecs(&mut self.iov, self.iovcnt.value, Direction::Out, pid, operation, remote, scratch)?;
+        raw.args[2] = self.iovcnt.value;
+        raw.args[3] = self.offset.value;
+        Ok(raw)
+    }
+}
 
 
 
 // ssize_t preadv2(int fd, const struct iovec *iov, int iovcnt, off_t offset, int flags)
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
-#[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Preadv2 {
     pub fd: Fd,
     pub iov: Struct,
     pub iovcnt: Integer,
     pub offset: Offset,
     pub flags: Flag,
+    /* The buffers of the iovec array, known once encoded */
+    pub bufs: Vec<Buffer>,
     pub retval: Option<Size>,
 }
 impl Preadv2 {
     pub fn new(raw: RawSyscall) -> Self {
         let fd = Fd::new(raw.args[0]);
-        let iov = Struct::new(raw.args[1], Direction::In);
+        let iov = Struct::new(raw.args[1], Direction::Out);
         let iovcnt = Integer::new(raw.args[2]);
         let offset = Offset::new(raw.args[3]);
         let flags = Flag::new(raw.args[4]);
+        let bufs = Vec::new();
         let retval = None;
-        Self { fd, iov, iovcnt, offset, flags, retval }
+        Self { fd, iov, iovcnt, offset, flags, bufs, retval }
     }
 }
 impl DecodeEntry for Preadv2 {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.fd.decode(pid, operation).unwrap();
         self.iov.decode(pid, operation).unwrap();
         self.iovcnt.decode(pid, operation).unwrap();
@@ -298,11 +407,28 @@ impl DecodeEntry for Preadv2 {
         self.flags.decode(pid, operation).unwrap();
     }
 }
+impl EncodeEntry for Preadv2 {
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.fd.value;
+        (raw.args[1], self.bufs) = encode_iovecs(&mut self.iov, self.iovcnt.value, Direction::In, pid, operation, remote, scratch)?;
+        raw.args[2] = self.iovcnt.value;
+        Ok(raw)
+    }
+}
+impl DecodeExit for Preadv2 {
+    fn decode_exit(&mut self, value: usize, _pid: i32, _operation: &Operation) -> Result<(), std::io::Error> {
+        self.retval = Some(Size::from(value));
+        fill_iovecs(&mut self.bufs, value);
+        Ok(())
+    }
+}
 
 // ssize_t pwritev2(int fd, const struct iovec *iov, int iovcnt, off_t offset, int flags)
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Pwritev2 {
     pub fd: Fd,
     pub iov: Struct,
@@ -315,7 +441,7 @@ pub struct Pwritev2 {
 impl Pwritev2 {
     pub fn new(raw: RawSyscall) -> Self {
         let fd = Fd::new(raw.args[0]);
-        let iov = Struct::new(raw.args[1], Direction::In);
+        let iov = Struct::new(raw.args[1], Direction::Out);
         let iovcnt = Integer::new(raw.args[2]);
         let offset = Offset::new(raw.args[3]);
         let flags = Flag::new(raw.args[4]);
@@ -324,11 +450,69 @@ impl Pwritev2 {
     }
 }
 impl DecodeEntry for Pwritev2 {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.fd.decode(pid, operation).unwrap();
         self.iov.decode(pid, operation).unwrap();
         self.iovcnt.decode(pid, operation).unwrap();
         self.offset.decode(pid, operation).unwrap();
         self.flags.decode(pid, operation).unwrap();
     }
+}
+impl EncodeEntry for Pwritev2 {
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.fd.value;
+        (raw.args[1], _) = encode_iovecs(&mut self.iov, self.iovcnt.value, Direction::Out, pid, operation, remote, scratch)?;
+        raw.args[2] = self.iovcnt.value;
+        Ok(raw)
+    }
+}
+
+
+
+/* struct iovec { void *iov_base; size_t iov_len; } only for x86_64 */
+const IOVEC_SIZE: usize = 16;
+
+/*
+ * Copy the iovec array, then each buffer it points to with the direction given.
+ * The copied array points to the copies of the buffers, which are returned with the tracee addresses.
+ */
+fn encode_iovecs(iov: &mut Struct, iovcnt: usize, direction: Direction, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<(usize, Vec<Buffer>), std::io::Error>
+{
+    // The kernel does not read an empty array
+    if iovcnt == 0 {
+        return Ok((iov.address, Vec::new()));
+    }
+    iov.size = iovcnt * IOVEC_SIZE;
+    let address = iov.encode(pid, operation, remote, scratch)?;
+
+    let mut bufs = Vec::with_capacity(iovcnt);
+    let mut iovecs = Vec::with_capacity(iov.content.len());
+    for iovec in iov.content.chunks_exact(IOVEC_SIZE) {
+        let iov_base = usize::from_le_bytes(iovec[0..8].try_into().unwrap());
+        let iov_len = usize::from_le_bytes(iovec[8..16].try_into().unwrap());
+        let mut buf = Buffer::new(iov_base, direction.clone(), iov_len);
+        let local = buf.encode(pid, operation, remote, scratch)?;
+        iovecs.extend_from_slice(&local.to_le_bytes());
+        iovecs.extend_from_slice(&iov_len.to_le_bytes());
+        bufs.push(buf);
+    }
+    if address != 0 {
+        operation.memory.write(pid, iov.address, iovecs);
+    }
+    Ok((address, bufs))
+}
+
+/*
+ * Bound the buffers of an iovec array to the bytes filled by the syscall, in order.
+ */
+fn fill_iovecs(bufs: &mut [Buffer], filled: usize)
+{
+    if (filled as isize) < 0 {
+        return;
+    }
+    let mut left = filled;
+    for buf in bufs.iter_mut() {
+        buf.size = std::cmp::min(buf.size, left);
+        left -= buf.size;
+    }
 }
\ No newline at end of file
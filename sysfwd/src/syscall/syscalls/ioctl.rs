@@ -3,40 +3,88 @@
  *
  */
 use serde::{ Serialize, Deserialize };
-use decoding_macro::DecodeExit;
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
 use crate::{
     syscall::RawSyscall,
-    //syscall::args::{ ArgType, Direction },
-    syscall::args::{ Integer, Fd },
-    syscall::decoder::{ DecodeArg, DecodeEntry, DecodeExit },
-    targets::operation::Operation,
+    syscall::args::{ Direction, Integer, Fd, Struct },
+    syscall::{
+        decoder::{ DecodeArg, DecodeEntry, DecodeExit },
+        encoder::{ EncodeArg, EncodeEntry },
+    },
+    targets::operation::{ Operation, MemoryOperation },
+    executor::Scratch,
 };
 
 // int ioctl(int fildes, int request, ... /* arg */)
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Ioctl {
     pub fd: Fd,
     pub request: Integer,
-    // TODO:
-    pub arg: Integer,
+    /* Only a structure when the request tells its size, otherwise an integer passed as is */
+    pub arg: Struct,
     pub retval: Option<Integer>
 }
 impl Ioctl {
     pub fn new(raw: RawSyscall) -> Self {
         let fd = Fd::new(raw.args[0]);
         let request = Integer::new(raw.args[1]);
-        // TODO:
-        let arg = Integer::new(raw.args[2]);
+        let (direction, size) = request_layout(raw.args[1]);
+        let mut arg = Struct::new(raw.args[2], direction);
+        arg.size = size;
         let retval = None;
         Self { fd, request, arg, retval }
     }
 }
 impl DecodeEntry for Ioctl {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.fd.decode(pid, operation).unwrap();
         self.request.decode(pid, operation).unwrap();
         self.arg.decode(pid, operation).unwrap();
     }
 }
+impl EncodeEntry for Ioctl {
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.fd.value;
+        raw.args[1] = self.request.value;
+        if self.arg.size > 0 {
+            raw.args[2] = self.arg.encode(pid, operation, remote, scratch)?;
+        }
+        Ok(raw)
+    }
+}
+
+
+/*
+ * Direction and size of the argument of an ioctl request.
+ * Requests built with _IOC() carry both, the legacy tty ones are listed.
+ * A size of 0 means the argument is not a known pointer.
+ */
+fn request_layout(request: usize) -> (Direction, usize)
+{
+    const _IOC_WRITE: usize = 1;
+    const _IOC_READ: usize = 2;
+
+    match request as u32 {
+        0x5401 /* TCGETS */ => (Direction::In, 36),
+        0x5402..=0x5404 /* TCSETS, TCSETSW, TCSETSF */ => (Direction::Out, 36),
+        0x540F /* TIOCGPGRP */ => (Direction::In, 4),
+        0x5410 /* TIOCSPGRP */ => (Direction::Out, 4),
+        0x5413 /* TIOCGWINSZ */ => (Direction::In, 8),
+        0x5414 /* TIOCSWINSZ */ => (Direction::Out, 8),
+        0x541B /* FIONREAD */ => (Direction::In, 4),
+        0x5421 /* FIONBIO */ => (Direction::Out, 4),
+        _ => {
+            let size = (request >> 16) & 0x3fff;
+            match (request >> 30) & 3 {
+                _IOC_WRITE => (Direction::Out, size),
+                _IOC_READ => (Direction::In, size),
+                3 => (Direction::InOut, size),
+                _ => (Direction::InOut, 0),
+            }
+        }
+    }
+}
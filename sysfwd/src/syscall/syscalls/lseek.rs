@@ -3,7 +3,7 @@
  */
 use serde::{ Serialize, Deserialize };
 
-use decoding_macro::DecodeExit;
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
 use crate::{
     syscall::RawSyscall,
     syscall::args::{ Direction, Integer, Fd, Offset, Address },
@@ -11,7 +11,8 @@ use crate::{
         decoder::{ DecodeArg, DecodeEntry, DecodeExit },
         encoder::EncodeEntry,
     },
-    targets::operation::Operation,
+    targets::operation::{ Operation, MemoryOperation },
+    executor::Scratch,
 };
 
 
@@ -20,6 +21,8 @@ use crate::{
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Lseek {
     pub fd: Fd,
     pub offset: Offset,
@@ -36,14 +39,14 @@ impl Lseek {
     }
 }
 impl DecodeEntry for Lseek {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.fd.decode(pid, operation).unwrap();
         self.offset.decode(pid, operation).unwrap();
         self.whence.decode(pid, operation).unwrap();
     }
 }
 impl EncodeEntry for Lseek {
-    fn encode_entry(&mut self, mut raw: RawSyscall, _pid: i32, _operation: &Box<Operation>) -> Result<RawSyscall, std::io::Error> {
+    fn encode_entry(&mut self, mut raw: RawSyscall, _pid: i32, _operation: &Operation, _remote: &dyn MemoryOperation, _scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
         raw.args[0] = self.fd.value;
         raw.args[1] = self.offset.value;
         raw.args[2] = self.whence.value;
@@ -56,6 +59,8 @@ impl EncodeEntry for Lseek {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Llseek {
     pub fd: Fd,
     pub offset_high: Offset,
@@ -76,7 +81,7 @@ impl Llseek {
     }
 }
 impl DecodeEntry for Llseek {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.fd.decode(pid, operation).unwrap();
         self.offset_high.decode(pid, operation).unwrap();
         self.offset_low.decode(pid, operation).unwrap();
@@ -85,7 +90,7 @@ impl DecodeEntry for Llseek {
     }
 }
 impl EncodeEntry for Llseek {
-    fn encode_entry(&mut self, mut raw: RawSyscall, _pid: i32, _operation: &Box<Operation>) -> Result<RawSyscall, std::io::Error> {
+    fn encode_entry(&mut self, mut raw: RawSyscall, _pid: i32, _operation: &Operation, _remote: &dyn MemoryOperation, _scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
         raw.args[0] = self.fd.value;
         raw.args[1] = self.offset_high.value;
         raw.args[2] = self.offset_low.value;
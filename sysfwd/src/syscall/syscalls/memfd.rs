@@ -2,12 +2,16 @@
  */
 use serde::{ Serialize, Deserialize };
 
-use decoding_macro::DecodeExit;
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
 use crate::{
     syscall::RawSyscall,
-    syscall::args::{ Direction, Integer, Flag, NullBuffer },
-    syscall::decoder::{ DecodeArg, DecodeEntry, DecodeExit },
-    targets::operation::Operation,
+    syscall::args::{ Direction, Fd, Flag, NullBuffer },
+    syscall::{
+        decoder::{ DecodeArg, DecodeEntry, DecodeExit },
+        encoder::{ EncodeArg, EncodeEntry },
+    },
+    targets::operation::{ Operation, MemoryOperation },
+    executor::Scratch,
 };
 
 
@@ -16,10 +20,12 @@ use crate::{
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct MemfdCreate {
     pub name: NullBuffer,
     pub flags: Flag,
-    pub retval: Option<Integer>,
+    pub retval: Option<Fd>,
 }
 
 impl MemfdCreate {
@@ -32,8 +38,15 @@ impl MemfdCreate {
 }
 
 impl DecodeEntry for MemfdCreate {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.name.decode(pid, operation).unwrap();
         self.flags.decode(pid, operation).unwrap();
     }
-}
\ No newline at end of file
+}
+impl EncodeEntry for MemfdCreate {
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.name.encode(pid, operation, remote, scratch)?;
+        // The flags do not fit in a Flag, they are kept as is
+        Ok(raw)
+    }
+}
@@ -2,7 +2,7 @@
  */
 use serde::{ Serialize, Deserialize };
 
-use decoding_macro::DecodeExit;
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
 use crate::{
     syscall::RawSyscall,
     syscall::args::{ Direction, Integer, Fd, NullBuffer },
@@ -15,6 +15,8 @@ use crate::{
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Mknod {
     pub pathname: NullBuffer,
     pub mode: Integer,
@@ -31,7 +33,7 @@ impl Mknod {
     }
 }
 impl DecodeEntry for Mknod {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.pathname.decode(pid, operation).unwrap();
         self.mode.decode(pid, operation).unwrap();
         self.dev.decode(pid, operation).unwrap();
@@ -42,6 +44,8 @@ impl DecodeEntry for Mknod {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Mknodat {
     pub dirfd: Fd,
     pub pathname: NullBuffer,
@@ -62,7 +66,7 @@ impl Mknodat {
 }
 
 impl DecodeEntry for Mknodat {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.dirfd.decode(pid, operation).unwrap();
         self.pathname.decode(pid, operation).unwrap();
         self.mode.decode(pid, operation).unwrap();
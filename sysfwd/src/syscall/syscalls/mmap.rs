@@ -7,15 +7,21 @@
  * int munmap(void addr[.length], size_t length);
  * int mprotect(void addr[.len], size_t len, int prot);
  * int madvise(void addr[.length], size_t length, int advice);
+ * int msync(void addr[.length], size_t length, int flags);
  */
+use nix::libc;
 use serde::{ Serialize, Deserialize };
 
-use decoding_macro::DecodeExit;
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
 use crate::{
     syscall::RawSyscall,
     syscall::args::{ Direction, Integer, Fd, Size, Offset, Protection, Flag, Address },
-    syscall::decoder::{ DecodeArg, DecodeEntry, DecodeExit },
-    targets::operation::Operation,
+    syscall::{
+        decoder::{ DecodeArg, DecodeEntry, DecodeExit },
+        encoder::EncodeEntry,
+    },
+    targets::operation::{ Operation, MemoryOperation },
+    executor::Scratch,
 };
 
 
@@ -24,6 +30,8 @@ use crate::{
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Brk{
     pub addr: Address,
     pub retval: Option<Integer>,
@@ -36,7 +44,7 @@ impl Brk {
     }
 }
 impl DecodeEntry for Brk {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.addr.decode(pid, operation).unwrap();
     }
 }
@@ -46,6 +54,8 @@ impl DecodeEntry for Brk {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Sbrk{
     pub increment: Integer,
     pub retval: Option<Address>,
@@ -58,7 +68,7 @@ impl Sbrk {
     }
 }
 impl DecodeEntry for Sbrk {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.increment.decode(pid, operation).unwrap();
     }
 }
@@ -68,6 +78,8 @@ impl DecodeEntry for Sbrk {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Mmap{
     pub addr: Address,
     pub length: Size,
@@ -90,7 +102,7 @@ impl Mmap {
     }
 }
 impl DecodeEntry for Mmap {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.addr.decode(pid, operation).unwrap();
         self.length.decode(pid, operation).unwrap();
         self.prot.decode(pid, operation).unwrap();
@@ -99,12 +111,24 @@ impl DecodeEntry for Mmap {
         self.offset.decode(pid, operation).unwrap();
     }
 }
+impl EncodeEntry for Mmap {
+    /* The address of the tracee is meaningless in the child, the mapping is placed by its kernel */
+    fn encode_entry(&mut self, mut raw: RawSyscall, _pid: i32, _operation: &Operation, _remote: &dyn MemoryOperation, _scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = 0;
+        raw.args[1] = self.length.value;
+        raw.args[3] &= !((libc::MAP_FIXED | libc::MAP_FIXED_NOREPLACE) as usize);
+        raw.args[4] = self.fd.value;
+        Ok(raw)
+    }
+}
 
 
 // void *mremap(void old_address[.old_size], size_t old_size, size_t new_size, int flags, ... /* void *new_address */);
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Mremap{
     pub old_address: Address,
     pub old_size: Size,
@@ -125,7 +149,7 @@ impl Mremap {
     }
 }
 impl DecodeEntry for Mremap {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.old_address.decode(pid, operation).unwrap();
         self.old_size.decode(pid, operation).unwrap();
         self.new_size.decode(pid, operation).unwrap();
@@ -133,6 +157,14 @@ impl DecodeEntry for Mremap {
         self.new_address.decode(pid, operation).unwrap();
     }
 }
+impl EncodeEntry for Mremap {
+    /* The new address of the tracee is meaningless in the child, the mapping is moved by its kernel */
+    fn encode_entry(&mut self, mut raw: RawSyscall, _pid: i32, _operation: &Operation, _remote: &dyn MemoryOperation, _scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[3] &= !((libc::MREMAP_FIXED | libc::MREMAP_DONTUNMAP) as usize);
+        raw.args[4] = 0;
+        Ok(raw)
+    }
+}
 
 
 
@@ -140,6 +172,8 @@ impl DecodeEntry for Mremap {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Munmap{
     pub addr: Address,
     pub length: Size,
@@ -154,17 +188,20 @@ impl Munmap {
     }
 }
 impl DecodeEntry for Munmap {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.addr.decode(pid, operation).unwrap();
         self.length.decode(pid, operation).unwrap();
     }
 }
+impl EncodeEntry for Munmap { }
 
 
 // int mprotect(void addr[.len], size_t len, int prot);
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Mprotect{
     pub addr: Address,
     pub len: Size,
@@ -181,7 +218,7 @@ impl Mprotect {
     }
 }
 impl DecodeEntry for Mprotect {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.addr.decode(pid, operation).unwrap();
         self.len.decode(pid, operation).unwrap();
         self.prot.decode(pid, operation).unwrap();
@@ -193,6 +230,8 @@ impl DecodeEntry for Mprotect {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Madvise{
     pub addr: Address,
     pub length: Size,
@@ -209,9 +248,40 @@ impl Madvise {
     }
 }
 impl DecodeEntry for Madvise {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.addr.decode(pid, operation).unwrap();
         self.length.decode(pid, operation).unwrap();
         self.advice.decode(pid, operation).unwrap();
     }
 }
+
+
+// int msync(void addr[.length], size_t length, int flags);
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug)]
+#[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
+pub struct Msync{
+    pub addr: Address,
+    pub length: Size,
+    pub flags: Integer,
+    pub retval: Option<Integer>,
+}
+impl Msync {
+    pub fn new(raw: RawSyscall) -> Self {
+        let addr = Address::new(raw.args[0], Direction::In);
+        let length = Size::new(raw.args[1]);
+        let flags = Integer::new(raw.args[2]);
+        let retval = None;
+        Self { addr, length, flags, retval }
+    }
+}
+impl DecodeEntry for Msync {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
+        self.addr.decode(pid, operation).unwrap();
+        self.length.decode(pid, operation).unwrap();
+        self.flags.decode(pid, operation).unwrap();
+    }
+}
+impl EncodeEntry for Msync { }
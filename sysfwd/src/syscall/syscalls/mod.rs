@@ -8,6 +8,7 @@ pub mod ioctl;
 pub mod access;
 pub mod fallocate;
 pub mod truncate;
+pub mod fsync;
 pub mod renameat;
 pub mod memfd;
 pub mod mknod;
@@ -25,4 +26,7 @@ pub mod epoll;
 pub mod robust_list;
 pub mod tid;
 pub mod lseek;
+pub mod dup;
+pub mod fcntl;
+pub mod socket;
 pub mod exit;
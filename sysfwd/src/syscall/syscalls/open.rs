@@ -3,14 +3,15 @@
  */
 use serde::{ Serialize, Deserialize };
 
-use decoding_macro::DecodeExit;
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
 
 use crate::{
     syscall::RawSyscall,
-    syscall::args::{ Direction, Integer, Fd, Size, Flag, NullBuffer, Struct },
+    syscall::args::{ Direction, Integer, Fd, Size, NullBuffer, Struct },
     syscall::decoder::{ DecodeArg, DecodeEntry, DecodeExit },
     syscall::encoder::{ EncodeEntry, EncodeArg },
-    targets::operation::Operation,
+    targets::operation::{ Operation, MemoryOperation },
+    executor::Scratch,
 };
 
 
@@ -18,6 +19,8 @@ use crate::{
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Close {
     pub fd: Fd,
     pub retval: Option<Integer>,
@@ -30,12 +33,12 @@ impl Close {
     }
 }
 impl DecodeEntry for Close {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.fd.decode(pid, operation).unwrap();
     }
 }
 impl EncodeEntry for Close {
-    fn encode_entry(&mut self, mut raw: RawSyscall, _pid: i32, _operation: &Box<Operation>) -> Result<RawSyscall, std::io::Error> {
+    fn encode_entry(&mut self, mut raw: RawSyscall, _pid: i32, _operation: &Operation, _remote: &dyn MemoryOperation, _scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
         raw.args[0] = self.fd.value;
         Ok(raw)
     }
@@ -45,6 +48,8 @@ impl EncodeEntry for Close {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Creat {
     pub pathname: NullBuffer,
     pub mode: Integer,
@@ -59,15 +64,14 @@ impl Creat {
     }
 }
 impl DecodeEntry for Creat {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.pathname.decode(pid, operation).unwrap();
         self.mode.decode(pid, operation).unwrap();
     }
 }
 impl EncodeEntry for Creat {
-    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Box<Operation>) -> Result<RawSyscall, std::io::Error> {
-        raw.args[0] = self.pathname.address;
-        self.pathname.encode(pid, operation).unwrap();
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.pathname.encode(pid, operation, remote, scratch)?;
         raw.args[1] = self.mode.value;
         Ok(raw)
     }
@@ -78,39 +82,41 @@ impl EncodeEntry for Creat {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Open {
     pub pathname: NullBuffer,
-    pub flags: Flag,
+    pub flags: Integer,
     pub mode: Integer,
     pub retval: Option<Fd>,
 }
 impl Open {
     pub fn new(raw: RawSyscall) -> Self {
         let pathname = NullBuffer::new(raw.args[0], Direction::In);
-        let flags = Flag::new(raw.args[1]);
+        let flags = Integer::new(raw.args[1]);
         let mode = Integer::new(raw.args[2]);
         let retval = None;
         Self { pathname, flags, mode, retval }
     }
 }
 impl DecodeEntry for Open {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.pathname.decode(pid, operation).unwrap();
         self.flags.decode(pid, operation).unwrap();
         self.mode.decode(pid, operation).unwrap();
     }
 }
 impl EncodeEntry for Open {
-    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Box<Operation>) -> Result<RawSyscall, std::io::Error> {
-        raw.args[0] = self.pathname.address;
-        self.pathname.encode(pid, operation).unwrap();
-        raw.args[1] = self.mode.value;
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.pathname.encode(pid, operation, remote, scratch)?;
+        raw.args[1] = self.flags.value;
+        raw.args[2] = self.mode.value;
         Ok(raw)
     }
 }
 /* 
 impl DecodeExit for Open {
-    fn decode_exit(&mut self, pid: i32, operation: &Box<Operation>) -> Result<(), std::io::Error> { 
+    fn decode_exit(&mut self, pid: i32, operation: &Operation) -> Result<(), std::io::Error> { 
         self.retval.as_mut().unwrap().decode(pid, operation).unwrap();
         Ok(())
     }
@@ -122,6 +128,8 @@ impl DecodeExit for Open {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Openat {
     pub dirfd: Fd,
     pub pathname: NullBuffer,
@@ -140,7 +148,7 @@ impl Openat {
     }
 }
 impl DecodeEntry for Openat {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.dirfd.decode(pid, operation).unwrap();
         self.pathname.decode(pid, operation).unwrap();
         self.flags.decode(pid, operation).unwrap();
@@ -148,20 +156,24 @@ impl DecodeEntry for Openat {
     }
 }
 impl EncodeEntry for Openat {
-    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Box<Operation>) -> Result<RawSyscall, std::io::Error> {
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
         raw.args[0] = self.dirfd.value;
-        raw.args[1] = self.pathname.address;
-        self.pathname.encode(pid, operation).unwrap();
+        raw.args[1] = self.pathname.encode(pid, operation, remote, scratch)?;
         raw.args[2] = self.flags.value;
         raw.args[3] = self.mode.value;
         Ok(raw)
     }
 }
 
+/* Larger than a page, openat2 fails with E2BIG */
+const OPEN_HOW_MAX_SIZE: usize = 4096;
+
 // int openat2(int dirfd, const char *pathname, const struct open_how *how, size_t size)
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Openat2 {
     pub dirfd: Fd,
     pub pathname: NullBuffer,
@@ -173,14 +185,16 @@ impl Openat2 {
     pub fn new(raw: RawSyscall) -> Self {
         let dirfd = Fd::new(raw.args[0]);
         let pathname = NullBuffer::new(raw.args[1], Direction::In);
-        let how = Struct::new(raw.args[2], Direction::In);
+        let mut how = Struct::new(raw.args[2], Direction::Out);
+        // The kernel rejects a size over a page before reading the structure
+        how.size = std::cmp::min(raw.args[3], OPEN_HOW_MAX_SIZE);
         let size = Size::new(raw.args[3]);
         let retval = None;
         Self { dirfd, pathname, how, size, retval }
     }
 }
 impl DecodeEntry for Openat2 {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.dirfd.decode(pid, operation).unwrap();
         self.pathname.decode(pid, operation).unwrap();
         self.how.decode(pid, operation).unwrap();
@@ -188,12 +202,10 @@ impl DecodeEntry for Openat2 {
     }
 }
 impl EncodeEntry for Openat2 {
-    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Box<Operation>) -> Result<RawSyscall, std::io::Error> {
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
         raw.args[0] = self.dirfd.value;
-        raw.args[1] = self.pathname.address;
-        self.pathname.encode(pid, operation).unwrap();
-        raw.args[2] = self.how.address;
-        self.how.encode(pid, operation).unwrap();
+        raw.args[1] = self.pathname.encode(pid, operation, remote, scratch)?;
+        raw.args[2] = self.how.encode(pid, operation, remote, scratch)?;
         raw.args[3] = self.size.value;
         Ok(raw)
     }
@@ -2,7 +2,7 @@
  *
  */
 use serde::{ Serialize, Deserialize };
-use decoding_macro::DecodeExit;
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
 use crate::{
     syscall::RawSyscall,
     syscall::args::{ Direction, Integer, Address },
@@ -15,6 +15,8 @@ use crate::{
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Prctl {
     pub option: Integer,
     pub arg2: Integer,
@@ -35,7 +37,7 @@ impl Prctl {
     }
 }
 impl DecodeEntry for Prctl {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.option.decode(pid, operation).unwrap();
         self.arg2.decode(pid, operation).unwrap();
         self.arg3.decode(pid, operation).unwrap();
@@ -50,6 +52,8 @@ impl DecodeEntry for Prctl {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct ArchPrctl {
     pub code: Integer,
     pub addr: Address,
@@ -64,7 +68,7 @@ impl ArchPrctl {
     }
 }
 impl DecodeEntry for ArchPrctl {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.code.decode(pid, operation).unwrap();
         self.addr.decode(pid, operation).unwrap();
     }
@@ -2,7 +2,7 @@
  */
 use serde::{ Serialize, Deserialize };
 
-use decoding_macro::DecodeExit;
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
 use crate::{
     syscall::RawSyscall,
     syscall::args::{ Direction, Integer, Fd, Flag, NullBuffer },
@@ -16,6 +16,8 @@ use crate::{
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Rename {
     pub oldpath: NullBuffer,
     pub newpath: NullBuffer,
@@ -32,7 +34,7 @@ impl Rename {
 }
 
 impl DecodeEntry for Rename {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.oldpath.decode(pid, operation).unwrap();
         self.newpath.decode(pid, operation).unwrap();
     }
@@ -42,6 +44,8 @@ impl DecodeEntry for Rename {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Renameat {
     pub olddirfd: Fd,
     pub oldpath: NullBuffer,
@@ -62,7 +66,7 @@ impl Renameat {
 }
 
 impl DecodeEntry for Renameat {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.olddirfd.decode(pid, operation).unwrap();
         self.oldpath.decode(pid, operation).unwrap();
         self.newdirfd.decode(pid, operation).unwrap();
@@ -75,6 +79,8 @@ impl DecodeEntry for Renameat {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Renameat2 {
     pub olddirfd: Fd,
     pub oldpath: NullBuffer,
@@ -97,7 +103,7 @@ impl Renameat2 {
 }
 
 impl DecodeEntry for Renameat2 {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.olddirfd.decode(pid, operation).unwrap();
         self.oldpath.decode(pid, operation).unwrap();
         self.newdirfd.decode(pid, operation).unwrap();
@@ -2,7 +2,7 @@
  *
  */
 use serde::{ Serialize, Deserialize };
-use decoding_macro::DecodeExit;
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
 use crate::{
     syscall::RawSyscall,
     syscall::args::{ Direction, Integer, Struct },
@@ -15,6 +15,8 @@ use crate::{
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Getrlimit {
     pub resource: Integer,
     pub rlim: Struct,
@@ -23,13 +25,13 @@ pub struct Getrlimit {
 impl Getrlimit {
     pub fn new(raw: RawSyscall) -> Self {
         let resource = Integer::new(raw.args[0]);
-        let rlim = Struct::new(raw.args[1], Direction::Out);
+        let rlim = Struct::new(raw.args[1], Direction::In);
         let retval = None;
         Self { resource, rlim, retval }
     }
 }
 impl DecodeEntry for Getrlimit {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.resource.decode(pid, operation).unwrap();
         self.rlim.decode(pid, operation).unwrap();
     }
@@ -40,6 +42,8 @@ impl DecodeEntry for Getrlimit {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Setrlimit {
     pub resource: Integer,
     pub rlim: Struct,
@@ -48,13 +52,13 @@ pub struct Setrlimit {
 impl Setrlimit {
     pub fn new(raw: RawSyscall) -> Self {
         let resource = Integer::new(raw.args[0]);
-        let rlim = Struct::new(raw.args[1], Direction::In);
+        let rlim = Struct::new(raw.args[1], Direction::Out);
         let retval = None;
         Self { resource, rlim, retval }
     }
 }
 impl DecodeEntry for Setrlimit {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.resource.decode(pid, operation).unwrap();
         self.rlim.decode(pid, operation).unwrap();
     }
@@ -65,6 +69,8 @@ impl DecodeEntry for Setrlimit {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Prlimit {
     pub pid: Integer,
     pub resource: Integer,
@@ -76,14 +82,14 @@ impl Prlimit {
     pub fn new(raw: RawSyscall) -> Self {
         let pid = Integer::new(raw.args[0]);
         let resource = Integer::new(raw.args[1]);
-        let new_limit = Struct::new(raw.args[2], Direction::In);
-        let old_limit = Struct::new(raw.args[3], Direction::InOut);
+        let new_limit = Struct::new(raw.args[2], Direction::Out);
+        let old_limit = Struct::new(raw.args[3], Direction::In);
         let retval = None;
         Self { pid, resource, new_limit, old_limit, retval }
     }
 }
 impl DecodeEntry for Prlimit {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.pid.decode(pid, operation).unwrap();
         self.resource.decode(pid, operation).unwrap();
         self.new_limit.decode(pid, operation).unwrap();
@@ -96,6 +102,8 @@ impl DecodeEntry for Prlimit {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Getrusage {
     pub who: Integer,
     pub usage: Struct,
@@ -104,13 +112,13 @@ pub struct Getrusage {
 impl Getrusage {
     pub fn new(raw: RawSyscall) -> Self {
         let who = Integer::new(raw.args[0]);
-        let usage = Struct::new(raw.args[1], Direction::InOut);
+        let usage = Struct::new(raw.args[1], Direction::In);
         let retval = None;
         Self { who, usage, retval }
     }
 }
 impl DecodeEntry for Getrusage {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.who.decode(pid, operation).unwrap();
         self.usage.decode(pid, operation).unwrap();
     }
@@ -2,7 +2,7 @@
  *
  */
 use serde::{ Serialize, Deserialize };
-use decoding_macro::DecodeExit;
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
 use crate::{
     syscall::RawSyscall,
     syscall::args::{ Direction, Integer, Size, Address },
@@ -16,6 +16,8 @@ use crate::{
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct GetRobustList {
     pub pid: Integer,
     pub head_ptr: Address,
@@ -32,7 +34,7 @@ impl GetRobustList {
     }
 }
 impl DecodeEntry for GetRobustList {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.pid.decode(pid, operation).unwrap();
         self.head_ptr.decode(pid, operation).unwrap();
         self.len_ptr.decode(pid, operation).unwrap();
@@ -44,6 +46,8 @@ impl DecodeEntry for GetRobustList {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct SetRobustList {
     pub pid: Integer,
     pub head_ptr: Address,
@@ -60,7 +64,7 @@ impl SetRobustList {
     }
 }
 impl DecodeEntry for SetRobustList {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.pid.decode(pid, operation).unwrap();
         self.head_ptr.decode(pid, operation).unwrap();
         self.len_ptr.decode(pid, operation).unwrap();
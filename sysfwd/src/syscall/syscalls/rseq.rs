@@ -2,7 +2,7 @@
  */
 use serde::{ Serialize, Deserialize };
 
-use decoding_macro::DecodeExit;
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
 use crate::{
     syscall::RawSyscall,
     syscall::args::{ Direction, Integer, Size, Flag, Struct },
@@ -15,6 +15,8 @@ use crate::{
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Rseq {
     pub rseq: Struct,
     pub rseq_len: Size,
@@ -33,7 +35,7 @@ impl Rseq {
     }
 }
 impl DecodeEntry for Rseq {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.rseq.decode(pid, operation).unwrap();
         self.rseq_len.decode(pid, operation).unwrap();
         self.flags.decode(pid, operation).unwrap();
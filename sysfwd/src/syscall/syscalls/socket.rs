@@ -0,0 +1,111 @@
+/*
+ *
+ */
+use std::io;
+
+use serde::{ Serialize, Deserialize };
+
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
+use crate::{
+    syscall::RawSyscall,
+    syscall::args::{ Direction, Integer, Fd, Address, Struct },
+    syscall::{
+        decoder::{ DecodeArg, DecodeEntry, DecodeExit },
+        encoder::EncodeEntry,
+    },
+    targets::operation::{ Operation, MemoryOperation },
+    executor::Scratch,
+};
+
+
+
+// int accept(int sockfd, struct sockaddr *_Nullable restrict addr, socklen_t *_Nullable restrict addrlen);
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug)]
+#[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
+pub struct Accept {
+    pub sockfd: Fd,
+    pub addr: Struct,
+    pub addrlen: Address,
+    pub retval: Option<Fd>,
+}
+impl Accept {
+    pub fn new(raw: RawSyscall) -> Self {
+        let sockfd = Fd::new(raw.args[0]);
+        let addr = Struct::new(raw.args[1], Direction::InOut);
+        let addrlen = Address::new(raw.args[2], Direction::InOut);
+        let retval = None;
+        Self { sockfd, addr, addrlen, retval }
+    }
+}
+impl DecodeEntry for Accept {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
+        self.sockfd.decode(pid, operation).unwrap();
+        self.addr.decode(pid, operation).unwrap();
+        self.addrlen.decode(pid, operation).unwrap();
+    }
+}
+impl EncodeEntry for Accept {
+    fn encode_entry(&mut self, mut raw: RawSyscall, _pid: i32, _operation: &Operation, _remote: &dyn MemoryOperation, _scratch: &Scratch) -> Result<RawSyscall, io::Error> {
+        check_no_peer_address(&self.addr)?;
+        raw.args[0] = self.sockfd.value;
+        raw.args[1] = 0;
+        raw.args[2] = 0;
+        Ok(raw)
+    }
+}
+
+// int accept4(int sockfd, struct sockaddr *_Nullable restrict addr, socklen_t *_Nullable restrict addrlen, int flags);
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug)]
+#[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
+pub struct Accept4 {
+    pub sockfd: Fd,
+    pub addr: Struct,
+    pub addrlen: Address,
+    pub flags: Integer,
+    pub retval: Option<Fd>,
+}
+impl Accept4 {
+    pub fn new(raw: RawSyscall) -> Self {
+        let sockfd = Fd::new(raw.args[0]);
+        let addr = Struct::new(raw.args[1], Direction::InOut);
+        let addrlen = Address::new(raw.args[2], Direction::InOut);
+        let flags = Integer::new(raw.args[3]);
+        let retval = None;
+        Self { sockfd, addr, addrlen, flags, retval }
+    }
+}
+impl DecodeEntry for Accept4 {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
+        self.sockfd.decode(pid, operation).unwrap();
+        self.addr.decode(pid, operation).unwrap();
+        self.addrlen.decode(pid, operation).unwrap();
+        self.flags.decode(pid, operation).unwrap();
+    }
+}
+impl EncodeEntry for Accept4 {
+    fn encode_entry(&mut self, mut raw: RawSyscall, _pid: i32, _operation: &Operation, _remote: &dyn MemoryOperation, _scratch: &Scratch) -> Result<RawSyscall, io::Error> {
+        check_no_peer_address(&self.addr)?;
+        raw.args[0] = self.sockfd.value;
+        raw.args[1] = 0;
+        raw.args[2] = 0;
+        raw.args[3] = self.flags.value;
+        Ok(raw)
+    }
+}
+
+/*
+ * The peer address is not copied back to the tracee yet, only an accept without it can be forwarded.
+ */
+fn check_no_peer_address(addr: &Struct) -> Result<(), io::Error>
+{
+    match addr.address {
+        0 => Ok(()),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "The peer address of accept is not forwarded")),
+    }
+}
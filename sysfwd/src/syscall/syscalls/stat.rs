@@ -2,20 +2,29 @@
  *
  */
 use serde::{ Serialize, Deserialize };
-use decoding_macro::DecodeExit;
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
 use crate::{
     syscall::RawSyscall,
     syscall::args::{ Direction, Integer, Fd, Flag, NullBuffer, Struct },
     //syscall::args::{ Integer, Fd, Size, Flag, Buffer, NullBuffer, Struct },
-    syscall::decoder::{ DecodeArg, DecodeEntry, DecodeExit },
-    targets::operation::Operation,
+    syscall::{
+        decoder::{ DecodeArg, DecodeEntry, DecodeExit },
+        encoder::{ EncodeArg, EncodeEntry },
+    },
+    targets::operation::{ Operation, MemoryOperation },
+    executor::Scratch,
 };
 
+/* sizeof(struct stat) only for x86_64 */
+const STAT_SIZE: usize = 144;
+
 
 // int stat(const char *restrict pathname, struct stat *restrict statbuf)
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Stat {
     pub pathname: NullBuffer,
     pub statbuf: Struct,
@@ -24,13 +33,14 @@ pub struct Stat {
 impl Stat {
     pub fn new(raw: RawSyscall) -> Self {
         let pathname = NullBuffer::new(raw.args[0], Direction::In);
-        let statbuf = Struct::new(raw.args[1], Direction::InOut);
+        let mut statbuf = Struct::new(raw.args[1], Direction::In);
+        statbuf.size = STAT_SIZE;
         let retval = None;
         Self { pathname, statbuf, retval }
     }
 }
 impl DecodeEntry for Stat {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.pathname.decode(pid, operation).unwrap();
         self.statbuf.decode(pid, operation).unwrap();
     }
@@ -41,6 +51,8 @@ impl DecodeEntry for Stat {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Fstat {
     pub fd: Fd,
     pub statbuf: Struct,
@@ -49,23 +61,33 @@ pub struct Fstat {
 impl Fstat {
     pub fn new(raw: RawSyscall) -> Self {
         let fd = Fd::new(raw.args[0]);
-        let statbuf = Struct::new(raw.args[1], Direction::InOut);
+        let mut statbuf = Struct::new(raw.args[1], Direction::In);
+        statbuf.size = STAT_SIZE;
         let retval = None;
         Self { fd, statbuf, retval }
     }
 }
 impl DecodeEntry for Fstat {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.fd.decode(pid, operation).unwrap();
         self.statbuf.decode(pid, operation).unwrap();
     }
 }
+impl EncodeEntry for Fstat {
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.fd.value;
+        raw.args[1] = self.statbuf.encode(pid, operation, remote, scratch)?;
+        Ok(raw)
+    }
+}
 
 
 // int lstat(const char *restrict pathname, struct stat *restrict statbuf)
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Lstat {
     pub pathname: NullBuffer,
     pub statbuf: Struct,
@@ -74,13 +96,14 @@ pub struct Lstat {
 impl Lstat {
     pub fn new(raw: RawSyscall) -> Self {
         let pathname = NullBuffer::new(raw.args[0], Direction::In);
-        let statbuf = Struct::new(raw.args[1], Direction::InOut);
+        let mut statbuf = Struct::new(raw.args[1], Direction::In);
+        statbuf.size = STAT_SIZE;
         let retval = None;
         Self { pathname, statbuf, retval }
     }
 }
 impl DecodeEntry for Lstat {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.pathname.decode(pid, operation).unwrap();
         self.statbuf.decode(pid, operation).unwrap();
     }
@@ -91,6 +114,8 @@ impl DecodeEntry for Lstat {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Fstatat {
     pub dirfd: Fd,
     pub pathname: NullBuffer,
@@ -102,17 +127,26 @@ impl Fstatat {
     pub fn new(raw: RawSyscall) -> Self {
         let dirfd = Fd::new(raw.args[0]);
         let pathname = NullBuffer::new(raw.args[1], Direction::In);
-        let statbuf = Struct::new(raw.args[2], Direction::InOut);
+        let mut statbuf = Struct::new(raw.args[2], Direction::In);
+        statbuf.size = STAT_SIZE;
         let flags = Flag::new(raw.args[3]);
         let retval = None;
         Self { dirfd, pathname, statbuf, flags, retval }
     }
 }
 impl DecodeEntry for Fstatat {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.dirfd.decode(pid, operation).unwrap();
         self.pathname.decode(pid, operation).unwrap();
         self.statbuf.decode(pid, operation).unwrap();
         self.flags.decode(pid, operation).unwrap();
     }
 }
+impl EncodeEntry for Fstatat {
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.dirfd.value;
+        raw.args[1] = self.pathname.encode(pid, operation, remote, scratch)?;
+        raw.args[2] = self.statbuf.encode(pid, operation, remote, scratch)?;
+        Ok(raw)
+    }
+}
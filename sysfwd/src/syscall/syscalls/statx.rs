@@ -3,21 +3,30 @@
  */
 use serde::{ Serialize, Deserialize };
 
-use decoding_macro::DecodeExit;
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
 use crate::{
     syscall::RawSyscall,
     syscall::args::Direction,
     syscall::args::{ Integer, Fd, Flag, NullBuffer, Struct },
-    syscall::decoder::{ DecodeArg, DecodeEntry, DecodeExit },
-    targets::operation::Operation,
+    syscall::{
+        decoder::{ DecodeArg, DecodeEntry, DecodeExit },
+        encoder::{ EncodeArg, EncodeEntry },
+    },
+    targets::operation::{ Operation, MemoryOperation },
+    executor::Scratch,
 };
 
+/* sizeof(struct statx) */
+const STATX_SIZE: usize = 256;
+
 
 
 // int statx(int dirfd, const char *restrict pathname, int flags, unsigned int mask, struct statx *restrict statxbuf);
 #[derive(Serialize, Deserialize)]
 #[derive(DecodeExit)]
 #[derive(Clone, Debug)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Statx {
     pub dirfd: Fd,
     pub pathname: NullBuffer,
@@ -32,17 +41,26 @@ impl Statx {
         let pathname = NullBuffer::new(raw.args[1], Direction::In);
         let flags = Flag::new(raw.args[2]);
         let mask = Integer::new(raw.args[3]);
-        let statxbuf = Struct::new(raw.args[4], Direction::InOut);
+        let mut statxbuf = Struct::new(raw.args[4], Direction::In);
+        statxbuf.size = STATX_SIZE;
         let retval = None;
         Self { dirfd, pathname, flags, mask, statxbuf, retval }
     }
 }
 impl DecodeEntry for Statx {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.dirfd.decode(pid, operation).unwrap();
         self.pathname.decode(pid, operation).unwrap();
         self.flags.decode(pid, operation).unwrap();
         self.mask.decode(pid, operation).unwrap();
         self.statxbuf.decode(pid, operation).unwrap();
     }
-}
\ No newline at end of file
+}
+impl EncodeEntry for Statx {
+    fn encode_entry(&mut self, mut raw: RawSyscall, pid: i32, operation: &Operation, remote: &dyn MemoryOperation, scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.dirfd.value;
+        raw.args[1] = self.pathname.encode(pid, operation, remote, scratch)?;
+        raw.args[4] = self.statxbuf.encode(pid, operation, remote, scratch)?;
+        Ok(raw)
+    }
+}
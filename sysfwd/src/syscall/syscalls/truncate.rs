@@ -2,12 +2,16 @@
  */
 use serde::{ Serialize, Deserialize };
 
-use decoding_macro::DecodeExit;
+use decoding_macro::{ DecodeExit, FdArgs, PointerArgs };
 use crate::{
     syscall::RawSyscall,
     syscall::args::{ Direction, Integer, Fd, Offset, NullBuffer },
-    syscall::decoder::{ DecodeArg, DecodeEntry, DecodeExit },
-    targets::operation::Operation,
+    syscall::{
+        decoder::{ DecodeArg, DecodeEntry, DecodeExit },
+        encoder::EncodeEntry,
+    },
+    targets::operation::{ Operation, MemoryOperation },
+    executor::Scratch,
 };
 
 
@@ -16,6 +20,8 @@ use crate::{
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Truncate {
     pub path: NullBuffer,
     pub length: Offset,
@@ -32,7 +38,7 @@ impl Truncate {
 }
 
 impl DecodeEntry for Truncate {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.path.decode(pid, operation).unwrap();
         self.length.decode(pid, operation).unwrap();
     }
@@ -43,6 +49,8 @@ impl DecodeEntry for Truncate {
 #[derive(Serialize, Deserialize)]
 #[derive(Clone, Debug)]
 #[derive(DecodeExit)]
+#[derive(FdArgs)]
+#[derive(PointerArgs)]
 pub struct Ftruncate {
     pub fd: Fd,
     pub length: Offset,
@@ -59,8 +67,15 @@ impl Ftruncate {
 }
 
 impl DecodeEntry for Ftruncate {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.fd.decode(pid, operation).unwrap();
         self.length.decode(pid, operation).unwrap();
     }
-}
\ No newline at end of file
+}
+impl EncodeEntry for Ftruncate {
+    fn encode_entry(&mut self, mut raw: RawSyscall, _pid: i32, _operation: &Operation, _remote: &dyn MemoryOperation, _scratch: &Scratch) -> Result<RawSyscall, std::io::Error> {
+        raw.args[0] = self.fd.value;
+        raw.args[1] = self.length.value;
+        Ok(raw)
+    }
+}
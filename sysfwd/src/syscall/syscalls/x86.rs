@@ -32,7 +32,7 @@ impl ArchPrctl {
     }
 }
 impl DecodeEntry for ArchPrctl {
-    fn decode_entry(&mut self, pid: i32, operation: &Box<Operation>) {
+    fn decode_entry(&mut self, pid: i32, operation: &Operation) {
         self.code.decode(pid, operation).unwrap();
         self.addr.decode(pid, operation).unwrap();
     }
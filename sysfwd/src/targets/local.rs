@@ -0,0 +1,58 @@
+/*
+ * The interfaces used by an executor running the syscalls in its own process, see NativeInvoker.
+ * The memory is accessed with process_vm_readv / process_vm_writev so that an invalid address
+ * fails instead of crashing the executor.
+ */
+use std::io::{ self, IoSlice, IoSliceMut };
+
+use nix::{
+    unistd::Pid,
+    libc::user_regs_struct,
+    sys::uio::{ process_vm_readv, process_vm_writev, RemoteIoVec },
+};
+use crate::targets::operation::{ RegisterOperation, MemoryOperation };
+
+
+
+#[derive(Clone, Debug)]
+pub struct Local { }
+
+impl RegisterOperation for Local {
+
+    /* The registers of the executor are not those of a process executing syscalls */
+    fn read_registers(&self, _pid: i32) -> Option<user_regs_struct> {
+        None
+    }
+
+    fn write_registers(&self, _pid: i32, _regs: user_regs_struct) -> Result<(), io::Error> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "No registers in a native session"))
+    }
+}
+
+impl MemoryOperation for Local {
+
+    fn read(&self, _pid: i32, addr: usize, size: usize) -> Vec<u8> {
+        let mut mem = vec![0; size];
+        let remote = [RemoteIoVec { base: addr, len: size }];
+        match process_vm_readv(Pid::this(), &mut [IoSliceMut::new(&mut mem)], &remote) {
+            Ok(count) => mem.truncate(count),
+            Err(err) => {
+                eprintln!("An error {} occured during read at {:#x} in the executor", err, addr);
+                mem.clear();
+            },
+        }
+        mem
+    }
+
+    fn write(&self, _pid: i32, addr: usize, mem: Vec<u8>) -> usize
+    {
+        let remote = [RemoteIoVec { base: addr, len: mem.len() }];
+        match process_vm_writev(Pid::this(), &[IoSlice::new(&mem)], &remote) {
+            Ok(count) => count,
+            Err(err) => {
+                eprintln!("An error {} occured during write at {:#x} in the executor", err, addr);
+                0
+            },
+        }
+    }
+}
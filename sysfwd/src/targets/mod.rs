@@ -4,4 +4,5 @@
 pub mod operation;
 
 pub mod ptrace;
+pub mod local;
 pub mod qemu;
\ No newline at end of file
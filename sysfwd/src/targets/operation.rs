@@ -5,6 +5,11 @@
  *
  * Note: we could at some point split the operations into different traits.
  */
+use std::{
+    io,
+    sync::mpsc::Sender,
+};
+
 use nix::libc::user_regs_struct;
 
 
@@ -46,6 +51,45 @@ pub struct Operation {
 }
 
 
+/*
+ * Operations requested by the control thread.
+ * With ptrace, only the thread tracing a process can read or write its state, so the requests are sent
+ * to this thread which serves them while the process is stopped and sends back the result.
+ */
+pub enum OperationRequest {
+    ReadMemory { address: usize, size: usize, reply: Sender<Vec<u8>> },
+    WriteMemory { address: usize, data: Vec<u8>, reply: Sender<usize> },
+    ReadRegisters { reply: Sender<Option<user_regs_struct>> },
+    WriteRegisters { regs: user_regs_struct, reply: Sender<Result<(), io::Error>> },
+}
+
+impl OperationRequest {
+
+    pub fn serve(self, pid: i32, operation: &Operation)
+    {
+        // The requester may have given up, ignore send errors
+        match self {
+            OperationRequest::ReadMemory { address, size, reply } => {
+                let mut mem = operation.memory.read(pid, address, size);
+                mem.truncate(size);
+                let _ = reply.send(mem);
+            },
+            OperationRequest::WriteMemory { address, data, reply } => {
+                let size = data.len();
+                let written = operation.memory.write(pid, address, data);
+                let _ = reply.send(written.min(size));
+            },
+            OperationRequest::ReadRegisters { reply } => {
+                let _ = reply.send(operation.register.read_registers(pid));
+            },
+            OperationRequest::WriteRegisters { regs, reply } => {
+                let _ = reply.send(operation.register.write_registers(pid, regs));
+            },
+        }
+    }
+}
+
+
 /*
  * XXX 
  * Another way to had more flexibility between the interceptors would be to have a structure
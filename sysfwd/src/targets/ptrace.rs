@@ -26,10 +26,10 @@ impl RegisterOperation for Ptrace {
     fn write_registers(&self, pid: i32, regs: user_regs_struct) -> Result<(), io::Error> {
         let pid = Pid::from_raw(pid);
         match ptrace::setregs(pid, regs) {
-            Ok(()) => return Ok(()),
+            Ok(()) => Ok(()),
             Err(e) => {
                 eprintln!("[{}] Error setting registers: {}", pid, e);
-                return Err(e.into())
+                Err(e.into())
             },
         }
     }
@@ -41,7 +41,10 @@ impl MemoryOperation for Ptrace {
         let pid = Pid::from_raw(pid);
         let mut mem: Vec<u8> = Vec::new();
         let mut addr = addr;
-        let mut count = size + (4 - size % 4);
+        let mut count = match size.checked_next_multiple_of(4) {
+            Some(count) => count,
+            None => return mem,
+        };
         
         /*
         println!("On process {}, read {} at {:#x}", pid, size, addr);
@@ -54,19 +57,20 @@ impl MemoryOperation for Ptrace {
             let address = addr as ptrace::AddressType;
             //mem.push(ptrace::read(pid, address).unwrap() as u32);
             //let word = ptrace::read(pid, address).unwrap() as u32;
-            let word: u32;
-            match ptrace::read(pid, address) {
-                Ok(w) => {
-                    word = w as u32;
-                }
+            let word = match read_word(pid, addr) {
+                Ok(w) => w,
                 Err(err)=> {
                     eprintln!("An error {} occured during read at {:?} on {}", err, address, pid);
                     break;
                 }
-            }
+            };
             mem.extend_from_slice(&word.to_le_bytes());
-            addr += 4;
             count -= 4;
+            // Nothing is mapped past the end of the address space
+            addr = match addr.checked_add(4) {
+                Some(next) => next,
+                None => break,
+            };
             //println!("Read returns {}, remains {} bytes", word, count);
         }
         mem
@@ -74,39 +78,76 @@ impl MemoryOperation for Ptrace {
 
     fn write(&self, pid: i32, addr: usize, mem: Vec<u8>) -> usize
     {
-        let pid = Pid::from_raw(pid);
-        let mut addr = addr;
+        let size = mem.len();
         let mut mem = mem;
 
-        // Pad the memory to be a multiple of 4
-        let pad_bytes = 4 - (mem.len() % 4);
-        mem.extend(std::iter::repeat(0).take(pad_bytes));
+        // Complete the last word with the bytes already in memory, so nothing after the buffer is overwritten
+        let pad_bytes = (4 - (size % 4)) % 4;
+        if pad_bytes > 0 {
+            let tail = self.read(pid, addr + size - (size % 4), 4);
+            if tail.len() < 4 {
+                eprintln!("Fail to read the last word to write at {:#x} on {}", addr + size, pid);
+                return 0;
+            }
+            mem.extend_from_slice(&tail[size % 4..4]);
+        }
+
+        let pid = Pid::from_raw(pid);
+        let mut addr = addr;
+        let mut written = 0;
 
         //println!("[WRITE] {:?}", mem);
 
-        let size = mem.len();
-        let mut count = mem.len();
-
         for chunk in mem.chunks(4) {
 
             let address = addr as ptrace::AddressType;
             let word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
-            let word = word as *mut c_void;
-
-            unsafe {
-                match ptrace::write(pid, address, word) {
-                    Ok(()) => (),
-                    Err(err) => {
-                        eprintln!("An error {} occured during write at {:?} on {}", err, address, pid);
-                        break;
-                    }
+
+            match write_word(pid, addr, word) {
+                Ok(()) => (),
+                Err(err) => {
+                    eprintln!("An error {} occured during write at {:?} on {}", err, address, pid);
+                    break;
                 }
             }
             addr += 4;
-            count -= 4;
+            written += 4;
         }
 
-        size - count
+        written.min(size)
     }
 
 }
+
+
+
+/*
+ * ptrace accesses a whole long, the last 4 bytes before an unmapped page are accessed through the long before.
+ */
+fn read_word(pid: Pid, addr: usize) -> nix::Result<u32>
+{
+    match ptrace::read(pid, addr as ptrace::AddressType) {
+        Ok(long) => Ok(long as u32),
+        Err(err) => {
+            let before = addr.checked_sub(4).ok_or(err)?;
+            let long = ptrace::read(pid, before as ptrace::AddressType).map_err(|_| err)?;
+            Ok((long as u64 >> 32) as u32)
+        },
+    }
+}
+
+/*
+ * Write the 4 bytes at `addr`, the other half of the long is kept as is.
+ */
+fn write_word(pid: Pid, addr: usize, word: u32) -> nix::Result<()>
+{
+    let (address, long) = match ptrace::read(pid, addr as ptrace::AddressType) {
+        Ok(long) => (addr, (long as u64 & 0xffffffff_00000000) | word as u64),
+        Err(err) => {
+            let before = addr.checked_sub(4).ok_or(err)?;
+            let long = ptrace::read(pid, before as ptrace::AddressType).map_err(|_| err)?;
+            (before, (long as u64 & 0xffffffff) | (word as u64) << 32)
+        },
+    };
+    unsafe { ptrace::write(pid, address as ptrace::AddressType, long as *mut c_void) }
+}
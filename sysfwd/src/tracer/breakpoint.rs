@@ -0,0 +1,110 @@
+/*
+ * Software breakpoints inserted in the tracee memory.
+ */
+use std::{
+    collections::HashMap,
+    io,
+};
+
+use crate::{
+    arch::BREAKPOINT_OPCODE,
+    targets::operation::MemoryOperation,
+};
+
+
+
+/*
+ * Keep the original instructions overwritten by the breakpoints.
+ * Note: the breakpoints are not hidden from the memory reads.
+ */
+pub struct Breakpoints {
+    saved: HashMap<usize, Vec<u8>>,
+}
+
+impl Default for Breakpoints {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Breakpoints {
+
+    pub fn new() -> Self
+    {
+        Self { saved: HashMap::new() }
+    }
+
+    pub fn contains(&self, address: usize) -> bool
+    {
+        self.saved.contains_key(&address)
+    }
+
+    pub fn insert(&mut self, pid: i32, address: usize, memory: &dyn MemoryOperation) -> Result<(), io::Error>
+    {
+        if self.contains(address) {
+            return Ok(());
+        }
+
+        let mut original = memory.read(pid, address, BREAKPOINT_OPCODE.len());
+        original.truncate(BREAKPOINT_OPCODE.len());
+        if original.len() < BREAKPOINT_OPCODE.len() {
+            let msg = format!("Fail to read the instruction at {:#x}", address);
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+        }
+
+        Self::write(pid, address, BREAKPOINT_OPCODE.to_vec(), memory)?;
+        self.saved.insert(address, original);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, pid: i32, address: usize, memory: &dyn MemoryOperation) -> Result<(), io::Error>
+    {
+        match self.saved.remove(&address) {
+            Some(original) => Self::write(pid, address, original, memory),
+            None => {
+                let msg = format!("No breakpoint at {:#x}", address);
+                Err(io::Error::new(io::ErrorKind::NotFound, msg))
+            },
+        }
+    }
+
+    /*
+     * Restore all the original instructions, e.g., before detaching from the tracee.
+     */
+    pub fn clear(&mut self, pid: i32, memory: &dyn MemoryOperation) -> Result<(), io::Error>
+    {
+        for (address, original) in self.saved.drain() {
+            Self::write(pid, address, original, memory)?;
+        }
+        Ok(())
+    }
+
+    /*
+     * Put back the original instruction while keeping the breakpoint, to step over it.
+     */
+    pub fn disable(&self, pid: i32, address: usize, memory: &dyn MemoryOperation) -> Result<(), io::Error>
+    {
+        match self.saved.get(&address) {
+            Some(original) => Self::write(pid, address, original.clone(), memory),
+            None => Ok(()),
+        }
+    }
+
+    pub fn enable(&self, pid: i32, address: usize, memory: &dyn MemoryOperation) -> Result<(), io::Error>
+    {
+        match self.saved.get(&address) {
+            Some(_) => Self::write(pid, address, BREAKPOINT_OPCODE.to_vec(), memory),
+            None => Ok(()),
+        }
+    }
+
+    fn write(pid: i32, address: usize, bytes: Vec<u8>, memory: &dyn MemoryOperation) -> Result<(), io::Error>
+    {
+        let size = bytes.len();
+        if memory.write(pid, address, bytes) < size {
+            let msg = format!("Fail to write the instruction at {:#x}", address);
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+        }
+        Ok(())
+    }
+}
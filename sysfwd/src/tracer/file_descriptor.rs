@@ -2,13 +2,14 @@
  * To manage the file descriptor management and translation between local, remote, user and kernel.
  */
 
-use std::collections::HashSet;
+use std::collections::BTreeMap;
+use std::rc::Rc;
 
 
 
 
 
-/* 
+/*
  * Used to store the location where the FD is valid
  */
 #[derive(Clone, Copy, Debug)]
@@ -19,102 +20,128 @@ pub enum FdLocation {
 
 
 /*
- * In order to differentiate local from remote file descriptor for the user program,
- * we add an offset to remote fd.
+ * A user fd of the table: the open file description it refers to and its fd flags.
+ * The description is shared by the fds dup'd from the same one, the remote fd is closed once the last of them is gone.
  */
-static REMOTE_FD_OFFSET: usize = 4096;
+#[derive(Clone, Debug)]
+struct FdEntry {
+    description: Rc<FdLocation>,
+    close_on_exec: bool,
+}
+
 
 /*
- * A wrapper structure around hashmap for managing file descriptor translation.
+ * A wrapper structure around a map for managing file descriptor translation.
+ * A remote fd is given to the user program as a placeholder fd opened in the program itself,
+ * so that its number is a genuine local fd: the user fd is the placeholder and the kernel fd the remote one.
  * For now, it is only used to store remote FD.
  */
 #[derive(Debug)]
 pub struct FdTable {
-    fd_table: Vec<Option<FdLocation>>,
-    available_fd: HashSet<usize>,
+    fd_table: BTreeMap<usize, FdEntry>,
+}
+
+impl Default for FdTable {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl FdTable {
 
     pub fn new() -> Self
     {
-        Self { 
-            fd_table: Vec::new(),
-            available_fd: HashSet::new(),
+        Self {
+            fd_table: BTreeMap::new(),
         }
     }
 
-    /*
-     * Create a new local-user / remote-kernel FD association.
-     * Typically used during the exit of an open() system call.
-     */
-    fn insert(&mut self, kernel_fd: FdLocation) -> usize
+    pub fn is_empty(&self) -> bool
     {
-        if let Some(user_fd) = self.available_fd.iter().copied().min() {
-            self.fd_table[user_fd] = Some(kernel_fd);
-            self.available_fd.remove(&user_fd);
-            user_fd
-        } else {
-            let user_fd = self.fd_table.len();
-            self.fd_table.push(Some(kernel_fd));
-            user_fd
-        }
+        self.fd_table.is_empty()
     }
 
-    pub fn open_remote(&mut self, kernel_fd: usize) -> usize
+    /*
+     * Associate the local placeholder `user_fd` to the remote `kernel_fd`, a new description.
+     * Typically used during the exit of an open() system call, once the placeholder is opened.
+     * The remote fd to close is returned when the placeholder replaced the last fd of another description.
+     */
+    pub fn open_remote(&mut self, user_fd: usize, kernel_fd: usize, close_on_exec: bool) -> Option<usize>
     {
-        let fd = FdLocation::Remote(kernel_fd);
-        self.insert(fd) + REMOTE_FD_OFFSET
+        let released = self.close_remote(user_fd);
+        let entry = FdEntry { description: Rc::new(FdLocation::Remote(kernel_fd)), close_on_exec };
+        self.fd_table.insert(user_fd, entry);
+        released
     }
 
     /* The table is not used for local FD.
     pub fn open_local(&mut self, kernel_fd: usize) -> usize
+    */
+
+    /*
+     * Make `new_user_fd` refer to the description of `user_fd`, as dup2() does.
+     * A local `user_fd` makes `new_user_fd` a local fd as well.
+     * The remote fd to close is returned when `new_user_fd` was the last fd of another description.
+     */
+    pub fn dup(&mut self, user_fd: usize, new_user_fd: usize, close_on_exec: bool) -> Option<usize>
     {
-        let fd = FdLocation::Local(kernel_fd);
-        self.insert(fd)
+        let description = self.fd_table.get(&user_fd).map(|entry| entry.description.clone());
+        let released = self.close_remote(new_user_fd);
+        if let Some(description) = description {
+            self.fd_table.insert(new_user_fd, FdEntry { description, close_on_exec });
+        }
+        released
     }
-    */
 
     /*
      * Close the local-user / remote-kernel FD association.
      * Typically used during the exit of an close() system call.
+     * The remote fd is returned when it was the last fd of its description, it must then be closed.
      */
-    fn remove(&mut self, user_fd: usize) -> Option<FdLocation>
+    pub fn close_remote(&mut self, user_fd: usize) -> Option<usize>
     {
-        if user_fd < self.fd_table.len() {
-            if let Some(kernel_fd) = self.fd_table[user_fd] {
-                self.fd_table[user_fd] = None;
-                self.available_fd.insert(user_fd);
-                Some(kernel_fd)
-            } else {
-                // Element not present
-                None
-            }
-        } else {
-            // Index out of range
-            None
-        }
+        let entry = self.fd_table.remove(&user_fd)?;
+        release(entry)
     }
 
-    pub fn close_remote(&mut self, user_fd: usize) -> Option<usize>
+    //pub fn close_local(&mut self, user_fd: usize) -> Option<usize>
+
+    /*
+     * Whether closing `user_fd` leaves its remote fd open, used by another fd of the description.
+     */
+    pub fn is_shared(&self, user_fd: usize) -> bool
     {
-        //if user_fd < REMOTE_FD_OFFSET {
-        //    return None;
-        //}
-        //let user_fd = user_fd - REMOTE_FD_OFFSET;
-
-        if let Some(kernel_fd) = self.remove(user_fd) {
-            if let FdLocation::Remote(remote_fd) = kernel_fd {
-                Some(remote_fd)
-            } else {
-                panic!("FdTable is not supposed to store Local(fd) yet.");
-            }
-        } else {
-            None
+        self.fd_table.get(&user_fd).is_some_and(|entry| Rc::strong_count(&entry.description) > 1)
+    }
+
+    pub fn set_close_on_exec(&mut self, user_fd: usize, close_on_exec: bool)
+    {
+        if let Some(entry) = self.fd_table.get_mut(&user_fd) {
+            entry.close_on_exec = close_on_exec;
         }
     }
 
-    //pub fn close_local(&mut self, user_fd: usize) -> Option<usize>
+    /*
+     * Remove the close-on-exec fds once the program executed another one.
+     * The user fds removed and the remote fds to close are returned.
+     */
+    pub fn exec(&mut self) -> (Vec<usize>, Vec<usize>)
+    {
+        let closed: Vec<usize> = self.fd_table.iter()
+            .filter(|(_, entry)| entry.close_on_exec)
+            .map(|(user_fd, _)| *user_fd)
+            .collect();
+        let released = closed.iter().filter_map(|user_fd| self.close_remote(*user_fd)).collect();
+        (closed, released)
+    }
+
+    /*
+     * Drop the table of a program gone, the remote fds to close are returned.
+     */
+    pub fn release(self) -> Vec<usize>
+    {
+        self.fd_table.into_values().filter_map(release).collect()
+    }
 
     /*
      * Translate a FD used in user space with the corresponding FD used by the remote kernel.
@@ -122,25 +149,88 @@ impl FdTable {
      */
     pub fn translate(&self, user_fd: usize) -> Option<usize>
     {
-        if user_fd < REMOTE_FD_OFFSET {
-            return None;
-        }
-        let user_fd = user_fd - REMOTE_FD_OFFSET;
-
-        if user_fd < self.fd_table.len() {
-            if let Some(kernel_fd) = &self.fd_table[user_fd] {
-                match kernel_fd {
-                    FdLocation::Local(fd) => Some(*fd),
-                    FdLocation::Remote(fd) => Some(*fd),
-                }
-            } else {
-                None
-            }
-        } else {
-            // Index out of range
-            None
+        match *self.fd_table.get(&user_fd)?.description {
+            FdLocation::Local(fd) => Some(fd),
+            FdLocation::Remote(fd) => Some(fd),
         }
     }
 
 
 }
+
+
+/*
+ * The remote fd of the entry if it was the last fd of its description.
+ */
+fn release(entry: FdEntry) -> Option<usize>
+{
+    match Rc::try_unwrap(entry.description).ok()? {
+        FdLocation::Remote(remote_fd) => Some(remote_fd),
+        FdLocation::Local(_) => panic!("FdTable is not supposed to store Local(fd) yet."),
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remote_fd_closed_with_its_last_dup()
+    {
+        let mut table = FdTable::new();
+        assert_eq!(table.open_remote(3, 10, false), None);
+        assert_eq!(table.dup(3, 4, false), None);
+        assert_eq!((table.translate(3), table.translate(4)), (Some(10), Some(10)));
+        assert!(table.is_shared(3) && table.is_shared(4));
+
+        assert_eq!(table.close_remote(3), None);
+        assert!(!table.is_shared(4));
+        assert_eq!(table.close_remote(4), Some(10));
+        assert_eq!(table.close_remote(4), None);
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn dup_over_an_open_fd()
+    {
+        let mut table = FdTable::new();
+        table.open_remote(3, 10, false);
+        table.open_remote(4, 11, false);
+
+        // As dup2(3, 4), which closes the description of 4
+        assert_eq!(table.dup(3, 4, false), Some(11));
+        assert_eq!(table.translate(4), Some(10));
+        // Onto itself, the description is kept
+        assert_eq!(table.dup(4, 4, false), None);
+        assert_eq!(table.translate(4), Some(10));
+
+        // A placeholder reusing the number of the last fd of a description
+        assert_eq!(table.close_remote(3), None);
+        assert_eq!(table.open_remote(4, 12, false), Some(10));
+
+        // Duplicating a local fd closes the remote one it replaces
+        assert_eq!(table.dup(0, 4, false), Some(12));
+        assert_eq!(table.translate(4), None);
+    }
+
+    #[test]
+    fn exec_closes_the_close_on_exec_fds()
+    {
+        let mut table = FdTable::new();
+        table.open_remote(3, 10, true);
+        table.dup(3, 4, false);
+        table.open_remote(5, 11, false);
+        table.open_remote(6, 12, false);
+        table.set_close_on_exec(6, true);
+
+        // The description of 3 stays open through 4
+        assert_eq!(table.exec(), (vec![3, 6], vec![12]));
+        assert_eq!((table.translate(3), table.translate(4), table.translate(6)), (None, Some(10), None));
+
+        let mut released = table.release();
+        released.sort();
+        assert_eq!(released, vec![10, 11]);
+    }
+}
@@ -5,7 +5,10 @@
  * In the future, it would be nice to support eBPF filter in a similar way as seccomp does.
  * But what would it brings more than the Rule trait?
  */
+use std::io;
+
 use serde::{Serialize, Deserialize};
+use serde_json::Value;
 use crate::tracer::Syscall;
 
 
@@ -40,6 +43,12 @@ pub enum Decision {
      * Terminate the process
      */
     Kill        = 5,
+    /*
+     * Forward the syscall without waiting for its result, the expected value is returned right away.
+     * Only for syscalls whose results the tracee ignores (e.g., write), otherwise same as Forward.
+     * Remote errors are reported on the next synchronizing forwarded syscall.
+     */
+    ForwardAsync = 6,
 }
 
 
@@ -57,6 +66,41 @@ pub trait Rule {
      * E.g., to keep track of file descriptors during an execution
      */
     fn on_syscall_exit(&mut self, syscall: Syscall);
+
+    /*
+     * A callback called when forwarded fds are closed without a close() syscall,
+     * e.g., the close-on-exec fds on execve.
+     */
+    fn on_fds_closed(&mut self, _fds: &[usize])
+    {
+    }
+
+    /*
+     * How the rule is described when listing the rules of a filter,
+     * the name and params it has been created with from a rule registry.
+     */
+    fn name(&self) -> String
+    {
+        String::from("anonymous")
+    }
+
+    fn params(&self) -> Value
+    {
+        Value::Null
+    }
+}
+
+
+
+/*
+ * A rule loaded in a filter, the lower the index the higher the priority.
+ */
+#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug)]
+pub struct RuleInfo {
+    pub index: usize,
+    pub name: String,
+    pub params: Value,
 }
 
 
@@ -65,6 +109,8 @@ pub struct Filter {
     pub name: String,
     rules: Vec<Box<dyn Rule>>,
     default_decision: Decision,
+    /* The index of the rule which took the last decision */
+    matched: Option<usize>,
 }
 
 impl Filter {
@@ -72,20 +118,58 @@ impl Filter {
     pub fn new(name: String) -> Self
     {
         Filter {
-            name: name,
+            name,
             rules: Vec::new(),
             default_decision: Decision::Continue,
+            matched: None,
+        }
+    }
+
+    pub fn insert(&mut self, index: usize, rule: Box<dyn Rule>) -> Result<(), io::Error>
+    {
+        if index > self.rules.len() {
+            return Err(invalid_index(index, self.rules.len() + 1));
+        }
+        self.rules.insert(index, rule);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, index: usize) -> Result<Box<dyn Rule>, io::Error>
+    {
+        if index >= self.rules.len() {
+            return Err(invalid_index(index, self.rules.len()));
+        }
+        Ok(self.rules.remove(index))
+    }
+
+    /*
+     * Move the rule at `from` so that it ends up at index `to`.
+     */
+    pub fn move_rule(&mut self, from: usize, to: usize) -> Result<(), io::Error>
+    {
+        if from >= self.rules.len() || to >= self.rules.len() {
+            return Err(invalid_index(from.max(to), self.rules.len()));
         }
+        let rule = self.rules.remove(from);
+        self.rules.insert(to, rule);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize
+    {
+        self.rules.len()
     }
 
-    pub fn insert(&mut self, index: usize, rule: Box<dyn Rule>)
+    pub fn is_empty(&self) -> bool
     {
-        self.rules.insert(index, rule)
+        self.rules.is_empty()
     }
 
-    pub fn remove(&mut self, index: usize) -> Box<dyn Rule>
+    pub fn list(&self) -> Vec<RuleInfo>
     {
-        self.rules.remove(index)
+        self.rules.iter().enumerate()
+            .map(|(index, rule)| RuleInfo { index, name: rule.name(), params: rule.params() })
+            .collect()
     }
 
     /*
@@ -96,8 +180,9 @@ impl Filter {
     {
         let mut decision: Option<Decision> = None;
         // = self.default_decision;
+        self.matched = None;
 
-        for rule in self.rules.iter_mut() {
+        for (index, rule) in self.rules.iter_mut().enumerate() {
 
             // We clone to ensure each rule has the correct syscall and has not been modified by the user.
             let clone_syscall = syscall.clone();
@@ -111,6 +196,7 @@ impl Filter {
                 Ok(result) => {
                     if result != Decision::Pass {
                         decision = Some(result);
+                        self.matched = Some(index);
                         break;
                     }
                 },
@@ -118,10 +204,19 @@ impl Filter {
             }
         }
 
-        if decision == None {
+        if decision.is_none() {
             return self.default_decision;
         }
-        return decision.unwrap();
+        decision.unwrap()
+    }
+
+    /*
+     * The rule which took the last decision, none when it was the default decision.
+     */
+    pub fn matched(&self) -> Option<RuleInfo>
+    {
+        let index = self.matched?;
+        self.rules.get(index).map(|rule| RuleInfo { index, name: rule.name(), params: rule.params() })
     }
 
     /*
@@ -136,5 +231,18 @@ impl Filter {
         }
     }
 
+    pub fn on_fds_closed(&mut self, fds: &[usize])
+    {
+        for rule in self.rules.iter_mut() {
+            rule.on_fds_closed(fds)
+        }
+    }
+
 }
 
+
+fn invalid_index(index: usize, count: usize) -> io::Error
+{
+    let msg = format!("Invalid rule index {}, the filter has {} slots", index, count);
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
@@ -0,0 +1,165 @@
+/*
+ * The mappings of forwarded fds, e.g., the registers or the buffers of a device.
+ * The tracee is given an anonymous mapping mirroring the one created by the executor child,
+ * their contents are synchronized around the forwarded syscalls on the mapped fd, and before msync, munmap,
+ * mremap or execve. The changes made meanwhile by the other side are not seen.
+ */
+
+use std::collections::BTreeMap;
+
+
+
+/* The granularity of the synchronization */
+pub const PAGE_SIZE: usize = 0x1000;
+
+
+
+/*
+ * A local mapping in the tracee and the remote one it mirrors in the executor child.
+ */
+#[derive(Debug)]
+pub struct Mirror {
+    pub local: usize,
+    pub remote: usize,
+    pub length: usize,
+    /* The remote fd mapped */
+    pub fd: usize,
+    /* Only the tracee writes to a read-only mapping */
+    pub writable: bool,
+    /* The content both sides had at the last synchronization */
+    synced: Vec<u8>,
+}
+
+impl Mirror {
+
+    /*
+     * The pages of `content` which changed since the last synchronization, with their offset.
+     * The content is then the synchronized one.
+     */
+    pub fn diff(&mut self, content: Vec<u8>) -> Vec<(usize, Vec<u8>)>
+    {
+        let changed = content.chunks(PAGE_SIZE)
+            .zip(self.synced.chunks(PAGE_SIZE))
+            .enumerate()
+            .filter(|(_, (page, synced))| page != synced)
+            .map(|(index, (page, _))| (index * PAGE_SIZE, page.to_vec()))
+            .collect();
+        if content.len() == self.synced.len() {
+            self.synced = content;
+        }
+        changed
+    }
+
+    /*
+     * The part of the mirror in [start, end), as a new mirror.
+     */
+    fn slice(&self, start: usize, end: usize) -> Mirror
+    {
+        let (start, end) = (start.max(self.local), end.min(self.local + self.length));
+        Mirror {
+            local: start,
+            remote: self.remote + (start - self.local),
+            length: end - start,
+            fd: self.fd,
+            writable: self.writable,
+            synced: self.synced[start - self.local..end - self.local].to_vec(),
+        }
+    }
+}
+
+
+
+/*
+ * The mirrors indexed by their local address.
+ */
+#[derive(Debug, Default)]
+pub struct MirrorTable {
+    mirrors: BTreeMap<usize, Mirror>,
+}
+
+impl MirrorTable {
+
+    pub fn new() -> Self
+    {
+        Self {
+            mirrors: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.mirrors.is_empty()
+    }
+
+    /*
+     * Mirror the remote mapping at `local`, both have `content`.
+     */
+    pub fn insert(&mut self, local: usize, remote: usize, fd: usize, writable: bool, content: Vec<u8>)
+    {
+        let length = content.len();
+        self.mirrors.insert(local, Mirror { local, remote, length, fd, writable, synced: content });
+    }
+
+    /*
+     * The remote fds mapped, e.g., to synchronize every mirror.
+     */
+    pub fn fds(&self) -> Vec<usize>
+    {
+        self.mirrors.values().map(|mirror| mirror.fd).collect()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Mirror>
+    {
+        self.mirrors.values_mut()
+    }
+
+    /*
+     * The parts of the mirrors in the local range, e.g., to be synchronized before the tracee unmaps them.
+     */
+    pub fn overlapping(&self, address: usize, length: usize) -> Vec<(usize, usize)>
+    {
+        let end = address.saturating_add(length.next_multiple_of(PAGE_SIZE));
+        self.mirrors.values()
+            .filter(|mirror| mirror.local < end && address < mirror.local + mirror.length)
+            .map(|mirror| {
+                let (start, end) = (address.max(mirror.local), end.min(mirror.local + mirror.length));
+                (mirror.remote + (start - mirror.local), end - start)
+            })
+            .collect()
+    }
+
+    /*
+     * Stop mirroring the local range, the parts of the remote mappings to unmap are returned.
+     * A mirror partly in the range keeps the rest.
+     */
+    pub fn unmap(&mut self, address: usize, length: usize) -> Vec<(usize, usize)>
+    {
+        let end = address.saturating_add(length.next_multiple_of(PAGE_SIZE));
+        let removed = self.overlapping(address, length);
+        let mirrors = std::mem::take(&mut self.mirrors);
+
+        for (local, mirror) in mirrors {
+            if mirror.local + mirror.length <= address || end <= local {
+                self.mirrors.insert(local, mirror);
+                continue;
+            }
+            if local < address {
+                self.mirrors.insert(local, mirror.slice(local, address));
+            }
+            if end < mirror.local + mirror.length {
+                self.mirrors.insert(end, mirror.slice(end, mirror.local + mirror.length));
+            }
+        }
+        removed
+    }
+
+    /*
+     * Stop mirroring everything, e.g., once the tracee executed another program.
+     */
+    pub fn clear(&mut self) -> Vec<(usize, usize)>
+    {
+        std::mem::take(&mut self.mirrors).into_values()
+            .map(|mirror| (mirror.remote, mirror.length))
+            .collect()
+    }
+}
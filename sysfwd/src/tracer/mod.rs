@@ -2,18 +2,29 @@
  * The tracer engine takes care of handling syscalls.
  */
 mod tracer_engine;
+pub mod breakpoint;
 pub mod file_descriptor;
 pub mod filtering;
+pub mod mirror;
 
 pub use tracer_engine::TracerEngine;
 
 
-use std::io;
+use std::{
+    io,
+    process::Child,
+};
 use nix::{
     unistd::Pid,
     sys::signal::Signal,
+    libc::user_regs_struct,
+};
+use serde_json::Value;
+use crate::{
+    syscall::Syscall,
+    protocol::control::Notifier,
+    tracer::filtering::RuleInfo,
 };
-use crate::syscall::Syscall;
 
 
 
@@ -27,5 +38,53 @@ pub trait TracerCallback {
     fn start_tracing(&mut self, pid: Pid) -> Result<(), io::Error>;
     fn cont_tracing(&mut self, pid: Pid, signal: Option<Signal>) -> Result<(), io::Error>;
     fn stop_tracing(&mut self, pid: Pid) -> Result<(), io::Error>;
+    /* Whether the pid is one of the processes of the tracer */
+    fn has_process(&self, pid: Pid) -> bool;
+
+    fn read_memory(&mut self, pid: Pid, address: usize, size: usize) -> Result<Vec<u8>, io::Error>;
+    fn write_memory(&mut self, pid: Pid, address: usize, data: Vec<u8>) -> Result<usize, io::Error>;
+    fn read_registers(&mut self, pid: Pid) -> Result<user_regs_struct, io::Error>;
+    fn write_registers(&mut self, pid: Pid, regs: user_regs_struct) -> Result<(), io::Error>;
+
+    fn set_breakpoint(&mut self, pid: Pid, address: usize) -> Result<(), io::Error>;
+    fn remove_breakpoint(&mut self, pid: Pid, address: usize) -> Result<(), io::Error>;
+
+    /*
+     * The filter rules of a tracee, created by name from the rule registry of the debugger.
+     * Without index, a rule is loaded with the lowest priority.
+     */
+    fn list_rules(&mut self, pid: Pid) -> Result<Vec<RuleInfo>, io::Error>;
+    fn available_rules(&mut self) -> Vec<String>;
+    fn load_rule(&mut self, pid: Pid, index: Option<usize>, name: String, params: Value) -> Result<(), io::Error>;
+    fn unload_rule(&mut self, pid: Pid, index: usize) -> Result<(), io::Error>;
+    fn move_rule(&mut self, pid: Pid, from: usize, to: usize) -> Result<(), io::Error>;
+
+    /*
+     * Give the debugger a way to report events, such as breakpoint hits, to the control client.
+     */
+    fn set_notifier(&mut self, _notifier: Notifier) {}
+
+    /*
+     * Used to switch the role of a debugger holding both a tracer and an executor.
+     * A released process is no longer traced and stays stopped, ready to be adopted by the other role.
+     */
+    fn release_process(&mut self, _pid: Pid) -> Result<Child, io::Error>
+    {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "The tracer cannot release its processes"))
+    }
+
+    fn adopt_process(&mut self, _child: Child) -> Result<Pid, io::Error>
+    {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "The tracer cannot adopt a process"))
+    }
+
+    /*
+     * Listen for gdb clients debugging the tracee, see protocol::gdb.
+     * Port 0 picks any free port, the port listened to is returned.
+     */
+    fn start_gdb_server(&mut self, _pid: Pid, _port: u16) -> Result<u16, io::Error>
+    {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "The tracer has no gdb server"))
+    }
 }
 
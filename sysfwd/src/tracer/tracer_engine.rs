@@ -8,24 +8,61 @@ use std::{
     collections::HashMap,
     sync::Arc,
     io,
+    time::{ Duration, Instant },
 };
-use nix::libc::user_regs_struct;
+use nix::libc::{ self, user_regs_struct };
 use serde_json;
 use crate::{
     arch::{ TargetArch, Architecture },
-    protocol::data::Client,
+    context::{ ProcessContext, CONTEXT_SYSCALLS, RLIMIT_SIZE },
+    protocol::{
+        data::{ Client, Heartbeat, HEARTBEAT_INTERVAL },
+        control::Notifier,
+        rpc::Event,
+    },
     syscall::{
         Syscall,
-        decoder::{ Decoder, DecodedSyscall },
+        decoder::{ Decoder, DecodedSyscall, FdArgs, PointerArgs },
+        args::Size,
+        args::Address,
+        syscalls::{ open::Close, mmap::{ Munmap, Msync } },
     },
     tracer::{
-        filtering::{ Decision, Filter, Rule },
+        filtering::{ Decision, Filter, Rule, RuleInfo },
         file_descriptor::FdTable,
+        mirror::{ MirrorTable, PAGE_SIZE },
+        breakpoint::Breakpoints,
     },
-    targets::operation::Operation,
+    targets::operation::{ Operation, OperationRequest },
 };
 
 
+/*
+ * Pipelined syscalls are sent by batch once one of these limits is reached.
+ * The size limit is on the serialized syscalls, it keeps the batch below the maximum UDP payload.
+ * Each byte of a buffer takes up to 4 characters in JSON, the rest of a write less than PIPELINE_SYSCALL_BYTES.
+ */
+const PIPELINE_MAX_SYSCALLS: usize = 32;
+const PIPELINE_MAX_BYTES: usize = 48 * 1024;
+const PIPELINE_SYSCALL_BYTES: usize = 512;
+/* The pipelined syscalls are not kept longer, they are sent on the next syscall of the tracee */
+const PIPELINE_MAX_DELAY: Duration = Duration::from_millis(100);
+
+/*
+ * Local syscalls which may block the tracee, e.g., waiting for an answer to what it wrote:
+ * the pipelined syscalls are sent before.
+ */
+const BLOCKING_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_read, libc::SYS_readv, libc::SYS_pread64, libc::SYS_preadv, libc::SYS_preadv2,
+    libc::SYS_recvfrom, libc::SYS_recvmsg, libc::SYS_recvmmsg, libc::SYS_accept, libc::SYS_accept4, libc::SYS_connect,
+    libc::SYS_poll, libc::SYS_ppoll, libc::SYS_select, libc::SYS_pselect6,
+    libc::SYS_epoll_wait, libc::SYS_epoll_pwait, libc::SYS_epoll_pwait2,
+    libc::SYS_nanosleep, libc::SYS_clock_nanosleep, libc::SYS_pause, libc::SYS_rt_sigsuspend, libc::SYS_rt_sigtimedwait,
+    libc::SYS_wait4, libc::SYS_waitid, libc::SYS_futex, libc::SYS_flock, libc::SYS_msgrcv, libc::SYS_semop,
+    libc::SYS_semtimedop, libc::SYS_mq_timedreceive,
+];
+
+
 /*
 struct TraceeState { 
     fd_table: HashMap<u16, FdLocation>,
@@ -49,17 +86,48 @@ pub struct TracerEngine {
     operator: Box<Operation>,
     decoder: Arc<Decoder>,
     protocol: Client,
+    /* Keep the session alive on the executor, stopped once the session ends */
+    heartbeat: Option<Heartbeat>,
 
     /* Tracee state */
     syscall: Syscall,
     remote_syscall: Syscall,
     insyscall: bool,
     fwd_fd_table: FdTable,
+    /* The remote fd returned by the forwarded syscall, given to the tracee as the local fd of the shadow syscall */
+    returned_fd: Option<usize>,
+    /* The syscall only runs locally on the placeholders, their remote fds are shared */
+    on_placeholders: bool,
+    /* The syscall creating a process is refused, see refuse_process_creation() */
+    refused: bool,
+    /* The mappings of the forwarded fds */
+    mirrors: MirrorTable,
+    /* The remote address of the forwarded mmap, mirrored by the local mapping of the shadow syscall */
+    mapped: Option<usize>,
     //state: TraceeState,
 
     filter: Filter,
 
+    /* Pipelined forwarding */
+    pipeline: Vec<Syscall>,
+    pipeline_bytes: usize,
+    /* When the oldest pipelined syscall was queued */
+    pipeline_since: Option<Instant>,
+    /* The syscall is queued, it was reported with its expected result on entry */
+    pipelined: bool,
+    /* The first error of the pipelined syscalls on each remote fd, reported by the next syscall on it */
+    deferred_errors: HashMap<usize, usize>,
+
+    /* Make the executor child adopt the context of the tracee, the last one sent is kept */
+    mirror_context: bool,
+    context: Option<ProcessContext>,
+    context_sent: Option<ProcessContext>,
+
     saved_syscall: Vec<Syscall>,
+
+    breakpoints: Breakpoints,
+
+    notifier: Option<Notifier>,
 }
 
 impl TracerEngine {
@@ -67,18 +135,19 @@ impl TracerEngine {
     pub fn new(
         pid: i32,
         target_arch: TargetArch,
-        ipv4_address: &str,
-        tracer_port: u16,
-        executor_port: u16,
+        protocol: Client,
         operator: Box<Operation>,
     ) -> Self 
     {
         let arch = Arc::new(Architecture::new(target_arch));
         let decoder = Arc::new(Decoder::new(arch.clone()));
+        let heartbeat = protocol.start_heartbeat(HEARTBEAT_INTERVAL)
+            .map_err(|err| println!("Fail to start the heartbeat of {}: {}", pid, err))
+            .ok();
 
         Self {
-            pid: pid,
-            arch: arch,
+            pid,
+            arch,
             //regs: vec![0; 33],
             regs: user_regs_struct {
                 r15: 0,
@@ -109,26 +178,103 @@ impl TracerEngine {
                 fs: 0,
                 gs: 0,
             },
-            operator: operator,
-            decoder: decoder,
-            protocol: Client::new(ipv4_address, tracer_port, executor_port),
+            operator,
+            decoder,
+            protocol,
+            heartbeat,
             syscall: Syscall::new(),
             remote_syscall: Syscall::new(),
             insyscall: false,   // Hypothesis: we do the tracing from the start!
             fwd_fd_table: FdTable::new(),
+            returned_fd: None,
+            on_placeholders: false,
+            refused: false,
+            mirrors: MirrorTable::new(),
+            mapped: None,
             //state: TraceeState::new(),
             filter: Filter::new(String::from("filtername")),
+            pipeline: Vec::new(),
+            pipeline_bytes: 0,
+            pipeline_since: None,
+            pipelined: false,
+            deferred_errors: HashMap::new(),
+            mirror_context: false,
+            context: None,
+            context_sent: None,
             saved_syscall: Vec::new(),
+            breakpoints: Breakpoints::new(),
+            notifier: None,
+        }
+    }
+
+
+    /*
+     * Report the rules fired and the forwarded syscalls to the control client.
+     */
+    pub fn set_notifier(&mut self, notifier: Notifier)
+    {
+        self.notifier = Some(notifier);
+    }
+
+    fn notify(&self, event: Event)
+    {
+        if let Some(notifier) = self.notifier.as_ref() {
+            notifier.notify(event);
         }
     }
 
+    fn is_subscribed(&self, event: &str) -> bool
+    {
+        self.notifier.as_ref().is_some_and(|notifier| notifier.is_subscribed(event))
+    }
+
+    pub fn set_breakpoint(&mut self, address: usize) -> Result<(), io::Error>
+    {
+        self.breakpoints.insert(self.pid, address, self.operator.memory.as_ref())
+    }
+
+    pub fn remove_breakpoint(&mut self, address: usize) -> Result<(), io::Error>
+    {
+        self.breakpoints.remove(self.pid, address, self.operator.memory.as_ref())
+    }
+
+    pub fn clear_breakpoints(&mut self) -> Result<(), io::Error>
+    {
+        self.breakpoints.clear(self.pid, self.operator.memory.as_ref())
+    }
+
+    pub fn has_breakpoint(&self, address: usize) -> bool
+    {
+        self.breakpoints.contains(address)
+    }
+
+    /*
+     * Used to single-step the original instruction of a breakpoint.
+     */
+    pub fn disable_breakpoint(&self, address: usize) -> Result<(), io::Error>
+    {
+        self.breakpoints.disable(self.pid, address, self.operator.memory.as_ref())
+    }
+
+    pub fn enable_breakpoint(&self, address: usize) -> Result<(), io::Error>
+    {
+        self.breakpoints.enable(self.pid, address, self.operator.memory.as_ref())
+    }
+
+    /*
+     * Serve an operation requested by the control thread while the tracee is stopped.
+     */
+    pub fn serve_request(&self, request: OperationRequest)
+    {
+        request.serve(self.pid, &self.operator);
+    }
 
     /*
      * When the tracking of the syscall entry/exit is left to the library,
      * we only synchronize the registers.
      */
     pub fn sync_registers(&mut self, regs: user_regs_struct) {
-        self.regs = regs.clone();
+        self.regs = regs;
     }
 
     pub fn trace(&mut self) -> Result<(), io::Error>
@@ -136,7 +282,7 @@ impl TracerEngine {
         match self.insyscall {
             false    => {
                 self.sync_entry();
-                self.trace_entry();
+                self.trace_entry()?;
             },
 
             true   => {
@@ -159,7 +305,7 @@ impl TracerEngine {
                                self.regs.r10 as usize,
                                self.regs.r8 as usize,
                                self.regs.r9 as usize,
-                               0 as usize,
+                               0_usize,
         );
     }
 
@@ -171,6 +317,7 @@ impl TracerEngine {
     /*
      * The other way is to directly call the right method.
      */
+    #[allow(clippy::too_many_arguments)]
     pub fn set_syscall_entry(&mut self, scno: usize, arg1: usize, 
                              arg2: usize, arg3: usize, arg4: usize,
                              arg5: usize, arg6: usize, arg7: usize) {
@@ -192,6 +339,11 @@ impl TracerEngine {
 
     pub fn shutdown(&mut self) -> Result<(), io::Error>
     {
+        // Do not lose the pipelined syscalls not sent yet
+        if let Err(err) = self.flush_pipeline() {
+            println!("Fail to flush the pipelined syscalls: {}", err);
+        }
+
         // Calculate & print syscall statistics
         // syscall number | how many? | is_decoded? | name
         match self.calculate_stats() {
@@ -205,9 +357,31 @@ impl TracerEngine {
         Ok(())
     }
 
+    /*
+     * The executor child adopts the credentials, umask, current directory and limits of the tracee
+     * before the forwarded syscalls, see ProcessContext.
+     */
+    pub fn set_mirror_context(&mut self, mirror: bool)
+    {
+        self.mirror_context = mirror;
+    }
+
+    /*
+     * Tell the executor to release what the forwarded syscalls created, once the tracee is gone.
+     */
+    pub fn end_session(&mut self)
+    {
+        if self.heartbeat.take().is_none() {
+            return;
+        }
+        if let Err(err) = self.flush_pipeline().and_then(|_| self.protocol.end_session()) {
+            println!("Fail to end the session of {}: {}", self.pid, err);
+        }
+    }
+
     /* Tracing */
 
-    fn trace_entry(&mut self) {
+    fn trace_entry(&mut self) -> Result<(), io::Error> {
         //self._log_raw_entry();
 
         // TODO: Add an option to decode only certain syscalls to increase speed.
@@ -215,22 +389,37 @@ impl TracerEngine {
 
         self.filter_entry();
         self.log_entry();
+        self.refused = self.refuse_process_creation();
 
         // Note: When should the decoded syscall be sync with the RawSyscall/tracee?
-        self.carry_out_entry_decision();
+        self.mirror_entry()?;
+        self.carry_out_entry_decision()?;
+
+        // The syscall does not return
+        if self.syscall.name == "exit_group" {
+            self.end_session();
+        }
 
         self.insyscall = true;
+        Ok(())
     }
 
     fn trace_exit(&mut self) {
         //self._log_raw_exit();
 
+        if self.refused {
+            self.fail_syscall(libc::EPERM);
+        }
+
         self.decoder.decode_exit(&mut self.syscall, self.pid, &self.operator);
 
         self.filter_exit();
         self.log_exit();
 
         self.carry_out_exit_decision();
+        self.update_context();
+        self.update_fd_table();
+        self.update_mirrors();
 
         self.insyscall = false;
     }
@@ -238,12 +427,12 @@ impl TracerEngine {
 
     fn _log_raw_entry(&self) {
         println!("[{}] [ENTRY] no: {:#x} args: {:x?}", 
-                 self.pid, self.syscall.raw.no as usize, self.syscall.raw.args)
+                 self.pid, { self.syscall.raw.no }, self.syscall.raw.args)
     }
 
     fn _log_raw_exit(&self) {
         println!("[{}] [EXIT] retval: {:#x}", 
-                 self.pid, self.syscall.raw.retval as usize)
+                 self.pid, { self.syscall.raw.retval })
     }
 
     fn log_entry(&self) {
@@ -254,7 +443,7 @@ impl TracerEngine {
     fn log_exit(&mut self) {
         let json = serde_json::to_string(&self.syscall).unwrap();
         println!("[{}] LOCAL: {}", self.pid, json);
-        println!("");
+        println!();
 
         self.saved_syscall.push(self.syscall.clone());
     }
@@ -262,7 +451,20 @@ impl TracerEngine {
     /* Filtering */
 
     fn filter_entry(&mut self) -> Option<Decision> {
-        self.syscall.decision = Some(self.filter.filter(self.insyscall, &self.syscall));
+        let decision = self.filter.filter(self.insyscall, &self.syscall);
+        self.syscall.decision = Some(decision);
+
+        if self.is_subscribed("rule_fired") {
+            if let Some(rule) = self.filter.matched() {
+                self.notify(Event::RuleFired {
+                    pid: self.pid,
+                    index: rule.index,
+                    rule: rule.name,
+                    syscall: self.syscall.name.clone(),
+                    decision,
+                });
+            }
+        }
         self.syscall.decision
     }
 
@@ -271,18 +473,29 @@ impl TracerEngine {
         self.syscall.decision
     }
 
-    fn carry_out_entry_decision(&mut self)
+    /*
+     * A forwarding error means the executor is gone, the tracing cannot go on.
+     */
+    fn carry_out_entry_decision(&mut self) -> Result<(), io::Error>
     {
         //TODO: finish implementing the decisions
-        match self.syscall.decision {
+        let result = self.flush_stale_pipeline().and_then(|_| match self.syscall.decision {
             Some(Decision::Continue) => {
-                self.continue_entry().unwrap();
+                self.continue_entry()
             },
             Some(Decision::Forward) => {
-                self.forward_entry().unwrap();
+                self.forward_entry()
+            },
+            Some(Decision::ForwardAsync) => {
+                self.forward_async_entry()
             },
             _ => panic!("Decision not implemented")
+        });
+
+        if let Err(err) = &result {
+            self.notify(Event::ExecutorDisconnected { pid: self.pid, reason: err.to_string() });
         }
+        result
     }
 
     fn carry_out_exit_decision(&mut self)
@@ -294,7 +507,7 @@ impl TracerEngine {
                 self.continue_exit().unwrap();
                 self.filter.on_syscall_exit(&self.syscall);
             },
-            Some(Decision::Forward) => {
+            Some(Decision::Forward) | Some(Decision::ForwardAsync) => {
                 self.forward_exit().unwrap();
                 self.filter.on_syscall_exit(&self.remote_syscall);
             },
@@ -319,11 +532,27 @@ impl TracerEngine {
 
     fn forward_entry(&mut self) -> Result<(), io::Error>
     {
+        /* The pipelined syscalls must be executed before */
+        self.pipelined = false;
+        self.flush_pipeline()?;
+
+        /* The syscalls acting on the fds only, see runs_on_placeholders() */
+        self.on_placeholders = self.syscall.decoded.as_ref()
+            .is_some_and(|decoded| runs_on_placeholders(decoded, &self.fwd_fd_table));
+        if self.on_placeholders {
+            self.remote_syscall = self.syscall.clone();
+            return Ok(());
+        }
+        self.sync_context()?;
+
         /* Pre-forward instrumentation */
         self.instr_pre_forward().unwrap();
 
-        /* Forward */
-        self.remote_syscall = self.protocol.send_syscall_entry(&self.remote_syscall).unwrap();
+        /* Forward, the mappings of its remote fds are synchronized around it */
+        let fds = fd_args(&mut self.remote_syscall);
+        self.push_mirrors(&fds)?;
+        self.remote_syscall = self.protocol.send_syscall_entry(&self.remote_syscall, self.pid, &self.operator)?;
+        self.pull_mirrors(&fds)?;
         //println!("[{}] remote syscall retval: {:#x}", self.pid, self.remote_syscall.raw.retval as usize);
         let json = serde_json::to_string(&self.remote_syscall).unwrap();
         println!("[{}] REMOTE: {}", self.pid, json);
@@ -331,101 +560,336 @@ impl TracerEngine {
         /* Post-forward instrumentation */
         self.instr_post_forward().unwrap();
 
+        /* Report the error of a previous pipelined syscall */
+        self.surface_deferred_error();
         Ok(())
     }
 
-    fn instr_pre_forward(&mut self) -> Result<(), io::Error>
+    /*
+     * Queue the syscall to be forwarded later and return the expected value right away.
+     */
+    fn forward_async_entry(&mut self) -> Result<(), io::Error>
     {
-        /* Syscall specific instrumentation */
-        self.remote_syscall = self.syscall.clone();
-        match self.remote_syscall.name.as_str() {
-            "close" => {
-                // translate the fd with the remote fd
-                if let DecodedSyscall::Close(remote_syscall) = self.remote_syscall.decoded.as_mut().unwrap() {
-                    let user_fd = remote_syscall.fd.value;
-                    let kernel_fd = self.fwd_fd_table.translate(user_fd).unwrap();
-                    remote_syscall.fd.value = kernel_fd;
-                }
-            },
-            "read" => {
-                // translate the fd with the remote fd
-                if let DecodedSyscall::Read(remote_syscall) = self.remote_syscall.decoded.as_mut().unwrap() {
-                    let user_fd = remote_syscall.fd.value;
-                    let kernel_fd = self.fwd_fd_table.translate(user_fd).unwrap(); // BUG=> la conversion ne s'est pas bien passe avec openat
-                    remote_syscall.fd.value = kernel_fd;
+        // The result of the other syscalls cannot be guessed, and a syscall too large goes alone
+        let size = self.syscall.raw.args[2].saturating_mul(4).saturating_add(PIPELINE_SYSCALL_BYTES);
+        if self.syscall.name != "write" || size > PIPELINE_MAX_BYTES {
+            return self.forward_entry();
+        }
+        if self.pipeline_bytes + size > PIPELINE_MAX_BYTES {
+            self.flush_pipeline()?;
+        }
+
+        /* Pre-forward instrumentation */
+        self.instr_pre_forward()?;
+
+        let count = self.remote_syscall.raw.args[2];
+        self.pipeline.push(self.remote_syscall.clone());
+        self.pipeline_bytes += size;
+        self.pipeline_since.get_or_insert_with(Instant::now);
+        self.pipelined = true;
+
+        /* Assume the whole buffer will be written */
+        self.remote_syscall.raw.retval = count;
+        self.remote_syscall.raw.errno = 0;
+        if let Some(DecodedSyscall::Write(remote_syscall)) = self.remote_syscall.decoded.as_mut() {
+            remote_syscall.retval = Some(Size::from(count));
+        }
+        self.notify_forwarded(&self.remote_syscall, true);
+
+        if self.pipeline.len() >= PIPELINE_MAX_SYSCALLS {
+            self.flush_pipeline()?;
+        }
+        Ok(())
+    }
+
+    /*
+     * The pipelined syscalls must not wait in the tracer while the tracee blocks in a local syscall,
+     * nor for longer than PIPELINE_MAX_DELAY.
+     */
+    fn flush_stale_pipeline(&mut self) -> Result<(), io::Error>
+    {
+        let local = matches!(self.syscall.decision, Some(Decision::Continue));
+        let blocking = local && BLOCKING_SYSCALLS.contains(&(self.syscall.raw.no as libc::c_long));
+        let stale = self.pipeline_since.is_some_and(|since| since.elapsed() >= PIPELINE_MAX_DELAY);
+        match blocking || stale {
+            true => self.flush_pipeline(),
+            false => Ok(()),
+        }
+    }
+
+    /*
+     * Send the pipelined syscalls to the executor.
+     * The first error on each fd is kept until the next syscall on it.
+     */
+    fn flush_pipeline(&mut self) -> Result<(), io::Error>
+    {
+        if self.pipeline.is_empty() {
+            return Ok(());
+        }
+
+        self.sync_context()?;
+        let mut batch = std::mem::take(&mut self.pipeline);
+        self.pipeline_bytes = 0;
+        self.pipeline_since = None;
+
+        let fds: Vec<usize> = batch.iter_mut().flat_map(fd_args).collect();
+        self.push_mirrors(&fds)?;
+        let results = self.protocol.send_syscall_batch(&batch, self.pid, &self.operator)?;
+        self.pull_mirrors(&fds)?;
+        for mut syscall in results {
+            if (syscall.raw.retval as isize) < 0 {
+                for kernel_fd in fd_args(&mut syscall) {
+                    self.deferred_errors.entry(kernel_fd).or_insert(syscall.raw.retval);
                 }
+            }
+        }
+        Ok(())
+    }
+
+    /*
+     * Send the context of the tracee, read on the first forwarded syscall, whenever it changed.
+     */
+    fn sync_context(&mut self) -> Result<(), io::Error>
+    {
+        if ! self.mirror_context {
+            return Ok(());
+        }
+        if self.context.is_none() {
+            self.context = Some(ProcessContext::from_pid(self.pid)?);
+        }
+        if self.context != self.context_sent {
+            let context = self.context.clone().unwrap();
+            self.protocol.send_context(&context)?;
+            self.context_sent = Some(context);
+        }
+        Ok(())
+    }
+
+    /*
+     * Follow the changes of the context made by the syscall which returned.
+     * The limits come from the decoded syscall, the rest from the status of the tracee.
+     */
+    fn update_context(&mut self)
+    {
+        if (self.syscall.raw.retval as isize) < 0 || ! CONTEXT_SYSCALLS.contains(&self.syscall.name.as_str()) {
+            return;
+        }
+        let context = match self.context.as_mut() {
+            Some(context) => context,
+            // Not read yet
+            None => return,
+        };
+
+        let result = match self.syscall.decoded.as_ref() {
+            Some(DecodedSyscall::Setrlimit(syscall)) => {
+                let rlimit = self.operator.memory.read(self.pid, syscall.rlim.address, RLIMIT_SIZE);
+                context.set_rlimit(syscall.resource.value as u32, &rlimit)
             },
-            "write" => {
-                // translate the fd with the remote fd
-                if let DecodedSyscall::Write(remote_syscall) = self.remote_syscall.decoded.as_mut().unwrap() {
-                    let user_fd = remote_syscall.fd.value;
-                    let kernel_fd = self.fwd_fd_table.translate(user_fd).unwrap();
-                    remote_syscall.fd.value = kernel_fd;
+            // Only the limits of the tracee itself
+            Some(DecodedSyscall::Prlimit(syscall)) => {
+                let pid = syscall.pid.value as i32;
+                match syscall.new_limit.address != 0 && (pid == 0 || pid == self.pid) {
+                    true => {
+                        let rlimit = self.operator.memory.read(self.pid, syscall.new_limit.address, RLIMIT_SIZE);
+                        context.set_rlimit(syscall.resource.value as u32, &rlimit)
+                    },
+                    false => Ok(()),
                 }
             },
-            "lseek" => {
-                // translate the fd with the remote fd
-                if let DecodedSyscall::Lseek(remote_syscall) = self.remote_syscall.decoded.as_mut().unwrap() {
-                    let user_fd = remote_syscall.fd.value;
-                    let kernel_fd = self.fwd_fd_table.translate(user_fd).unwrap(); // BUG=> la conversion ne s'est pas bien passe avec openat
-                    remote_syscall.fd.value = kernel_fd;
+            _ => context.read_status(self.pid),
+        };
+        if let Err(err) = result {
+            println!("[{}] Fail to update the context after {}: {}", self.pid, self.syscall.name, err);
+        }
+    }
+
+    /*
+     * The return value of a pipelined syscall is the expected one, its actual result is not reported.
+     */
+    fn notify_forwarded(&self, syscall: &Syscall, pipelined: bool)
+    {
+        if !self.is_subscribed("syscall_forwarded") {
+            return;
+        }
+        self.notify(Event::SyscallForwarded {
+            pid: self.pid,
+            name: syscall.name.clone(),
+            no: syscall.raw.no,
+            retval: syscall.raw.retval as isize,
+            pipelined,
+        });
+    }
+
+    /*
+     * Report the error of a pipelined syscall on one of the fds of the syscall, e.g., a later write, fsync or close.
+     */
+    fn surface_deferred_error(&mut self)
+    {
+        if self.deferred_errors.is_empty() {
+            return;
+        }
+        // A failed syscall reports its own error, a syscall creating a fd must keep its value otherwise the remote fd leaks
+        let returns_fd = self.remote_syscall.decoded.as_mut().is_some_and(|decoded| decoded.returned_fd().is_some());
+        if (self.remote_syscall.raw.retval as isize) < 0 || returns_fd {
+            return;
+        }
+
+        let kernel_fds: Vec<usize> = fd_args(&mut self.syscall).into_iter()
+            .filter_map(|user_fd| self.fwd_fd_table.translate(user_fd))
+            .collect();
+        if let Some(retval) = kernel_fds.iter().find_map(|kernel_fd| self.deferred_errors.remove(kernel_fd)) {
+            println!("[{}] Report error {} of a pipelined syscall", self.pid, retval as isize);
+            self.remote_syscall.raw.retval = retval;
+        }
+    }
+
+    fn instr_pre_forward(&mut self) -> Result<(), io::Error>
+    {
+        self.remote_syscall = self.syscall.clone();
+
+        /* Translate the forwarded fds given to the syscall with the remote fds, the local ones are left as is */
+        let Syscall { raw, decoded, .. } = &mut self.remote_syscall;
+        if let Some(decoded) = decoded.as_mut() {
+            for (index, fd) in decoded.fd_args() {
+                if let Some(kernel_fd) = self.fwd_fd_table.translate(fd.value) {
+                    fd.value = kernel_fd;
+                    raw.args[index] = kernel_fd;
                 }
             }
-            _ => (),
-        };
+        }
 
         /* Replace local syscall with a dummy one */
         // note: it would be more clean to modify self.syscall.raw values and synchronized once we return to the program execution.
         // for now on x86-64, replace with getpid()
         let mut regs = self.operator.register.read_registers(self.pid).unwrap();
-        regs.orig_rax = 39 as u64;  // getpid() in x86_64
+        regs.orig_rax = 39_u64;  // getpid() in x86_64
         self.operator.register.write_registers(self.pid, regs).unwrap();
 
         Ok(())
     }
 
+    /*
+     * Choose the shadow syscall the tracee executes locally in place of the forwarded one.
+     * A remote fd is given to the tracee as a new local placeholder, and the placeholder of a remote fd
+     * closed is closed as well. A remote mapping is mirrored by a new anonymous mapping.
+     * Otherwise the shadow syscall is getpid().
+     */
     fn instr_post_forward(&mut self) -> Result<(), io::Error>
     {
-        match self.remote_syscall.name.as_str() {
-            "open"  => {
-                // a bit ugly but we replace the return value with the remote fd to not overlap with local fd space.
-                if let DecodedSyscall::Open(remote_syscall) = self.remote_syscall.decoded.as_mut().unwrap() {
-                    let retval = remote_syscall.retval.as_ref().unwrap().value;
-                    if retval as i64 >= 0 {
-                        let user_fd = self.fwd_fd_table.open_remote(retval);
-                        remote_syscall.retval.as_mut().unwrap().value = user_fd;
-                        self.remote_syscall.raw.retval = user_fd;
-                    }
-                }
-            },
-            "openat" => {
-                // a bit ugly but we replace the return value with the remote fd to not overlap with local fd space.
-                if let DecodedSyscall::Openat(remote_syscall) = self.remote_syscall.decoded.as_mut().unwrap() {
-                    let retval = remote_syscall.retval.as_ref().unwrap().value;
-                    if retval as i64 >= 0 {
-                        let user_fd = self.fwd_fd_table.open_remote(retval);
-                        remote_syscall.retval.as_mut().unwrap().value = user_fd;
-                        self.remote_syscall.raw.retval = user_fd;
-                    }
-                }
-            }
-            "close" => {
-                // on successful close, remove the fd from the table
-                if let DecodedSyscall::Close(remote_syscall) = self.remote_syscall.decoded.as_mut().unwrap() {
-                    let retval = remote_syscall.retval.as_ref().unwrap().value;
-                    let user_fd = remote_syscall.fd.value;
-                    if retval as i64 >= 0 {
-                        let _kernel_fd = self.fwd_fd_table.close_remote(user_fd);
-                    }
-                }
-            },
-            _ => (),
+        let Syscall { raw, decoded: remote_decoded, .. } = &mut self.remote_syscall;
+        let (decoded, remote_decoded) = match (self.syscall.decoded.as_mut(), remote_decoded.as_mut()) {
+            (Some(decoded), Some(remote_decoded)) => (decoded, remote_decoded),
+            _ => return Ok(()),
+        };
+
+        /* The forwarded syscall is given back with the fds of the tracee, e.g., to the rules */
+        for ((index, user_fd), (_, fd)) in decoded.fd_args().into_iter().zip(remote_decoded.fd_args()) {
+            fd.value = user_fd.value;
+            raw.args[index] = user_fd.value;
+        }
+
+        if (raw.retval as isize) < 0 {
+            return Ok(());
+        }
+        self.returned_fd = remote_decoded.returned_fd().map(|fd| fd.value);
+
+        let mut regs = self.operator.register.read_registers(self.pid).unwrap();
+        if let DecodedSyscall::Close(syscall) = decoded {
+            // On successful close, remove the fd from the table
+            self.fwd_fd_table.close_remote(syscall.fd.value);
+            regs.orig_rax = self.syscall.raw.no as u64;
+        } else if self.returned_fd.is_some() {
+            // The arguments are restored on exit
+            regs.orig_rax = libc::SYS_eventfd2 as u64;
+            regs.rdi = 0;
+            regs.rsi = match close_on_exec(&self.syscall) { true => libc::EFD_CLOEXEC as u64, false => 0 };
+        } else if let DecodedSyscall::Mmap(_) = decoded {
+            // At the address asked by the tracee, if any
+            self.mapped = Some(raw.retval);
+            let fixed = self.syscall.raw.args[3] & (libc::MAP_FIXED | libc::MAP_FIXED_NOREPLACE) as usize;
+            regs.orig_rax = libc::SYS_mmap as u64;
+            regs.r10 = (fixed | (libc::MAP_PRIVATE | libc::MAP_ANONYMOUS) as usize) as u64;
+            regs.r8 = u64::MAX;
+            regs.r9 = 0;
+        }
+        self.operator.register.write_registers(self.pid, regs).unwrap();
+        Ok(())
+    }
+
+    /*
+     * Give the tracee the local placeholder of the returned remote fd.
+     * When the tracee cannot have it, e.g., with too many fds open, the remote fd is closed.
+     */
+    fn map_returned_fd(&mut self, user_fd: usize)
+    {
+        let kernel_fd = match self.returned_fd.take() {
+            Some(kernel_fd) => kernel_fd,
+            None => return,
         };
+
+        if (user_fd as isize) < 0 {
+            println!("[{}] No placeholder for the remote fd {}: {}", self.pid, kernel_fd, user_fd as isize);
+            self.close_released_fds(vec![kernel_fd]);
+        } else {
+            let close_on_exec = close_on_exec(&self.syscall);
+            if let Some(released) = self.fwd_fd_table.open_remote(user_fd, kernel_fd, close_on_exec) {
+                self.close_released_fds(vec![released]);
+            }
+        }
+
+        self.remote_syscall.raw.retval = user_fd;
+        if let Some(fd) = self.remote_syscall.decoded.as_mut().and_then(|decoded| decoded.returned_fd()) {
+            fd.value = user_fd;
+        }
+    }
+
+    fn close_remote_fd(&mut self, kernel_fd: usize) -> Result<(), io::Error>
+    {
+        self.send_remote_syscall(libc::SYS_close, &[kernel_fd])?;
         Ok(())
     }
 
+    /*
+     * Execute in the executor child a syscall the tracee did not make, e.g., to release what it no longer uses.
+     */
+    fn send_remote_syscall(&mut self, no: libc::c_long, args: &[usize]) -> Result<Syscall, io::Error>
+    {
+        let mut syscall = Syscall::new();
+        syscall.raw.no = no as usize;
+        syscall.raw.args[..args.len()].copy_from_slice(args);
+        let raw = syscall.raw.clone();
+        let (name, decoded) = match no {
+            libc::SYS_close => ("close", DecodedSyscall::Close(Close::new(raw))),
+            libc::SYS_munmap => ("munmap", DecodedSyscall::Munmap(Munmap::new(raw))),
+            libc::SYS_msync => ("msync", DecodedSyscall::Msync(Msync::new(raw))),
+            _ => unreachable!(),
+        };
+        syscall.name = name.to_string();
+        syscall.decoded = Some(decoded);
+        self.protocol.send_syscall_entry(&syscall, self.pid, &self.operator)
+    }
+
+    /*
+     * Close the remote fds no longer used by any fd of the tracee or of its children.
+     */
+    fn close_released_fds(&mut self, kernel_fds: Vec<usize>)
+    {
+        for kernel_fd in kernel_fds {
+            // The number may be given to another remote fd
+            self.deferred_errors.remove(&kernel_fd);
+            if let Err(err) = self.close_remote_fd(kernel_fd) {
+                println!("[{}] Fail to close the remote fd {}: {}", self.pid, kernel_fd, err);
+            }
+        }
+    }
+
     fn forward_exit(&mut self) -> Result<(), io::Error>
     {
+        if self.on_placeholders {
+            self.exit_on_placeholders();
+            return Ok(());
+        }
+
         /* 
          * On local syscall exit, this is usually the moment when the forwarded syscall synchronizes 
          * its state and its side-effects on the local system.
@@ -433,59 +897,321 @@ impl TracerEngine {
         // TODO
         //self.write_syscall_ret(self.remote_syscall.raw.retval, self.remote_syscall.raw.errno)?;
 
-        match self.remote_syscall.name.as_str() {
-            "read" => {
-                // sync the memory buffer
-                // TODO: should be automated by passing over all arguments
-                if let DecodedSyscall::Read(remote_syscall) = self.remote_syscall.decoded.as_ref().unwrap() {
-                    let size = remote_syscall.buf.size;
-                    let mem = &remote_syscall.buf.content[0..size];
-                    let addr = remote_syscall.buf.address;
-                    self.operator.memory.write(self.pid, addr, mem.to_vec());
-                }
+        /* Sync the memory filled by the forwarded syscall */
+        if (self.remote_syscall.raw.retval as isize) >= 0 {
+            if let Some(decoded) = self.remote_syscall.decoded.as_mut() {
+                decoded.write_outputs(self.pid, &self.operator);
             }
-            _ => (),
-        };
+        }
 
-        /* Syncrhonize back the return value and errno */
+        /* The local fd of the shadow syscall is the one of the tracee */
         let mut regs = self.operator.register.read_registers(self.pid).unwrap();
+        self.map_returned_fd(regs.rax as usize);
+        self.map_mirror(regs.rax as usize);
+
+        /* Syncrhonize back the return value and errno */
         regs.rax = self.remote_syscall.raw.retval as u64;
         regs.rdx = self.remote_syscall.raw.errno as u64;
+        // The shadow syscall may have changed the arguments
+        regs.rdi = self.syscall.raw.args[0] as u64;
+        regs.rsi = self.syscall.raw.args[1] as u64;
+        regs.r10 = self.syscall.raw.args[3] as u64;
+        regs.r8 = self.syscall.raw.args[4] as u64;
+        regs.r9 = self.syscall.raw.args[5] as u64;
         self.operator.register.write_registers(self.pid, regs).unwrap();
 
         // verify the register write...
         let regs = self.operator.register.read_registers(self.pid).unwrap();
         println!("fwd exit regs: {:?}", regs);
 
+        if !self.pipelined {
+            self.notify_forwarded(&self.remote_syscall, false);
+        }
         Ok(())
     }
 
     /*
-    fn update_fd_table(&mut self)
+     * Follow the fds created or closed by the syscall run on the placeholders, its result is the local one.
+     */
+    fn exit_on_placeholders(&mut self)
     {
-        let sc: &dyn Any = self.syscall.decoded.as_ref().unwrap().get_syscall();
-        match self.syscall.name.as_str() {
-            "open" => {
+        self.remote_syscall = self.syscall.clone();
+        let retval = self.syscall.raw.retval;
+        if (retval as isize) < 0 {
+            return;
+        }
+
+        let table = &mut self.fwd_fd_table;
+        let released = match self.syscall.decoded.as_ref() {
+            Some(DecodedSyscall::Close(syscall)) => table.close_remote(syscall.fd.value),
+            Some(DecodedSyscall::Dup(syscall)) => table.dup(syscall.oldfd.value, retval, false),
+            // Nothing is done when both fds are the same
+            Some(DecodedSyscall::Dup2(syscall)) if syscall.oldfd.value != syscall.newfd.value => {
+                table.dup(syscall.oldfd.value, retval, false)
+            },
+            Some(DecodedSyscall::Dup3(syscall)) => table.dup(syscall.oldfd.value, retval, close_on_exec(&self.syscall)),
+            Some(DecodedSyscall::Fcntl(syscall)) => match syscall.cmd.value as i32 {
+                libc::F_DUPFD => table.dup(syscall.fd.value, retval, false),
+                libc::F_DUPFD_CLOEXEC => table.dup(syscall.fd.value, retval, true),
+                libc::F_SETFD => {
+                    table.set_close_on_exec(syscall.fd.value, syscall.arg.value as i32 & libc::FD_CLOEXEC != 0);
+                    None
+                },
+                _ => None,
             },
-            "close" => {
+            _ => None,
+        };
+        self.close_released_fds(released.into_iter().collect());
+    }
+
+    /*
+     * Follow the fds of the tracee across the syscalls changing of program: the close-on-exec fds are closed on execve.
+     * The tracee cannot create processes once it has forwarded fds, see refuse_process_creation().
+     */
+    fn update_fd_table(&mut self)
+    {
+        let retval = self.syscall.raw.retval;
+        if (retval as isize) < 0 {
+            return;
+        }
+
+        if let libc::SYS_execve | libc::SYS_execveat = self.syscall.raw.no as i64 {
+            let (closed, released) = self.fwd_fd_table.exec();
+            if !closed.is_empty() {
+                println!("[{}] Close-on-exec forwarded fds: {:?}", self.pid, closed);
+                self.filter.on_fds_closed(&closed);
             }
-            _ => (),
+            self.close_released_fds(released);
+        }
+    }
+
+    /*
+     * The children and the threads created by the tracee are not traced: they would only have the local
+     * placeholders of the forwarded fds and the copies of the mirrors, their syscalls on them running locally.
+     * Once the tracee has forwarded fds or mappings, the syscalls creating them are refused with EPERM.
+     * The syscall is replaced with getpid(), see fail_syscall().
+     */
+    fn refuse_process_creation(&mut self) -> bool
+    {
+        let no = self.syscall.raw.no as libc::c_long;
+        if !matches!(no, libc::SYS_fork | libc::SYS_vfork | libc::SYS_clone | libc::SYS_clone3) {
+            return false;
+        }
+        if self.fwd_fd_table.is_empty() && self.mirrors.fds().is_empty() {
+            return false;
+        }
+
+        let mut regs = self.operator.register.read_registers(self.pid).unwrap();
+        regs.orig_rax = libc::SYS_getpid as u64;
+        self.operator.register.write_registers(self.pid, regs).unwrap();
+        self.syscall.decision = Some(Decision::Continue);
+
+        let reason = String::from("The children of a tracee with forwarded fds are not traced");
+        println!("[{}] Refuse {}: {}", self.pid, self.syscall.name, reason);
+        self.notify(Event::SyscallRefused { pid: self.pid, name: self.syscall.name.clone(), no: no as usize, reason });
+        true
+    }
+
+    /*
+     * Make the syscall, replaced on entry, return -errno to the tracee.
+     */
+    fn fail_syscall(&mut self, errno: i32)
+    {
+        let retval = -(errno as isize) as usize;
+        let mut regs = self.operator.register.read_registers(self.pid).unwrap();
+        regs.rax = retval as u64;
+        self.operator.register.write_registers(self.pid, regs).unwrap();
+        self.syscall.raw.retval = retval;
+    }
+
+    /* Mirrored mappings */
+
+    /*
+     * Give the tracee the local mapping of the shadow syscall mirroring the remote one, with its content.
+     * A shared mapping is then synchronized around the syscalls on its fd, while a private one is a copy.
+     */
+    fn map_mirror(&mut self, local: usize)
+    {
+        let remote = match self.mapped.take() {
+            Some(remote) => remote,
+            None => return,
         };
+        let args = &self.syscall.raw.args;
+        let length = args[1].next_multiple_of(PAGE_SIZE);
+        let shared = matches!(args[3] as i32 & libc::MAP_TYPE, libc::MAP_SHARED | libc::MAP_SHARED_VALIDATE);
+        let writable = args[2] as i32 & libc::PROT_WRITE != 0;
+
+        if (local as isize) < 0 {
+            println!("[{}] No local mapping for the remote one at {:#x}: {}", self.pid, remote, local as isize);
+            self.unmap_remote(vec![(remote, length)]);
+        } else {
+            match self.protocol.read_remote_memory(remote, length) {
+                Ok(content) => {
+                    self.operator.memory.write(self.pid, local, content.clone());
+                    match shared {
+                        true => {
+                            let fd = self.fwd_fd_table.translate(args[4]).unwrap_or(args[4]);
+                            self.mirrors.insert(local, remote, fd, writable, content)
+                        },
+                        false => self.unmap_remote(vec![(remote, length)]),
+                    }
+                },
+                Err(err) => println!("[{}] Fail to read the remote mapping at {:#x}: {}", self.pid, remote, err),
+            }
+        }
+
+        self.remote_syscall.raw.retval = local;
+        if let Some(DecodedSyscall::Mmap(syscall)) = self.remote_syscall.decoded.as_mut() {
+            syscall.retval = Some(Address::from(local));
+        }
+    }
+
+    /*
+     * Send the pages the tracee wrote to the remote mappings of the remote `fds`.
+     */
+    fn push_mirrors(&mut self, fds: &[usize]) -> Result<(), io::Error>
+    {
+        for mirror in self.mirrors.iter_mut().filter(|mirror| mirror.writable && fds.contains(&mirror.fd)) {
+            let content = self.operator.memory.read(self.pid, mirror.local, mirror.length);
+            for (offset, page) in mirror.diff(content) {
+                self.protocol.write_remote_memory(mirror.remote + offset, &page)?;
+            }
+        }
+        Ok(())
+    }
+
+    /*
+     * Give the tracee the pages changed in the remote mappings of the remote `fds`, e.g., by the device.
+     * A mapping which cannot be read is left as it is.
+     */
+    fn pull_mirrors(&mut self, fds: &[usize]) -> Result<(), io::Error>
+    {
+        for mirror in self.mirrors.iter_mut().filter(|mirror| fds.contains(&mirror.fd)) {
+            let content = match self.protocol.read_remote_memory(mirror.remote, mirror.length) {
+                Ok(content) => content,
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                    println!("[{}] Fail to read the remote mapping at {:#x}: {}", self.pid, mirror.remote, err);
+                    continue;
+                },
+                Err(err) => return Err(err),
+            };
+            for (offset, page) in mirror.diff(content) {
+                self.operator.memory.write(self.pid, mirror.local + offset, page);
+            }
+        }
+        Ok(())
+    }
+
+    /*
+     * The remote mappings must be up to date before the tracee syncs, unmaps or loses its mirrors,
+     * and a msync is also made on the remote mappings.
+     */
+    fn mirror_entry(&mut self) -> Result<(), io::Error>
+    {
+        if self.mirrors.is_empty() {
+            return Ok(());
+        }
+        let args = self.syscall.raw.args.clone();
+        let parts = match self.syscall.raw.no as i64 {
+            libc::SYS_msync | libc::SYS_munmap | libc::SYS_mremap => self.mirrors.overlapping(args[0], args[1]),
+            libc::SYS_execve | libc::SYS_execveat => Vec::new(),
+            _ => return Ok(()),
+        };
+
+        self.flush_pipeline()?;
+        let fds = self.mirrors.fds();
+        self.push_mirrors(&fds)?;
+        if self.syscall.raw.no == libc::SYS_msync as usize {
+            for (remote, length) in parts {
+                let msync = self.send_remote_syscall(libc::SYS_msync, &[remote, length, args[2]])?;
+                if (msync.raw.retval as isize) < 0 {
+                    println!("[{}] Fail to msync the remote mapping at {:#x}: {}", self.pid, remote, msync.raw.retval as isize);
+                }
+            }
+            // The tracee also sees what the other side wrote
+            self.pull_mirrors(&fds)?;
+        }
+        Ok(())
+    }
+
+    /*
+     * Stop mirroring the mappings the tracee unmapped, moved or lost by executing another program.
+     */
+    fn update_mirrors(&mut self)
+    {
+        if self.mirrors.is_empty() || (self.syscall.raw.retval as isize) < 0 {
+            return;
+        }
+        let args = &self.syscall.raw.args;
+        let parts = match self.syscall.raw.no as i64 {
+            libc::SYS_munmap | libc::SYS_mremap => self.mirrors.unmap(args[0], args[1]),
+            libc::SYS_execve | libc::SYS_execveat => self.mirrors.clear(),
+            _ => return,
+        };
+        self.unmap_remote(parts);
+    }
+
+    fn unmap_remote(&mut self, parts: Vec<(usize, usize)>)
+    {
+        for (remote, length) in parts {
+            let result = self.send_remote_syscall(libc::SYS_munmap, &[remote, length])
+                .and_then(|munmap| match (munmap.raw.retval as isize) < 0 {
+                    true => Err(io::Error::from_raw_os_error(-(munmap.raw.retval as isize) as i32)),
+                    false => Ok(()),
+                });
+            if let Err(err) = result {
+                println!("[{}] Fail to unmap the remote mapping at {:#x}: {}", self.pid, remote, err);
+            }
+        }
     }
-    */
 
     /* Filtering management */
 
-    pub fn load_rule(&mut self, index: usize, rule: Box<dyn Rule>)
+    /*
+     * The rules can only be changed between two syscalls, otherwise the decision taken on the entry of the
+     * current syscall may not be the one found on its exit.
+     */
+    pub fn is_in_syscall(&self) -> bool
     {
+        self.insyscall
+    }
+
+    fn check_not_in_syscall(&self) -> Result<(), io::Error>
+    {
+        match self.insyscall {
+            true => Err(io::Error::new(io::ErrorKind::WouldBlock, "The rules cannot change inside a syscall")),
+            false => Ok(()),
+        }
+    }
+
+    pub fn load_rule(&mut self, index: usize, rule: Box<dyn Rule>) -> Result<(), io::Error>
+    {
+        self.check_not_in_syscall()?;
         self.filter.insert(index, rule)
     }
 
-    pub fn unload_rule(&mut self, index: usize) -> Box<dyn Rule>
+    pub fn unload_rule(&mut self, index: usize) -> Result<Box<dyn Rule>, io::Error>
     {
+        self.check_not_in_syscall()?;
         self.filter.remove(index)
     }
 
+    pub fn move_rule(&mut self, from: usize, to: usize) -> Result<(), io::Error>
+    {
+        self.check_not_in_syscall()?;
+        self.filter.move_rule(from, to)
+    }
+
+    pub fn list_rules(&self) -> Vec<RuleInfo>
+    {
+        self.filter.list()
+    }
+
+    pub fn rule_count(&self) -> usize
+    {
+        self.filter.len()
+    }
+
     /* Statistics */
 
     fn calculate_stats(&self) -> Result<HashMap<(usize, String), i32>, io::Error>
@@ -493,7 +1219,7 @@ impl TracerEngine {
         let mut syscall_stats: HashMap<(usize, String), i32> = HashMap::new();
 
         for syscall in &self.saved_syscall {
-            let key = (syscall.raw.no.clone(), syscall.name.clone());
+            let key = (syscall.raw.no, syscall.name.clone());
             let count = syscall_stats.entry(key).or_insert(0);
             *count += 1;
         }
@@ -557,4 +1283,69 @@ impl TracerEngine {
         self.interceptor.write_syscall_ret(self.pid, retval, errno)
     }
     */
-}
\ No newline at end of file
+}
+
+
+
+/*
+ * The syscalls acting on the fd itself rather than on the file, only executed locally on the placeholders:
+ * the new fds share the remote fd of their description, and a close only closes the remote fd with
+ * the last fd of the description.
+ */
+fn runs_on_placeholders(decoded: &DecodedSyscall, fd_table: &FdTable) -> bool
+{
+    match decoded {
+        DecodedSyscall::Close(syscall) => fd_table.is_shared(syscall.fd.value),
+        DecodedSyscall::Dup(_) | DecodedSyscall::Dup2(_) | DecodedSyscall::Dup3(_) => true,
+        DecodedSyscall::Fcntl(syscall) => {
+            matches!(syscall.cmd.value as i32, libc::F_DUPFD | libc::F_DUPFD_CLOEXEC | libc::F_GETFD | libc::F_SETFD)
+        },
+        _ => false,
+    }
+}
+
+/*
+ * The fds given to the syscall.
+ */
+fn fd_args(syscall: &mut Syscall) -> Vec<usize>
+{
+    match syscall.decoded.as_mut() {
+        Some(decoded) => decoded.fd_args().into_iter().map(|(_, fd)| fd.value).collect(),
+        None => Vec::new(),
+    }
+}
+
+/*
+ * Whether the fd returned by the syscall is closed on execve, from the flags of each syscall.
+ */
+fn close_on_exec(syscall: &Syscall) -> bool
+{
+    let args = &syscall.raw.args;
+    match syscall.decoded.as_ref() {
+        Some(DecodedSyscall::Openat(syscall)) => syscall.flags.value as i32 & libc::O_CLOEXEC != 0,
+        // struct open_how { __u64 flags; __u64 mode; __u64 resolve; }
+        Some(DecodedSyscall::Openat2(syscall)) => match syscall.how.content.get(0..8) {
+            Some(flags) => u64::from_le_bytes(flags.try_into().unwrap()) & libc::O_CLOEXEC as u64 != 0,
+            None => false,
+        },
+        Some(DecodedSyscall::Dup3(syscall)) => syscall.flags.value as i32 & libc::O_CLOEXEC != 0,
+        Some(DecodedSyscall::Accept4(syscall)) => syscall.flags.value as i32 & libc::SOCK_CLOEXEC != 0,
+        Some(DecodedSyscall::EpollCreate1(syscall)) => syscall.size.value as i32 & libc::EPOLL_CLOEXEC != 0,
+        Some(DecodedSyscall::MemfdCreate(syscall)) => syscall.flags.value as u32 & libc::MFD_CLOEXEC != 0,
+        // A Flag only keeps the low byte of the flags
+        Some(DecodedSyscall::Open(_)) => args[1] as i32 & libc::O_CLOEXEC != 0,
+        Some(DecodedSyscall::OpenByHandleAt(_)) => args[2] as i32 & libc::O_CLOEXEC != 0,
+        Some(_) => false,
+        // Not decoded yet, from the raw flags
+        None => match syscall.raw.no as libc::c_long {
+            libc::SYS_socket | libc::SYS_socketpair => args[1] as i32 & libc::SOCK_CLOEXEC != 0,
+            libc::SYS_eventfd2 => args[1] as i32 & libc::EFD_CLOEXEC != 0,
+            libc::SYS_timerfd_create => args[1] as i32 & libc::TFD_CLOEXEC != 0,
+            libc::SYS_signalfd4 => args[3] as i32 & libc::SFD_CLOEXEC != 0,
+            libc::SYS_inotify_init1 => args[0] as i32 & libc::IN_CLOEXEC != 0,
+            // A pidfd is always closed on execve
+            libc::SYS_pidfd_open => true,
+            _ => false,
+        },
+    }
+}
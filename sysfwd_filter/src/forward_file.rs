@@ -1,10 +1,18 @@
 /*
  *
  */
+use std::{
+    collections::BTreeSet,
+    io,
+};
+
+use serde::Deserialize;
+use serde_json::{ json, Value };
+
 use sysfwd::{
     syscall::{
         Syscall,
-        decoder::DecodedSyscall,
+        decoder::{ DecodedSyscall, FdArgs },
     },
     tracer::filtering::{ Decision, Rule },
 };
@@ -13,15 +21,48 @@ use sysfwd::{
 
 pub struct ForwardFileRule {
     pub filename: String,
-    fd: Option<usize>,
+    fds: BTreeSet<usize>,       // the forwarded fds, from an open of the file or a dup of them
     trigger_on_entry: bool,     // to avoid having to recheck during the callback
+    pipelined: bool,            // writes on the fd do not wait for the executor
+}
+
+/*
+ * {"filename": "/dev/kbuf", "pipelined": false}
+ */
+#[derive(Deserialize)]
+struct ForwardFileParams {
+    filename: String,
+    #[serde(default)]
+    pipelined: bool,
 }
 
 impl ForwardFileRule {
 
+    pub const NAME: &'static str = "forward_file";
+
     pub fn new(filename: String) -> Self 
     {
-        ForwardFileRule { filename: filename, fd: None, trigger_on_entry: false }
+        ForwardFileRule { filename, fds: BTreeSet::new(), trigger_on_entry: false, pipelined: false }
+    }
+
+    /*
+     * Same rule but the writes are pipelined, for devices where the data is only streamed.
+     */
+    pub fn new_pipelined(filename: String) -> Self
+    {
+        ForwardFileRule { filename, fds: BTreeSet::new(), trigger_on_entry: false, pipelined: true }
+    }
+
+    pub fn from_params(params: Value) -> Result<Box<dyn Rule>, io::Error>
+    {
+        let params: ForwardFileParams = serde_json::from_value(params)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid params of {}: {}", Self::NAME, err)))?;
+
+        let rule = match params.pipelined {
+            true => Self::new_pipelined(params.filename),
+            false => Self::new(params.filename),
+        };
+        Ok(Box::new(rule))
     }
 
 }
@@ -79,58 +120,16 @@ impl Rule for ForwardFileRule {
                     }
                     // TODO: recuperer FD on syscall returns
                 }
-                DecodedSyscall::Close(sc) => {
-                    match self.fd {
-                        Some(fd) => {
-                            if sc.fd.value == fd {
-                                self.trigger_on_entry = true;
-                                decision = Decision::Forward;
-                            }
-                        },
-                        None => (),
-                    }
-                }
-
-                /* Read class syscall */
-                DecodedSyscall::Read(sc) => {
-                    match self.fd {
-                        Some(fd) => {
-                            if sc.fd.value == fd {
-                                self.trigger_on_entry = true;
-                                decision = Decision::Forward;
-                            }
-                        },
-                        None => (),
-                    }
-                }
-
-                /* Write class syscall */
-                DecodedSyscall::Write(sc) => {
-                    match self.fd {
-                        Some(fd) => {
-                            if sc.fd.value == fd {
-                                self.trigger_on_entry = true;
-                                decision = Decision::Forward;
-                            }
-                        },
-                        None => (),
-                    }
-                }
-
-                DecodedSyscall::Lseek(sc) => {
-                    match self.fd {
-                        Some(fd) => {
-                            if sc.fd.value == fd {
-                                self.trigger_on_entry = true;
-                                decision = Decision::Forward;
-                            }
-                        },
-                        None => (),
+                /* Any syscall given a forwarded fd, e.g., read, ioctl or dup */
+                mut sc => {
+                    if sc.fd_args().iter().any(|(_, fd)| self.fds.contains(&fd.value)) {
+                        self.trigger_on_entry = true;
+                        decision = match sc {
+                            DecodedSyscall::Write(_) if self.pipelined => Decision::ForwardAsync,
+                            _ => Decision::Forward,
+                        };
                     }
                 }
-
-                /* Others */
-                _ => (),
             }   // match
 
         }   // if let
@@ -145,48 +144,52 @@ impl Rule for ForwardFileRule {
 
     fn on_syscall_exit(&mut self, syscall: Syscall)
     {
-        if syscall.decision.unwrap() != Decision::Forward {
-            return;
+        match syscall.decision.unwrap() {
+            Decision::Forward | Decision::ForwardAsync => (),
+            _ => return,
         }
 
         if self.trigger_on_entry {
-            let decoded_syscall = syscall.decoded.as_ref().unwrap();
+            let mut decoded_syscall = syscall.decoded.unwrap();
 
-            match decoded_syscall {
-
-                DecodedSyscall::Close(_sc) => {
-                    self.fd = None;
-                }
-
-                DecodedSyscall::Creat(sc) => {
-                    match sc.retval.as_ref() {
-                        Some(fd) => self.fd = Some(fd.value),
-                        None => (),
-                    }
-                }
-                DecodedSyscall::Open(sc) => {
-                    match sc.retval.as_ref() {
-                        Some(fd) => self.fd = Some(fd.value),
-                        None => (),
-                    }
-                }
-                DecodedSyscall::Openat(sc) => {
-                    match sc.retval.as_ref() {
-                        Some(fd) => self.fd = Some(fd.value),
-                        None => (),
-                    }
-                }
-                DecodedSyscall::Openat2(sc) => {
-                    match sc.retval.as_ref() {
-                        Some(fd) => self.fd = Some(fd.value),
-                        None => (),
-                    }
+            // A dup2 of a local fd on a forwarded fd makes it a local fd
+            let local_dup = match &decoded_syscall {
+                DecodedSyscall::Close(sc) => {
+                    self.fds.remove(&sc.fd.value);
+                    false
+                },
+                DecodedSyscall::Dup2(sc) => !self.fds.contains(&sc.oldfd.value),
+                DecodedSyscall::Dup3(sc) => !self.fds.contains(&sc.oldfd.value),
+                _ => false,
+            };
+            // The fd of the opened file or a new fd of the same file
+            if let Some(fd) = decoded_syscall.returned_fd() {
+                if (fd.value as isize) >= 0 {
+                    match local_dup {
+                        true => self.fds.remove(&fd.value),
+                        false => self.fds.insert(fd.value),
+                    };
                 }
-
-                _ => (),
             }
         }
 
         self.trigger_on_entry = false;
     }
+
+    fn on_fds_closed(&mut self, fds: &[usize])
+    {
+        for fd in fds {
+            self.fds.remove(fd);
+        }
+    }
+
+    fn name(&self) -> String
+    {
+        Self::NAME.to_string()
+    }
+
+    fn params(&self) -> Value
+    {
+        json!({ "filename": self.filename, "pipelined": self.pipelined })
+    }
 }
\ No newline at end of file
@@ -5,7 +5,11 @@
 /* Each filter is contained in a module */
 mod forward_file;
 
+/* Rules loadable by name */
+mod registry;
+
 
 
 /* List of exported filters */
 pub use forward_file::ForwardFileRule;
+pub use registry::{ RuleRegistry, RuleConstructor };
@@ -0,0 +1,72 @@
+/*
+ *
+ */
+use std::{
+    collections::BTreeMap,
+    io,
+};
+
+use serde_json::Value;
+use sysfwd::tracer::filtering::Rule;
+
+use crate::forward_file::ForwardFileRule;
+
+
+
+/*
+ * Create a rule from its JSON params.
+ */
+pub type RuleConstructor = fn(Value) -> Result<Box<dyn Rule>, io::Error>;
+
+/*
+ * The rules a debugger can load at runtime, by name.
+ * The debuggers can register their own rules next to the ones of this crate.
+ */
+#[derive(Clone)]
+pub struct RuleRegistry {
+    constructors: BTreeMap<String, RuleConstructor>,
+}
+
+impl RuleRegistry {
+
+    pub fn new() -> Self
+    {
+        Self { constructors: BTreeMap::new() }
+    }
+
+    /*
+     * A registry with all the rules of this crate.
+     */
+    pub fn with_builtin_rules() -> Self
+    {
+        let mut registry = Self::new();
+        registry.register(ForwardFileRule::NAME, ForwardFileRule::from_params);
+        registry
+    }
+
+    pub fn register(&mut self, name: &str, constructor: RuleConstructor)
+    {
+        self.constructors.insert(name.to_string(), constructor);
+    }
+
+    pub fn create(&self, name: &str, params: Value) -> Result<Box<dyn Rule>, io::Error>
+    {
+        match self.constructors.get(name) {
+            Some(constructor) => constructor(params),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, format!("Unknown rule {:?}", name))),
+        }
+    }
+
+    pub fn names(&self) -> Vec<String>
+    {
+        self.constructors.keys().cloned().collect()
+    }
+}
+
+impl Default for RuleRegistry {
+
+    fn default() -> Self
+    {
+        Self::with_builtin_rules()
+    }
+}